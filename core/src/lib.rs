@@ -1,5 +1,6 @@
 extern crate ethereum_types;
 extern crate futures;
+extern crate futures_cpupool;
 extern crate graphql_parser;
 #[macro_use]
 extern crate slog;
@@ -14,6 +15,6 @@ mod query;
 mod schema;
 mod subgraph;
 
-pub use query::QueryRunner;
+pub use query::{QueryRunner, QueryRunnerConfig};
 pub use schema::SchemaProvider;
 pub use subgraph::RuntimeManager;
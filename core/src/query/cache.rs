@@ -0,0 +1,360 @@
+use graphql_parser::query as q;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use graph::components::store::StoreEvent;
+use graph::prelude::{Query, QueryResult, Value};
+use graph_graphql::query::ast as qast;
+use graph_graphql::query::get_selected_object_types;
+
+/// Identifies a query whose result can be cached.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct CacheKey {
+    /// The ID of the subgraph the query runs against.
+    subgraph: String,
+    /// The query document, normalized by printing it.
+    document: String,
+    /// The query variables, sorted by name.
+    variables: String,
+    /// The name of the operation to execute.
+    operation_name: Option<String>,
+}
+
+impl CacheKey {
+    fn new(query: &Query) -> Self {
+        let variables = query.variables.as_ref().map(|variables| {
+            variables
+                .iter()
+                .map(|(name, value)| (name, format!("{:?}", value)))
+                .collect::<BTreeMap<_, _>>()
+        });
+
+        CacheKey {
+            subgraph: query.schema.id.clone(),
+            document: format!("{}", query.document),
+            variables: format!("{:?}", variables),
+            operation_name: query.operation_name.clone(),
+        }
+    }
+}
+
+/// A cached query result.
+struct CacheEntry {
+    data: q::Value,
+    /// The object types the query selects; changes to entities of these
+    /// types invalidate the entry.
+    object_types: HashSet<String>,
+    /// The estimated size of the data in bytes.
+    size: usize,
+    created_at: Instant,
+}
+
+/// A cache for the results of queries, bounded by the age of the results and
+/// by their estimated total size.
+///
+/// Only results without errors are cached. Results are invalidated when store
+/// events for entities of any of the object types selected by the query arrive.
+///
+/// Every invalidation starts a new generation of the cache. Results of queries
+/// that were executed before the current generation started may be stale and
+/// are not cached.
+pub struct QueryCache {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Keys of the cached entries, oldest first.
+    insertion_order: VecDeque<CacheKey>,
+    ttl: Duration,
+    max_size: usize,
+    size: usize,
+    generation: u64,
+}
+
+impl QueryCache {
+    /// Creates a cache that keeps results for at most `ttl` and holds at most
+    /// `max_size` bytes of results.
+    pub fn new(ttl: Duration, max_size: usize) -> Self {
+        QueryCache {
+            entries: HashMap::new(),
+            insertion_order: VecDeque::new(),
+            ttl,
+            max_size,
+            size: 0,
+            generation: 0,
+        }
+    }
+
+    /// Returns the current generation of the cache. It should be recorded
+    /// before a query is executed and passed to `insert` with its result.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the cached result of a query, if there is one that has not expired.
+    pub fn get(&mut self, query: &Query) -> Option<QueryResult> {
+        let key = CacheKey::new(query);
+
+        let expired = match self.entries.get(&key) {
+            Some(entry) => entry.created_at.elapsed() > self.ttl,
+            None => return None,
+        };
+
+        if expired {
+            self.remove(&key);
+            None
+        } else {
+            self.entries
+                .get(&key)
+                .map(|entry| QueryResult::new(Some(entry.data.clone())))
+        }
+    }
+
+    /// Caches the result of a query that was executed in the given generation,
+    /// evicting the oldest results if the cache grows too large.
+    pub fn insert(&mut self, query: &Query, result: &QueryResult, generation: u64) {
+        // The store may have changed while the query was executed
+        if generation != self.generation {
+            return;
+        }
+
+        // Don't cache failed or partial results
+        let data = match (&result.data, &result.errors) {
+            (Some(data), None) => data,
+            _ => return,
+        };
+
+        // Only cache query results; subscriptions are re-executed on every
+        // store event and must never be answered from the cache
+        let operation_name = query.operation_name.as_ref().map(|name| name.as_str());
+        let object_types = match qast::get_operation(&query.document, operation_name) {
+            Ok(operation @ q::OperationDefinition::Query(_))
+            | Ok(operation @ q::OperationDefinition::SelectionSet(_)) => {
+                get_selected_object_types(&query.schema.document, &query.document, operation)
+            }
+            _ => return,
+        };
+
+        let size = estimate_size(data);
+        if size > self.max_size {
+            return;
+        }
+
+        let key = CacheKey::new(query);
+        self.remove(&key);
+
+        while self.size + size > self.max_size {
+            match self.insertion_order.pop_front() {
+                Some(oldest) => self.remove(&oldest),
+                None => break,
+            }
+        }
+
+        self.entries.insert(
+            key.clone(),
+            CacheEntry {
+                data: data.clone(),
+                object_types,
+                size,
+                created_at: Instant::now(),
+            },
+        );
+        self.insertion_order.push_back(key);
+        self.size += size;
+    }
+
+    /// Invalidates all cached results that a store event may affect.
+    pub fn invalidate(&mut self, event: &StoreEvent) {
+        self.generation += 1;
+
+        let entity = match event {
+            StoreEvent::EntityAdded(entity)
+            | StoreEvent::EntityRemoved(entity)
+            | StoreEvent::EntityChanged(entity) => entity,
+            StoreEvent::EventsDropped => return self.clear(),
+        };
+
+        // Changes to entities of an unknown type may affect any result
+        let affected_keys: Vec<_> = match entity.get("__typename") {
+            Some(Value::String(entity_type)) => self.entries
+                .iter()
+                .filter(|(_, entry)| entry.object_types.contains(entity_type))
+                .map(|(key, _)| key.clone())
+                .collect(),
+            _ => self.entries.keys().cloned().collect(),
+        };
+
+        for key in affected_keys {
+            self.remove(&key);
+        }
+    }
+
+    /// Removes all cached results.
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.insertion_order.clear();
+        self.size = 0;
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.size -= entry.size;
+            self.insertion_order.retain(|k| k != key);
+        }
+    }
+}
+
+/// Estimates the number of bytes a value occupies in memory.
+fn estimate_size(value: &q::Value) -> usize {
+    use std::mem::size_of;
+
+    size_of::<q::Value>() + match value {
+        q::Value::String(s) | q::Value::Enum(s) | q::Value::Variable(s) => s.len(),
+        q::Value::List(values) => values.iter().map(estimate_size).sum(),
+        q::Value::Object(object) => object
+            .iter()
+            .map(|(key, value)| key.len() + estimate_size(value))
+            .sum(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::sync::oneshot;
+    use graphql_parser;
+    use graphql_parser::query as q;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use graph::components::store::StoreEvent;
+    use graph::prelude::*;
+    use graph_graphql::prelude::api_schema;
+
+    use super::QueryCache;
+
+    fn query(s: &str) -> Query {
+        let schema = api_schema(
+            &graphql_parser::parse_schema(
+                "
+                type Musician { id: ID!, name: String!, bands: [Band!]! }
+                type Band { id: ID!, name: String! }
+                type Song { id: ID!, title: String! }
+                ",
+            ).unwrap(),
+        ).unwrap();
+
+        Query {
+            schema: Schema {
+                id: String::from("subgraph"),
+                document: schema,
+            },
+            document: graphql_parser::parse_query(s).unwrap(),
+            variables: None,
+            operation_name: None,
+            tracing: false,
+            result_sender: oneshot::channel().0,
+            pending: None,
+            deadline: None,
+        }
+    }
+
+    fn result() -> QueryResult {
+        QueryResult::new(Some(q::Value::String(String::from("data"))))
+    }
+
+    fn event(entity_type: &str) -> StoreEvent {
+        StoreEvent::EntityChanged(Entity::from(vec![(
+            "__typename",
+            Value::from(entity_type),
+        )]))
+    }
+
+    #[test]
+    fn returns_cached_results_for_equivalent_queries() {
+        let mut cache = QueryCache::new(Duration::from_secs(60), 1024 * 1024);
+        cache.insert(&query("{ musicians { name } }"), &result(), 0);
+
+        assert!(
+            cache
+                .get(&query("{\n  musicians { # All musicians\n    name\n  }\n}"))
+                .is_some()
+        );
+        assert!(cache.get(&query("{ musicians { id } }")).is_none());
+    }
+
+    #[test]
+    fn does_not_cache_results_with_errors() {
+        let mut cache = QueryCache::new(Duration::from_secs(60), 1024 * 1024);
+        cache.insert(
+            &query("{ musicians { name } }"),
+            &QueryResult::from(QueryExecutionError::NoRootQueryObjectType),
+            0,
+        );
+        assert_eq!(cache.entries.len(), 0);
+    }
+
+    #[test]
+    fn does_not_cache_subscription_results() {
+        let mut cache = QueryCache::new(Duration::from_secs(60), 1024 * 1024);
+        cache.insert(&query("subscription { musicians { name } }"), &result(), 0);
+        assert_eq!(cache.entries.len(), 0);
+    }
+
+    #[test]
+    fn invalidates_results_affected_by_store_events() {
+        let mut cache = QueryCache::new(Duration::from_secs(60), 1024 * 1024);
+        cache.insert(&query("{ musicians { name bands { name } } }"), &result(), 0);
+        cache.insert(&query("{ songs { title } }"), &result(), 0);
+
+        cache.invalidate(&event("Band"));
+        assert!(cache.get(&query("{ musicians { name bands { name } } }")).is_none());
+        assert!(cache.get(&query("{ songs { title } }")).is_some());
+    }
+
+    #[test]
+    fn invalidates_all_results_if_store_events_were_dropped() {
+        let mut cache = QueryCache::new(Duration::from_secs(60), 1024 * 1024);
+        cache.insert(&query("{ musicians { name } }"), &result(), 0);
+        cache.insert(&query("{ songs { title } }"), &result(), 0);
+
+        cache.invalidate(&StoreEvent::EventsDropped);
+        assert_eq!(cache.entries.len(), 0);
+        assert_eq!(cache.size, 0);
+    }
+
+    #[test]
+    fn does_not_cache_results_of_earlier_generations() {
+        let mut cache = QueryCache::new(Duration::from_secs(60), 1024 * 1024);
+        let generation = cache.generation();
+
+        // An unrelated change still means that the result may be stale
+        cache.invalidate(&event("Song"));
+        cache.insert(&query("{ musicians { name } }"), &result(), generation);
+        assert_eq!(cache.entries.len(), 0);
+
+        cache.insert(&query("{ musicians { name } }"), &result(), cache.generation());
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn expires_results_after_the_ttl() {
+        let mut cache = QueryCache::new(Duration::from_millis(10), 1024 * 1024);
+        cache.insert(&query("{ songs { title } }"), &result(), 0);
+        sleep(Duration::from_millis(20));
+        assert!(cache.get(&query("{ songs { title } }")).is_none());
+        assert_eq!(cache.entries.len(), 0);
+    }
+
+    #[test]
+    fn evicts_the_oldest_results_when_full() {
+        // Make room for exactly two results
+        let size = super::estimate_size(&result().data.unwrap());
+        let mut cache = QueryCache::new(Duration::from_secs(60), 2 * size);
+        cache.insert(&query("{ songs { title } }"), &result(), 0);
+        cache.insert(&query("{ musicians { name } }"), &result(), 0);
+        cache.insert(&query("{ bands { name } }"), &result(), 0);
+
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.get(&query("{ songs { title } }")).is_none());
+        assert!(cache.get(&query("{ musicians { name } }")).is_some());
+        assert!(cache.get(&query("{ bands { name } }")).is_some());
+    }
+}
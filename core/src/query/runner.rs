@@ -1,62 +1,376 @@
+use futures::future::{self, Either};
 use futures::prelude::*;
+use futures::stream;
 use futures::sync::mpsc::{channel, Receiver, Sender};
+use futures_cpupool::{Builder as CpuPoolBuilder, CpuPool};
 use slog;
 use std::sync::{Arc, Mutex};
-use tokio_core::reactor::Handle;
+use std::time::{Duration, Instant};
+use tokio_core::reactor::{Handle, Timeout};
 
-use graph::prelude::{Query, QueryRunner as QueryRunnerTrait, Store};
+use graph::components::store::StoreEvent;
+use graph::prelude::{
+    IncrementalResult, Query, QueryError, QueryExecutionError, QueryResult,
+    QueryRunner as QueryRunnerTrait, QueryTracer, Store,
+};
 use graph_graphql::prelude::*;
 
+use super::cache::QueryCache;
+
+/// Settings for the query runner.
+#[derive(Clone, Debug)]
+pub struct QueryRunnerConfig {
+    /// How long query results are cached at most. A zero TTL disables the cache.
+    pub cache_ttl: Duration,
+    /// The maximum estimated size of all cached query results in bytes. A zero
+    /// size disables the cache.
+    pub cache_max_size: usize,
+    /// The maximum nesting depth of fields in a query.
+    pub max_depth: Option<u8>,
+    /// The maximum complexity of a query, i.e. the number of values it may resolve.
+    pub max_complexity: Option<u64>,
+    /// The maximum value of `first` arguments.
+    pub max_first: Option<u32>,
+    /// The maximum value of `skip` arguments.
+    pub max_skip: Option<u32>,
+    /// The maximum number of entities a query may resolve.
+    pub max_entities: Option<u64>,
+}
+
+impl Default for QueryRunnerConfig {
+    fn default() -> Self {
+        QueryRunnerConfig {
+            cache_ttl: Duration::from_secs(300),
+            cache_max_size: 64 * 1024 * 1024,
+            max_depth: Some(10),
+            max_complexity: Some(10_000_000),
+            max_first: Some(1000),
+            max_skip: Some(5000),
+            max_entities: Some(2_000_000),
+        }
+    }
+}
+
 /// Common query runner implementation for The Graph.
+///
+/// Queries are executed on a worker thread, so that they don't block the
+/// reactor while they are executed. Since all queries and all indexing share
+/// a single store behind a mutex, queries are executed one at a time;
+/// indexing events handled on the reactor still wait for the query that is
+/// holding the store.
 pub struct QueryRunner<S> {
     logger: slog::Logger,
     query_sink: Sender<Query>,
+    store_event_sink: Sender<StoreEvent>,
     store: Arc<Mutex<S>>,
+    cache: Option<Arc<Mutex<QueryCache>>>,
     runtime: Handle,
+    pool: CpuPool,
+    config: QueryRunnerConfig,
 }
 
 impl<S> QueryRunner<S>
 where
-    S: Store + Sized + 'static,
+    S: Store + Send + Sized + 'static,
 {
     /// Creates a new query runner.
-    pub fn new(logger: &slog::Logger, runtime: Handle, store: Arc<Mutex<S>>) -> Self {
+    ///
+    /// Cached results are only invalidated by the events passed to the
+    /// store event sink, so the cache should only be enabled if the store
+    /// publishes its changes.
+    pub fn new(
+        logger: &slog::Logger,
+        runtime: Handle,
+        store: Arc<Mutex<S>>,
+        config: QueryRunnerConfig,
+    ) -> Self {
         let (sink, stream) = channel(100);
+        let (store_event_sink, store_event_stream) = channel(100);
         let runner = QueryRunner {
             logger: logger.new(o!("component" => "QueryRunner")),
             query_sink: sink,
+            store_event_sink,
             store: store,
+            cache: if config.cache_ttl == Duration::from_secs(0) || config.cache_max_size == 0 {
+                None
+            } else {
+                Some(Arc::new(Mutex::new(QueryCache::new(
+                    config.cache_ttl,
+                    config.cache_max_size,
+                ))))
+            },
             runtime,
+            pool: CpuPoolBuilder::new()
+                .pool_size(1)
+                .name_prefix("query-runner-")
+                .create(),
+            config,
         };
         runner.run_queries(stream);
+        runner.handle_store_events(store_event_stream);
         runner
     }
 
-    /// Spawns a Tokio task to run any queries received through the given stream.
+    /// Spawns a Tokio task that passes any queries received through the given
+    /// stream on to the worker thread, one at a time.
+    ///
+    /// Queries count as pending until their subsequent results have all been
+    /// produced or their deadline has passed. Subsequent results are produced
+    /// on the worker thread as well, in between the queries that follow.
     fn run_queries(&self, stream: Receiver<Query>) {
         info!(self.logger, "Preparing to run queries");
 
         let logger = self.logger.clone();
         let store = self.store.clone();
+        let cache = self.cache.clone();
+        let pool = self.pool.clone();
+        let config = self.config.clone();
+        let runtime = self.runtime.clone();
+
+        self.runtime.spawn(stream.for_each(move |mut query| {
+            let logger = logger.clone();
+            let store = store.clone();
+            let cache = cache.clone();
+            let config = config.clone();
+            let subsequent_results_pool = pool.clone();
+            let runtime = runtime.clone();
+            let pending = query.pending.take();
+            let deadline = query.deadline;
+
+            pool.spawn_fn(move || {
+                let (result, subsequent_results) = execute_query(
+                    &logger,
+                    &query,
+                    store,
+                    cache.as_ref().map(|cache| &**cache),
+                    &config,
+                );
+
+                // The query may have timed out in the meantime
+                if query.result_sender.send(result).is_err() {
+                    debug!(logger, "Query result is no longer awaited");
+                }
+                Ok(subsequent_results)
+            }).map(move |subsequent_results| {
+                let produced = match subsequent_results {
+                    Some(subsequent_results) => Either::A(produce_subsequent_results(
+                        subsequent_results,
+                        deadline,
+                        &subsequent_results_pool,
+                        &runtime,
+                    )),
+                    None => Either::B(future::ok(())),
+                };
+
+                // Only now that it has been executed does the query stop
+                // counting as pending
+                runtime.spawn(produced.then(move |_| {
+                    drop(pending);
+                    Ok(())
+                }));
+            })
+        }));
+    }
 
-        self.runtime.spawn(stream.for_each(move |query| {
-            let options = ExecutionOptions {
-                logger: logger.clone(),
-                resolver: StoreResolver::new(&logger, store.clone()),
-            };
-            let result = execute(&query, options);
-
-            query
-                .result_sender
-                .send(result)
-                .expect("Failed to deliver query result");
+    /// Spawns a Tokio task to invalidate cached query results on store events.
+    fn handle_store_events(&self, stream: Receiver<StoreEvent>) {
+        let cache = self.cache.clone();
+
+        self.runtime.spawn(stream.for_each(move |event| {
+            if let Some(ref cache) = cache {
+                cache.lock().unwrap().invalidate(&event);
+            }
             Ok(())
         }));
     }
 }
 
+/// The subsequent results of a query that remain to be produced.
+struct PendingResults {
+    /// Produces the results as they are consumed.
+    producer: Box<Future<Item = (), Error = ()> + Send>,
+    /// Ends the results early if they are not produced in time.
+    sender: Sender<IncrementalResult>,
+}
+
+/// Executes a query, unless its result is cached, and returns its result
+/// along with its subsequent results, if there are any.
+fn execute_query<S>(
+    logger: &slog::Logger,
+    query: &Query,
+    store: Arc<Mutex<S>>,
+    cache: Option<&Mutex<QueryCache>>,
+    config: &QueryRunnerConfig,
+) -> (QueryResult, Option<PendingResults>)
+where
+    S: Store + Send + 'static,
+{
+    // Serve identical queries from the cache as long as the entities
+    // they select remain unchanged; traced queries are always executed.
+    // The generation is recorded before executing the query, so that its
+    // result isn't cached if the store changes in the meantime
+    let (cached_result, generation) = match cache {
+        Some(cache) => {
+            let mut cache = cache.lock().unwrap();
+            let generation = cache.generation();
+            if query.tracing {
+                (None, generation)
+            } else {
+                (cache.get(query), generation)
+            }
+        }
+        None => (None, 0),
+    };
+    if let Some(result) = cached_result {
+        return (result, None);
+    }
+
+    let tracer = if query.tracing {
+        Some(QueryTracer::new())
+    } else {
+        None
+    };
+
+    let mut resolver = StoreResolver::new(logger, store);
+    if let Some(ref tracer) = tracer {
+        resolver = resolver.with_tracer(tracer.clone());
+    }
+    let mut options = ExecutionOptions::new(logger.clone(), resolver);
+    options.max_depth = config.max_depth;
+    options.max_complexity = config.max_complexity;
+    options.max_first = config.max_first;
+    options.max_skip = config.max_skip;
+    options.max_entities = config.max_entities;
+    options.tracer = tracer;
+    let (mut result, subsequent_results) = execute_incrementally(query, options);
+
+    // Results with `@defer` or `@stream` parts are not cached; their
+    // subsequent results are only produced as they are consumed
+    match subsequent_results {
+        Some(subsequent_results) => {
+            let (sender, receiver) = channel(1);
+            let producer = Box::new(
+                sender
+                    .clone()
+                    .sink_map_err(|_| ())
+                    .send_all(stream::iter_ok(subsequent_results))
+                    .map(|_| ()),
+            );
+            result.subsequent_results = Some(receiver);
+            (result, Some(PendingResults { producer, sender }))
+        }
+        None => {
+            if let Some(cache) = cache {
+                cache.lock().unwrap().insert(query, &result, generation)
+            }
+            (result, None)
+        }
+    }
+}
+
+/// Produces the subsequent results of a query on the worker thread until they
+/// have all been produced or are no longer consumed. If the deadline of the
+/// query passes first, the results end with a timeout error instead.
+fn produce_subsequent_results(
+    results: PendingResults,
+    deadline: Option<Instant>,
+    pool: &CpuPool,
+    runtime: &Handle,
+) -> Box<Future<Item = (), Error = ()>> {
+    let PendingResults { producer, sender } = results;
+    let producer = pool.spawn(producer);
+
+    let timeout = match deadline.map(|deadline| Timeout::new_at(deadline, runtime)) {
+        Some(Ok(timeout)) => timeout,
+        _ => return Box::new(producer),
+    };
+
+    // Dropping the producer stops it before it produces the next result
+    Box::new(producer.select2(timeout).then(move |result| {
+        if let Ok(Either::B(_)) = result {
+            let mut sender = sender;
+            let _ = sender.try_send(IncrementalResult {
+                data: None,
+                items: None,
+                path: vec![],
+                label: None,
+                errors: Some(vec![QueryError::from(QueryExecutionError::Timeout)]),
+                has_next: false,
+            });
+        }
+        Ok(())
+    }))
+}
+
 impl<S> QueryRunnerTrait for QueryRunner<S> {
     fn query_sink(&mut self) -> Sender<Query> {
         self.query_sink.clone()
     }
+
+    fn store_event_sink(&mut self) -> Sender<StoreEvent> {
+        self.store_event_sink.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+    use futures::prelude::*;
+    use futures::sync::mpsc::channel;
+    use futures_cpupool::CpuPool;
+    use std::time::{Duration, Instant};
+    use tokio_core::reactor::Core;
+
+    use graph::prelude::*;
+
+    use super::{produce_subsequent_results, PendingResults};
+
+    #[test]
+    fn subsequent_results_end_with_an_error_once_the_deadline_passes() {
+        let mut core = Core::new().unwrap();
+        let pool = CpuPool::new(1);
+        let (sender, receiver) = channel(1);
+
+        // A producer that never produces a result
+        let results = PendingResults {
+            producer: Box::new(future::empty()),
+            sender,
+        };
+        let deadline = Instant::now() + Duration::from_millis(10);
+        core.run(produce_subsequent_results(
+            results,
+            Some(deadline),
+            &pool,
+            &core.handle(),
+        )).unwrap();
+
+        let results = core.run(receiver.collect()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].has_next);
+        assert_eq!(
+            results[0].errors.as_ref().unwrap()[0].code(),
+            QueryErrorCode::Timeout
+        );
+    }
+
+    #[test]
+    fn subsequent_results_end_once_they_have_been_produced() {
+        let mut core = Core::new().unwrap();
+        let pool = CpuPool::new(1);
+        let (sender, receiver) = channel(1);
+
+        let results = PendingResults {
+            producer: Box::new(future::ok(())),
+            sender,
+        };
+        let deadline = Instant::now() + Duration::from_secs(60);
+        core.run(produce_subsequent_results(
+            results,
+            Some(deadline),
+            &pool,
+            &core.handle(),
+        )).unwrap();
+
+        assert!(core.run(receiver.collect()).unwrap().is_empty());
+    }
 }
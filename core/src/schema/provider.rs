@@ -1,7 +1,7 @@
 use futures::prelude::*;
 use futures::sync::mpsc::{channel, Receiver, Sender};
 use slog::Logger;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use tokio_core::reactor::Handle;
 
 use graph::components::schema::{SchemaProvider as SchemaProviderTrait, SchemaProviderEvent};
@@ -69,7 +69,7 @@ impl SchemaProvider {
         output: Sender<SchemaProviderEvent>,
     ) -> impl Future<Item = (), Error = ()> {
         let sink_err_logger = logger.clone();
-        let mut input_schemas = HashMap::new();
+        let mut input_schemas = BTreeMap::new();
         let mut combined_schema: Option<Schema> = None;
 
         input
@@ -85,31 +85,41 @@ impl SchemaProvider {
                     }
                 };
 
-                // Derive a full-fledged API schema from the first input schema
-                //
-                // NOTE: For the moment, we're simply picking the first schema
-                // we can find in the map. Once we support multiple subgraphs,
-                // this would be where we combine them into one and also detect
-                // conflicts
-                combined_schema = input_schemas.values().next().and_then(|schema| {
-                    api_schema(&schema.document)
-                        .map(|document| Schema {
-                            id: schema.id.clone(),
-                            document,
-                        })
-                        .map_err(|e| {
-                            error!(
-                                logger,
-                                "Failed to derive API schema from input schemas: {}", e
-                            );
-                        })
-                        .ok()
-                });
-
-                // Forward the new combined schema to them through the event channel
+                // Derive a full-fledged API schema from each input schema;
+                // the input schemas are ordered by subgraph ID, so that
+                // conflicts are always resolved the same way
+                let api_schemas: Vec<_> = input_schemas
+                    .values()
+                    .filter_map(|schema: &Schema| {
+                        api_schema(&schema.document)
+                            .map(|document| Schema {
+                                id: schema.id.clone(),
+                                document,
+                            })
+                            .map_err(|e| {
+                                error!(
+                                    logger,
+                                    "Failed to derive API schema from input schema: {}", e;
+                                    "subgraph" => &schema.id
+                                );
+                            })
+                            .ok()
+                    })
+                    .collect();
+
+                // Combine the API schemas into one, reporting subgraphs that
+                // had to be left out because of conflicts; they are still
+                // served at their own endpoints
+                let (schema, errors) = compose_schemas(&api_schemas);
+                for e in errors {
+                    warn!(logger, "Subgraph left out of the combined schema: {}", e);
+                }
+                combined_schema = schema;
+
+                // Forward the new combined schema to them through the event
+                // channel, along with the schemas of the individual subgraphs
                 info!(logger, "Forwarding the combined schema");
-                // Mock processing the event from the subgraph provider
-                SchemaProviderEvent::SchemaChanged(combined_schema.clone())
+                SchemaProviderEvent::SchemaChanged(combined_schema.clone(), api_schemas)
             })
             .forward(output.sink_map_err(move |e| {
                 error!(
@@ -166,10 +176,12 @@ mod tests {
         };
 
         // Extract the output schema from the schema provider event
-        let SchemaProviderEvent::SchemaChanged(output_schema) = output_event;
+        let SchemaProviderEvent::SchemaChanged(output_schema, subgraph_schemas) = output_event;
         let output_schema = output_schema.expect("Combined schema must not be None");
 
         assert_eq!(output_schema.id, input_schema.id);
+        assert_eq!(subgraph_schemas.len(), 1);
+        assert_eq!(subgraph_schemas[0].id, input_schema.id);
 
         // The output schema must include the input schema types
         assert_eq!(
@@ -181,4 +193,95 @@ mod tests {
         ast::get_named_type(&output_schema.document, &"Query".to_string())
             .expect("Query type missing in output schema");
     }
+
+    #[test]
+    fn emits_a_combined_schema_of_all_added_schemas() {
+        let mut core = Core::new().unwrap();
+
+        // Set up the schema provider
+        let logger = slog::Logger::root(slog::Discard, o!());
+        let mut schema_provider = CoreSchemaProvider::new(&logger, core.handle());
+        let mut schema_sink = schema_provider.event_sink();
+        let schema_stream = schema_provider.take_event_stream().unwrap();
+
+        // Send two input schemas to the schema provider
+        for (id, sdl) in [
+            ("subgraph-a", "type User { name: String! }"),
+            ("subgraph-b", "type Band { name: String! }"),
+        ].iter()
+        {
+            let input_schema = Schema {
+                id: id.to_string(),
+                document: graphql_parser::parse_schema(sdl).unwrap(),
+            };
+            schema_sink = schema_sink
+                .send(SchemaEvent::SchemaAdded(input_schema))
+                .wait()
+                .unwrap();
+        }
+
+        // The second schema provider event combines both schemas
+        let work = schema_stream.skip(1).take(1).into_future();
+        let output_event = if let Ok(x) = core.run(work) {
+            x.0.expect("Schema provider event must not be None")
+        } else {
+            panic!("Failed to receive schema provider event from the stream")
+        };
+
+        let SchemaProviderEvent::SchemaChanged(output_schema, _) = output_event;
+        let output_schema = output_schema.expect("Combined schema must not be None");
+
+        assert_eq!(output_schema.id, "subgraph-a,subgraph-b");
+        for name in ["User", "Band", "Query"].iter() {
+            ast::get_named_type(&output_schema.document, &name.to_string())
+                .expect("Type missing in output schema");
+        }
+    }
+
+    #[test]
+    fn emits_the_schemas_of_all_subgraphs_along_with_the_combined_schema() {
+        let mut core = Core::new().unwrap();
+
+        // Set up the schema provider
+        let logger = slog::Logger::root(slog::Discard, o!());
+        let mut schema_provider = CoreSchemaProvider::new(&logger, core.handle());
+        let mut schema_sink = schema_provider.event_sink();
+        let schema_stream = schema_provider.take_event_stream().unwrap();
+
+        // Send two input schemas with a union to the schema provider
+        for (id, sdl) in [
+            ("subgraph-a", "type User { id: ID! } union Member = User"),
+            ("subgraph-b", "type Band { id: ID! }"),
+        ].iter()
+        {
+            let input_schema = Schema {
+                id: id.to_string(),
+                document: graphql_parser::parse_schema(sdl).unwrap(),
+            };
+            schema_sink = schema_sink
+                .send(SchemaEvent::SchemaAdded(input_schema))
+                .wait()
+                .unwrap();
+        }
+
+        let work = schema_stream.skip(1).take(1).into_future();
+        let output_event = if let Ok(x) = core.run(work) {
+            x.0.expect("Schema provider event must not be None")
+        } else {
+            panic!("Failed to receive schema provider event from the stream")
+        };
+
+        // Each subgraph schema is the complete API schema of the subgraph
+        let SchemaProviderEvent::SchemaChanged(_, subgraph_schemas) = output_event;
+        assert_eq!(
+            subgraph_schemas
+                .iter()
+                .map(|schema| schema.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["subgraph-a", "subgraph-b"]
+        );
+        ast::get_named_type(&subgraph_schemas[0].document, &"Member".to_string())
+            .expect("Union missing in subgraph schema");
+        assert!(ast::get_named_type(&subgraph_schemas[1].document, &"User".to_string()).is_none());
+    }
 }
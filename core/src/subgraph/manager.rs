@@ -1,13 +1,14 @@
-use ethereum_types::H256;
 use futures::prelude::*;
 use futures::sync::mpsc::{channel, Receiver, Sender};
 use slog::Logger;
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use tokio_core::reactor::Handle;
 
 use graph::components::store::EventSource;
 use graph::components::subgraph::RuntimeHostEvent;
 use graph::components::subgraph::SubgraphProviderEvent;
+use graph::data::meta::{block_processed_meta, deployment_meta, indexing_failed_meta, meta_key};
 use graph::prelude::*;
 
 pub struct RuntimeManager {
@@ -58,27 +59,55 @@ impl RuntimeManager where {
         S: Store + 'static,
         T: RuntimeHostBuilder + 'static,
     {
-        // Handles each incoming event from the subgraph.
-        fn handle_event<S: Store + 'static>(store: Arc<Mutex<S>>, event: RuntimeHostEvent) {
+        // Handles each incoming event from the subgraph. This runs on the
+        // reactor and waits for any query that is accessing the store.
+        fn handle_event<S: Store + 'static>(
+            logger: &Logger,
+            store: Arc<Mutex<S>>,
+            event: RuntimeHostEvent,
+        ) {
             match event {
-                RuntimeHostEvent::EntitySet(store_key, entity) => {
+                RuntimeHostEvent::EntitySet(store_key, entity, block) => {
                     store
                         .lock()
                         .unwrap()
-                        .set(
-                            store_key,
-                            entity,
-                            EventSource::EthereumBlock(H256::random()),
-                        )
+                        .set(store_key, entity, EventSource::EthereumBlock(block))
                         .expect("Failed to set entity in the store");
                 }
-                RuntimeHostEvent::EntityRemoved(store_key) => {
+                RuntimeHostEvent::EntityRemoved(store_key, block) => {
                     store
                         .lock()
                         .unwrap()
-                        .delete(store_key, EventSource::EthereumBlock(H256::random()))
+                        .delete(store_key, EventSource::EthereumBlock(block))
                         .expect("Failed to delete entity from the store");
                 }
+
+                // Record the indexing progress of the subgraph, attributed to
+                // the block so that it is reverted along with the block; this
+                // is only bookkeeping, so failing to record it doesn't stop
+                // indexing
+                RuntimeHostEvent::EthereumEventHandled(subgraph_id, block) => {
+                    let result = store.lock().unwrap().set(
+                        meta_key(&subgraph_id),
+                        block_processed_meta(&block),
+                        EventSource::EthereumBlock(block),
+                    );
+                    if result.is_err() {
+                        error!(logger, "Failed to record indexing progress in the store";
+                               "subgraph" => &subgraph_id);
+                    }
+                }
+                RuntimeHostEvent::EthereumEventFailed(subgraph_id, block) => {
+                    let result = store.lock().unwrap().set(
+                        meta_key(&subgraph_id),
+                        indexing_failed_meta(),
+                        EventSource::EthereumBlock(block),
+                    );
+                    if result.is_err() {
+                        error!(logger, "Failed to record indexing error in the store";
+                               "subgraph" => &subgraph_id);
+                    }
+                }
             }
         }
 
@@ -90,6 +119,16 @@ impl RuntimeManager where {
                     info!(logger, "Host mapping runtimes for subgraph";
                           "location" => &manifest.location);
 
+                    let result = store.lock().unwrap().set(
+                        meta_key(&manifest.id),
+                        deployment_meta(&manifest.id, SystemTime::now()),
+                        EventSource::None,
+                    );
+                    if result.is_err() {
+                        error!(logger, "Failed to record subgraph deployment in the store";
+                               "subgraph" => &manifest.id);
+                    }
+
                     // Create a new runtime host for each data source in the subgraph manifest
                     let mut new_hosts = manifest
                         .data_sources
@@ -100,10 +139,11 @@ impl RuntimeManager where {
                     // Tokio task will terminate when the corresponding subgraph
                     // is removed and the host and its event sender are dropped
                     for mut new_host in new_hosts {
+                        let logger = logger.clone();
                         let store = store.clone();
                         runtime.spawn(new_host.take_event_stream().unwrap().for_each(
                             move |event| {
-                                handle_event(store.clone(), event);
+                                handle_event(&logger, store.clone(), event);
                                 Ok(())
                             },
                         ));
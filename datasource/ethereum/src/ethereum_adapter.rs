@@ -141,6 +141,7 @@ impl<T: 'static + web3::Transport> EthereumAdapterTrait for EthereumAdapter<T> {
                     address: log.address,
                     event_signature: log.topics[0],
                     block_hash: log.block_hash.unwrap(),
+                    block_number: log.block_number.unwrap().as_u64(),
                     params: log_data.params,
                     removed: log.is_removed(),
                 }),
@@ -117,12 +117,20 @@ pub struct EthereumEventSubscription {
     pub event: Event,
 }
 
+/// A reference to an Ethereum block by its hash and number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EthereumBlockPointer {
+    pub hash: H256,
+    pub number: u64,
+}
+
 /// An event logged for a specific contract address and event signature.
 #[derive(Debug)]
 pub struct EthereumEvent {
     pub address: Address,
     pub event_signature: H256,
     pub block_hash: H256,
+    pub block_number: u64,
     pub params: Vec<LogParam>,
     pub removed: bool,
 }
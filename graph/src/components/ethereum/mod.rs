@@ -1,9 +1,10 @@
 mod adapter;
 
 pub use self::adapter::{
-    BlockNumberRange, EthereumAdapter, EthereumContractCall, EthereumContractCallError,
-    EthereumContractState, EthereumContractStateError, EthereumContractStateRequest, EthereumEvent,
-    EthereumEventSubscription, EthereumSubscriptionError,
+    BlockNumberRange, EthereumAdapter, EthereumBlockPointer, EthereumContractCall,
+    EthereumContractCallError, EthereumContractState, EthereumContractStateError,
+    EthereumContractStateRequest, EthereumEvent, EthereumEventSubscription,
+    EthereumSubscriptionError,
 };
 
 pub use web3::types::BlockNumber;
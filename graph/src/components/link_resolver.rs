@@ -1,8 +1,11 @@
 use data::subgraph::Link;
 use failure;
+use futures::future;
 use futures::prelude::*;
 use ipfs_api;
 use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
 
 /// Resolves links to subgraph manifests and resources referenced by them.
 pub trait LinkResolver {
@@ -35,3 +38,24 @@ impl LinkResolver for ipfs_api::IpfsClient {
         )
     }
 }
+
+/// Resolves links to files on disk, relative to a base directory. Allows to
+/// work with subgraph manifests offline, before they are deployed to IPFS.
+#[derive(Clone, Debug)]
+pub struct FileLinkResolver {
+    base: PathBuf,
+}
+
+impl FileLinkResolver {
+    pub fn new(base: PathBuf) -> Self {
+        FileLinkResolver { base }
+    }
+}
+
+impl LinkResolver for FileLinkResolver {
+    fn cat(&self, link: &Link) -> Box<Future<Item = Vec<u8>, Error = Box<Error>>> {
+        Box::new(future::result(
+            fs::read(self.base.join(&link.link)).map_err(|e| Box::new(e) as Box<Error>),
+        ))
+    }
+}
@@ -1,9 +1,14 @@
 use futures::sync::mpsc::Sender;
 
+use components::store::StoreEvent;
 use data::query::Query;
 
 /// Common trait for query runners that run queries against a [Store](../store/trait.Store.html).
 pub trait QueryRunner {
     // Sender to which others can write queries that need to be run.
     fn query_sink(&mut self) -> Sender<Query>;
+
+    /// Sender to which others should write store events that might invalidate
+    /// query results.
+    fn store_event_sink(&mut self) -> Sender<StoreEvent>;
 }
@@ -6,8 +6,9 @@ use data::schema::Schema;
 /// Events emitted by [SchemaProvider](trait.SchemaProvider.html) implementations.
 #[derive(Clone, Debug)]
 pub enum SchemaProviderEvent {
-    /// The provided schema has changed.
-    SchemaChanged(Option<Schema>),
+    /// The provided schema has changed. Carries the schema composed from all
+    /// subgraphs, if there are any, along with the API schema of each subgraph.
+    SchemaChanged(Option<Schema>, Vec<Schema>),
 }
 
 /// A `SchemaProvider` is responsible for spawning a task that listens to the
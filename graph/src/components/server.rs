@@ -4,10 +4,11 @@ use futures::sync::oneshot::Canceled;
 use serde::ser::*;
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
 
 use super::schema::SchemaProviderEvent;
 use super::store::StoreEvent;
-use data::query::{Query, QueryError};
+use data::query::{ErrorExtensions, Query, QueryError, QueryErrorCode};
 use util::stream::StreamError;
 
 /// Errors that can occur while processing incoming requests.
@@ -17,6 +18,29 @@ pub enum GraphQLServerError {
     ClientError(String),
     QueryError(QueryError),
     InternalError(String),
+    /// The request body is larger than the limit, in bytes.
+    PayloadTooLarge(usize),
+    /// The query did not finish within the time limit.
+    Timeout(Duration),
+    /// Too many queries are pending to accept another one.
+    Overloaded,
+}
+
+impl GraphQLServerError {
+    /// Returns the code that classifies the error for clients.
+    pub fn code(&self) -> QueryErrorCode {
+        match self {
+            GraphQLServerError::ClientError(_) | GraphQLServerError::PayloadTooLarge(_) => {
+                QueryErrorCode::BadRequest
+            }
+            GraphQLServerError::Timeout(_) => QueryErrorCode::Timeout,
+            GraphQLServerError::Overloaded => QueryErrorCode::ServiceUnavailable,
+            GraphQLServerError::QueryError(e) => e.code(),
+            GraphQLServerError::Canceled(_) | GraphQLServerError::InternalError(_) => {
+                QueryErrorCode::InternalServerError
+            }
+        }
+    }
 }
 
 impl From<Canceled> for GraphQLServerError {
@@ -50,6 +74,17 @@ impl fmt::Display for GraphQLServerError {
             &GraphQLServerError::ClientError(ref s) => write!(f, "{}", s),
             &GraphQLServerError::QueryError(ref e) => write!(f, "{}", e),
             &GraphQLServerError::InternalError(ref s) => write!(f, "{}", s),
+            &GraphQLServerError::PayloadTooLarge(limit) => {
+                write!(f, "Request body is larger than {} bytes", limit)
+            }
+            &GraphQLServerError::Timeout(timeout) => write!(
+                f,
+                "Query did not finish within {} ms",
+                timeout.as_secs() * 1000 + u64::from(timeout.subsec_millis())
+            ),
+            &GraphQLServerError::Overloaded => {
+                write!(f, "Too many queries are pending, try again later")
+            }
         }
     }
 }
@@ -65,6 +100,9 @@ impl Error for GraphQLServerError {
             &GraphQLServerError::ClientError(_) => None,
             &GraphQLServerError::QueryError(ref e) => Some(e),
             &GraphQLServerError::InternalError(_) => None,
+            &GraphQLServerError::PayloadTooLarge(_) => None,
+            &GraphQLServerError::Timeout(_) => None,
+            &GraphQLServerError::Overloaded => None,
         }
     }
 }
@@ -77,9 +115,10 @@ impl Serialize for GraphQLServerError {
         if let &GraphQLServerError::QueryError(ref e) = self {
             serializer.serialize_some(e)
         } else {
-            let mut map = serializer.serialize_map(Some(1))?;
+            let mut map = serializer.serialize_map(Some(2))?;
             let msg = format!("{}", self);
             map.serialize_entry("message", msg.as_str())?;
+            map.serialize_entry("extensions", &ErrorExtensions { code: self.code() })?;
             map.end()
         }
     }
@@ -1,6 +1,7 @@
 use ethereum_types::H256;
 use futures::sync::mpsc::{Receiver, Sender};
 
+use components::ethereum::EthereumBlockPointer;
 use components::schema::SchemaProviderEvent;
 use data::store::*;
 use std::fmt;
@@ -79,8 +80,47 @@ pub struct StoreQuery {
     pub range: Option<StoreRange>,
 }
 
+/// A query for the changes that were made to an entity in a store.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoreHistoryQuery {
+    /// The entity whose changes to return.
+    pub key: StoreKey,
+
+    /// How many changes to return at most, starting with the oldest.
+    pub first: Option<usize>,
+
+    /// Only return changes made in this block or later.
+    pub from_block: Option<u64>,
+}
+
+/// The kind of a change made to an entity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntityOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A change that was made to an entity, along with the block it was made for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityChange {
+    pub operation: EntityOperation,
+
+    /// The hash of the block the change was made for, if it is known.
+    pub block_hash: Option<H256>,
+
+    /// The number of the block the change was made for, if it is known.
+    pub block_number: Option<u64>,
+
+    /// The entity before the change; `None` if the entity was inserted.
+    pub before: Option<Entity>,
+
+    /// The entity after the change; `None` if the entity was deleted.
+    pub after: Option<Entity>,
+}
+
 /// Events emitted by implementations of [Store](trait.Store.html).
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum StoreEvent {
     /// An entity was added to the store.
     EntityAdded(Entity),
@@ -90,11 +130,17 @@ pub enum StoreEvent {
 
     /// An entity was changed in the store.
     EntityChanged(Entity),
+
+    /// Events were dropped because their receiver didn't keep up with them;
+    /// any entity may have changed.
+    EventsDropped,
 }
 
 /// The source of the events being sent to the store
 pub enum EventSource {
-    EthereumBlock(H256),
+    /// Changes that are not made for any block, like deploying a subgraph.
+    None,
+    EthereumBlock(EthereumBlockPointer),
 }
 
 // Implementing the display trait also provides a ToString trait implementation
@@ -102,7 +148,8 @@ impl fmt::Display for EventSource {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let printable_source = match *self {
             // Use LowerHex to format hash as hex string
-            EventSource::EthereumBlock(hash) => format!("{:x}", hash),
+            EventSource::None => String::from("NONE"),
+            EventSource::EthereumBlock(block) => format!("{:x}", block.hash),
         };
         write!(f, "{}", printable_source)
     }
@@ -121,6 +168,15 @@ pub trait BasicStore {
 
     /// Queries the store for entities that match the store query.
     fn find(&self, query: StoreQuery) -> Result<Vec<Entity>, ()>;
+
+    /// Returns the changes made to an entity, oldest first.
+    fn history(&self, query: StoreHistoryQuery) -> Result<Vec<EntityChange>, ()>;
+
+    /// Returns the SQL that `find` would run for the store query, if the store
+    /// is backed by a SQL database. Used for tracing query executions.
+    fn query_sql(&self, _query: &StoreQuery) -> Option<String> {
+        None
+    }
 }
 
 /// Common trait for store implementations.
@@ -1,12 +1,19 @@
+use components::ethereum::EthereumBlockPointer;
 use prelude::*;
 
 /// Events emitted by a runtime host.
 #[derive(Debug, Clone, PartialEq)]
 pub enum RuntimeHostEvent {
-    /// An entity should be created or updated.
-    EntitySet(StoreKey, Entity),
-    /// An entity should be removed.
-    EntityRemoved(StoreKey),
+    /// An entity should be created or updated for the given block.
+    EntitySet(StoreKey, Entity, EthereumBlockPointer),
+    /// An entity should be removed for the given block.
+    EntityRemoved(StoreKey, EthereumBlockPointer),
+    /// The mappings of a subgraph handled an Ethereum event of the given block:
+    /// `(subgraph ID, block)`.
+    EthereumEventHandled(String, EthereumBlockPointer),
+    /// The mappings of a subgraph failed to handle an Ethereum event of the
+    /// given block: `(subgraph ID, block)`.
+    EthereumEventFailed(String, EthereumBlockPointer),
 }
 
 /// Common trait for runtime host implementations.
@@ -0,0 +1,48 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use components::ethereum::EthereumBlockPointer;
+use components::store::StoreKey;
+use data::store::{scalar, Entity, Value};
+
+/// The entity type under which the node records the indexing status of each
+/// subgraph; also the name of the GraphQL type of the `_meta` field.
+pub const META_TYPE_NAME: &'static str = "_Meta_";
+
+/// Returns the store key of the indexing status of a subgraph.
+pub fn meta_key(subgraph_id: &str) -> StoreKey {
+    StoreKey {
+        subgraph: subgraph_id.to_owned(),
+        entity: META_TYPE_NAME.to_owned(),
+        id: subgraph_id.to_owned(),
+    }
+}
+
+/// The indexing status of a subgraph that was just deployed. Merged into the
+/// existing status, so that the last block processed before is kept.
+pub fn deployment_meta(subgraph_id: &str, deployed_at: SystemTime) -> Entity {
+    let deployed_at = deployed_at
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    Entity::from(vec![
+        ("subgraph", Value::from(subgraph_id)),
+        ("hasIndexingErrors", Value::Bool(false)),
+        ("deployedAt", Value::Int(deployed_at as i32)),
+    ])
+}
+
+/// The update of the indexing status of a subgraph after its mappings have
+/// handled an event of `block`.
+pub fn block_processed_meta(block: &EthereumBlockPointer) -> Entity {
+    Entity::from(vec![
+        ("blockNumber", Value::Int(block.number as i32)),
+        ("blockHash", Value::Bytes(scalar::Bytes::from(&block.hash[..]))),
+    ])
+}
+
+/// The update of the indexing status of a subgraph after its mappings have
+/// failed to handle an event.
+pub fn indexing_failed_meta() -> Entity {
+    Entity::from(vec![("hasIndexingErrors", Value::Bool(true))])
+}
@@ -12,6 +12,7 @@ pub enum QueryExecutionError {
     OperationNotFound(String),
     NotSupported(String),
     NoRootQueryObjectType,
+    NoRootSubscriptionObjectType,
     ResolveEntityError(Pos, String),
     NonNullError(Pos, String),
     ListValueError(Pos, String),
@@ -19,6 +20,19 @@ pub enum QueryExecutionError {
     AbstractTypeError(String),
     InvalidArgumentError(Pos, String, q::Value),
     MissingArgumentError(Pos, String),
+    InvalidVariableTypeError(Pos, String),
+    MissingVariableError(Pos, String),
+    InvalidVariableError(Pos, String, q::Value),
+    ValidationError(Pos, String),
+    ResolveEntitiesError(String),
+    ValueParseError(String, String),
+    EntityAttributeError(String, String),
+    ListFilterError(String),
+    TooDeep(u8),
+    TooComplex(u64, u64),
+    TooManyEntities(u64, u64),
+    RangeArgumentError(Pos, String, i64, u32),
+    Timeout,
 }
 
 impl Error for QueryExecutionError {
@@ -42,6 +56,9 @@ impl fmt::Display for QueryExecutionError {
             QueryExecutionError::NoRootQueryObjectType => {
                 write!(f, "No root Query type defined in the schema")
             }
+            QueryExecutionError::NoRootSubscriptionObjectType => {
+                write!(f, "No root Subscription type defined in the schema")
+            }
             QueryExecutionError::ResolveEntityError(_, s) => {
                 write!(f, "Failed to resolve entity: {}", s)
             }
@@ -63,16 +80,189 @@ impl fmt::Display for QueryExecutionError {
             QueryExecutionError::MissingArgumentError(_, s) => {
                 write!(f, "No value provided for required argument: {}", s)
             }
+            QueryExecutionError::InvalidVariableTypeError(_, s) => {
+                write!(f, "Variable \"{}\" must have an input type", s)
+            }
+            QueryExecutionError::MissingVariableError(_, s) => {
+                write!(f, "No value provided for required variable: {}", s)
+            }
+            QueryExecutionError::InvalidVariableError(_, s, v) => {
+                write!(f, "Invalid value provided for variable \"{}\": {:?}", s, v)
+            }
+            QueryExecutionError::ValidationError(_, s) => write!(f, "{}", s),
+            QueryExecutionError::ResolveEntitiesError(s) => {
+                write!(f, "Failed to resolve entities: {}", s)
+            }
+            QueryExecutionError::ValueParseError(t, e) => {
+                write!(f, "Failed to decode `{}` value: {}", t, e)
+            }
+            QueryExecutionError::EntityAttributeError(e, a) => {
+                write!(f, "Entity `{}` has no attribute `{}`", e, a)
+            }
+            QueryExecutionError::ListFilterError(s) => {
+                write!(f, "Non-list value passed to `{}` filter", s)
+            }
+            QueryExecutionError::TooDeep(max) => {
+                write!(f, "Query is nested deeper than the maximum depth of {}", max)
+            }
+            QueryExecutionError::TooComplex(complexity, max) => write!(
+                f,
+                "Query has a complexity of {}, which exceeds the maximum of {}",
+                complexity, max
+            ),
+            QueryExecutionError::TooManyEntities(entities, max) => write!(
+                f,
+                "Query may return up to {} entities, which exceeds the maximum of {}",
+                entities, max
+            ),
+            QueryExecutionError::RangeArgumentError(_, s, value, max) => write!(
+                f,
+                "Value of argument \"{}\" ({}) exceeds the maximum of {}",
+                s, value, max
+            ),
+            QueryExecutionError::Timeout => write!(f, "Query did not finish in time"),
+        }
+    }
+}
+
+impl QueryExecutionError {
+    /// Returns the position in the query that the error refers to, if any.
+    pub fn position(&self) -> Option<&Pos> {
+        match self {
+            QueryExecutionError::ResolveEntityError(pos, _)
+            | QueryExecutionError::NonNullError(pos, _)
+            | QueryExecutionError::ListValueError(pos, _)
+            | QueryExecutionError::InvalidArgumentError(pos, _, _)
+            | QueryExecutionError::MissingArgumentError(pos, _)
+            | QueryExecutionError::InvalidVariableTypeError(pos, _)
+            | QueryExecutionError::MissingVariableError(pos, _)
+            | QueryExecutionError::InvalidVariableError(pos, _, _)
+            | QueryExecutionError::ValidationError(pos, _)
+            | QueryExecutionError::RangeArgumentError(pos, _, _, _) => Some(pos),
+            _ => None,
+        }
+    }
+
+    /// Returns the code that classifies the error for clients.
+    pub fn code(&self) -> QueryErrorCode {
+        match self {
+            QueryExecutionError::OperationNameRequired
+            | QueryExecutionError::OperationNotFound(_)
+            | QueryExecutionError::InvalidArgumentError(_, _, _)
+            | QueryExecutionError::MissingArgumentError(_, _)
+            | QueryExecutionError::InvalidVariableTypeError(_, _)
+            | QueryExecutionError::MissingVariableError(_, _)
+            | QueryExecutionError::InvalidVariableError(_, _, _)
+            | QueryExecutionError::ValidationError(_, _)
+            | QueryExecutionError::ListFilterError(_)
+            | QueryExecutionError::TooDeep(_)
+            | QueryExecutionError::TooComplex(_, _)
+            | QueryExecutionError::TooManyEntities(_, _)
+            | QueryExecutionError::RangeArgumentError(_, _, _, _) => {
+                QueryErrorCode::ValidationFailed
+            }
+            QueryExecutionError::ResolveEntityError(_, _)
+            | QueryExecutionError::ResolveEntitiesError(_)
+            | QueryExecutionError::ValueParseError(_, _)
+            | QueryExecutionError::EntityAttributeError(_, _) => QueryErrorCode::StoreError,
+            QueryExecutionError::NotSupported(_) => QueryErrorCode::NotSupported,
+            QueryExecutionError::NoRootQueryObjectType
+            | QueryExecutionError::NoRootSubscriptionObjectType
+            | QueryExecutionError::NonNullError(_, _)
+            | QueryExecutionError::ListValueError(_, _)
+            | QueryExecutionError::NamedTypeError(_)
+            | QueryExecutionError::AbstractTypeError(_) => QueryErrorCode::ExecutionFailed,
+            QueryExecutionError::Timeout => QueryErrorCode::Timeout,
+        }
+    }
+}
+
+/// A segment of the path to a value in a query result; either the response
+/// key of a field or the index of a list item.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+/// Classification of errors for clients, serialized as the `extensions.code`
+/// of GraphQL errors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueryErrorCode {
+    /// The request is malformed, e.g. it contains no query.
+    BadRequest,
+    /// The query could not be parsed.
+    ParseFailed,
+    /// The query is invalid against the schema or exceeds the query limits.
+    ValidationFailed,
+    /// Entities could not be loaded from the store.
+    StoreError,
+    /// The query took too long to execute.
+    Timeout,
+    /// The server is too busy to accept the query right now.
+    ServiceUnavailable,
+    /// The query uses a feature that is not supported.
+    NotSupported,
+    /// The query failed during execution for any other reason.
+    ExecutionFailed,
+    /// The server failed to process the request.
+    InternalServerError,
+}
+
+impl QueryErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueryErrorCode::BadRequest => "BAD_REQUEST",
+            QueryErrorCode::ParseFailed => "GRAPHQL_PARSE_FAILED",
+            QueryErrorCode::ValidationFailed => "GRAPHQL_VALIDATION_FAILED",
+            QueryErrorCode::StoreError => "STORE_ERROR",
+            QueryErrorCode::Timeout => "TIMEOUT",
+            QueryErrorCode::ServiceUnavailable => "SERVICE_UNAVAILABLE",
+            QueryErrorCode::NotSupported => "NOT_SUPPORTED",
+            QueryErrorCode::ExecutionFailed => "EXECUTION_FAILED",
+            QueryErrorCode::InternalServerError => "INTERNAL_SERVER_ERROR",
         }
     }
 }
 
+impl Serialize for QueryErrorCode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// The `extensions` entry of serialized GraphQL errors.
+#[derive(Serialize)]
+pub struct ErrorExtensions {
+    pub code: QueryErrorCode,
+}
+
 /// Error caused while processing a [Query](struct.Query.html) request.
 #[derive(Debug)]
 pub enum QueryError {
     EncodingError(FromUtf8Error),
     ParseError(q::ParseError),
     ExecutionError(QueryExecutionError),
+    /// An error that occurred while resolving or completing a field: the
+    /// error, the path of the field in the response and the position of the
+    /// field in the query, if known.
+    FieldError(QueryExecutionError, Vec<PathSegment>, Option<Pos>),
+}
+
+impl QueryError {
+    /// Returns the code that classifies the error for clients.
+    pub fn code(&self) -> QueryErrorCode {
+        match self {
+            QueryError::EncodingError(_) | QueryError::ParseError(_) => {
+                QueryErrorCode::ParseFailed
+            }
+            QueryError::ExecutionError(e) | QueryError::FieldError(e, _, _) => e.code(),
+        }
+    }
 }
 
 impl From<FromUtf8Error> for QueryError {
@@ -102,6 +292,7 @@ impl Error for QueryError {
         match self {
             &QueryError::EncodingError(ref e) => Some(e),
             &QueryError::ExecutionError(ref e) => Some(e),
+            &QueryError::FieldError(ref e, _, _) => Some(e),
             _ => None,
         }
     }
@@ -112,6 +303,7 @@ impl fmt::Display for QueryError {
         match self {
             &QueryError::EncodingError(ref e) => write!(f, "{}", e),
             &QueryError::ExecutionError(ref e) => write!(f, "{}", e),
+            &QueryError::FieldError(ref e, _, _) => write!(f, "{}", e),
             &QueryError::ParseError(ref e) => write!(f, "{}", e),
         }
     }
@@ -122,7 +314,7 @@ impl Serialize for QueryError {
     where
         S: Serializer,
     {
-        let mut map = serializer.serialize_map(Some(1))?;
+        let mut map = serializer.serialize_map(None)?;
 
         let msg = match self {
             // Serialize parse errors with their location (line, column) to make it easier
@@ -159,22 +351,35 @@ impl Serialize for QueryError {
                 parts[1].to_string()
             }
 
-            // Serialize entity resolution errors using their position
-            QueryError::ExecutionError(QueryExecutionError::ResolveEntityError(pos, _))
-            | QueryError::ExecutionError(QueryExecutionError::NonNullError(pos, _))
-            | QueryError::ExecutionError(QueryExecutionError::ListValueError(pos, _))
-            | QueryError::ExecutionError(QueryExecutionError::InvalidArgumentError(pos, _, _))
-            | QueryError::ExecutionError(QueryExecutionError::MissingArgumentError(pos, _)) => {
-                let mut location = HashMap::new();
-                location.insert("line", pos.line);
-                location.insert("column", pos.column);
-                map.serialize_entry("locations", &vec![location])?;
+            // Serialize execution errors using their position
+            QueryError::ExecutionError(e) => {
+                if let Some(pos) = e.position() {
+                    let mut location = HashMap::new();
+                    location.insert("line", pos.line);
+                    location.insert("column", pos.column);
+                    map.serialize_entry("locations", &vec![location])?;
+                }
+                format!("{}", self)
+            }
+
+            // Serialize field errors using their position (or that of the field)
+            // and the path of the field
+            QueryError::FieldError(e, path, field_pos) => {
+                if let Some(pos) = e.position().or(field_pos.as_ref()) {
+                    let mut location = HashMap::new();
+                    location.insert("line", pos.line);
+                    location.insert("column", pos.column);
+                    map.serialize_entry("locations", &vec![location])?;
+                }
+                map.serialize_entry("path", path)?;
                 format!("{}", self)
             }
+
             _ => format!("{}", self),
         };
 
         map.serialize_entry("message", msg.as_str())?;
+        map.serialize_entry("extensions", &ErrorExtensions { code: self.code() })?;
         map.end()
     }
 }
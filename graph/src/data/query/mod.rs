@@ -1,7 +1,11 @@
 mod error;
 mod query;
 mod result;
+mod trace;
 
-pub use self::error::{QueryError, QueryExecutionError};
-pub use self::query::{Query, QueryVariableValue, QueryVariables};
-pub use self::result::QueryResult;
+pub use self::error::{
+    ErrorExtensions, PathSegment, QueryError, QueryErrorCode, QueryExecutionError,
+};
+pub use self::query::{PendingQuery, Query, QueryVariableValue, QueryVariables};
+pub use self::result::{IncrementalResult, QueryResult};
+pub use self::trace::{QueryTrace, QueryTracer, ResolverTrace, StoreQueryTrace};
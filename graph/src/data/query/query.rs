@@ -1,21 +1,118 @@
 use futures::sync::oneshot;
 use graphql_parser::query as q;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
 use std::cmp::PartialEq;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
 use super::QueryResult;
 use data::schema::Schema;
 
-#[derive(Deserialize)]
-#[serde(untagged, remote = "q::Value")]
-enum GraphQLValue {
-    String(String),
+/// Variable value for a GraphQL query.
+#[derive(Clone, Debug)]
+pub struct QueryVariableValue(q::Value);
+
+impl QueryVariableValue {
+    /// Unwraps the underlying GraphQL value.
+    pub fn into_inner(self) -> q::Value {
+        self.0
+    }
 }
 
-/// Variable value for a GraphQL query.
-#[derive(Debug, Deserialize)]
-pub struct QueryVariableValue(#[serde(with = "GraphQLValue")] q::Value);
+/// Visitor that maps arbitrary JSON-like input onto GraphQL values.
+struct QueryVariableValueVisitor;
+
+impl<'de> Visitor<'de> for QueryVariableValueVisitor {
+    type Value = QueryVariableValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a GraphQL variable value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(QueryVariableValue(q::Value::Boolean(value)))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        // GraphQL integers are 32 bit; larger numbers are passed on as floats
+        // so that coercion can reject them for `Int` inputs
+        if value >= i32::min_value() as i64 && value <= i32::max_value() as i64 {
+            Ok(QueryVariableValue(q::Value::Int(q::Number::from(value as i32))))
+        } else {
+            Ok(QueryVariableValue(q::Value::Float(value as f64)))
+        }
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        if value <= i32::max_value() as u64 {
+            Ok(QueryVariableValue(q::Value::Int(q::Number::from(value as i32))))
+        } else {
+            Ok(QueryVariableValue(q::Value::Float(value as f64)))
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(QueryVariableValue(q::Value::Float(value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(QueryVariableValue(q::Value::String(value.to_owned())))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(QueryVariableValue(q::Value::String(value)))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(QueryVariableValue(q::Value::Null))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(QueryVariableValue(q::Value::Null))
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        QueryVariableValue::deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = vec![];
+        while let Some(value) = seq.next_element::<QueryVariableValue>()? {
+            values.push(value.0);
+        }
+        Ok(QueryVariableValue(q::Value::List(values)))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut values = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry::<String, QueryVariableValue>()? {
+            values.insert(key, value.0);
+        }
+        Ok(QueryVariableValue(q::Value::Object(values)))
+    }
+}
+
+impl<'de> Deserialize<'de> for QueryVariableValue {
+    fn deserialize<D>(deserializer: D) -> Result<QueryVariableValue, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(QueryVariableValueVisitor)
+    }
+}
 
 impl Deref for QueryVariableValue {
     type Target = q::Value;
@@ -43,8 +140,14 @@ impl<'a> From<&'a str> for QueryVariableValue {
     }
 }
 
+impl From<q::Value> for QueryVariableValue {
+    fn from(value: q::Value) -> Self {
+        QueryVariableValue(value)
+    }
+}
+
 /// Variable values for a GraphQL query.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct QueryVariables(HashMap<String, QueryVariableValue>);
 
 impl QueryVariables {
@@ -79,5 +182,42 @@ pub struct Query {
     pub schema: Schema,
     pub document: q::Document,
     pub variables: Option<QueryVariables>,
+    /// The name of the operation in the document to execute, if provided.
+    pub operation_name: Option<String>,
+    /// Whether to trace the execution of the query.
+    pub tracing: bool,
     pub result_sender: oneshot::Sender<QueryResult>,
+    /// Keeps the query counted as pending until it is dropped, i.e. until
+    /// it has been executed or turned away.
+    pub pending: Option<PendingQuery>,
+    /// The time by which the query, including its subsequent results, has
+    /// to be finished, if any.
+    pub deadline: Option<Instant>,
+}
+
+/// Counts a query as pending for as long as it is alive.
+#[derive(Debug)]
+pub struct PendingQuery {
+    pending: Arc<AtomicUsize>,
+    /// The number of pending queries, including this one, when it was created.
+    position: usize,
+}
+
+impl PendingQuery {
+    /// Adds a query to the given count of pending queries.
+    pub fn new(pending: Arc<AtomicUsize>) -> Self {
+        let position = pending.fetch_add(1, Ordering::SeqCst) + 1;
+        PendingQuery { pending, position }
+    }
+
+    /// The number of pending queries, including this one, when it was created.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl Drop for PendingQuery {
+    fn drop(&mut self) {
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+    }
 }
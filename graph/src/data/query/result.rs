@@ -1,17 +1,30 @@
+use futures::sync::mpsc::Receiver;
 use graphql_parser::query as q;
 
-use super::error::{QueryError, QueryExecutionError};
+use super::error::{PathSegment, QueryError, QueryExecutionError};
+use super::trace::QueryTrace;
 
 /// The result of running a query, if successful.
 #[derive(Debug)]
 pub struct QueryResult {
     pub data: Option<q::Value>,
     pub errors: Option<Vec<QueryError>>,
+    /// Results of `@defer` fragments and `@stream` lists that are delivered
+    /// after this result, if there are any.
+    pub subsequent_results: Option<Receiver<IncrementalResult>>,
+    /// Resolver timings and store queries of the execution, if the query
+    /// asked to be traced.
+    pub trace: Option<QueryTrace>,
 }
 
 impl QueryResult {
     pub fn new(data: Option<q::Value>) -> Self {
-        QueryResult { data, errors: None }
+        QueryResult {
+            data,
+            errors: None,
+            subsequent_results: None,
+            trace: None,
+        }
     }
 
     pub fn add_error(&mut self, e: QueryError) {
@@ -27,3 +40,28 @@ impl From<QueryExecutionError> for QueryResult {
         result
     }
 }
+
+impl From<Vec<QueryExecutionError>> for QueryResult {
+    fn from(e: Vec<QueryExecutionError>) -> Self {
+        let mut result = Self::new(None);
+        result.errors = Some(e.into_iter().map(QueryError::from).collect());
+        result
+    }
+}
+
+/// A part of a query result that is delivered after the initial result.
+///
+/// Either contains the data of a fragment marked with `@defer`, which belongs
+/// to the object at `path`, or further items of a list marked with `@stream`,
+/// the first of which belongs at `path`.
+#[derive(Debug)]
+pub struct IncrementalResult {
+    pub data: Option<q::Value>,
+    pub items: Option<Vec<q::Value>>,
+    pub path: Vec<PathSegment>,
+    /// The label passed to the `@defer` or `@stream` directive.
+    pub label: Option<String>,
+    pub errors: Option<Vec<QueryError>>,
+    /// Whether more results follow this one.
+    pub has_next: bool,
+}
@@ -0,0 +1,147 @@
+use chrono::{DateTime, Utc};
+use serde::ser::*;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::error::PathSegment;
+use components::store::StoreQuery;
+
+/// Version of the Apollo tracing format that traces are serialized in.
+const TRACING_FORMAT_VERSION: u8 = 1;
+
+/// Converts a duration into nanoseconds, the unit of all Apollo tracing timings.
+fn nanos(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000_000 + duration.subsec_nanos() as u64
+}
+
+/// Timing of a single field resolver.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolverTrace {
+    pub path: Vec<PathSegment>,
+    pub parent_type: String,
+    pub field_name: String,
+    pub return_type: String,
+    /// Nanoseconds between the start of the query and the start of the resolver.
+    pub start_offset: u64,
+    /// Nanoseconds the resolver took, including the resolvers of its subfields.
+    pub duration: u64,
+}
+
+/// Timing of a query sent to the store, along with what it returned.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreQueryTrace {
+    pub entity: String,
+    /// The store query, as issued by the resolver.
+    pub query: String,
+    /// The SQL the store generated for the query, if it is backed by a SQL database.
+    pub sql: Option<String>,
+    /// The number of entities returned, or `None` if the query failed.
+    pub rows: Option<usize>,
+    pub start_offset: u64,
+    pub duration: u64,
+}
+
+/// The trace of a query execution, serialized in the Apollo tracing format
+/// with an additional `storeQueries` field.
+#[derive(Clone, Debug)]
+pub struct QueryTrace {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub duration: u64,
+    pub resolvers: Vec<ResolverTrace>,
+    pub store_queries: Vec<StoreQueryTrace>,
+}
+
+impl Serialize for QueryTrace {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut execution = BTreeMap::new();
+        execution.insert("resolvers", &self.resolvers);
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("version", &TRACING_FORMAT_VERSION)?;
+        map.serialize_entry("startTime", &self.start_time.to_rfc3339())?;
+        map.serialize_entry("endTime", &self.end_time.to_rfc3339())?;
+        map.serialize_entry("duration", &self.duration)?;
+        map.serialize_entry("execution", &execution)?;
+        map.serialize_entry("storeQueries", &self.store_queries)?;
+        map.end()
+    }
+}
+
+/// Records the resolver timings and store queries of a query execution.
+///
+/// Clones share the same records, so that the executor and the resolver it
+/// uses can trace into the same `QueryTrace`.
+#[derive(Clone, Debug)]
+pub struct QueryTracer {
+    start_time: DateTime<Utc>,
+    start: Instant,
+    resolvers: Arc<Mutex<Vec<ResolverTrace>>>,
+    store_queries: Arc<Mutex<Vec<StoreQueryTrace>>>,
+}
+
+impl QueryTracer {
+    /// Creates a tracer whose timings are relative to now.
+    pub fn new() -> Self {
+        QueryTracer {
+            start_time: Utc::now(),
+            start: Instant::now(),
+            resolvers: Arc::new(Mutex::new(vec![])),
+            store_queries: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Records a field resolver that started at `started` and has just finished.
+    pub fn trace_resolver(
+        &self,
+        path: Vec<PathSegment>,
+        parent_type: &str,
+        field_name: &str,
+        return_type: String,
+        started: Instant,
+    ) {
+        self.resolvers.lock().unwrap().push(ResolverTrace {
+            path,
+            parent_type: parent_type.to_owned(),
+            field_name: field_name.to_owned(),
+            return_type,
+            start_offset: nanos(started.duration_since(self.start)),
+            duration: nanos(started.elapsed()),
+        });
+    }
+
+    /// Records a store query that started at `started` and has just finished.
+    pub fn trace_store_query(
+        &self,
+        query: &StoreQuery,
+        sql: Option<String>,
+        rows: Option<usize>,
+        started: Instant,
+    ) {
+        self.store_queries.lock().unwrap().push(StoreQueryTrace {
+            entity: query.entity.clone(),
+            query: format!("{:?}", query),
+            sql,
+            rows,
+            start_offset: nanos(started.duration_since(self.start)),
+            duration: nanos(started.elapsed()),
+        });
+    }
+
+    /// Returns everything recorded so far, ending the trace now.
+    pub fn finish(&self) -> QueryTrace {
+        QueryTrace {
+            start_time: self.start_time,
+            end_time: Utc::now(),
+            duration: nanos(self.start.elapsed()),
+            resolvers: self.resolvers.lock().unwrap().clone(),
+            store_queries: self.store_queries.lock().unwrap().clone(),
+        }
+    }
+}
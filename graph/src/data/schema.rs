@@ -1,4 +1,11 @@
-use graphql_parser::schema;
+use graphql_parser::schema::{self, Field, InputValue, Name, Type, TypeDefinition};
+use graphql_parser::Pos;
+use std::error::Error;
+use std::fmt;
+
+/// Scalar types that subgraph schemas can use without defining them.
+const BUILT_IN_SCALAR_TYPES: [&'static str; 7] =
+    ["Boolean", "ID", "Int", "Float", "String", "Bytes", "BigInt"];
 
 /// A GraphQL schema with additional meta data.
 #[derive(Clone, Debug)]
@@ -6,3 +13,203 @@ pub struct Schema {
     pub id: String,
     pub document: schema::Document,
 }
+
+/// A violation of the rules that subgraph schemas have to follow.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaValidationError {
+    /// A type is referenced but not defined.
+    UndefinedType(Pos, Name),
+    /// An entity type has no `id: ID!` field.
+    EntityIdMissing(Pos, Name),
+    /// A `@derivedFrom` directive has no `field` argument.
+    DerivedFromFieldArgumentMissing(Pos, Name),
+    /// A `@derivedFrom` directive refers to a field that the type of the
+    /// derived field does not have. Includes the derived field, its type and
+    /// the missing field.
+    DerivedFromFieldUndefined(Pos, Name, Name, Name),
+}
+
+impl Error for SchemaValidationError {
+    fn description(&self) -> &str {
+        "Schema validation error"
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        None
+    }
+}
+
+impl fmt::Display for SchemaValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaValidationError::UndefinedType(pos, name) => {
+                write!(f, "{}: Type `{}` is not defined", pos, name)
+            }
+            SchemaValidationError::EntityIdMissing(pos, name) => {
+                write!(f, "{}: Entity type `{}` has no `id: ID!` field", pos, name)
+            }
+            SchemaValidationError::DerivedFromFieldArgumentMissing(pos, field) => write!(
+                f,
+                "{}: The @derivedFrom directive of field `{}` has no `field` argument",
+                pos, field
+            ),
+            SchemaValidationError::DerivedFromFieldUndefined(pos, field, type_name, target) => {
+                write!(
+                    f,
+                    "{}: Field `{}` is derived from `{}.{}`, which is not defined",
+                    pos, field, type_name, target
+                )
+            }
+        }
+    }
+}
+
+impl Schema {
+    /// Checks that entity types have IDs, that all referenced types are
+    /// defined and that `@derivedFrom` directives refer to existing fields.
+    ///
+    /// Reports every violation rather than stopping at the first.
+    pub fn validate(&self) -> Result<(), Vec<SchemaValidationError>> {
+        let mut errors = vec![];
+
+        for type_definition in self.type_definitions() {
+            match type_definition {
+                TypeDefinition::Object(t) => {
+                    if !has_entity_id(&t.fields) {
+                        errors.push(SchemaValidationError::EntityIdMissing(
+                            t.position,
+                            t.name.to_owned(),
+                        ));
+                    }
+                    for name in t.implements_interfaces.iter() {
+                        self.validate_type_name(t.position, name, &mut errors);
+                    }
+                    self.validate_fields(&t.fields, &mut errors);
+                }
+                TypeDefinition::Interface(t) => self.validate_fields(&t.fields, &mut errors),
+                TypeDefinition::Union(t) => for name in t.types.iter() {
+                    self.validate_type_name(t.position, name, &mut errors);
+                },
+                TypeDefinition::InputObject(t) => {
+                    self.validate_input_values(&t.fields, &mut errors)
+                }
+                TypeDefinition::Scalar(_) | TypeDefinition::Enum(_) => (),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn type_definitions(&self) -> impl Iterator<Item = &TypeDefinition> {
+        self.document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                schema::Definition::TypeDefinition(t) => Some(t),
+                _ => None,
+            })
+    }
+
+    fn get_type_definition(&self, name: &Name) -> Option<&TypeDefinition> {
+        self.type_definitions().find(|t| match t {
+            TypeDefinition::Scalar(t) => &t.name == name,
+            TypeDefinition::Object(t) => &t.name == name,
+            TypeDefinition::Interface(t) => &t.name == name,
+            TypeDefinition::Union(t) => &t.name == name,
+            TypeDefinition::Enum(t) => &t.name == name,
+            TypeDefinition::InputObject(t) => &t.name == name,
+        })
+    }
+
+    fn validate_fields(&self, fields: &Vec<Field>, errors: &mut Vec<SchemaValidationError>) {
+        for field in fields {
+            self.validate_type_name(field.position, base_type(&field.field_type), errors);
+            self.validate_input_values(&field.arguments, errors);
+            self.validate_derived_from(field, errors);
+        }
+    }
+
+    fn validate_input_values(
+        &self,
+        input_values: &Vec<InputValue>,
+        errors: &mut Vec<SchemaValidationError>,
+    ) {
+        for input_value in input_values {
+            let type_name = base_type(&input_value.value_type);
+            self.validate_type_name(input_value.position, type_name, errors);
+        }
+    }
+
+    fn validate_type_name(&self, pos: Pos, name: &Name, errors: &mut Vec<SchemaValidationError>) {
+        let is_built_in = BUILT_IN_SCALAR_TYPES.contains(&name.as_str());
+        if !is_built_in && self.get_type_definition(name).is_none() {
+            errors.push(SchemaValidationError::UndefinedType(pos, name.to_owned()));
+        }
+    }
+
+    /// Checks that the field a `@derivedFrom` directive refers to exists on
+    /// the type of the derived field. Undefined types are reported elsewhere.
+    fn validate_derived_from(&self, field: &Field, errors: &mut Vec<SchemaValidationError>) {
+        let directive = match field.directives.iter().find(|d| d.name == "derivedFrom") {
+            Some(directive) => directive,
+            None => return,
+        };
+
+        let target_field = directive
+            .arguments
+            .iter()
+            .find(|(name, _)| name == "field")
+            .and_then(|(_, value)| match value {
+                schema::Value::String(s) => Some(s),
+                _ => None,
+            });
+        let target_field = match target_field {
+            Some(target_field) => target_field,
+            None => {
+                errors.push(SchemaValidationError::DerivedFromFieldArgumentMissing(
+                    directive.position,
+                    field.name.to_owned(),
+                ));
+                return;
+            }
+        };
+
+        let type_name = base_type(&field.field_type);
+        let target_fields = match self.get_type_definition(type_name) {
+            Some(TypeDefinition::Object(t)) => &t.fields,
+            Some(TypeDefinition::Interface(t)) => &t.fields,
+            _ => return,
+        };
+
+        if !target_fields.iter().any(|f| &f.name == target_field) {
+            errors.push(SchemaValidationError::DerivedFromFieldUndefined(
+                directive.position,
+                field.name.to_owned(),
+                type_name.to_owned(),
+                target_field.to_owned(),
+            ));
+        }
+    }
+}
+
+/// Whether the fields of an entity type include `id: ID!`.
+fn has_entity_id(fields: &Vec<Field>) -> bool {
+    fields.iter().any(|field| {
+        field.name == "id" && match field.field_type {
+            Type::NonNullType(ref inner) => **inner == Type::NamedType("ID".to_owned()),
+            _ => false,
+        }
+    })
+}
+
+/// Returns the name of the named type inside of list and non-null types.
+fn base_type(t: &Type) -> &Name {
+    match t {
+        Type::NamedType(name) => name,
+        Type::ListType(inner) | Type::NonNullType(inner) => base_type(inner),
+    }
+}
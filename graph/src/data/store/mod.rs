@@ -6,6 +6,8 @@ use std::iter::FromIterator;
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
+use data::query::QueryExecutionError;
+
 /// Custom scalars in GraphQL.
 pub mod scalar;
 
@@ -31,42 +33,49 @@ pub enum Value {
 }
 
 impl Value {
-    pub fn from_query_value(value: &query::Value, ty: &schema::Type) -> Value {
+    pub fn from_query_value(
+        value: &query::Value,
+        ty: &schema::Type,
+    ) -> Result<Value, QueryExecutionError> {
         use self::schema::Type::{ListType, NamedType, NonNullType};
 
-        match (value, ty) {
+        Ok(match (value, ty) {
             // When dealing with non-null types, use the inner type to convert the value
-            (value, NonNullType(t)) => Value::from_query_value(value, t),
+            (value, NonNullType(t)) => Value::from_query_value(value, t)?,
 
             (query::Value::List(values), ListType(ty)) => Value::List(
                 values
                     .iter()
                     .map(|value| Self::from_query_value(value, ty))
-                    .collect(),
+                    .collect::<Result<Vec<_>, _>>()?,
             ),
 
             (query::Value::String(s), NamedType(n)) => {
                 // Check if `ty` is a custom scalar type, otherwise assume it's
                 // just a string.
                 match n.as_str() {
-                    BYTES_SCALAR => {
-                        Value::Bytes(scalar::Bytes::from_str(s).expect("Value is not a hex string"))
-                    }
-                    BIG_INT_SCALAR => {
-                        Value::BigInt(scalar::BigInt::from_str(s).expect("Value is not a number"))
-                    }
+                    BYTES_SCALAR => Value::Bytes(scalar::Bytes::from_str(s).map_err(|e| {
+                        QueryExecutionError::ValueParseError(n.to_owned(), format!("{}", e))
+                    })?),
+                    BIG_INT_SCALAR => Value::BigInt(scalar::BigInt::from_str(s).map_err(|e| {
+                        QueryExecutionError::ValueParseError(n.to_owned(), format!("{}", e))
+                    })?),
                     _ => Value::String(s.clone()),
                 }
             }
-            (query::Value::Int(i), _) => Value::Int(i.to_owned()
-                .as_i64()
-                .expect("Unable to parse graphql_parser::query::Number into i64")
-                as i32),
+            (query::Value::Int(i), _) => Value::Int(i.to_owned().as_i64().ok_or_else(|| {
+                QueryExecutionError::ValueParseError(format!("{}", ty), format!("{:?}", i))
+            })? as i32),
             (query::Value::Float(f), _) => Value::Float(f.to_owned() as f32),
             (query::Value::Boolean(b), _) => Value::Bool(b.to_owned()),
             (query::Value::Null, _) => Value::Null,
-            _ => unimplemented!(),
-        }
+            _ => {
+                return Err(QueryExecutionError::ValueParseError(
+                    format!("{}", ty),
+                    format!("unsupported value {}", value),
+                ))
+            }
+        })
     }
 }
 
@@ -171,7 +180,7 @@ impl<'a> From<Vec<(&'a str, Value)>> for Entity {
 fn value_bytes() {
     let graphql_value = query::Value::String("0x8f494c66afc1d3f8ac1b45df21f02a46".to_owned());
     let ty = query::Type::NamedType(BYTES_SCALAR.to_owned());
-    let from_query = Value::from_query_value(&graphql_value, &ty);
+    let from_query = Value::from_query_value(&graphql_value, &ty).unwrap();
     assert_eq!(
         from_query,
         Value::Bytes(scalar::Bytes::from(
@@ -186,10 +195,21 @@ fn value_bigint() {
     let big_num = "340282366920938463463374607431768211456";
     let graphql_value = query::Value::String(big_num.to_owned());
     let ty = query::Type::NamedType(BIG_INT_SCALAR.to_owned());
-    let from_query = Value::from_query_value(&graphql_value, &ty);
+    let from_query = Value::from_query_value(&graphql_value, &ty).unwrap();
     assert_eq!(
         from_query,
         Value::BigInt(FromStr::from_str(big_num).unwrap())
     );
     assert_eq!(query::Value::from(from_query), graphql_value);
 }
+
+#[test]
+fn malformed_custom_scalar_values_are_rejected() {
+    let graphql_value = query::Value::String("not hex".to_owned());
+    let ty = query::Type::NamedType(BYTES_SCALAR.to_owned());
+    assert!(Value::from_query_value(&graphql_value, &ty).is_err());
+
+    let graphql_value = query::Value::String("not a number".to_owned());
+    let ty = query::Type::NamedType(BIG_INT_SCALAR.to_owned());
+    assert!(Value::from_query_value(&graphql_value, &ty).is_err());
+}
@@ -44,7 +44,7 @@ impl<'de> Deserialize<'de> for BigInt {
 }
 
 /// A byte array that's serialized as a hex string prefixed by `0x`.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Bytes(Box<[u8]>);
 
 impl Display for Bytes {
@@ -1,5 +1,5 @@
 use components::link_resolver::LinkResolver;
-use data::schema::Schema;
+use data::schema::{Schema, SchemaValidationError};
 use ethabi::Contract;
 use failure::Fail;
 use futures::prelude::*;
@@ -7,8 +7,10 @@ use futures::stream;
 use graphql_parser;
 use parity_wasm;
 use parity_wasm::elements::Module;
+use serde::de;
 use serde_yaml;
 use std::error::Error;
+use std::fmt;
 
 #[derive(Debug)]
 pub enum SubgraphManifestResolveError {
@@ -16,6 +18,43 @@ pub enum SubgraphManifestResolveError {
     NonUtf8,
     InvalidFormat,
     ResolveError(Box<Error>),
+    SchemaValidationError(Vec<SchemaValidationError>),
+}
+
+impl Error for SubgraphManifestResolveError {
+    fn description(&self) -> &str {
+        "Failed to resolve subgraph manifest"
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match self {
+            SubgraphManifestResolveError::ParseError(ref e) => Some(e),
+            SubgraphManifestResolveError::ResolveError(ref e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SubgraphManifestResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SubgraphManifestResolveError::ParseError(e) => write!(f, "Invalid manifest: {}", e),
+            SubgraphManifestResolveError::NonUtf8 => write!(f, "Manifest is not valid UTF-8"),
+            SubgraphManifestResolveError::InvalidFormat => {
+                write!(f, "Manifest is not a YAML mapping")
+            }
+            SubgraphManifestResolveError::ResolveError(e) => {
+                write!(f, "Failed to resolve manifest: {}", e)
+            }
+            SubgraphManifestResolveError::SchemaValidationError(errors) => {
+                write!(f, "Invalid schema:")?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 impl From<serde_yaml::Error> for SubgraphManifestResolveError {
@@ -25,18 +64,52 @@ impl From<serde_yaml::Error> for SubgraphManifestResolveError {
 }
 
 /// IPLD link.
-#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub struct Link {
-    #[serde(rename = "/")]
     pub link: String,
 }
 
+impl<'de> de::Deserialize<'de> for Link {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        /// Links are written as `{ "/": "/ipfs/<hash>" }` in deployed manifests
+        /// and as plain file paths in manifests that have not been deployed yet.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawLink {
+            Ipld {
+                #[serde(rename = "/")]
+                link: String,
+            },
+            Path(String),
+        }
+
+        Ok(match RawLink::deserialize(deserializer)? {
+            RawLink::Ipld { link } | RawLink::Path(link) => Link { link },
+        })
+    }
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
 pub struct SchemaData {
     pub file: Link,
 }
 
 impl SchemaData {
+    /// Reads the schema link of a subgraph manifest, without resolving the
+    /// rest of the manifest.
+    pub fn from_manifest(manifest: &str) -> Result<Self, SubgraphManifestResolveError> {
+        #[derive(Deserialize)]
+        struct ManifestSchema {
+            schema: SchemaData,
+        }
+
+        let manifest: ManifestSchema = serde_yaml::from_str(manifest)?;
+        Ok(manifest.schema)
+    }
+
     pub fn resolve(
         self,
         resolver: &impl LinkResolver,
@@ -235,6 +308,14 @@ impl SubgraphManifest {
                     unresolved
                         .resolve(resolver)
                         .map_err(|e| SubgraphManifestResolveError::ResolveError(e))
+                })
+                .and_then(|manifest| {
+                    // Refuse to deploy subgraphs whose schemas would fail at query time
+                    manifest
+                        .schema
+                        .validate()
+                        .map_err(SubgraphManifestResolveError::SchemaValidationError)?;
+                    Ok(manifest)
                 }),
         )
     }
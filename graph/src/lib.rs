@@ -1,3 +1,4 @@
+extern crate chrono;
 extern crate ethabi;
 extern crate ethereum_types;
 extern crate futures;
@@ -42,7 +43,8 @@ pub mod prelude {
     pub use components::schema::{SchemaProvider, SchemaProviderEvent};
     pub use components::server::GraphQLServer;
     pub use components::store::{
-        BasicStore, Store, StoreEvent, StoreFilter, StoreKey, StoreOrder, StoreQuery, StoreRange,
+        BasicStore, EntityChange, EntityOperation, Store, StoreEvent, StoreFilter,
+        StoreHistoryQuery, StoreKey, StoreOrder, StoreQuery, StoreRange,
     };
     pub use components::subgraph::{
         RuntimeHost, RuntimeHostBuilder, RuntimeHostEvent, RuntimeManager, SubgraphProvider,
@@ -50,11 +52,13 @@ pub mod prelude {
     };
     pub use components::{EventConsumer, EventProducer};
 
-    pub use components::link_resolver::LinkResolver;
+    pub use components::link_resolver::{FileLinkResolver, LinkResolver};
     pub use data::query::{
-        Query, QueryError, QueryExecutionError, QueryResult, QueryVariableValue, QueryVariables,
+        IncrementalResult, PathSegment, PendingQuery, Query, QueryError, QueryErrorCode,
+        QueryExecutionError, QueryResult, QueryTrace, QueryTracer, QueryVariableValue,
+        QueryVariables,
     };
-    pub use data::schema::Schema;
+    pub use data::schema::{Schema, SchemaValidationError};
     pub use data::store::{Attribute, Entity, Value};
     pub use data::subgraph::{DataSource, Link, SubgraphManifest, SubgraphManifestResolveError};
     pub use util::stream::StreamError;
@@ -0,0 +1,81 @@
+extern crate graph;
+extern crate graphql_parser;
+
+use graphql_parser::Pos;
+
+use graph::prelude::*;
+
+fn schema(sdl: &str) -> Schema {
+    Schema {
+        id: "test".to_string(),
+        document: graphql_parser::parse_schema(sdl).expect("Failed to parse schema"),
+    }
+}
+
+#[test]
+fn valid_schemas_pass_validation() {
+    let schema = schema(
+        "
+        enum Genre { rock, jazz }
+
+        type Band {
+          id: ID!
+          name: String!
+          genre: Genre
+          members: [Musician!]! @derivedFrom(field: \"band\")
+        }
+
+        type Musician {
+          id: ID!
+          band: Band
+        }
+        ",
+    );
+
+    assert_eq!(schema.validate(), Ok(()));
+}
+
+#[test]
+fn validation_reports_every_violation_with_its_position() {
+    let schema = schema(
+        "type Band {
+           id: ID!
+           label: Label
+           members: [Musician!]! @derivedFrom(field: \"bands\")
+           songs: [Song!]! @derivedFrom
+         }
+         type Musician {
+           id: ID
+           band: Band
+         }
+         type Song {
+           id: ID!
+         }",
+    );
+
+    let errors = schema.validate().unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SchemaValidationError::UndefinedType(Pos { line: 3, column: 12 }, "Label".to_string()),
+            SchemaValidationError::DerivedFromFieldUndefined(
+                Pos { line: 4, column: 34 },
+                "members".to_string(),
+                "Musician".to_string(),
+                "bands".to_string(),
+            ),
+            SchemaValidationError::DerivedFromFieldArgumentMissing(
+                Pos { line: 5, column: 28 },
+                "songs".to_string(),
+            ),
+            SchemaValidationError::EntityIdMissing(
+                Pos { line: 7, column: 10 },
+                "Musician".to_string(),
+            ),
+        ]
+    );
+    assert_eq!(
+        format!("{}", errors[0]),
+        "3:12: Type `Label` is not defined"
+    );
+}
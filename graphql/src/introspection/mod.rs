@@ -2,4 +2,4 @@ mod resolver;
 mod schema;
 
 pub use self::resolver::IntrospectionResolver;
-pub use self::schema::introspection_schema;
+pub use self::schema::{built_in_directives, introspection_schema};
@@ -4,11 +4,15 @@ use std::collections::{BTreeMap, HashMap};
 
 use graph::prelude::*;
 
+use introspection::built_in_directives;
 use prelude::*;
 use schema::ast as sast;
 
 type TypeObjectsMap = BTreeMap<String, q::Value>;
 
+/// The reason reported for `@deprecated` directives without a `reason`.
+const DEFAULT_DEPRECATION_REASON: &'static str = "No longer supported";
+
 fn object_field<'a>(object: &'a Option<q::Value>, field: &str) -> Option<&'a q::Value> {
     object
         .as_ref()
@@ -19,6 +23,14 @@ fn object_field<'a>(object: &'a Option<q::Value>, field: &str) -> Option<&'a q::
         .and_then(|data| data.get(field))
 }
 
+/// Whether a field or enum value object is marked as deprecated.
+fn is_deprecated_object(value: &q::Value) -> bool {
+    match value {
+        q::Value::Object(data) => data.get("isDeprecated") == Some(&q::Value::Boolean(true)),
+        _ => false,
+    }
+}
+
 fn schema_type_objects(schema: &Schema) -> TypeObjectsMap {
     sast::get_type_definitions(&schema.document).iter().fold(
         BTreeMap::new(),
@@ -137,8 +149,8 @@ fn enum_value(enum_value: &s::EnumValue) -> q::Value {
                 .as_ref()
                 .map_or(q::Value::Null, |s| q::Value::String(s.to_owned())),
         ),
-        ("isDeprecated", q::Value::Boolean(false)),
-        ("deprecationReason", q::Value::Null),
+        ("isDeprecated", is_deprecated(&enum_value.directives)),
+        ("deprecationReason", deprecation_reason(&enum_value.directives)),
     ])
 }
 
@@ -261,11 +273,34 @@ fn field_object(schema: &Schema, type_objects: &mut TypeObjectsMap, field: &s::F
         ),
         ("args", input_values(schema, type_objects, &field.arguments)),
         ("type", type_object(schema, type_objects, &field.field_type)),
-        ("isDeprecated", q::Value::Boolean(false)),
-        ("deprecationReason", q::Value::Null),
+        ("isDeprecated", is_deprecated(&field.directives)),
+        ("deprecationReason", deprecation_reason(&field.directives)),
     ])
 }
 
+fn deprecated_directive(directives: &Vec<s::Directive>) -> Option<&s::Directive> {
+    directives
+        .iter()
+        .find(|directive| directive.name == "deprecated")
+}
+
+fn is_deprecated(directives: &Vec<s::Directive>) -> q::Value {
+    q::Value::Boolean(deprecated_directive(directives).is_some())
+}
+
+/// Returns the `reason` of a `@deprecated` directive, falling back to the
+/// default reason of the directive definition.
+fn deprecation_reason(directives: &Vec<s::Directive>) -> q::Value {
+    deprecated_directive(directives).map_or(q::Value::Null, |directive| {
+        directive
+            .arguments
+            .iter()
+            .find(|(name, _)| name == "reason")
+            .map(|(_, value)| value.clone())
+            .unwrap_or_else(|| q::Value::String(DEFAULT_DEPRECATION_REASON.to_owned()))
+    })
+}
+
 fn object_interfaces(
     schema: &Schema,
     type_objects: &mut TypeObjectsMap,
@@ -313,10 +348,10 @@ fn union_type_object(schema: &Schema, union_type: &s::UnionType) -> q::Value {
                 sast::get_object_type_definitions(&schema.document)
                     .iter()
                     .filter(|object_type| {
-                        object_type
-                            .implements_interfaces
+                        union_type
+                            .types
                             .iter()
-                            .find(|implemented_name| implemented_name == &&union_type.name)
+                            .find(|member_name| member_name == &&object_type.name)
                             .is_some()
                     })
                     .map(|object_type| q::Value::String(object_type.name.to_owned()))
@@ -327,11 +362,13 @@ fn union_type_object(schema: &Schema, union_type: &s::UnionType) -> q::Value {
 }
 
 fn schema_directive_objects(schema: &Schema, type_objects: &mut TypeObjectsMap) -> q::Value {
+    let built_in_directives = built_in_directives();
+
     q::Value::List(
-        schema
-            .document
+        built_in_directives
             .definitions
             .iter()
+            .chain(schema.document.definitions.iter())
             .filter_map(|d| match d {
                 s::Definition::DirectiveDefinition(dd) => Some(dd),
                 _ => None,
@@ -405,12 +442,13 @@ fn input_value(
             "type",
             type_object(schema, type_objects, &input_value.value_type),
         ),
+        // Default values are introspected as GraphQL literals
         (
             "defaultValue",
             input_value
                 .default_value
                 .as_ref()
-                .map_or(q::Value::Null, |value| value.clone()),
+                .map_or(q::Value::Null, |value| q::Value::String(format!("{}", value))),
         ),
     ])
 }
@@ -451,6 +489,13 @@ impl<'a> IntrospectionResolver<'a> {
                     .unwrap_or(q::Value::Null),
             ),
             ("mutationType", q::Value::Null),
+            (
+                "subscriptionType",
+                self.type_objects
+                    .get(&String::from("Subscription"))
+                    .map(|t| t.clone())
+                    .unwrap_or(q::Value::Null),
+            ),
             (
                 "types",
                 q::Value::List(
@@ -484,9 +529,26 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
         field: &q::Name,
         _field_definition: &s::Field,
         _object_type: &s::ObjectType,
-        _arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value {
-        match field.as_str() {
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(match field.as_str() {
+            "fields" | "enumValues" => {
+                let include_deprecated = match arguments.get(&String::from("includeDeprecated")) {
+                    Some(q::Value::Boolean(b)) => *b,
+                    _ => false,
+                };
+
+                match object_field(parent, field.as_str()) {
+                    Some(q::Value::List(values)) => q::Value::List(
+                        values
+                            .iter()
+                            .filter(|value| include_deprecated || !is_deprecated_object(value))
+                            .cloned()
+                            .collect(),
+                    ),
+                    _ => q::Value::Null,
+                }
+            }
             "possibleTypes" => {
                 let type_names = object_field(parent, "possibleTypes")
                     .and_then(|value| match value {
@@ -513,7 +575,7 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
             _ => object_field(parent, field.as_str())
                 .map(|value| value.clone())
                 .unwrap_or(q::Value::Null),
-        }
+        })
     }
 
     fn resolve_object(
@@ -523,8 +585,8 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
         _field_definition: &s::Field,
         _object_type: &s::ObjectType,
         arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value {
-        match field.as_str() {
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(match field.as_str() {
             "__schema" => self.schema_object(),
             "__type" => self.type_object(arguments),
             "type" => object_field(parent, "type")
@@ -542,6 +604,6 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
             _ => object_field(parent, field.as_str())
                 .map(|value| value.clone())
                 .unwrap_or(q::Value::Null),
-        }
+        })
     }
 }
@@ -1,6 +1,24 @@
 use graphql_parser;
 use graphql_parser::schema as s;
 
+/// Directives that are available in every schema.
+const BUILT_IN_DIRECTIVES: &'static str = "
+\"Skips a field or fragment if the argument is true.\"
+directive @skip(if: Boolean!) on FIELD | FRAGMENT_SPREAD | INLINE_FRAGMENT
+
+\"Includes a field or fragment only if the argument is true.\"
+directive @include(if: Boolean!) on FIELD | FRAGMENT_SPREAD | INLINE_FRAGMENT
+
+\"Delivers a fragment in a later part of the response.\"
+directive @defer(if: Boolean! = true, label: String) on FRAGMENT_SPREAD | INLINE_FRAGMENT
+
+\"Delivers the items of a list field incrementally, after the first `initialCount`.\"
+directive @stream(if: Boolean! = true, label: String, initialCount: Int = 0) on FIELD
+
+\"Marks a field or enum value as no longer supported.\"
+directive @deprecated(reason: String = \"No longer supported\") on FIELD_DEFINITION | ENUM_VALUE
+";
+
 const INTROSPECTION_SCHEMA: &'static str = "
 scalar Boolean
 scalar Float
@@ -110,3 +128,8 @@ enum __DirectiveLocation {
 pub fn introspection_schema() -> s::Document {
     graphql_parser::parse_schema(INTROSPECTION_SCHEMA).unwrap()
 }
+
+/// Returns the definitions of the directives that are available in every schema.
+pub fn built_in_directives() -> s::Document {
+    graphql_parser::parse_schema(BUILT_IN_DIRECTIVES).expect("Failed to parse built-in directives")
+}
@@ -24,8 +24,12 @@ mod store;
 /// Prelude that exports the most important traits and types.
 pub mod prelude {
     pub use super::introspection::{introspection_schema, IntrospectionResolver};
-    pub use super::query::{execute, ExecutionOptions, Resolver};
-    pub use super::schema::{api_schema, APISchemaError};
+    pub use super::query::{
+        execute, execute_incrementally, ExecutionOptions, Resolver, SubsequentResults,
+    };
+    pub use super::schema::{
+        api_schema, compose_schemas, print_schema, APISchemaError, CompositionError,
+    };
     pub use super::store::{build_query, StoreResolver};
     pub use super::values::{object_value, MaybeCoercible, SerializableValue};
 }
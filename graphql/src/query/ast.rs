@@ -1,4 +1,6 @@
 use graphql_parser::query::*;
+use std::cmp;
+use std::collections::HashMap;
 
 use graph::prelude::QueryExecutionError;
 
@@ -47,15 +49,27 @@ pub fn get_operation_name(operation: &OperationDefinition) -> Option<&Name> {
     }
 }
 
+/// Returns the variable definitions of the given operation.
+pub fn get_variable_definitions(
+    operation: &OperationDefinition,
+) -> Option<&Vec<VariableDefinition>> {
+    match operation {
+        OperationDefinition::Query(q) => Some(&q.variable_definitions),
+        OperationDefinition::Subscription(s) => Some(&s.variable_definitions),
+        OperationDefinition::Mutation(m) => Some(&m.variable_definitions),
+        OperationDefinition::SelectionSet(_) => None,
+    }
+}
+
 /// Looks up a directive in a selection, if it is provided.
 pub fn get_directive(selection: &Selection, name: Name) -> Option<&Directive> {
-    match selection {
-        Selection::Field(field) => field
-            .directives
-            .iter()
-            .find(|directive| directive.name == name),
-        _ => None,
-    }
+    let directives = match selection {
+        Selection::Field(field) => &field.directives,
+        Selection::FragmentSpread(spread) => &spread.directives,
+        Selection::InlineFragment(fragment) => &fragment.directives,
+    };
+
+    directives.iter().find(|directive| directive.name == name)
 }
 
 /// Looks up the value of an argument in a vector of (name, value) tuples.
@@ -64,33 +78,92 @@ pub fn get_argument_value<'a>(arguments: &'a Vec<(Name, Value)>, name: &Name) ->
 }
 
 /// Returns true if a selection should be skipped (as per the `@skip` directive).
-pub fn skip_selection(selection: &Selection) -> bool {
+pub fn skip_selection(selection: &Selection, variables: &HashMap<Name, Value>) -> bool {
     match get_directive(selection, "skip".to_string()) {
-        Some(directive) => match get_argument_value(&directive.arguments, &"if".to_string()) {
-            Some(val) => match val {
-                Value::Boolean(skip_if) => *skip_if,
-                _ => false,
-            },
-            None => true,
-        },
+        Some(directive) => get_if_argument(directive, variables).unwrap_or(false),
         None => false,
     }
 }
 
 /// Returns true if a selection should be included (as per the `@include` directive).
-pub fn include_selection(selection: &Selection) -> bool {
+pub fn include_selection(selection: &Selection, variables: &HashMap<Name, Value>) -> bool {
     match get_directive(selection, "include".to_string()) {
-        Some(directive) => match get_argument_value(&directive.arguments, &"include".to_string()) {
-            Some(val) => match val {
-                Value::Boolean(include) => *include,
-                _ => false,
-            },
-            None => true,
-        },
+        Some(directive) => get_if_argument(directive, variables).unwrap_or(false),
         None => true,
     }
 }
 
+/// Returns the `@defer` directive of a fragment if the fragment should be deferred.
+pub fn get_defer_directive<'a>(
+    selection: &'a Selection,
+    variables: &HashMap<Name, Value>,
+) -> Option<&'a Directive> {
+    get_directive(selection, "defer".to_string())
+        .and_then(|directive| match get_if_argument(directive, variables) {
+            Some(false) => None,
+            _ => Some(directive),
+        })
+}
+
+/// Returns the `@stream` directive of a field if its items should be streamed.
+pub fn get_stream_directive<'a>(
+    field: &'a Field,
+    variables: &HashMap<Name, Value>,
+) -> Option<&'a Directive> {
+    field
+        .directives
+        .iter()
+        .find(|directive| directive.name == "stream")
+        .and_then(|directive| match get_if_argument(directive, variables) {
+            Some(false) => None,
+            _ => Some(directive),
+        })
+}
+
+/// Returns the value of the `label` argument of a `@defer` or `@stream` directive.
+pub fn get_label_argument(
+    directive: &Directive,
+    variables: &HashMap<Name, Value>,
+) -> Option<String> {
+    match get_directive_argument(directive, "label", variables) {
+        Some(Value::String(label)) => Some(label.to_owned()),
+        _ => None,
+    }
+}
+
+/// Returns the value of the `initialCount` argument of a `@stream` directive.
+pub fn get_initial_count_argument(
+    directive: &Directive,
+    variables: &HashMap<Name, Value>,
+) -> usize {
+    match get_directive_argument(directive, "initialCount", variables) {
+        Some(Value::Int(n)) => n.as_i64().map_or(0, |n| cmp::max(n, 0) as usize),
+        _ => 0,
+    }
+}
+
+/// Returns the value of the `if` argument of a directive like `@skip` or
+/// `@include`, if it is provided.
+fn get_if_argument(directive: &Directive, variables: &HashMap<Name, Value>) -> Option<bool> {
+    match get_directive_argument(directive, "if", variables) {
+        Some(Value::Boolean(condition)) => Some(*condition),
+        _ => None,
+    }
+}
+
+/// Returns the value of a directive argument, which may be provided directly
+/// or through a variable.
+fn get_directive_argument<'a>(
+    directive: &'a Directive,
+    name: &str,
+    variables: &'a HashMap<Name, Value>,
+) -> Option<&'a Value> {
+    match get_argument_value(&directive.arguments, &name.to_string()) {
+        Some(Value::Variable(name)) => variables.get(name),
+        value => value,
+    }
+}
+
 /// Returns the response key of a field, which is either its name or its alias (if there is one).
 pub fn get_response_key(field: &Field) -> &Name {
     field.alias.as_ref().unwrap_or(&field.name)
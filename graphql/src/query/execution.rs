@@ -3,14 +3,23 @@ use graphql_parser::schema as s;
 use indexmap::IndexMap;
 use slog;
 use std::cmp;
-use std::collections::{BTreeMap, HashMap, HashSet};
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::mem;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use graph::prelude::*;
 
 use prelude::*;
 use query::ast as qast;
+use query::limits::check_limits;
+use query::validation::validate;
 use schema::ast as sast;
+use store::DEFAULT_FIRST;
+
+/// The number of items of a `@stream` list that each subsequent result contains.
+const STREAM_PAGE_SIZE: usize = 100;
 
 /// Contextual information passed around during query execution.
 #[derive(Clone)]
@@ -25,16 +34,30 @@ where
     pub schema: &'a Schema,
     /// Introspection data that corresponds to the schema.
     pub introspection_schema: &'a s::Document,
-    /// The query to execute.
-    pub query: &'a Query,
+    /// The query document, for looking up fragments.
+    pub document: &'a q::Document,
     /// The resolver to use.
     pub resolver: Arc<R1>,
     /// The introspection resolver to use.
     pub introspection_resolver: Arc<R2>,
     /// The current field stack (e.g. allUsers > friends > name).
     pub fields: Vec<&'a q::Field>,
+    /// Coerced variable values of the operation being executed.
+    pub variable_values: Arc<HashMap<q::Name, q::Value>>,
     /// Whether or not we're executing an introspection query
     pub introspecting: bool,
+    /// The path of the value currently being completed (e.g. allUsers > 0 > name).
+    pub path: Vec<PathSegment>,
+    /// Field errors collected during the execution.
+    pub errors: Arc<Mutex<Vec<QueryError>>>,
+    /// Field values of the current object that were resolved in a batch
+    /// together with its siblings, by response key.
+    pub prefetched: Arc<HashMap<String, q::Value>>,
+    /// Work deferred with `@defer` and `@stream`, if results are delivered
+    /// incrementally; otherwise these directives are ignored.
+    pub incremental_tasks: Option<Arc<Mutex<Vec<IncrementalTask>>>>,
+    /// Records the timings of field resolvers, if the query is traced.
+    pub tracer: Option<QueryTracer>,
 }
 
 impl<'a, R1, R2> ExecutionContext<'a, R1, R2>
@@ -46,8 +69,90 @@ where
     pub fn for_field(&mut self, field: &'a q::Field) -> Self {
         let mut ctx = self.clone();
         ctx.fields.push(field);
+        ctx.path
+            .push(PathSegment::Field(qast::get_response_key(field).to_owned()));
+        ctx.prefetched = Arc::new(HashMap::new());
+        ctx
+    }
+
+    /// Creates a derived context for an item of the list value being completed.
+    pub fn for_list_item(&self, index: usize) -> Self {
+        let mut ctx = self.clone();
+        ctx.path.push(PathSegment::Index(index));
         ctx
     }
+
+    /// Records a field error for the value currently being completed.
+    pub fn add_error(&self, e: QueryExecutionError) {
+        self.errors.lock().unwrap().push(QueryError::FieldError(
+            e,
+            self.path.clone(),
+            self.fields.last().map(|field| field.position),
+        ));
+    }
+
+    /// Returns true if `@defer` and `@stream` apply to the value currently
+    /// being completed. Introspection results are never delivered incrementally.
+    pub fn is_incremental(&self) -> bool {
+        self.incremental_tasks.is_some() && !self.introspecting
+    }
+
+    /// Defers work to a subsequent result.
+    pub fn add_incremental_task(&self, task: IncrementalTask) {
+        if let Some(ref tasks) = self.incremental_tasks {
+            tasks.lock().unwrap().push(task);
+        }
+    }
+}
+
+/// Work that is delivered in a subsequent result.
+enum IncrementalTask {
+    /// A fragment marked with `@defer`, to be executed against an object.
+    Defer {
+        path: Vec<PathSegment>,
+        label: Option<String>,
+        object_type: s::Name,
+        object_value: Option<q::Value>,
+        selection_set: q::SelectionSet,
+    },
+
+    /// The items of a list field marked with `@stream` that were not part
+    /// of the initial result.
+    Stream {
+        /// The path of the list field.
+        path: Vec<PathSegment>,
+        label: Option<String>,
+        fields: Vec<q::Field>,
+        item_type: s::Type,
+        /// The index of the next item in the list.
+        next_index: usize,
+        items: StreamItems,
+    },
+}
+
+/// The remaining items of a list field marked with `@stream`.
+enum StreamItems {
+    /// Items that were resolved along with the list.
+    Resolved(VecDeque<q::Value>),
+
+    /// Items that are fetched from the resolver page by page, ordered by ID.
+    /// Each page continues after the ID of the last item of the previous one,
+    /// so items written between pages are neither skipped nor repeated.
+    Paged {
+        object_value: Option<q::Value>,
+        field_definition: s::Field,
+        item_object_type: s::Name,
+        argument_values: HashMap<q::Name, q::Value>,
+        /// The `skip` argument of the field; it only applies until the first
+        /// page has been fetched.
+        skip: usize,
+        /// The ID of the last item fetched so far.
+        last_id: Option<String>,
+        /// Whether items are ordered by descending IDs.
+        descending: bool,
+        /// How many items are left to fetch.
+        remaining: usize,
+    },
 }
 
 /// Optionsp available for the `execute` function.
@@ -59,21 +164,147 @@ where
     pub logger: slog::Logger,
     /// The resolver to use.
     pub resolver: R,
+    /// The maximum nesting depth of fields in a query.
+    pub max_depth: Option<u8>,
+    /// The maximum complexity of a query, i.e. the number of values it may resolve.
+    pub max_complexity: Option<u64>,
+    /// The maximum value of `first` arguments.
+    pub max_first: Option<u32>,
+    /// The maximum value of `skip` arguments.
+    pub max_skip: Option<u32>,
+    /// The maximum number of entities a query may resolve.
+    pub max_entities: Option<u64>,
+    /// The tracer to record resolver timings with; the trace is returned
+    /// with the result.
+    pub tracer: Option<QueryTracer>,
+}
+
+impl<R> ExecutionOptions<R>
+where
+    R: Resolver,
+{
+    /// Creates execution options with the default limits.
+    pub fn new(logger: slog::Logger, resolver: R) -> Self {
+        ExecutionOptions {
+            logger,
+            resolver,
+            max_depth: Some(10),
+            max_complexity: Some(10_000_000),
+            max_first: Some(1000),
+            max_skip: Some(5000),
+            max_entities: Some(2_000_000),
+            tracer: None,
+        }
+    }
 }
 
 /// Executes a query and returns a result.
+///
+/// Fragments marked with `@defer` and lists marked with `@stream` are
+/// included in the result right away.
 pub fn execute<R>(query: &Query, options: ExecutionOptions<R>) -> QueryResult
+where
+    R: Resolver,
+{
+    let tracer = options.tracer.clone();
+    let mut result = execute_operation(query, options, None);
+    result.trace = tracer.map(|tracer| tracer.finish());
+    result
+}
+
+/// Executes a query and returns its initial result, leaving out fragments
+/// marked with `@defer` and the items of lists marked with `@stream` beyond
+/// their `initialCount`.
+///
+/// These are produced by the returned `SubsequentResults`, one subsequent
+/// result at a time; store-backed lists are only fetched page by page as the
+/// results are consumed.
+pub fn execute_incrementally<R>(
+    query: &Query,
+    options: ExecutionOptions<R>,
+) -> (QueryResult, Option<SubsequentResults<R>>)
+where
+    R: Resolver,
+{
+    let logger = options.logger.clone();
+    let resolver = options.resolver.clone();
+    let tracer = options.tracer.clone();
+    let incremental_tasks = Arc::new(Mutex::new(vec![]));
+
+    // Only the initial result is traced
+    let mut result = execute_operation(query, options, Some(incremental_tasks.clone()));
+    result.trace = tracer.map(|tracer| tracer.finish());
+
+    let tasks = mem::replace(&mut *incremental_tasks.lock().unwrap(), vec![]);
+    if tasks.is_empty() {
+        return (result, None);
+    }
+
+    // The operation and its variables were valid, otherwise there would be
+    // no tasks
+    let operation_name = query.operation_name.as_ref().map(|name| name.as_str());
+    let variable_values = qast::get_operation(&query.document, operation_name)
+        .map_err(|e| vec![e])
+        .and_then(|operation| {
+            coerce_variable_values(&query.schema, operation, &query.variables)
+        })
+        .unwrap_or_default();
+
+    let subsequent_results = SubsequentResults {
+        logger,
+        resolver: Arc::new(resolver),
+        schema: query.schema.clone(),
+        document: query.document.clone(),
+        introspection_schema: introspection_schema(),
+        variable_values: Arc::new(variable_values),
+        tasks: tasks.into_iter().collect(),
+    };
+    (result, Some(subsequent_results))
+}
+
+/// Executes the operation of a query, deferring work to the given tasks if
+/// results are delivered incrementally.
+fn execute_operation<R>(
+    query: &Query,
+    options: ExecutionOptions<R>,
+    incremental_tasks: Option<Arc<Mutex<Vec<IncrementalTask>>>>,
+) -> QueryResult
 where
     R: Resolver,
 {
     info!(options.logger, "Execute");
 
-    // Obtain the only operation of the query (fail if there is none or more than one)
-    let operation = match qast::get_operation(&query.document, None) {
+    // Validate the query against the schema before executing anything
+    if let Err(errors) = validate(&query.schema, &query.document) {
+        return QueryResult::from(errors);
+    }
+
+    // Obtain the operation to execute, either by name or the only operation
+    // in the query (fail if there is none or more than one)
+    let operation_name = query.operation_name.as_ref().map(|name| name.as_str());
+    let operation = match qast::get_operation(&query.document, operation_name) {
         Ok(op) => op,
         Err(e) => return QueryResult::from(e),
     };
 
+    // Coerce the variable values provided with the query
+    let variable_values = match coerce_variable_values(&query.schema, operation, &query.variables)
+    {
+        Ok(values) => values,
+        Err(errors) => return QueryResult::from(errors),
+    };
+
+    // Reject queries that exceed the limits before resolving anything
+    if let Err(errors) = check_limits(
+        &query.schema.document,
+        &query.document,
+        operation,
+        &variable_values,
+        &options,
+    ) {
+        return QueryResult::from(errors);
+    }
+
     // Create an introspection type store and resolver
     let introspection_schema = introspection_schema();
     let introspection_resolver = IntrospectionResolver::new(&options.logger, &query.schema);
@@ -86,8 +317,14 @@ where
         introspection_resolver: Arc::new(introspection_resolver),
         introspection_schema: &introspection_schema,
         introspecting: false,
-        query,
+        document: &query.document,
         fields: vec![],
+        variable_values: Arc::new(variable_values),
+        path: vec![],
+        errors: Arc::new(Mutex::new(vec![])),
+        prefetched: Arc::new(HashMap::new()),
+        incremental_tasks,
+        tracer: options.tracer,
     };
 
     match operation {
@@ -101,6 +338,12 @@ where
             execute_root_selection_set(ctx, selection_set, &None)
         }
 
+        // Execute top-level `subscription { ... }` expressions; each execution
+        // produces the current result of the subscription
+        &q::OperationDefinition::Subscription(q::Subscription {
+            ref selection_set, ..
+        }) => execute_subscription_selection_set(ctx, selection_set, &None),
+
         // Everything else (e.g. mutations) is unsupported
         _ => QueryResult::from(QueryExecutionError::NotSupported(
             "Only queries are supported".to_string(),
@@ -125,66 +368,438 @@ where
     };
 
     // Execute the root selection set against the root query type
-    execute_selection_set(ctx, selection_set, query_type, initial_value)
-        .unwrap_or_else(QueryResult::from)
+    execute_root_selection_set_for_type(ctx, selection_set, query_type, initial_value)
+}
+
+/// Executes the root selection set of a subscription.
+fn execute_subscription_selection_set<'a, R1, R2>(
+    ctx: ExecutionContext<'a, R1, R2>,
+    selection_set: &'a q::SelectionSet,
+    initial_value: &Option<q::Value>,
+) -> QueryResult
+where
+    R1: Resolver,
+    R2: Resolver,
+{
+    // Obtain the root Subscription type and fail if there isn't one
+    let subscription_type = match sast::get_root_subscription_type(&ctx.schema.document) {
+        Some(t) => t,
+        None => return QueryResult::from(QueryExecutionError::NoRootSubscriptionObjectType),
+    };
+
+    // Execute the root selection set against the root subscription type
+    execute_root_selection_set_for_type(ctx, selection_set, subscription_type, initial_value)
+}
+
+/// Executes a root selection set against the given root type.
+fn execute_root_selection_set_for_type<'a, R1, R2>(
+    ctx: ExecutionContext<'a, R1, R2>,
+    selection_set: &'a q::SelectionSet,
+    root_type: &s::ObjectType,
+    initial_value: &Option<q::Value>,
+) -> QueryResult
+where
+    R1: Resolver,
+    R2: Resolver,
+{
+    // Errors in non-null fields that propagate all the way up result in no data at all
+    let data = execute_selection_set(ctx.clone(), selection_set, root_type, initial_value);
+
+    // Return the data along with all field errors that occurred
+    let mut result = QueryResult::new(data);
+    for e in ctx.errors.lock().unwrap().drain(..) {
+        result.add_error(e);
+    }
+    result
+}
+
+/// The subsequent results of a query that is executed incrementally, which
+/// are produced one at a time by executing the deferred work of the query.
+pub struct SubsequentResults<R>
+where
+    R: Resolver,
+{
+    logger: slog::Logger,
+    resolver: Arc<R>,
+    schema: Schema,
+    document: q::Document,
+    introspection_schema: s::Document,
+    variable_values: Arc<HashMap<q::Name, q::Value>>,
+    tasks: VecDeque<IncrementalTask>,
+}
+
+impl<R> Iterator for SubsequentResults<R>
+where
+    R: Resolver,
+{
+    type Item = IncrementalResult;
+
+    fn next(&mut self) -> Option<IncrementalResult> {
+        loop {
+            let mut task = self.tasks.pop_front()?;
+
+            // Take the next items of streamed lists out of the task, fetching
+            // them from the resolver if necessary
+            let (stream_items, fetch_error) = match task {
+                IncrementalTask::Defer { .. } => (vec![], None),
+                IncrementalTask::Stream { ref mut items, .. } => match self.next_stream_items(items)
+                {
+                    Ok(stream_items) => (stream_items, None),
+                    Err(e) => (vec![], Some(e)),
+                },
+            };
+
+            let incremental_tasks = Arc::new(Mutex::new(vec![]));
+            let mut result = {
+                let introspection_resolver = IntrospectionResolver::new(&self.logger, &self.schema);
+                let ctx = ExecutionContext {
+                    logger: self.logger.clone(),
+                    resolver: self.resolver.clone(),
+                    schema: &self.schema,
+                    introspection_resolver: Arc::new(introspection_resolver),
+                    introspection_schema: &self.introspection_schema,
+                    introspecting: false,
+                    document: &self.document,
+                    fields: vec![],
+                    variable_values: self.variable_values.clone(),
+                    path: vec![],
+                    errors: Arc::new(Mutex::new(vec![])),
+                    prefetched: Arc::new(HashMap::new()),
+                    incremental_tasks: Some(incremental_tasks.clone()),
+                    tracer: None,
+                };
+                execute_incremental_task(ctx, &task, stream_items)
+            };
+
+            // Streams end with the first page that can't be fetched
+            let failed = fetch_error.is_some();
+            if let Some(e) = fetch_error {
+                let path = result.path.clone();
+                let position = match task {
+                    IncrementalTask::Stream { ref fields, .. } => Some(fields[0].position),
+                    IncrementalTask::Defer { .. } => None,
+                };
+                result.items = None;
+                result
+                    .errors
+                    .get_or_insert(vec![])
+                    .push(QueryError::FieldError(e, path, position));
+            }
+
+            // Streams continue until all their items have been delivered
+            let empty_page = result
+                .items
+                .as_ref()
+                .map_or(false, |items| items.is_empty());
+            if let IncrementalTask::Stream {
+                ref mut next_index,
+                ref items,
+                ..
+            } = task
+            {
+                *next_index += result.items.as_ref().map_or(0, |items| items.len());
+                if !failed && !is_exhausted(items) {
+                    self.tasks.push_back(task);
+                }
+            }
+
+            // Work deferred while executing the task follows the other tasks
+            self.tasks
+                .extend(incremental_tasks.lock().unwrap().drain(..));
+            result.has_next = !self.tasks.is_empty();
+
+            // Skip empty pages; if there are no results left, signal the end
+            // of the results without any further items
+            if empty_page && result.errors.is_none() {
+                if result.has_next {
+                    continue;
+                }
+                result.items = None;
+                result.path = vec![];
+                result.label = None;
+            }
+            return Some(result);
+        }
+    }
+}
+
+impl<R> SubsequentResults<R>
+where
+    R: Resolver,
+{
+    /// Takes the next page of items of a streamed list.
+    fn next_stream_items(
+        &self,
+        items: &mut StreamItems,
+    ) -> Result<Vec<q::Value>, QueryExecutionError> {
+        match items {
+            StreamItems::Resolved(values) => {
+                let page_size = cmp::min(values.len(), STREAM_PAGE_SIZE);
+                Ok(values.drain(..page_size).collect())
+            }
+
+            StreamItems::Paged {
+                object_value,
+                field_definition,
+                item_object_type,
+                argument_values,
+                skip,
+                last_id,
+                descending,
+                remaining,
+            } => {
+                let page_size = cmp::min(*remaining, STREAM_PAGE_SIZE);
+                let item_object_type =
+                    match sast::get_named_type(&self.schema.document, item_object_type) {
+                        Some(s::TypeDefinition::Object(t)) => t,
+                        _ => {
+                            return Err(QueryExecutionError::NamedTypeError(
+                                item_object_type.to_owned(),
+                            ))
+                        }
+                    };
+
+                let skip_name = String::from("skip");
+                let first_name = String::from("first");
+                let where_name = String::from("where");
+                let mut page_argument_values: HashMap<&q::Name, q::Value> = argument_values
+                    .iter()
+                    .map(|(name, value)| (name, value.clone()))
+                    .collect();
+                page_argument_values.insert(&first_name, count_value(page_size)?);
+                match last_id {
+                    Some(last_id) => {
+                        page_argument_values.insert(&skip_name, count_value(0)?);
+                        page_argument_values.insert(
+                            &where_name,
+                            id_filter_after(
+                                argument_values.get(&where_name),
+                                last_id,
+                                *descending,
+                            ),
+                        );
+                    }
+                    None => {
+                        page_argument_values.insert(&skip_name, count_value(*skip)?);
+                    }
+                }
+
+                let page = match self.resolver.resolve_objects(
+                    object_value,
+                    &field_definition.name,
+                    field_definition,
+                    item_object_type,
+                    &page_argument_values,
+                )? {
+                    q::Value::List(page) => page,
+                    _ => vec![],
+                };
+
+                // A short page is the last one
+                if let Some(item) = page.last() {
+                    *last_id = Some(get_item_id(item)?);
+                }
+                *remaining = if page.len() < page_size {
+                    0
+                } else {
+                    *remaining - page.len()
+                };
+                Ok(page)
+            }
+        }
+    }
+}
+
+/// Returns true if all items of a streamed list have been taken.
+fn is_exhausted(items: &StreamItems) -> bool {
+    match items {
+        StreamItems::Resolved(values) => values.is_empty(),
+        StreamItems::Paged { remaining, .. } => *remaining == 0,
+    }
+}
+
+/// Executes deferred work, completing the given items if the work is a
+/// streamed list.
+fn execute_incremental_task<'a, R1, R2>(
+    mut ctx: ExecutionContext<'a, R1, R2>,
+    task: &'a IncrementalTask,
+    stream_items: Vec<q::Value>,
+) -> IncrementalResult
+where
+    R1: Resolver,
+    R2: Resolver,
+{
+    let (path, label, data, items) = match task {
+        IncrementalTask::Defer {
+            path,
+            label,
+            object_type,
+            object_value,
+            selection_set,
+        } => {
+            ctx.path = path.clone();
+            let data = match sast::get_named_type(&ctx.schema.document, object_type) {
+                Some(s::TypeDefinition::Object(object_type)) => {
+                    execute_selection_set(ctx.clone(), selection_set, object_type, object_value)
+                }
+                _ => {
+                    ctx.add_error(QueryExecutionError::NamedTypeError(object_type.to_owned()));
+                    None
+                }
+            };
+            (path.clone(), label, data, None)
+        }
+
+        IncrementalTask::Stream {
+            path,
+            label,
+            fields,
+            item_type,
+            next_index,
+            ..
+        } => {
+            ctx.path = path.clone();
+            ctx.fields.push(&fields[0]);
+            let fields: Vec<_> = fields.iter().collect();
+
+            // Resolve the object fields of all items in batches up front
+            let mut prefetched =
+                prefetch_object_fields(ctx.clone(), item_type, &fields, &stream_items).into_iter();
+
+            let mut items = Vec::with_capacity(stream_items.len());
+            for (index, value) in stream_items.into_iter().enumerate() {
+                let mut item_ctx = ctx.for_list_item(next_index + index);
+                if let Some(item_prefetched) = prefetched.next() {
+                    item_ctx.prefetched = Arc::new(item_prefetched);
+                }
+
+                // Items that fail are nulled out; the list they belong to has
+                // been delivered already
+                items.push(
+                    complete_value(item_ctx, fields[0], item_type, fields.clone(), value)
+                        .unwrap_or(q::Value::Null),
+                );
+            }
+
+            let mut path = path.clone();
+            path.push(PathSegment::Index(*next_index));
+            (path, label, None, Some(items))
+        }
+    };
+
+    let errors: Vec<_> = ctx.errors.lock().unwrap().drain(..).collect();
+    IncrementalResult {
+        data,
+        items,
+        path,
+        label: label.clone(),
+        errors: if errors.is_empty() {
+            None
+        } else {
+            Some(errors)
+        },
+        has_next: false,
+    }
 }
 
 /// Executes a selection set, requiring the result to be of the given object type.
 ///
 /// Allows passing in a parent value during recursive processing of objects and their fields.
+///
+/// Returns `None` if a non-null field failed, in which case the error has already
+/// been recorded and the null value propagates to the parent.
 fn execute_selection_set<'a, R1, R2>(
     mut ctx: ExecutionContext<'a, R1, R2>,
     selection_set: &'a q::SelectionSet,
     object_type: &s::ObjectType,
     object_value: &Option<q::Value>,
-) -> Result<QueryResult, QueryExecutionError>
+) -> Option<q::Value>
 where
     R1: Resolver,
     R2: Resolver,
 {
-    let mut result = QueryResult::new(None);
     let mut result_map: BTreeMap<String, q::Value> = BTreeMap::new();
 
     // Group fields with the same response key, so we can execute them together
-    let grouped_field_set = collect_fields(ctx.clone(), object_type, selection_set, None);
+    let mut deferred_fragments = vec![];
+    let grouped_field_set = collect_fields(
+        ctx.clone(),
+        object_type,
+        selection_set,
+        None,
+        &mut deferred_fragments,
+    );
 
     // Process all field groups in order
     for (response_key, fields) in grouped_field_set {
+        // Resolve the `__typename` meta field to the name of the object type
+        if fields[0].name == "__typename" {
+            result_map.insert(
+                response_key.to_owned(),
+                q::Value::String(object_type.name.to_owned()),
+            );
+            continue;
+        }
+
         // If the field exists on the object, execute it and add its result to the result map
         if let Some((ref field, introspecting)) =
             get_field_type(ctx.clone(), object_type, &fields[0].name)
         {
+            // Use the value of the field if it was resolved in a batch already
+            let prefetched = ctx.prefetched.get(response_key.as_str()).cloned();
+
             // Push the new field onto the context's field stack
             let mut ctx = ctx.for_field(&fields[0]);
 
             // Remember whether or not we're introspecting now
             ctx.introspecting = introspecting;
 
-            match execute_field(ctx, object_type, object_value, &fields[0], field, fields) {
-                Ok(v) => {
+            let value = match prefetched {
+                Some(value) => complete_value(ctx, &fields[0], &field.field_type, fields, value),
+                None => execute_field(ctx, object_type, object_value, &fields[0], field, fields),
+            };
+
+            match value {
+                Some(v) => {
                     result_map.insert(response_key.to_owned(), v);
                 }
-                Err(e) => {
-                    result.add_error(QueryError::from(e));
-                }
+
+                // The field failed; null it out if it is nullable, otherwise
+                // null out the entire object
+                None => match field.field_type {
+                    s::Type::NonNullType(_) => return None,
+                    _ => {
+                        result_map.insert(response_key.to_owned(), q::Value::Null);
+                    }
+                },
             };
         }
     }
 
-    // If we have result data, wrap it in an output object
-    if !result_map.is_empty() {
-        result.data = Some(q::Value::Object(result_map));
+    // Execute fragments marked with `@defer` for subsequent results
+    for (label, selection_set) in deferred_fragments {
+        ctx.add_incremental_task(IncrementalTask::Defer {
+            path: ctx.path.clone(),
+            label,
+            object_type: object_type.name.clone(),
+            object_value: object_value.clone(),
+            selection_set: selection_set.clone(),
+        });
     }
 
-    Ok(result)
+    Some(q::Value::Object(result_map))
 }
 
 /// Collects fields of a selection set.
+///
+/// Fragments that are deferred with `@defer` are not collected; their labels
+/// and selection sets are added to `deferred_fragments` instead.
 fn collect_fields<'a, R1, R2>(
     ctx: ExecutionContext<'a, R1, R2>,
     object_type: &s::ObjectType,
     selection_set: &'a q::SelectionSet,
     visited_fragments: Option<HashSet<&'a q::Name>>,
+    deferred_fragments: &mut Vec<(Option<String>, &'a q::SelectionSet)>,
 ) -> IndexMap<&'a String, Vec<&'a q::Field>>
 where
     R1: Resolver,
@@ -197,8 +812,8 @@ where
     let selections: Vec<_> = selection_set
         .items
         .iter()
-        .filter(|selection| !qast::skip_selection(selection))
-        .filter(|selection| qast::include_selection(selection))
+        .filter(|selection| !qast::skip_selection(selection, &ctx.variable_values))
+        .filter(|selection| qast::include_selection(selection, &ctx.variable_values))
         .collect();
 
     for selection in selections {
@@ -227,7 +842,7 @@ where
                     // Resolve the fragment using its name and, if it applies, collect
                     // fields for the fragment and group them
                     let fragment_grouped_field_set =
-                        qast::get_fragment(&ctx.query.document, &spread.fragment_name)
+                        qast::get_fragment(ctx.document, &spread.fragment_name)
                             .and_then(|fragment| {
                                 // We have a fragment, only pass it on if it applies to the
                                 // current object type
@@ -241,15 +856,30 @@ where
                                     None
                                 }
                             })
-                            .map(|fragment| {
+                            .and_then(|fragment| {
+                                // Defer the fragment if it is marked with `@defer`
+                                if ctx.is_incremental() {
+                                    if let Some(directive) =
+                                        qast::get_defer_directive(selection, &ctx.variable_values)
+                                    {
+                                        let label = qast::get_label_argument(
+                                            directive,
+                                            &ctx.variable_values,
+                                        );
+                                        deferred_fragments.push((label, &fragment.selection_set));
+                                        return None;
+                                    }
+                                }
+
                                 // We have a fragment that applies to the current object type,
                                 // collect its fields into response key groups
-                                collect_fields(
+                                Some(collect_fields(
                                     ctx.clone(),
                                     object_type,
                                     &fragment.selection_set,
                                     Some(visited_fragments.clone()),
-                                )
+                                    deferred_fragments,
+                                ))
                             });
 
                     if let Some(grouped_field_set) = fragment_grouped_field_set {
@@ -266,7 +896,47 @@ where
                 }
             }
 
-            q::Selection::InlineFragment(_) => unimplemented!(),
+            q::Selection::InlineFragment(fragment) => {
+                // Only consider the fragment if it has no type condition or if its
+                // type condition applies to the current object type
+                let applies = fragment
+                    .type_condition
+                    .as_ref()
+                    .map(|type_condition| {
+                        does_fragment_type_apply(ctx.clone(), object_type, type_condition)
+                    })
+                    .unwrap_or(true);
+
+                let defer_directive = if ctx.is_incremental() {
+                    qast::get_defer_directive(selection, &ctx.variable_values)
+                } else {
+                    None
+                };
+
+                // Defer the fragment if it is marked with `@defer`
+                if let (true, Some(directive)) = (applies, defer_directive) {
+                    let label = qast::get_label_argument(directive, &ctx.variable_values);
+                    deferred_fragments.push((label, &fragment.selection_set));
+                } else if applies {
+                    let grouped_field_set = collect_fields(
+                        ctx.clone(),
+                        object_type,
+                        &fragment.selection_set,
+                        Some(visited_fragments.clone()),
+                        deferred_fragments,
+                    );
+
+                    // Add all items from the fragment's groups to the field group
+                    // with the corresponding response key
+                    for (response_key, mut fragment_group) in grouped_field_set {
+                        if !grouped_fields.contains_key(response_key) {
+                            grouped_fields.insert(response_key, vec![]);
+                        }
+                        let group = grouped_fields.get_mut(response_key).unwrap();
+                        group.append(&mut fragment_group);
+                    }
+                }
+            }
         };
     }
 
@@ -323,6 +993,9 @@ where
 }
 
 /// Executes a field.
+///
+/// Returns `None` if the field failed, in which case the error has already
+/// been recorded.
 fn execute_field<'a, R1, R2>(
     ctx: ExecutionContext<'a, R1, R2>,
     object_type: &s::ObjectType,
@@ -330,24 +1003,241 @@ fn execute_field<'a, R1, R2>(
     field: &'a q::Field,
     field_definition: &s::Field,
     fields: Vec<&'a q::Field>,
-) -> Result<q::Value, QueryExecutionError>
+) -> Option<q::Value>
 where
     R1: Resolver,
     R2: Resolver,
 {
-    coerce_argument_values(ctx.clone(), object_type, field)
-        .and_then(|argument_values| {
-            resolve_field_value(
+    // Trace the field, including the completion of its value
+    let trace = ctx.tracer
+        .clone()
+        .map(|tracer| (tracer, ctx.path.clone(), Instant::now()));
+
+    let resolved_value =
+        coerce_argument_values(ctx.clone(), object_type, field).and_then(|argument_values| {
+            resolve_initial_stream_items(
                 ctx.clone(),
-                object_type,
                 object_value,
                 field,
                 field_definition,
-                &field_definition.field_type,
+                &fields,
                 &argument_values,
-            )
-        })
-        .and_then(|value| complete_value(ctx, field, &field_definition.field_type, fields, value))
+            ).unwrap_or_else(|| {
+                resolve_field_value(
+                    ctx.clone(),
+                    object_type,
+                    object_value,
+                    field,
+                    field_definition,
+                    &field_definition.field_type,
+                    &argument_values,
+                )
+            })
+        });
+
+    let value = match resolved_value {
+        Ok(value) => complete_value(ctx, field, &field_definition.field_type, fields, value),
+        Err(e) => {
+            ctx.add_error(e);
+            None
+        }
+    };
+
+    if let Some((tracer, path, started)) = trace {
+        tracer.trace_resolver(
+            path,
+            &object_type.name,
+            &field.name,
+            format!("{}", field_definition.field_type),
+            started,
+        );
+    }
+
+    value
+}
+
+/// Resolves only the initial items of a list field marked with `@stream` if
+/// the field has `skip`, `first`, `where` and `orderBy` arguments and is not
+/// ordered by anything but ID, leaving the remaining items to be fetched page
+/// by page for subsequent results.
+///
+/// Returns `None` if the items of the field are not fetched this way.
+fn resolve_initial_stream_items<'a, R1, R2>(
+    ctx: ExecutionContext<'a, R1, R2>,
+    object_value: &Option<q::Value>,
+    field: &'a q::Field,
+    field_definition: &s::Field,
+    fields: &Vec<&'a q::Field>,
+    argument_values: &HashMap<&q::Name, q::Value>,
+) -> Option<Result<q::Value, QueryExecutionError>>
+where
+    R1: Resolver,
+    R2: Resolver,
+{
+    if !ctx.is_incremental() {
+        return None;
+    }
+
+    let directive = qast::get_stream_directive(field, &ctx.variable_values)?;
+    let item_type = get_list_item_type(&field_definition.field_type)?;
+    let item_object_type =
+        match sast::get_named_type(&ctx.schema.document, sast::get_base_type(item_type)) {
+            Some(s::TypeDefinition::Object(t)) => t,
+            _ => return None,
+        };
+    if ["skip", "first", "where", "orderBy"].iter().any(|name| {
+        !field_definition
+            .arguments
+            .iter()
+            .any(|argument| &argument.name == name)
+    }) {
+        return None;
+    }
+
+    // Pages continue after the last ID of the previous page, which requires
+    // the items to be ordered by ID
+    if sast::get_field_type(item_object_type, &"id".to_string()).is_none() {
+        return None;
+    }
+    match argument_values.get(&"orderBy".to_string()) {
+        None | Some(q::Value::Null) => (),
+        Some(q::Value::Enum(name)) if name == "id" => (),
+        Some(_) => return None,
+    }
+    let descending = match argument_values.get(&"orderDirection".to_string()) {
+        Some(q::Value::Enum(name)) => name == "desc",
+        _ => false,
+    };
+    let order_by_name = String::from("orderBy");
+    let mut argument_values = argument_values.clone();
+    argument_values.insert(&order_by_name, q::Value::Enum(String::from("id")));
+
+    let initial_count = qast::get_initial_count_argument(directive, &ctx.variable_values);
+    let skip = get_count_argument(&argument_values, "skip").unwrap_or(0);
+    // Like the store, fetch a default number of items if `first` is missing
+    // or zero
+    let first = get_count_argument(&argument_values, "first")
+        .and_then(|n| if n > 0 { Some(n) } else { None })
+        .unwrap_or(DEFAULT_FIRST);
+    // Initial pages are capped at the largest page that can be requested
+    let initial_first = cmp::min(cmp::min(first, initial_count), i32::max_value() as usize);
+
+    // Only fetch the initial page if it contains any items
+    let first_name = String::from("first");
+    let items = if initial_first > 0 {
+        let mut initial_argument_values = argument_values.clone();
+        initial_argument_values.insert(
+            &first_name,
+            q::Value::Int(q::Number::from(initial_first as i32)),
+        );
+        match ctx.resolver.resolve_objects(
+            object_value,
+            &field.name,
+            field_definition,
+            item_object_type,
+            &initial_argument_values,
+        ) {
+            Ok(q::Value::List(items)) => items,
+            result => return Some(result),
+        }
+    } else {
+        vec![]
+    };
+
+    // Fetch the remaining items page by page, unless the initial page was the last one
+    let remaining = first - initial_first;
+    if items.len() == initial_first && remaining != 0 {
+        ctx.add_incremental_task(IncrementalTask::Stream {
+            path: ctx.path.clone(),
+            label: qast::get_label_argument(directive, &ctx.variable_values),
+            fields: fields.iter().map(|field| (*field).clone()).collect(),
+            item_type: item_type.clone(),
+            next_index: initial_first,
+            items: StreamItems::Paged {
+                object_value: object_value.clone(),
+                field_definition: field_definition.clone(),
+                item_object_type: item_object_type.name.clone(),
+                argument_values: argument_values
+                    .iter()
+                    .map(|(name, value)| ((*name).clone(), value.clone()))
+                    .collect(),
+                skip,
+                last_id: match items.last() {
+                    Some(item) => match get_item_id(item) {
+                        Ok(id) => Some(id),
+                        Err(e) => return Some(Err(e)),
+                    },
+                    None => None,
+                },
+                descending,
+                remaining,
+            },
+        });
+    }
+
+    Some(Ok(q::Value::List(items)))
+}
+
+/// Converts a `skip` or `first` count into an `Int` value, failing if it
+/// doesn't fit into a 32-bit GraphQL integer.
+fn count_value(n: usize) -> Result<q::Value, QueryExecutionError> {
+    if n > i32::max_value() as usize {
+        return Err(QueryExecutionError::ResolveEntitiesError(format!(
+            "Cannot page through more than {} items",
+            i32::max_value()
+        )));
+    }
+    Ok(q::Value::Int(q::Number::from(n as i32)))
+}
+
+/// Returns the ID of an item of a streamed list.
+fn get_item_id(item: &q::Value) -> Result<String, QueryExecutionError> {
+    match item {
+        q::Value::Object(object) => match object.get("id") {
+            Some(q::Value::String(id)) => Ok(id.to_owned()),
+            _ => Err(QueryExecutionError::ResolveEntitiesError(String::from(
+                "Cannot page through items without an ID",
+            ))),
+        },
+        _ => Err(QueryExecutionError::ResolveEntitiesError(String::from(
+            "Cannot page through items that are not objects",
+        ))),
+    }
+}
+
+/// Adds a condition to a `where` argument value that only matches items after
+/// the given ID, replacing any other condition on the ID in the same direction.
+fn id_filter_after(filter: Option<&q::Value>, last_id: &str, descending: bool) -> q::Value {
+    let mut filter = match filter {
+        Some(q::Value::Object(filter)) => filter.clone(),
+        _ => BTreeMap::new(),
+    };
+    let key = if descending { "id_lt" } else { "id_gt" };
+    filter.insert(String::from(key), q::Value::String(last_id.to_owned()));
+    q::Value::Object(filter)
+}
+
+/// Returns the item type of a (possibly non-null) list type.
+fn get_list_item_type(t: &s::Type) -> Option<&s::Type> {
+    match t {
+        s::Type::NonNullType(inner_type) => get_list_item_type(inner_type),
+        s::Type::ListType(item_type) => Some(item_type),
+        s::Type::NamedType(_) => None,
+    }
+}
+
+/// Returns the value of a non-negative `Int` argument like `skip` or `first`.
+fn get_count_argument(argument_values: &HashMap<&q::Name, q::Value>, name: &str) -> Option<usize> {
+    match argument_values.get(&name.to_string()) {
+        Some(q::Value::Int(n)) => n.as_i64().and_then(|n| {
+            if n >= 0 {
+                Some(n as usize)
+            } else {
+                None
+            }
+        }),
+        _ => None,
+    }
 }
 
 /// Resolves the value of a field.
@@ -423,31 +1313,31 @@ where
         // Let the resolver decide how the field (with the given object type)
         // is resolved into an entity based on the (potential) parent object
         s::TypeDefinition::Object(t) => if ctx.introspecting {
-            Ok(ctx.introspection_resolver.resolve_object(
+            ctx.introspection_resolver.resolve_object(
                 object_value,
                 &field.name,
                 field_definition,
                 t,
                 argument_values,
-            ))
+            )
         } else {
-            Ok(ctx.resolver.resolve_object(
+            ctx.resolver.resolve_object(
                 object_value,
                 &field.name,
                 field_definition,
                 t,
                 argument_values,
-            ))
+            )
         },
 
         // Let the resolver decide how values in the resolved object value
         // map to values of GraphQL enums
         s::TypeDefinition::Enum(t) => match object_value {
             Some(q::Value::Object(o)) => if ctx.introspecting {
-                Ok(ctx.introspection_resolver
-                    .resolve_enum_value(t, o.get(&field.name)))
+                ctx.introspection_resolver
+                    .resolve_enum_value(t, o.get(&field.name))
             } else {
-                Ok(ctx.resolver.resolve_enum_value(t, o.get(&field.name)))
+                ctx.resolver.resolve_enum_value(t, o.get(&field.name))
             },
             _ => Ok(q::Value::Null),
         },
@@ -456,19 +1346,38 @@ where
         // map to values of GraphQL scalars
         s::TypeDefinition::Scalar(t) => match object_value {
             Some(q::Value::Object(o)) => if ctx.introspecting {
-                Ok(ctx.introspection_resolver
-                    .resolve_scalar_value(t, o.get(&field.name)))
+                ctx.introspection_resolver
+                    .resolve_scalar_value(t, o.get(&field.name))
             } else {
-                Ok(ctx.resolver.resolve_scalar_value(t, o.get(&field.name)))
+                ctx.resolver.resolve_scalar_value(t, o.get(&field.name))
             },
             _ => Ok(q::Value::Null),
         },
 
-        // We will implement these later
-        s::TypeDefinition::Interface(_) => unimplemented!(),
-        s::TypeDefinition::Union(_) => unimplemented!(),
+        // Let the resolver decide how the field (with the given interface or union
+        // type) is resolved into an entity of one of the possible types
+        s::TypeDefinition::Interface(_) | s::TypeDefinition::Union(_) => if ctx.introspecting {
+            ctx.introspection_resolver.resolve_abstract_object(
+                ctx.introspection_schema,
+                object_value,
+                &field.name,
+                field_definition,
+                named_type,
+                argument_values,
+            )
+        } else {
+            ctx.resolver.resolve_abstract_object(
+                &ctx.schema.document,
+                object_value,
+                &field.name,
+                field_definition,
+                named_type,
+                argument_values,
+            )
+        },
 
-        _ => unimplemented!(),
+        // Input object types can't be used as field types
+        _ => Err(QueryExecutionError::NamedTypeError(type_name.to_string())),
     }
 }
 
@@ -505,37 +1414,37 @@ where
                     &ctx.schema.document
                 },
                 type_name,
-            ).expect("Failed to resolve named type inside list type");
+            ).ok_or(QueryExecutionError::NamedTypeError(type_name.to_string()))?;
 
             match named_type {
                 // Let the resolver decide how the list field (with the given item object type)
                 // is resolved into a entities based on the (potential) parent object
                 s::TypeDefinition::Object(t) => if ctx.introspecting {
-                    Ok(ctx.introspection_resolver.resolve_objects(
+                    ctx.introspection_resolver.resolve_objects(
                         object_value,
                         &field.name,
                         field_definition,
                         t,
                         argument_values,
-                    ))
+                    )
                 } else {
-                    Ok(ctx.resolver.resolve_objects(
+                    ctx.resolver.resolve_objects(
                         object_value,
                         &field.name,
                         field_definition,
                         t,
                         argument_values,
-                    ))
+                    )
                 },
 
                 // Let the resolver decide how values in the resolved object value
                 // map to values of GraphQL enums
                 s::TypeDefinition::Enum(t) => match object_value {
                     Some(q::Value::Object(o)) => if ctx.introspecting {
-                        Ok(ctx.introspection_resolver
-                            .resolve_enum_values(t, o.get(&field.name)))
+                        ctx.introspection_resolver
+                            .resolve_enum_values(t, o.get(&field.name))
                     } else {
-                        Ok(ctx.resolver.resolve_enum_values(t, o.get(&field.name)))
+                        ctx.resolver.resolve_enum_values(t, o.get(&field.name))
                     },
                     _ => Ok(q::Value::Null),
                 },
@@ -544,92 +1453,165 @@ where
                 // map to values of GraphQL scalars
                 s::TypeDefinition::Scalar(t) => match object_value {
                     Some(q::Value::Object(o)) => if ctx.introspecting {
-                        Ok(ctx.introspection_resolver
-                            .resolve_scalar_values(t, o.get(&field.name)))
+                        ctx.introspection_resolver
+                            .resolve_scalar_values(t, o.get(&field.name))
                     } else {
-                        Ok(ctx.resolver.resolve_scalar_values(t, o.get(&field.name)))
+                        ctx.resolver.resolve_scalar_values(t, o.get(&field.name))
                     },
                     _ => Ok(q::Value::Null),
                 },
 
-                // We will implement these later
-                s::TypeDefinition::Interface(_) => unimplemented!(),
-                s::TypeDefinition::Union(_) => unimplemented!(),
+                // Let the resolver decide how the list field (with the given item
+                // interface or union type) is resolved into entities of the possible types
+                s::TypeDefinition::Interface(_) | s::TypeDefinition::Union(_) => {
+                    if ctx.introspecting {
+                        ctx.introspection_resolver.resolve_abstract_objects(
+                            ctx.introspection_schema,
+                            object_value,
+                            &field.name,
+                            field_definition,
+                            named_type,
+                            argument_values,
+                        )
+                    } else {
+                        ctx.resolver.resolve_abstract_objects(
+                            &ctx.schema.document,
+                            object_value,
+                            &field.name,
+                            field_definition,
+                            named_type,
+                            argument_values,
+                        )
+                    }
+                }
 
-                _ => unimplemented!(),
+                // Input object types can't be used as field types
+                _ => Err(QueryExecutionError::NamedTypeError(type_name.to_string())),
             }
         }
 
         // We don't support nested lists yet
-        s::Type::ListType(_) => unimplemented!(),
+        s::Type::ListType(_) => Err(QueryExecutionError::NotSupported(format!(
+            "Nested list types (field \"{}\")",
+            field.name
+        ))),
     }
 }
 
 /// Ensures that a value matches the expected return type.
+///
+/// Returns `None` if completing the value failed in a way that requires the
+/// null value to propagate to the nearest nullable parent; the error has
+/// already been recorded in that case.
 fn complete_value<'a, R1, R2>(
     ctx: ExecutionContext<'a, R1, R2>,
     field: &'a q::Field,
     field_type: &'a s::Type,
     fields: Vec<&'a q::Field>,
     resolved_value: q::Value,
-) -> Result<q::Value, QueryExecutionError>
+) -> Option<q::Value>
 where
     R1: Resolver,
     R2: Resolver,
 {
     // Fail if the field type is non-null but the value is null
     if let s::Type::NonNullType(inner_type) = field_type {
-        return match complete_value(ctx.clone(), field, inner_type, fields, resolved_value)? {
-            q::Value::Null => Err(QueryExecutionError::NonNullError(
-                field.position,
-                field.name.to_string(),
-            )),
+        return match complete_value(ctx.clone(), field, inner_type, fields, resolved_value) {
+            Some(q::Value::Null) => {
+                ctx.add_error(QueryExecutionError::NonNullError(
+                    field.position,
+                    field.name.to_string(),
+                ));
+                None
+            }
 
-            v => Ok(v),
+            v => v,
         };
     }
 
     // If the resolved value is null, return null
     if resolved_value == q::Value::Null {
-        return Ok(resolved_value);
+        return Some(resolved_value);
     }
 
     // Complete list values
     if let s::Type::ListType(inner_type) = field_type {
         return match resolved_value {
             // Complete list values individually
-            q::Value::List(values) => {
+            q::Value::List(mut values) => {
+                // Complete only the initial items of lists marked with `@stream`
+                // and deliver the remaining items in subsequent results
+                let stream_directive = if ctx.is_incremental() {
+                    qast::get_stream_directive(field, &ctx.variable_values)
+                } else {
+                    None
+                };
+                if let Some(directive) = stream_directive {
+                    let initial_count =
+                        qast::get_initial_count_argument(directive, &ctx.variable_values);
+                    if values.len() > initial_count {
+                        let remaining_values = values.split_off(initial_count);
+                        ctx.add_incremental_task(IncrementalTask::Stream {
+                            path: ctx.path.clone(),
+                            label: qast::get_label_argument(directive, &ctx.variable_values),
+                            fields: fields.iter().map(|field| (*field).clone()).collect(),
+                            item_type: inner_type.as_ref().clone(),
+                            next_index: initial_count,
+                            items: StreamItems::Resolved(remaining_values.into_iter().collect()),
+                        });
+                    }
+                }
+
+                // Resolve the object fields of all items in batches up front
+                let mut prefetched =
+                    prefetch_object_fields(ctx.clone(), inner_type, &fields, &values).into_iter();
+
                 let mut out = Vec::with_capacity(values.len());
-                for value in values.into_iter() {
-                    out.push(complete_value(
-                        ctx.clone(),
+                for (index, value) in values.into_iter().enumerate() {
+                    let mut item_ctx = ctx.for_list_item(index);
+                    if let Some(item_prefetched) = prefetched.next() {
+                        item_ctx.prefetched = Arc::new(item_prefetched);
+                    }
+
+                    match complete_value(
+                        item_ctx,
                         field,
                         inner_type,
                         fields.clone(),
                         value,
-                    )?);
+                    ) {
+                        Some(v) => out.push(v),
+
+                        // The item failed; null it out if the item type is nullable,
+                        // otherwise null out the entire list
+                        None => match inner_type.as_ref() {
+                            s::Type::NonNullType(_) => return None,
+                            _ => out.push(q::Value::Null),
+                        },
+                    }
                 }
-                Ok(q::Value::List(out))
+                Some(q::Value::List(out))
             }
 
             // Return field error if the resolved value for the list is not a list
-            _ => Err(QueryExecutionError::ListValueError(
-                field.position,
-                field.name.to_string(),
-            )),
+            _ => {
+                ctx.add_error(QueryExecutionError::ListValueError(
+                    field.position,
+                    field.name.to_string(),
+                ));
+                None
+            }
         };
     }
 
     let named_type = if let s::Type::NamedType(name) = field_type {
-        Some(
-            sast::get_named_type(
-                if ctx.introspecting {
-                    ctx.introspection_schema
-                } else {
-                    &ctx.schema.document
-                },
-                name,
-            ).unwrap(),
+        sast::get_named_type(
+            if ctx.introspecting {
+                ctx.introspection_schema
+            } else {
+                &ctx.schema.document
+            },
+            name,
         )
     } else {
         None
@@ -638,57 +1620,128 @@ where
     match named_type {
         // Complete scalar values; we're assuming that the resolver has
         // already returned a valid value for the scalar type
-        Some(s::TypeDefinition::Scalar(_)) => return Ok(resolved_value),
+        Some(s::TypeDefinition::Scalar(_)) => Some(resolved_value),
 
         // Complete enum values; we're assuming that the resolver has
         // already returned a valid value for the enum type
-        Some(s::TypeDefinition::Enum(_)) => return Ok(resolved_value),
+        Some(s::TypeDefinition::Enum(_)) => Some(resolved_value),
 
         // Complete object types recursively
         Some(s::TypeDefinition::Object(object_type)) => execute_selection_set(
-            ctx.clone(),
+            ctx,
             &merge_selection_sets(fields),
             object_type,
             &Some(resolved_value),
-        ).map(|result| match result.data {
-            Some(v) => v,
-            None => q::Value::Null,
-        }),
+        ),
 
-        // Resolve interface types using the resolved value and complete the value recursively
-        Some(s::TypeDefinition::Interface(_)) => {
-            let object_type =
-                resolve_abstract_type(ctx.clone(), named_type.unwrap(), &resolved_value)?;
+        // Resolve interface and union types using the resolved value and complete
+        // the value recursively
+        Some(abstract_type @ s::TypeDefinition::Interface(_))
+        | Some(abstract_type @ s::TypeDefinition::Union(_)) => {
+            match resolve_abstract_type(ctx.clone(), abstract_type, &resolved_value) {
+                Ok(object_type) => execute_selection_set(
+                    ctx,
+                    &merge_selection_sets(fields),
+                    object_type,
+                    &Some(resolved_value),
+                ),
+                Err(e) => {
+                    ctx.add_error(e);
+                    None
+                }
+            }
+        }
 
-            execute_selection_set(
-                ctx.clone(),
-                &merge_selection_sets(fields),
-                object_type,
-                &Some(resolved_value),
-            ).map(|result| match result.data {
-                Some(v) => v,
-                None => q::Value::Null,
-            })
+        // Input object types can't be used as field types
+        _ => {
+            ctx.add_error(QueryExecutionError::NamedTypeError(
+                sast::get_base_type(field_type).to_string(),
+            ));
+            None
         }
+    }
+}
 
-        // Resolve union types using the resolved value and complete the value recursively
-        Some(s::TypeDefinition::Union(_)) => {
-            let object_type =
-                resolve_abstract_type(ctx.clone(), named_type.unwrap(), &resolved_value)?;
+/// Resolves the fields of a list of objects that refer to other objects in
+/// batches, so that the referenced objects of all items are resolved at once
+/// rather than item by item.
+///
+/// Returns the resolved field values of each item by response key, or nothing
+/// if the items are not objects. Fields that fail to resolve in a batch are
+/// left out, so they are resolved (and their errors reported) per item.
+fn prefetch_object_fields<'a, R1, R2>(
+    ctx: ExecutionContext<'a, R1, R2>,
+    item_type: &s::Type,
+    fields: &Vec<&'a q::Field>,
+    items: &[q::Value],
+) -> Vec<HashMap<String, q::Value>>
+where
+    R1: Resolver,
+    R2: Resolver,
+{
+    // Batching only pays off for more than one item
+    if ctx.introspecting || items.len() < 2 {
+        return vec![];
+    }
 
-            execute_selection_set(
-                ctx.clone(),
-                &merge_selection_sets(fields),
-                object_type,
-                &Some(resolved_value),
-            ).map(|result| match result.data {
-                Some(v) => v,
-                None => q::Value::Null,
-            })
-        }
+    let schema = &ctx.schema.document;
+    let object_type = match sast::get_named_type(schema, sast::get_base_type(item_type)) {
+        Some(s::TypeDefinition::Object(t)) => t,
+        _ => return vec![],
+    };
+
+    let mut prefetched = vec![HashMap::new(); items.len()];
+    let selection_set = merge_selection_sets(fields.clone());
+
+    let grouped_field_set =
+        collect_fields(ctx.clone(), object_type, &selection_set, None, &mut vec![]);
+    for (response_key, fields) in grouped_field_set {
+        let field_definition = match sast::get_field_type(object_type, &fields[0].name) {
+            Some(field_definition) => field_definition,
+            None => continue,
+        };
+
+        // Only fields that refer to objects are resolved in batches
+        let field_object_type =
+            match sast::get_named_type(schema, sast::get_base_type(&field_definition.field_type)) {
+                Some(s::TypeDefinition::Object(t)) => t,
+                _ => continue,
+            };
+
+        let argument_values = match coerce_argument_values(ctx.clone(), object_type, fields[0]) {
+            Ok(argument_values) => argument_values,
+            Err(_) => continue,
+        };
+
+        let values = if sast::is_list_type(&field_definition.field_type) {
+            ctx.resolver.resolve_objects_for_parents(
+                items,
+                &fields[0].name,
+                field_definition,
+                field_object_type,
+                &argument_values,
+            )
+        } else {
+            ctx.resolver.resolve_object_for_parents(
+                items,
+                &fields[0].name,
+                field_definition,
+                field_object_type,
+                &argument_values,
+            )
+        };
 
-        _ => unimplemented!(),
+        match values {
+            Ok(ref values) if values.len() == items.len() => {
+                for (item_prefetched, value) in prefetched.iter_mut().zip(values) {
+                    item_prefetched.insert(response_key.to_owned(), value.clone());
+                }
+            }
+            _ => (),
+        }
     }
+
+    prefetched
 }
 
 /// Resolves an abstract type (interface, union) into an object type based on the given value.
@@ -703,17 +1756,16 @@ where
 {
     // Let the resolver handle the type resolution, return an error if the resolution
     // yields nothing
-    ctx.resolver
-        .resolve_abstract_type(
-            if ctx.introspecting {
-                ctx.introspection_schema
-            } else {
-                &ctx.schema.document
-            },
+    if ctx.introspecting {
+        ctx.introspection_resolver.resolve_abstract_type(
+            ctx.introspection_schema,
             abstract_type,
             object_value,
         )
-        .ok_or(QueryExecutionError::AbstractTypeError(
+    } else {
+        ctx.resolver
+            .resolve_abstract_type(&ctx.schema.document, abstract_type, object_value)
+    }.ok_or(QueryExecutionError::AbstractTypeError(
             sast::get_type_name(abstract_type).to_string(),
         ))
 }
@@ -758,12 +1810,26 @@ where
 {
     let mut coerced_values = HashMap::new();
 
-    if let Some(argument_definitions) = sast::get_argument_definitions(object_type, &field.name) {
+    // The `__schema` and `__type` fields on the root query type are defined in
+    // the introspection schema
+    let argument_definitions = sast::get_argument_definitions(object_type, &field.name)
+        .or_else(|| {
+            if ctx.introspecting {
+                sast::get_root_query_type(ctx.introspection_schema)
+                    .and_then(|t| sast::get_argument_definitions(t, &field.name))
+            } else {
+                None
+            }
+        });
+
+    if let Some(argument_definitions) = argument_definitions {
         for argument_def in argument_definitions.iter() {
-            match qast::get_argument_value(&field.arguments, &argument_def.name) {
-                // We don't support variables yet
-                Some(q::Value::Variable(_)) => unimplemented!(),
+            // Substitute variables in the argument value; arguments that refer to
+            // variables without a value are treated as if they were not provided
+            let value = qast::get_argument_value(&field.arguments, &argument_def.name)
+                .and_then(|value| substitute_variables(value, &ctx.variable_values));
 
+            match value {
                 // There is no value, either use the default or fail
                 None => {
                     if let Some(ref default_value) = argument_def.default_value {
@@ -781,7 +1847,7 @@ where
                 Some(v) => {
                     coerced_values.insert(
                         &argument_def.name,
-                        coerce_argument_value(ctx.clone(), field, argument_def, v)?,
+                        coerce_argument_value(ctx.clone(), field, argument_def, &v)?,
                     );
                 }
             };
@@ -791,6 +1857,100 @@ where
     Ok(coerced_values)
 }
 
+/// Replaces variables in a value with their coerced values.
+///
+/// Returns `None` if the value is a variable for which no value exists. Object
+/// fields referring to such variables are dropped, list items are set to `null`.
+pub(crate) fn substitute_variables(
+    value: &q::Value,
+    variable_values: &HashMap<q::Name, q::Value>,
+) -> Option<q::Value> {
+    match value {
+        q::Value::Variable(name) => variable_values.get(name).cloned(),
+        q::Value::List(values) => Some(q::Value::List(
+            values
+                .iter()
+                .map(|value| substitute_variables(value, variable_values).unwrap_or(q::Value::Null))
+                .collect(),
+        )),
+        q::Value::Object(fields) => Some(q::Value::Object(
+            fields
+                .iter()
+                .filter_map(|(name, value)| {
+                    substitute_variables(value, variable_values).map(|value| (name.clone(), value))
+                })
+                .collect(),
+        )),
+        _ => Some(value.clone()),
+    }
+}
+
+/// Coerces the variable values provided with a query based on the variable
+/// definitions of the operation to execute.
+fn coerce_variable_values(
+    schema: &Schema,
+    operation: &q::OperationDefinition,
+    variables: &Option<QueryVariables>,
+) -> Result<HashMap<q::Name, q::Value>, Vec<QueryExecutionError>> {
+    use values::coercion::coerce_value;
+
+    let resolver = |name: &s::Name| sast::get_named_type(&schema.document, name);
+
+    let mut coerced_values = HashMap::new();
+    let mut errors = vec![];
+
+    for variable_def in qast::get_variable_definitions(operation)
+        .into_iter()
+        .flat_map(|defs| defs.iter())
+    {
+        // Reject variables that aren't declared with input types
+        if !sast::is_input_type(&schema.document, &variable_def.var_type) {
+            errors.push(QueryExecutionError::InvalidVariableTypeError(
+                variable_def.position,
+                variable_def.name.to_owned(),
+            ));
+            continue;
+        }
+
+        // Use the provided value or, if there is none, the default value
+        let value = variables
+            .as_ref()
+            .and_then(|variables| variables.get(&variable_def.name))
+            .map(|value| value.deref())
+            .or(variable_def.default_value.as_ref());
+
+        match value {
+            // There is no value, fail if the variable is required
+            None => {
+                if let q::Type::NonNullType(_) = variable_def.var_type {
+                    errors.push(QueryExecutionError::MissingVariableError(
+                        variable_def.position,
+                        variable_def.name.to_owned(),
+                    ));
+                }
+            }
+
+            // There is a value, attempt to coerce it into the variable type
+            Some(value) => match coerce_value(value, &variable_def.var_type, &resolver) {
+                Some(coerced_value) => {
+                    coerced_values.insert(variable_def.name.to_owned(), coerced_value);
+                }
+                None => errors.push(QueryExecutionError::InvalidVariableError(
+                    variable_def.position,
+                    variable_def.name.to_owned(),
+                    value.clone(),
+                )),
+            },
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(coerced_values)
+    } else {
+        Err(errors)
+    }
+}
+
 /// Coerces a single argument value into a GraphQL value.
 fn coerce_argument_value<'a, R1, R2>(
     ctx: ExecutionContext<'a, R1, R2>,
@@ -0,0 +1,424 @@
+use graphql_parser::{query as q, schema as s};
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+
+use graph::prelude::QueryExecutionError;
+
+use prelude::*;
+use query::ast as qast;
+use query::execution::substitute_variables;
+use schema::ast as sast;
+use store::DEFAULT_FIRST;
+
+/// The estimated cost of executing a selection set.
+#[derive(Debug, Default, PartialEq)]
+struct Cost {
+    /// The deepest nesting of fields.
+    depth: u64,
+    /// The maximum number of values that may be resolved.
+    complexity: u64,
+    /// The maximum number of entities that may be resolved.
+    entities: u64,
+}
+
+impl Cost {
+    fn add(&mut self, other: Cost) {
+        self.depth = cmp::max(self.depth, other.depth);
+        self.complexity = self.complexity.saturating_add(other.complexity);
+        self.entities = self.entities.saturating_add(other.entities);
+    }
+}
+
+/// Context for estimating the cost of an operation.
+struct CostContext<'a> {
+    schema: &'a s::Document,
+    document: &'a q::Document,
+    variable_values: &'a HashMap<q::Name, q::Value>,
+    max_first: Option<u32>,
+    max_skip: Option<u32>,
+    errors: Vec<QueryExecutionError>,
+}
+
+/// Checks an operation against the limits in the execution options before it
+/// is executed.
+///
+/// The depth, complexity and number of entities are estimated from the
+/// selections of the operation and the `first` arguments of list fields;
+/// introspection fields are not taken into account.
+pub fn check_limits<R>(
+    schema: &s::Document,
+    document: &q::Document,
+    operation: &q::OperationDefinition,
+    variable_values: &HashMap<q::Name, q::Value>,
+    options: &ExecutionOptions<R>,
+) -> Result<(), Vec<QueryExecutionError>>
+where
+    R: Resolver,
+{
+    let (root_type_name, selection_set) = match operation {
+        q::OperationDefinition::Query(query) => ("Query", &query.selection_set),
+        q::OperationDefinition::SelectionSet(selection_set) => ("Query", selection_set),
+        q::OperationDefinition::Mutation(mutation) => ("Mutation", &mutation.selection_set),
+        q::OperationDefinition::Subscription(subscription) => {
+            ("Subscription", &subscription.selection_set)
+        }
+    };
+
+    let root_type = match sast::get_named_type(schema, &root_type_name.to_string()) {
+        Some(root_type) => root_type,
+        None => return Ok(()),
+    };
+
+    let mut ctx = CostContext {
+        schema,
+        document,
+        variable_values,
+        max_first: options.max_first,
+        max_skip: options.max_skip,
+        errors: vec![],
+    };
+    let cost = selection_set_cost(&mut ctx, root_type, selection_set, &mut HashSet::new());
+    let mut errors = ctx.errors;
+
+    if let Some(max_depth) = options.max_depth {
+        if cost.depth > max_depth as u64 {
+            errors.push(QueryExecutionError::TooDeep(max_depth));
+        }
+    }
+
+    if let Some(max_complexity) = options.max_complexity {
+        if cost.complexity > max_complexity {
+            errors.push(QueryExecutionError::TooComplex(
+                cost.complexity,
+                max_complexity,
+            ));
+        }
+    }
+
+    if let Some(max_entities) = options.max_entities {
+        if cost.entities > max_entities {
+            errors.push(QueryExecutionError::TooManyEntities(
+                cost.entities,
+                max_entities,
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Estimates the cost of a selection set of the given parent type.
+fn selection_set_cost<'a>(
+    ctx: &mut CostContext<'a>,
+    parent_type: &'a s::TypeDefinition,
+    selection_set: &'a q::SelectionSet,
+    visited_fragments: &mut HashSet<&'a q::Name>,
+) -> Cost {
+    let mut cost = Cost::default();
+
+    // Skipped selections are not executed and don't add to the cost
+    let variable_values = ctx.variable_values;
+    let selections = selection_set.items.iter().filter(|selection| {
+        !qast::skip_selection(selection, variable_values)
+            && qast::include_selection(selection, variable_values)
+    });
+
+    for selection in selections {
+        match selection {
+            q::Selection::Field(field) => cost.add(field_cost(ctx, parent_type, field, visited_fragments)),
+
+            q::Selection::FragmentSpread(spread) => {
+                // Fragments are only expanded once per path, so that cyclic
+                // fragments don't send us into an endless loop
+                if !visited_fragments.insert(&spread.fragment_name) {
+                    continue;
+                }
+
+                if let Some(fragment) = qast::get_fragment(ctx.document, &spread.fragment_name) {
+                    let q::TypeCondition::On(ref name) = fragment.type_condition;
+                    if let Some(fragment_type) = sast::get_named_type(ctx.schema, name) {
+                        let fragment_cost = selection_set_cost(
+                            ctx,
+                            fragment_type,
+                            &fragment.selection_set,
+                            visited_fragments,
+                        );
+                        cost.add(fragment_cost);
+                    }
+                }
+
+                visited_fragments.remove(&spread.fragment_name);
+            }
+
+            q::Selection::InlineFragment(fragment) => {
+                let fragment_type = match fragment.type_condition {
+                    Some(q::TypeCondition::On(ref name)) => sast::get_named_type(ctx.schema, name),
+                    None => Some(parent_type),
+                };
+
+                if let Some(fragment_type) = fragment_type {
+                    let fragment_cost = selection_set_cost(
+                        ctx,
+                        fragment_type,
+                        &fragment.selection_set,
+                        visited_fragments,
+                    );
+                    cost.add(fragment_cost);
+                }
+            }
+        }
+    }
+
+    cost
+}
+
+/// Estimates the cost of a field of the given parent type.
+fn field_cost<'a>(
+    ctx: &mut CostContext<'a>,
+    parent_type: &'a s::TypeDefinition,
+    field: &'a q::Field,
+    visited_fragments: &mut HashSet<&'a q::Name>,
+) -> Cost {
+    // Introspection is bounded by the size of the schema
+    if field.name.starts_with("__") {
+        return Cost::default();
+    }
+
+    let field_definition = match parent_type {
+        s::TypeDefinition::Object(t) => sast::get_field_type(t, &field.name),
+        s::TypeDefinition::Interface(t) => t.fields.iter().find(|f| f.name == field.name),
+        _ => None,
+    };
+    let field_definition = match field_definition {
+        Some(field_definition) => field_definition,
+        None => return Cost::default(),
+    };
+
+    let (max_first, max_skip) = (ctx.max_first, ctx.max_skip);
+    let first = range_argument(ctx, field, "first", max_first);
+    range_argument(ctx, field, "skip", max_skip);
+
+    // Fields that don't refer to entities are leaves
+    let field_type = sast::get_named_type(ctx.schema, sast::get_base_type(&field_definition.field_type));
+    let field_type = match field_type {
+        Some(t @ s::TypeDefinition::Object(_))
+        | Some(t @ s::TypeDefinition::Interface(_))
+        | Some(t @ s::TypeDefinition::Union(_)) => t,
+        _ => {
+            return Cost {
+                depth: 1,
+                complexity: 1,
+                entities: 0,
+            }
+        }
+    };
+
+    // Each entity of a list field resolves the entire selection set again;
+    // the store fetches a default number of entities if `first` is missing
+    // or not positive
+    let items = if sast::is_list_type(&field_definition.field_type) {
+        first
+            .and_then(|first| if first > 0 { Some(first as u64) } else { None })
+            .unwrap_or(DEFAULT_FIRST as u64)
+    } else {
+        1
+    };
+
+    let children = selection_set_cost(ctx, field_type, &field.selection_set, visited_fragments);
+
+    Cost {
+        depth: children.depth.saturating_add(1),
+        complexity: items.saturating_mul(children.complexity.saturating_add(1)),
+        entities: items.saturating_mul(children.entities.saturating_add(1)),
+    }
+}
+
+/// Returns the value of a `first` or `skip` argument of a field, recording an
+/// error if it exceeds the given maximum.
+fn range_argument(
+    ctx: &mut CostContext,
+    field: &q::Field,
+    name: &str,
+    max: Option<u32>,
+) -> Option<i64> {
+    let value = qast::get_argument_value(&field.arguments, &name.to_string())
+        .and_then(|value| substitute_variables(value, ctx.variable_values))
+        .and_then(|value| match value {
+            q::Value::Int(n) => n.as_i64(),
+            _ => None,
+        });
+
+    match (value, max) {
+        (Some(value), Some(max)) if value > max as i64 => {
+            ctx.errors.push(QueryExecutionError::RangeArgumentError(
+                field.position,
+                name.to_string(),
+                value,
+                max,
+            ));
+        }
+        _ => (),
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use graphql_parser;
+    use graphql_parser::{query as q, schema as s};
+    use slog;
+    use std::collections::HashMap;
+
+    use graph::prelude::QueryExecutionError;
+
+    use super::check_limits;
+    use prelude::*;
+    use query::ast as qast;
+
+    #[derive(Clone)]
+    struct MockResolver;
+
+    impl Resolver for MockResolver {
+        fn resolve_objects(
+            &self,
+            _parent: &Option<q::Value>,
+            _field: &q::Name,
+            _field_definition: &s::Field,
+            _object_type: &s::ObjectType,
+            _arguments: &HashMap<&q::Name, q::Value>,
+        ) -> Result<q::Value, QueryExecutionError> {
+            Ok(q::Value::Null)
+        }
+
+        fn resolve_object(
+            &self,
+            _parent: &Option<q::Value>,
+            _field: &q::Name,
+            _field_definition: &s::Field,
+            _object_type: &s::ObjectType,
+            _arguments: &HashMap<&q::Name, q::Value>,
+        ) -> Result<q::Value, QueryExecutionError> {
+            Ok(q::Value::Null)
+        }
+    }
+
+    fn options() -> ExecutionOptions<MockResolver> {
+        ExecutionOptions {
+            logger: slog::Logger::root(slog::Discard, o!()),
+            resolver: MockResolver,
+            max_depth: None,
+            max_complexity: None,
+            max_first: None,
+            max_skip: None,
+            max_entities: None,
+            tracer: None,
+        }
+    }
+
+    fn check(query: &str, options: ExecutionOptions<MockResolver>) -> Vec<String> {
+        let schema = api_schema(
+            &graphql_parser::parse_schema(
+                "
+                type Musician { id: ID!, name: String!, bands: [Band!]! }
+                type Band { id: ID!, name: String!, members: [Musician!]! }
+                ",
+            ).expect("Failed to parse test schema"),
+        ).expect("Failed to derive API schema");
+        let document = graphql_parser::parse_query(query).expect("Failed to parse test query");
+        let operation = qast::get_operation(&document, None).expect("No operation in test query");
+
+        match check_limits(&schema, &document, operation, &HashMap::new(), &options) {
+            Ok(()) => vec![],
+            Err(errors) => errors.iter().map(|e| format!("{}", e)).collect(),
+        }
+    }
+
+    #[test]
+    fn rejects_queries_that_are_too_deep() {
+        let query = "{ musicians { bands { members { name } } } }";
+
+        let mut limits = options();
+        limits.max_depth = Some(4);
+        assert!(check(query, limits).is_empty());
+
+        let mut limits = options();
+        limits.max_depth = Some(3);
+        assert_eq!(
+            check(query, limits),
+            vec!["Query is nested deeper than the maximum depth of 3"]
+        );
+    }
+
+    #[test]
+    fn rejects_queries_that_are_too_complex() {
+        // 10 musicians with a name and 5 bands with a name each
+        let query = "{ musicians(first: 10) { name bands(first: 5) { name } } }";
+
+        let mut limits = options();
+        limits.max_complexity = Some(120);
+        assert!(check(query, limits).is_empty());
+
+        let mut limits = options();
+        limits.max_complexity = Some(119);
+        assert_eq!(
+            check(query, limits),
+            vec!["Query has a complexity of 120, which exceeds the maximum of 119"]
+        );
+    }
+
+    #[test]
+    fn rejects_queries_that_may_return_too_many_entities() {
+        // Lists without `first` are assumed to return 100 entities
+        let query = "{ musicians(first: 10) { bands { name } } }";
+
+        let mut limits = options();
+        limits.max_entities = Some(1010);
+        assert!(check(query, limits).is_empty());
+
+        let mut limits = options();
+        limits.max_entities = Some(1000);
+        assert_eq!(
+            check(query, limits),
+            vec!["Query may return up to 1010 entities, which exceeds the maximum of 1000"]
+        );
+    }
+
+    #[test]
+    fn charges_the_default_page_size_for_lists_without_a_positive_first() {
+        let mut limits = options();
+        limits.max_entities = Some(1000);
+        assert_eq!(
+            check("{ musicians(first: 0) { bands(first: 10) { name } } }", limits),
+            vec!["Query may return up to 1100 entities, which exceeds the maximum of 1000"]
+        );
+    }
+
+    #[test]
+    fn rejects_range_arguments_above_the_maximum() {
+        let mut limits = options();
+        limits.max_first = Some(100);
+        limits.max_skip = Some(1000);
+        assert_eq!(
+            check(
+                "{ musicians(first: 1000000, skip: 10) { bands(skip: 5000) { name } } }",
+                limits
+            ),
+            vec![
+                "Value of argument \"first\" (1000000) exceeds the maximum of 100",
+                "Value of argument \"skip\" (5000) exceeds the maximum of 1000",
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_introspection_fields() {
+        let mut limits = options();
+        limits.max_depth = Some(1);
+        assert!(check("{ __schema { types { fields { type { name } } } } }", limits).is_empty());
+    }
+}
@@ -4,8 +4,19 @@ pub mod ast;
 /// Implementation of the GraphQL query execution algorithm.
 pub mod execution;
 
+/// Limits on the depth, complexity and size of queries.
+pub mod limits;
+
 /// Common trait for field resolvers used in the execution.
 pub mod resolver;
 
-pub use self::execution::{execute, ExecutionOptions};
+/// Utilities for analyzing which types a query selects.
+pub mod selection;
+
+/// Validation of queries against a schema before execution.
+pub mod validation;
+
+pub use self::execution::{execute, execute_incrementally, ExecutionOptions, SubsequentResults};
 pub use self::resolver::Resolver;
+pub use self::selection::get_selected_object_types;
+pub use self::validation::validate;
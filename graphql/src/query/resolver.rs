@@ -1,7 +1,10 @@
 use graphql_parser::{query as q, schema as s};
 use std::collections::HashMap;
 
+use graph::prelude::QueryExecutionError;
+
 use prelude::*;
+use schema::ast as sast;
 
 /// A GraphQL resolver that can resolve entities, enum values, scalar types and interfaces/unions.
 pub trait Resolver: Clone {
@@ -13,7 +16,7 @@ pub trait Resolver: Clone {
         field_definition: &s::Field,
         object_type: &s::ObjectType,
         arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value;
+    ) -> Result<q::Value, QueryExecutionError>;
 
     /// Resolves an entity referenced by a parent object.
     fn resolve_object(
@@ -23,13 +26,71 @@ pub trait Resolver: Clone {
         field_definition: &s::Field,
         object_type: &s::ObjectType,
         arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value;
+    ) -> Result<q::Value, QueryExecutionError>;
+
+    /// Resolves entities referenced by several parent objects at once.
+    ///
+    /// Returns one list of entities per parent, in the order of the parents.
+    /// By default, entities are resolved for each parent individually;
+    /// resolvers that can fetch the entities of all parents in one go should
+    /// override this.
+    fn resolve_objects_for_parents(
+        &self,
+        parents: &[q::Value],
+        field: &q::Name,
+        field_definition: &s::Field,
+        object_type: &s::ObjectType,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<Vec<q::Value>, QueryExecutionError> {
+        parents
+            .iter()
+            .map(|parent| {
+                self.resolve_objects(
+                    &Some(parent.clone()),
+                    field,
+                    field_definition,
+                    object_type,
+                    arguments,
+                )
+            })
+            .collect()
+    }
+
+    /// Resolves the entity referenced by each of several parent objects at once.
+    ///
+    /// Returns one entity (or null) per parent, in the order of the parents.
+    /// By default, entities are resolved for each parent individually.
+    fn resolve_object_for_parents(
+        &self,
+        parents: &[q::Value],
+        field: &q::Name,
+        field_definition: &s::Field,
+        object_type: &s::ObjectType,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<Vec<q::Value>, QueryExecutionError> {
+        parents
+            .iter()
+            .map(|parent| {
+                self.resolve_object(
+                    &Some(parent.clone()),
+                    field,
+                    field_definition,
+                    object_type,
+                    arguments,
+                )
+            })
+            .collect()
+    }
 
     /// Resolves an enum value for a given enum type.
-    fn resolve_enum_value(&self, enum_type: &s::EnumType, value: Option<&q::Value>) -> q::Value {
-        value
+    fn resolve_enum_value(
+        &self,
+        enum_type: &s::EnumType,
+        value: Option<&q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(value
             .and_then(|value| value.coerce(enum_type))
-            .unwrap_or(q::Value::Null)
+            .unwrap_or(q::Value::Null))
     }
 
     /// Resolves a scalar value for a given scalar type.
@@ -37,15 +98,19 @@ pub trait Resolver: Clone {
         &self,
         scalar_type: &s::ScalarType,
         value: Option<&q::Value>,
-    ) -> q::Value {
-        value
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(value
             .and_then(|value| value.coerce(scalar_type))
-            .unwrap_or(q::Value::Null)
+            .unwrap_or(q::Value::Null))
     }
 
     /// Resolves a list of enum values for a given enum type.
-    fn resolve_enum_values(&self, enum_type: &s::EnumType, value: Option<&q::Value>) -> q::Value {
-        value
+    fn resolve_enum_values(
+        &self,
+        enum_type: &s::EnumType,
+        value: Option<&q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(value
             .and_then(|value| match value {
                 q::Value::List(values) => Some(values),
                 _ => None,
@@ -62,7 +127,7 @@ pub trait Resolver: Clone {
                     None
                 }
             })
-            .unwrap_or(q::Value::Null)
+            .unwrap_or(q::Value::Null))
     }
 
     /// Resolves a list of scalar values for a given list type.
@@ -70,8 +135,8 @@ pub trait Resolver: Clone {
         &self,
         scalar_type: &s::ScalarType,
         value: Option<&q::Value>,
-    ) -> q::Value {
-        value
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(value
             .and_then(|value| match value {
                 q::Value::List(values) => Some(values),
                 _ => None,
@@ -88,16 +153,98 @@ pub trait Resolver: Clone {
                     None
                 }
             })
-            .unwrap_or(q::Value::Null)
+            .unwrap_or(q::Value::Null))
     }
 
-    // Resolves an abstract type into the specific type of an object.
+    /// Resolves entities of an abstract type (interface or union) referenced by
+    /// a parent object.
+    ///
+    /// By default, entities are resolved for each possible type of the abstract
+    /// type and the results are concatenated.
+    fn resolve_abstract_objects(
+        &self,
+        schema: &s::Document,
+        parent: &Option<q::Value>,
+        field: &q::Name,
+        field_definition: &s::Field,
+        abstract_type: &s::TypeDefinition,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let mut values = vec![];
+
+        for object_type in sast::get_possible_types(schema, abstract_type) {
+            if let q::Value::List(objects) =
+                self.resolve_objects(parent, field, field_definition, object_type, arguments)?
+            {
+                values.extend(
+                    objects
+                        .into_iter()
+                        .map(|value| with_typename(value, object_type)),
+                );
+            }
+        }
+
+        Ok(q::Value::List(values))
+    }
+
+    /// Resolves an entity of an abstract type (interface or union) referenced by
+    /// a parent object.
+    ///
+    /// By default, the first possible type of the abstract type for which an
+    /// entity can be resolved is used.
+    fn resolve_abstract_object(
+        &self,
+        schema: &s::Document,
+        parent: &Option<q::Value>,
+        field: &q::Name,
+        field_definition: &s::Field,
+        abstract_type: &s::TypeDefinition,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        for object_type in sast::get_possible_types(schema, abstract_type) {
+            let value =
+                self.resolve_object(parent, field, field_definition, object_type, arguments)?;
+            if value != q::Value::Null {
+                return Ok(with_typename(value, object_type));
+            }
+        }
+
+        Ok(q::Value::Null)
+    }
+
+    /// Resolves an abstract type into the specific type of an object.
+    ///
+    /// By default, the `__typename` of the object value is used to identify
+    /// its type.
     fn resolve_abstract_type<'a>(
         &self,
-        _schema: &'a s::Document,
-        _abstract_type: &s::TypeDefinition,
-        _object_value: &q::Value,
+        schema: &'a s::Document,
+        abstract_type: &s::TypeDefinition,
+        object_value: &q::Value,
     ) -> Option<&'a s::ObjectType> {
-        None
+        let type_name = match object_value {
+            q::Value::Object(object) => match object.get("__typename") {
+                Some(q::Value::String(name)) => name,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        sast::get_possible_types(schema, abstract_type)
+            .into_iter()
+            .find(|object_type| &object_type.name == type_name)
+    }
+}
+
+/// Adds a `__typename` to an object value, unless it already has one.
+pub(crate) fn with_typename(value: q::Value, object_type: &s::ObjectType) -> q::Value {
+    match value {
+        q::Value::Object(mut object) => {
+            object
+                .entry(String::from("__typename"))
+                .or_insert_with(|| q::Value::String(object_type.name.to_owned()));
+            q::Value::Object(object)
+        }
+        value => value,
     }
 }
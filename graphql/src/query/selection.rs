@@ -0,0 +1,201 @@
+use graphql_parser::{query as q, schema as s};
+use std::collections::HashSet;
+
+use query::ast as qast;
+use schema::ast as sast;
+
+/// Returns the entity names of all object types that an operation may select
+/// entities of, e.g. to decide whether a change to an entity affects the
+/// result of the operation.
+///
+/// Interfaces and unions contribute all of their possible types.
+pub fn get_selected_object_types(
+    schema: &s::Document,
+    document: &q::Document,
+    operation: &q::OperationDefinition,
+) -> HashSet<s::Name> {
+    let (root_type_name, selection_set) = match operation {
+        q::OperationDefinition::Query(query) => ("Query", &query.selection_set),
+        q::OperationDefinition::SelectionSet(selection_set) => ("Query", selection_set),
+        q::OperationDefinition::Mutation(mutation) => ("Mutation", &mutation.selection_set),
+        q::OperationDefinition::Subscription(subscription) => {
+            ("Subscription", &subscription.selection_set)
+        }
+    };
+
+    let mut types = HashSet::new();
+    if let Some(root_type) = sast::get_named_type(schema, &root_type_name.to_string()) {
+        collect_object_types(
+            schema,
+            document,
+            root_type,
+            selection_set,
+            &mut types,
+            &mut HashSet::new(),
+        );
+    }
+    types
+}
+
+/// Collects the object types selected by a selection set of the given parent type.
+fn collect_object_types<'a>(
+    schema: &s::Document,
+    document: &'a q::Document,
+    parent_type: &s::TypeDefinition,
+    selection_set: &'a q::SelectionSet,
+    types: &mut HashSet<s::Name>,
+    visited_fragments: &mut HashSet<&'a q::Name>,
+) {
+    for selection in selection_set.items.iter() {
+        match selection {
+            q::Selection::Field(field) => {
+                let field_definition = match parent_type {
+                    s::TypeDefinition::Object(t) => sast::get_field_type(t, &field.name),
+                    s::TypeDefinition::Interface(t) => {
+                        t.fields.iter().find(|f| f.name == field.name)
+                    }
+                    _ => None,
+                };
+
+                let field_type = field_definition.and_then(|field_definition| {
+                    sast::get_named_type(schema, sast::get_base_type(&field_definition.field_type))
+                });
+
+                // Changes of an entity are affected by changes to the entity
+                if let Some(entity_type) =
+                    field_definition.and_then(|field| sast::get_history_entity_type(field))
+                {
+                    types.insert(entity_type.to_owned());
+                }
+
+                if let Some(field_type) = field_type {
+                    match field_type {
+                        s::TypeDefinition::Object(t) => {
+                            types.insert(sast::get_entity_name(t).to_owned());
+                        }
+                        s::TypeDefinition::Interface(_) | s::TypeDefinition::Union(_) => {
+                            types.extend(
+                                sast::get_possible_types(schema, field_type)
+                                    .into_iter()
+                                    .map(|t| sast::get_entity_name(t).to_owned()),
+                            );
+                        }
+                        _ => (),
+                    }
+
+                    collect_object_types(
+                        schema,
+                        document,
+                        field_type,
+                        &field.selection_set,
+                        types,
+                        visited_fragments,
+                    );
+                }
+            }
+
+            q::Selection::FragmentSpread(spread) => {
+                // Only visit each fragment once
+                if !visited_fragments.insert(&spread.fragment_name) {
+                    continue;
+                }
+
+                if let Some(fragment) = qast::get_fragment(document, &spread.fragment_name) {
+                    let q::TypeCondition::On(ref name) = fragment.type_condition;
+                    if let Some(fragment_type) = sast::get_named_type(schema, name) {
+                        collect_object_types(
+                            schema,
+                            document,
+                            fragment_type,
+                            &fragment.selection_set,
+                            types,
+                            visited_fragments,
+                        );
+                    }
+                }
+            }
+
+            q::Selection::InlineFragment(fragment) => {
+                // Inline fragments without a type condition apply to the parent type
+                let fragment_type = match fragment.type_condition {
+                    Some(q::TypeCondition::On(ref name)) => sast::get_named_type(schema, name),
+                    None => Some(parent_type),
+                };
+
+                if let Some(fragment_type) = fragment_type {
+                    collect_object_types(
+                        schema,
+                        document,
+                        fragment_type,
+                        &fragment.selection_set,
+                        types,
+                        visited_fragments,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graphql_parser;
+    use std::collections::HashSet;
+
+    use super::get_selected_object_types;
+    use prelude::api_schema;
+    use query::ast as qast;
+
+    fn selected_object_types(query: &str) -> HashSet<String> {
+        let schema = api_schema(
+            &graphql_parser::parse_schema(
+                "
+                interface Named { id: ID!, name: String! }
+                type Musician implements Named { id: ID!, name: String!, bands: [Band!]! }
+                type Band implements Named { id: ID!, name: String! }
+                type Song { id: ID!, title: String!, writtenBy: Musician! }
+                ",
+            ).expect("Failed to parse test schema"),
+        ).expect("Failed to derive API schema");
+        let document = graphql_parser::parse_query(query).expect("Failed to parse test query");
+        let operation = qast::get_operation(&document, None).expect("No operation in test query");
+        get_selected_object_types(&schema, &document, operation)
+    }
+
+    fn names(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn collects_types_of_nested_fields() {
+        assert_eq!(
+            selected_object_types("subscription { songs { title writtenBy { name } } }"),
+            names(&["Song", "Musician"])
+        );
+        assert_eq!(
+            selected_object_types("{ musician(id: \"m1\") { name } }"),
+            names(&["Musician"])
+        );
+    }
+
+    #[test]
+    fn collects_possible_types_of_abstract_fields() {
+        assert_eq!(
+            selected_object_types("subscription { nameds { name } }"),
+            names(&["Musician", "Band"])
+        );
+    }
+
+    #[test]
+    fn collects_types_selected_in_fragments() {
+        assert_eq!(
+            selected_object_types(
+                "
+                subscription { musicians { ...MusicianFields } }
+                fragment MusicianFields on Musician { bands { ... on Band { name } } }
+                "
+            ),
+            names(&["Musician", "Band"])
+        );
+    }
+}
@@ -0,0 +1,1070 @@
+use graphql_parser::query as q;
+use graphql_parser::schema as s;
+use graphql_parser::Pos;
+use std::collections::{HashMap, HashSet};
+
+use graph::prelude::*;
+
+use introspection::{built_in_directives, introspection_schema};
+use query::ast as qast;
+use schema::ast as sast;
+use values::coercion::coerce_value;
+
+/// Validates a query document against a schema.
+///
+/// Implements the validation rules of the GraphQL specification
+/// (https://facebook.github.io/graphql/draft/#sec-Validation) and returns
+/// all errors found in the document.
+pub fn validate(schema: &Schema, document: &q::Document) -> Result<(), Vec<QueryExecutionError>> {
+    let introspection_schema = introspection_schema();
+    let built_in_directives = built_in_directives();
+
+    let mut validator = Validator::new(
+        &schema.document,
+        &introspection_schema,
+        &built_in_directives,
+        document,
+    );
+    validator.validate();
+
+    if validator.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(validator.errors)
+    }
+}
+
+/// A usage of a variable in a position that expects a certain type.
+struct VariableUsage<'a> {
+    name: &'a q::Name,
+    position: Pos,
+    expected_type: &'a s::Type,
+    has_location_default: bool,
+}
+
+/// Variables used and fragments spread in an operation or fragment.
+#[derive(Default)]
+struct Usages<'a> {
+    variables: Vec<VariableUsage<'a>>,
+    fragments: Vec<&'a q::Name>,
+}
+
+/// Validates a query document against a schema, collecting errors as it goes.
+struct Validator<'a> {
+    schema: &'a s::Document,
+    introspection_schema: &'a s::Document,
+    directives: Vec<&'a s::DirectiveDefinition>,
+    document: &'a q::Document,
+    fragments: HashMap<&'a q::Name, &'a q::FragmentDefinition>,
+    fragment_usages: HashMap<&'a q::Name, Usages<'a>>,
+    errors: Vec<QueryExecutionError>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(
+        schema: &'a s::Document,
+        introspection_schema: &'a s::Document,
+        built_in_directives: &'a s::Document,
+        document: &'a q::Document,
+    ) -> Self {
+        let directives = schema
+            .definitions
+            .iter()
+            .chain(built_in_directives.definitions.iter())
+            .filter_map(|definition| match definition {
+                s::Definition::DirectiveDefinition(directive) => Some(directive),
+                _ => None,
+            })
+            .collect();
+
+        Validator {
+            schema,
+            introspection_schema,
+            directives,
+            document,
+            fragments: HashMap::new(),
+            fragment_usages: HashMap::new(),
+            errors: vec![],
+        }
+    }
+
+    fn error(&mut self, position: Pos, message: String) {
+        self.errors
+            .push(QueryExecutionError::ValidationError(position, message));
+    }
+
+    fn validate(&mut self) {
+        let operations = qast::get_operations(self.document);
+        let fragments: Vec<&'a q::FragmentDefinition> = self.document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                q::Definition::Fragment(fragment) => Some(fragment),
+                _ => None,
+            })
+            .collect();
+
+        // Operation names must be unique and anonymous operations must be
+        // the only operation in the document
+        let mut operation_names = HashSet::new();
+        for operation in operations.iter() {
+            match qast::get_operation_name(operation) {
+                Some(name) => if !operation_names.insert(name) {
+                    self.error(
+                        get_operation_position(operation),
+                        format!("There can be only one operation named \"{}\".", name),
+                    );
+                },
+                None => if operations.len() > 1 {
+                    self.error(
+                        get_operation_position(operation),
+                        "This anonymous operation must be the only defined operation.".to_string(),
+                    );
+                },
+            }
+        }
+
+        // Fragment names must be unique
+        for fragment in fragments.iter() {
+            if self.fragments.insert(&fragment.name, fragment).is_some() {
+                self.error(
+                    fragment.position,
+                    format!("There can be only one fragment named \"{}\".", fragment.name),
+                );
+            }
+        }
+
+        // Validate fragment definitions and remember which variables they use
+        // and which fragments they spread
+        for fragment in fragments.iter() {
+            let usages = self.validate_fragment_definition(fragment);
+            self.fragment_usages.insert(&fragment.name, usages);
+        }
+
+        // Fragment spreads must not form cycles
+        for fragment in fragments.iter() {
+            if self.reachable_fragments(&self.fragment_usages[&fragment.name].fragments)
+                .contains(&fragment.name)
+            {
+                self.error(
+                    fragment.position,
+                    format!("Cannot spread fragment \"{}\" within itself.", fragment.name),
+                );
+            }
+        }
+
+        // Validate operations, keeping track of the fragments they use
+        let mut used_fragments = HashSet::new();
+        for operation in operations {
+            used_fragments.extend(self.validate_operation(operation));
+        }
+
+        // Fragments must be used
+        for fragment in fragments {
+            if !used_fragments.contains(&fragment.name) {
+                self.error(
+                    fragment.position,
+                    format!("Fragment \"{}\" is never used.", fragment.name),
+                );
+            }
+        }
+    }
+
+    /// Returns the names of all fragments reachable from the given fragment spreads.
+    fn reachable_fragments(&self, spreads: &Vec<&'a q::Name>) -> HashSet<&'a q::Name> {
+        let mut reachable = HashSet::new();
+        let mut queue = spreads.clone();
+
+        while let Some(name) = queue.pop() {
+            if reachable.insert(name) {
+                if let Some(usages) = self.fragment_usages.get(name) {
+                    queue.extend(usages.fragments.iter());
+                }
+            }
+        }
+
+        reachable
+    }
+
+    /// Validates an operation and returns the names of all fragments it uses.
+    fn validate_operation(&mut self, operation: &'a q::OperationDefinition) -> HashSet<&'a q::Name> {
+        let (root_type_name, location, directives, selection_set) = match operation {
+            q::OperationDefinition::SelectionSet(selection_set) => {
+                ("Query", s::DirectiveLocation::Query, None, selection_set)
+            }
+            q::OperationDefinition::Query(query) => (
+                "Query",
+                s::DirectiveLocation::Query,
+                Some(&query.directives),
+                &query.selection_set,
+            ),
+            q::OperationDefinition::Mutation(mutation) => (
+                "Mutation",
+                s::DirectiveLocation::Mutation,
+                Some(&mutation.directives),
+                &mutation.selection_set,
+            ),
+            q::OperationDefinition::Subscription(subscription) => (
+                "Subscription",
+                s::DirectiveLocation::Subscription,
+                Some(&subscription.directives),
+                &subscription.selection_set,
+            ),
+        };
+        let operation_name = qast::get_operation_name(operation);
+        let variable_definitions = qast::get_variable_definitions(operation);
+
+        let mut usages = Usages::default();
+
+        if let Some(directives) = directives {
+            self.validate_directives(directives, location, &mut usages);
+        }
+
+        let variables = variable_definitions
+            .map(|definitions| self.validate_variable_definitions(definitions))
+            .unwrap_or_default();
+
+        // Operations whose root type is missing in the schema are rejected
+        // during execution
+        if let Some(root_type) = sast::get_named_type(self.schema, &root_type_name.to_string()) {
+            self.validate_selection_set(selection_set, root_type, &mut usages);
+        }
+
+        // Collect the variables used by the operation itself and by all fragments
+        // it uses
+        let used_fragments = self.reachable_fragments(&usages.fragments);
+        let variable_usages = usages.variables.iter().chain(
+            used_fragments
+                .iter()
+                .filter_map(|name| self.fragment_usages.get(name))
+                .flat_map(|usages| usages.variables.iter()),
+        );
+
+        // Variables must be defined and their usages must be allowed
+        let mut errors = vec![];
+        let mut used_variables = HashSet::new();
+        for usage in variable_usages {
+            used_variables.insert(usage.name);
+
+            match variables.get(usage.name) {
+                None => errors.push((
+                    usage.position,
+                    match operation_name {
+                        Some(operation_name) => format!(
+                            "Variable \"${}\" is not defined by operation \"{}\".",
+                            usage.name, operation_name
+                        ),
+                        None => format!("Variable \"${}\" is not defined.", usage.name),
+                    },
+                )),
+                Some(variable) => if !is_variable_usage_allowed(variable, usage) {
+                    errors.push((
+                        usage.position,
+                        format!(
+                            "Variable \"${}\" of type \"{}\" used in position expecting type \"{}\".",
+                            usage.name, variable.var_type, usage.expected_type
+                        ),
+                    ))
+                },
+            }
+        }
+
+        // Variables must be used
+        for variable in variable_definitions.into_iter().flat_map(|defs| defs.iter()) {
+            if !used_variables.contains(&variable.name) {
+                errors.push((
+                    variable.position,
+                    match operation_name {
+                        Some(operation_name) => format!(
+                            "Variable \"${}\" is never used in operation \"{}\".",
+                            variable.name, operation_name
+                        ),
+                        None => format!("Variable \"${}\" is never used.", variable.name),
+                    },
+                ));
+            }
+        }
+
+        for (position, message) in errors {
+            self.error(position, message);
+        }
+
+        used_fragments
+    }
+
+    /// Validates variable definitions and returns the definitions by name.
+    fn validate_variable_definitions(
+        &mut self,
+        definitions: &'a Vec<q::VariableDefinition>,
+    ) -> HashMap<&'a q::Name, &'a q::VariableDefinition> {
+        let mut variables = HashMap::new();
+
+        for definition in definitions {
+            // Variable names must be unique
+            if variables.insert(&definition.name, definition).is_some() {
+                self.error(
+                    definition.position,
+                    format!(
+                        "There can be only one variable named \"${}\".",
+                        definition.name
+                    ),
+                );
+                continue;
+            }
+
+            // Variables must be of known input types
+            let type_name = sast::get_base_type(&definition.var_type);
+            if sast::get_named_type(self.schema, type_name).is_none() {
+                self.error(
+                    definition.position,
+                    format!("Unknown type \"{}\".", type_name),
+                );
+                continue;
+            }
+            if !sast::is_input_type(self.schema, &definition.var_type) {
+                self.error(
+                    definition.position,
+                    format!(
+                        "Variable \"${}\" cannot be non-input type \"{}\".",
+                        definition.name, definition.var_type
+                    ),
+                );
+                continue;
+            }
+
+            // Default values must be valid values of the variable type
+            if let Some(ref default_value) = definition.default_value {
+                if !self.is_valid_literal(default_value, &definition.var_type) {
+                    self.error(
+                        definition.position,
+                        format!(
+                            "Variable \"${}\" of type \"{}\" has invalid default value {}.",
+                            definition.name, definition.var_type, default_value
+                        ),
+                    );
+                }
+            }
+        }
+
+        variables
+    }
+
+    fn validate_fragment_definition(&mut self, fragment: &'a q::FragmentDefinition) -> Usages<'a> {
+        let mut usages = Usages::default();
+
+        self.validate_directives(
+            &fragment.directives,
+            s::DirectiveLocation::FragmentDefinition,
+            &mut usages,
+        );
+
+        let q::TypeCondition::On(ref type_name) = fragment.type_condition;
+        if let Some(fragment_type) =
+            self.validate_type_condition(type_name, fragment.position, Some(&fragment.name))
+        {
+            self.validate_selection_set(&fragment.selection_set, fragment_type, &mut usages);
+        }
+
+        usages
+    }
+
+    /// Validates that the type condition of a fragment refers to a composite type
+    /// and returns that type.
+    fn validate_type_condition(
+        &mut self,
+        type_name: &q::Name,
+        position: Pos,
+        fragment_name: Option<&q::Name>,
+    ) -> Option<&'a s::TypeDefinition> {
+        match self.get_named_type(type_name) {
+            None => {
+                self.error(position, format!("Unknown type \"{}\".", type_name));
+                None
+            }
+            Some(t) if !is_composite_type(t) => {
+                self.error(
+                    position,
+                    match fragment_name {
+                        Some(name) => format!(
+                            "Fragment \"{}\" cannot condition on non composite type \"{}\".",
+                            name, type_name
+                        ),
+                        None => format!(
+                            "Fragment cannot condition on non composite type \"{}\".",
+                            type_name
+                        ),
+                    },
+                );
+                None
+            }
+            Some(t) => Some(t),
+        }
+    }
+
+    fn validate_selection_set(
+        &mut self,
+        selection_set: &'a q::SelectionSet,
+        parent_type: &'a s::TypeDefinition,
+        usages: &mut Usages<'a>,
+    ) {
+        let mut fields_by_response_key: HashMap<&q::Name, &q::Field> = HashMap::new();
+
+        for selection in selection_set.items.iter() {
+            match selection {
+                q::Selection::Field(field) => {
+                    self.validate_field(field, parent_type, usages);
+
+                    // Fields with the same response key must be the same field with
+                    // the same arguments
+                    let response_key = qast::get_response_key(field);
+                    match fields_by_response_key.get(response_key).cloned() {
+                        Some(other) => if other.name != field.name
+                            || other.arguments != field.arguments
+                        {
+                            self.error(
+                                field.position,
+                                format!(
+                                    "Fields \"{}\" conflict because they select different \
+                                     fields or use different arguments.",
+                                    response_key
+                                ),
+                            );
+                        },
+                        None => {
+                            fields_by_response_key.insert(response_key, field);
+                        }
+                    }
+                }
+
+                q::Selection::FragmentSpread(spread) => {
+                    self.validate_directives(
+                        &spread.directives,
+                        s::DirectiveLocation::FragmentSpread,
+                        usages,
+                    );
+
+                    usages.fragments.push(&spread.fragment_name);
+
+                    // The fragment must exist and be possible in the parent type
+                    match self.fragments.get(&spread.fragment_name).cloned() {
+                        None => self.error(
+                            spread.position,
+                            format!("Unknown fragment \"{}\".", spread.fragment_name),
+                        ),
+                        Some(fragment) => {
+                            let q::TypeCondition::On(ref type_name) = fragment.type_condition;
+                            match self.get_named_type(type_name) {
+                                Some(fragment_type)
+                                    if is_composite_type(fragment_type)
+                                        && !self.types_overlap(parent_type, fragment_type) =>
+                                {
+                                    self.error(
+                                        spread.position,
+                                        format!(
+                                            "Fragment \"{}\" cannot be spread here as objects of \
+                                             type \"{}\" can never be of type \"{}\".",
+                                            spread.fragment_name,
+                                            sast::get_type_name(parent_type),
+                                            type_name
+                                        ),
+                                    )
+                                }
+                                _ => (),
+                            }
+                        }
+                    }
+                }
+
+                q::Selection::InlineFragment(fragment) => {
+                    self.validate_directives(
+                        &fragment.directives,
+                        s::DirectiveLocation::InlineFragment,
+                        usages,
+                    );
+
+                    // Inline fragments without a type condition apply to the parent type
+                    let fragment_type = match fragment.type_condition {
+                        Some(q::TypeCondition::On(ref type_name)) => {
+                            match self.validate_type_condition(type_name, fragment.position, None)
+                            {
+                                Some(t) => t,
+                                None => continue,
+                            }
+                        }
+                        None => parent_type,
+                    };
+
+                    // The fragment must be possible in the parent type
+                    if !self.types_overlap(parent_type, fragment_type) {
+                        self.error(
+                            fragment.position,
+                            format!(
+                                "Fragment cannot be spread here as objects of type \"{}\" \
+                                 can never be of type \"{}\".",
+                                sast::get_type_name(parent_type),
+                                sast::get_type_name(fragment_type)
+                            ),
+                        );
+                        continue;
+                    }
+
+                    self.validate_selection_set(&fragment.selection_set, fragment_type, usages);
+                }
+            }
+        }
+    }
+
+    fn validate_field(
+        &mut self,
+        field: &'a q::Field,
+        parent_type: &'a s::TypeDefinition,
+        usages: &mut Usages<'a>,
+    ) {
+        self.validate_directives(&field.directives, s::DirectiveLocation::Field, usages);
+
+        // `__typename` can be selected on any composite type
+        if field.name == "__typename" {
+            for (name, _) in field.arguments.iter() {
+                self.error(
+                    field.position,
+                    format!("Unknown argument \"{}\" on field \"{}\".", name, field.name),
+                );
+            }
+            if !field.selection_set.items.is_empty() {
+                self.error(
+                    field.position,
+                    format!(
+                        "Field \"{}\" must not have a selection since type \"String!\" \
+                         has no subfields.",
+                        field.name
+                    ),
+                );
+            }
+            return;
+        }
+
+        // The field must be defined on the parent type
+        let field_definition = match self.get_field_definition(parent_type, &field.name) {
+            Some(field_definition) => field_definition,
+            None => {
+                self.error(
+                    field.position,
+                    format!(
+                        "Cannot query field \"{}\" on type \"{}\".",
+                        field.name,
+                        sast::get_type_name(parent_type)
+                    ),
+                );
+                return;
+            }
+        };
+
+        self.validate_arguments(
+            &field.arguments,
+            &field_definition.arguments,
+            field.position,
+            &format!(
+                "field \"{}.{}\"",
+                sast::get_type_name(parent_type),
+                field.name
+            ),
+            usages,
+        );
+
+        // Fields of composite types must have a selection of subfields, leaf
+        // fields must not
+        let type_name = sast::get_base_type(&field_definition.field_type);
+        match self.get_named_type(type_name) {
+            Some(field_type) if is_composite_type(field_type) => {
+                if field.selection_set.items.is_empty() {
+                    self.error(
+                        field.position,
+                        format!(
+                            "Field \"{}\" of type \"{}\" must have a selection of subfields.",
+                            field.name, field_definition.field_type
+                        ),
+                    );
+                } else {
+                    self.validate_selection_set(&field.selection_set, field_type, usages);
+                }
+            }
+            Some(_) => if !field.selection_set.items.is_empty() {
+                self.error(
+                    field.position,
+                    format!(
+                        "Field \"{}\" must not have a selection since type \"{}\" \
+                         has no subfields.",
+                        field.name, field_definition.field_type
+                    ),
+                );
+            },
+            None => self.error(field.position, format!("Unknown type \"{}\".", type_name)),
+        }
+    }
+
+    fn validate_arguments(
+        &mut self,
+        arguments: &'a Vec<(q::Name, q::Value)>,
+        argument_definitions: &'a Vec<s::InputValue>,
+        position: Pos,
+        target: &str,
+        usages: &mut Usages<'a>,
+    ) {
+        let mut argument_names = HashSet::new();
+
+        for (name, value) in arguments.iter() {
+            // Argument names must be unique
+            if !argument_names.insert(name) {
+                self.error(
+                    position,
+                    format!(
+                        "There can be only one argument named \"{}\" on {}.",
+                        name, target
+                    ),
+                );
+                continue;
+            }
+
+            // Arguments must be defined and have valid values
+            match argument_definitions
+                .iter()
+                .find(|definition| &definition.name == name)
+            {
+                None => self.error(
+                    position,
+                    format!("Unknown argument \"{}\" on {}.", name, target),
+                ),
+                Some(definition) => if !self.validate_value(
+                    value,
+                    &definition.value_type,
+                    position,
+                    definition.default_value.is_some(),
+                    usages,
+                ) {
+                    self.error(
+                        position,
+                        format!(
+                            "Argument \"{}\" on {} has invalid value {}.",
+                            name, target, value
+                        ),
+                    );
+                },
+            }
+        }
+
+        // Required arguments must be provided
+        for definition in argument_definitions.iter() {
+            if let s::Type::NonNullType(_) = definition.value_type {
+                if definition.default_value.is_none() && !argument_names.contains(&definition.name)
+                {
+                    self.error(
+                        position,
+                        format!(
+                            "Argument \"{}\" of type \"{}\" is required on {}, \
+                             but it was not provided.",
+                            definition.name, definition.value_type, target
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn validate_directives(
+        &mut self,
+        directives: &'a Vec<q::Directive>,
+        location: s::DirectiveLocation,
+        usages: &mut Usages<'a>,
+    ) {
+        let mut directive_names = HashSet::new();
+
+        for directive in directives.iter() {
+            // Directives must be defined
+            let definition = match self.directives
+                .iter()
+                .find(|definition| definition.name == directive.name)
+                .cloned()
+            {
+                Some(definition) => definition,
+                None => {
+                    self.error(
+                        directive.position,
+                        format!("Unknown directive \"@{}\".", directive.name),
+                    );
+                    continue;
+                }
+            };
+
+            // Directives must be used in valid locations
+            if !definition.locations.contains(&location) {
+                self.error(
+                    directive.position,
+                    format!(
+                        "Directive \"@{}\" may not be used on {}.",
+                        directive.name,
+                        location.as_str()
+                    ),
+                );
+            }
+
+            // Directives must be unique per location
+            if !directive_names.insert(&directive.name) {
+                self.error(
+                    directive.position,
+                    format!(
+                        "The directive \"@{}\" can only be used once at this location.",
+                        directive.name
+                    ),
+                );
+            }
+
+            self.validate_arguments(
+                &directive.arguments,
+                &definition.arguments,
+                directive.position,
+                &format!("directive \"@{}\"", directive.name),
+                usages,
+            );
+        }
+    }
+
+    /// Returns true if the value is valid for the given type, recording all
+    /// variables used in the value.
+    fn validate_value(
+        &self,
+        value: &'a q::Value,
+        value_type: &'a s::Type,
+        position: Pos,
+        has_location_default: bool,
+        usages: &mut Usages<'a>,
+    ) -> bool {
+        match value {
+            q::Value::Variable(name) => {
+                usages.variables.push(VariableUsage {
+                    name,
+                    position,
+                    expected_type: value_type,
+                    has_location_default,
+                });
+                true
+            }
+
+            // Values without variables can be checked by coercing them
+            _ if !contains_variables(value) => self.is_valid_literal(value, value_type),
+
+            q::Value::List(values) => {
+                let item_type = match value_type {
+                    s::Type::NonNullType(inner) => match inner.as_ref() {
+                        s::Type::ListType(item_type) => Some(item_type),
+                        _ => None,
+                    },
+                    s::Type::ListType(item_type) => Some(item_type),
+                    _ => None,
+                };
+
+                match item_type {
+                    Some(item_type) => values
+                        .iter()
+                        .all(|value| self.validate_value(value, item_type, position, false, usages)),
+                    None => false,
+                }
+            }
+
+            q::Value::Object(fields) => {
+                let input_type = match self.get_named_type(sast::get_base_type(value_type)) {
+                    Some(s::TypeDefinition::InputObject(t)) => t,
+                    _ => return false,
+                };
+
+                fields.iter().all(|(name, value)| {
+                    input_type
+                        .fields
+                        .iter()
+                        .find(|field| &field.name == name)
+                        .map(|field| {
+                            self.validate_value(
+                                value,
+                                &field.value_type,
+                                position,
+                                field.default_value.is_some(),
+                                usages,
+                            )
+                        })
+                        .unwrap_or(false)
+                }) && input_type.fields.iter().all(|field| match field.value_type {
+                    s::Type::NonNullType(_) => {
+                        field.default_value.is_some() || fields.contains_key(&field.name)
+                    }
+                    _ => true,
+                })
+            }
+
+            _ => true,
+        }
+    }
+
+    /// Returns true if the (variable-free) value can be coerced into the given type.
+    fn is_valid_literal(&self, value: &q::Value, value_type: &s::Type) -> bool {
+        coerce_value(value, value_type, &|name: &s::Name| self.get_named_type(name)).is_some()
+    }
+
+    /// Looks up a type in the schema or, failing that, in the introspection schema.
+    fn get_named_type(&self, name: &s::Name) -> Option<&'a s::TypeDefinition> {
+        sast::get_named_type(self.schema, name)
+            .or_else(|| sast::get_named_type(self.introspection_schema, name))
+    }
+
+    /// Looks up a field of an object or interface type. The introspection fields
+    /// are available on the root query type.
+    fn get_field_definition(
+        &self,
+        parent_type: &'a s::TypeDefinition,
+        name: &s::Name,
+    ) -> Option<&'a s::Field> {
+        match parent_type {
+            s::TypeDefinition::Object(t) => sast::get_field_type(t, name).or_else(|| {
+                sast::get_root_query_type(self.schema)
+                    .filter(|root_type| root_type.name == t.name)
+                    .and_then(|_| sast::get_root_query_type(self.introspection_schema))
+                    .and_then(|root_type| sast::get_field_type(root_type, name))
+            }),
+            s::TypeDefinition::Interface(t) => t.fields.iter().find(|field| &field.name == name),
+            _ => None,
+        }
+    }
+
+    /// Returns true if there is an object type that is a possible type of both types.
+    fn types_overlap(&self, a: &s::TypeDefinition, b: &s::TypeDefinition) -> bool {
+        let possible_types_a = self.get_possible_type_names(a);
+        self.get_possible_type_names(b)
+            .iter()
+            .any(|name| possible_types_a.contains(name))
+    }
+
+    fn get_possible_type_names(&self, t: &s::TypeDefinition) -> Vec<s::Name> {
+        match t {
+            s::TypeDefinition::Object(t) => vec![t.name.to_owned()],
+            _ => sast::get_possible_types(self.schema, t)
+                .into_iter()
+                .map(|t| t.name.to_owned())
+                .collect(),
+        }
+    }
+}
+
+fn get_operation_position(operation: &q::OperationDefinition) -> Pos {
+    match operation {
+        q::OperationDefinition::SelectionSet(selection_set) => selection_set.span.0,
+        q::OperationDefinition::Query(query) => query.position,
+        q::OperationDefinition::Mutation(mutation) => mutation.position,
+        q::OperationDefinition::Subscription(subscription) => subscription.position,
+    }
+}
+
+fn is_composite_type(t: &s::TypeDefinition) -> bool {
+    match t {
+        s::TypeDefinition::Object(_)
+        | s::TypeDefinition::Interface(_)
+        | s::TypeDefinition::Union(_) => true,
+        _ => false,
+    }
+}
+
+fn contains_variables(value: &q::Value) -> bool {
+    match value {
+        q::Value::Variable(_) => true,
+        q::Value::List(values) => values.iter().any(contains_variables),
+        q::Value::Object(fields) => fields.values().any(contains_variables),
+        _ => false,
+    }
+}
+
+/// Returns true if a variable may be used in the position of the usage.
+///
+/// Implements https://facebook.github.io/graphql/draft/#IsVariableUsageAllowed().
+fn is_variable_usage_allowed(variable: &q::VariableDefinition, usage: &VariableUsage) -> bool {
+    match (&variable.var_type, usage.expected_type) {
+        (q::Type::NonNullType(_), _) => are_types_compatible(&variable.var_type, usage.expected_type),
+        (_, s::Type::NonNullType(inner)) => {
+            let has_variable_default = variable
+                .default_value
+                .as_ref()
+                .map(|value| value != &q::Value::Null)
+                .unwrap_or(false);
+
+            (has_variable_default || usage.has_location_default)
+                && are_types_compatible(&variable.var_type, inner)
+        }
+        _ => are_types_compatible(&variable.var_type, usage.expected_type),
+    }
+}
+
+/// Implements https://facebook.github.io/graphql/draft/#AreTypesCompatible().
+fn are_types_compatible(variable_type: &q::Type, location_type: &s::Type) -> bool {
+    match (variable_type, location_type) {
+        (q::Type::NonNullType(a), s::Type::NonNullType(b)) => are_types_compatible(a, b),
+        (q::Type::NonNullType(a), b) => are_types_compatible(a, b),
+        (_, s::Type::NonNullType(_)) => false,
+        (q::Type::ListType(a), s::Type::ListType(b)) => are_types_compatible(a, b),
+        (q::Type::ListType(_), _) | (_, s::Type::ListType(_)) => false,
+        (q::Type::NamedType(a), s::Type::NamedType(b)) => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graphql_parser;
+
+    use graph::prelude::*;
+
+    use super::validate;
+    use schema::api::api_schema;
+
+    fn test_schema() -> Schema {
+        Schema {
+            id: String::from("test"),
+            document: api_schema(
+                &graphql_parser::parse_schema(
+                    "
+                    interface Named { id: ID!, name: String! }
+                    type User implements Named { id: ID!, name: String!, friends: [User!]! }
+                    type Pet implements Named { id: ID!, name: String! }
+                    ",
+                ).expect("Failed to parse test schema"),
+            ).expect("Failed to derive API schema"),
+        }
+    }
+
+    fn validation_errors(query: &str) -> Vec<String> {
+        let document = graphql_parser::parse_query(query).expect("Failed to parse test query");
+        match validate(&test_schema(), &document) {
+            Ok(()) => vec![],
+            Err(errors) => errors.iter().map(|e| format!("{}", e)).collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_queries() {
+        assert_eq!(
+            validation_errors(
+                "
+                query users($first: Int = 10, $id: ID!) {
+                    users(first: $first) { ...UserFields }
+                    user(id: $id) { friends @include(if: true) { name } }
+                    nameds { __typename ... on Pet { name } }
+                    __schema { types { name } }
+                }
+                fragment UserFields on User { id name }
+                ",
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_arguments() {
+        assert_eq!(
+            validation_errors("{ users(limit: 5) { id age } }"),
+            vec![
+                "Unknown argument \"limit\" on field \"Query.users\".".to_string(),
+                "Cannot query field \"age\" on type \"User\".".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_invalid_arguments() {
+        assert_eq!(
+            validation_errors("{ user { id } users(first: \"ten\") { id } }"),
+            vec![
+                "Argument \"id\" of type \"ID!\" is required on field \"Query.user\", \
+                 but it was not provided."
+                    .to_string(),
+                "Argument \"first\" on field \"Query.users\" has invalid value \"ten\"."
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_leaf_selections() {
+        assert_eq!(
+            validation_errors("{ users { name { length } friends } }"),
+            vec![
+                "Field \"name\" must not have a selection since type \"String!\" \
+                 has no subfields."
+                    .to_string(),
+                "Field \"friends\" of type \"[User!]!\" must have a selection of subfields."
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_fragments() {
+        assert_eq!(
+            validation_errors(
+                "
+                { users { ...Missing ...PetFields ...A } }
+                fragment PetFields on Pet { name }
+                fragment A on User { ...B }
+                fragment B on User { ...A }
+                fragment Unused on User { id }
+                ",
+            ),
+            vec![
+                "Cannot spread fragment \"A\" within itself.".to_string(),
+                "Cannot spread fragment \"B\" within itself.".to_string(),
+                "Unknown fragment \"Missing\".".to_string(),
+                "Fragment \"PetFields\" cannot be spread here as objects of type \"User\" \
+                 can never be of type \"Pet\"."
+                    .to_string(),
+                "Fragment \"Unused\" is never used.".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_variable_usage() {
+        assert_eq!(
+            validation_errors(
+                "
+                query users($first: String, $unused: Int) {
+                    users(first: $first, skip: $skip) { id }
+                }
+                ",
+            ),
+            vec![
+                "Variable \"$first\" of type \"String\" used in position expecting type \"Int\"."
+                    .to_string(),
+                "Variable \"$skip\" is not defined by operation \"users\".".to_string(),
+                "Variable \"$unused\" is never used in operation \"users\".".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_multiple_operations_with_anonymous_or_duplicate_names() {
+        assert_eq!(
+            validation_errors("{ users { id } } query a { users { id } } query a { users { id } }"),
+            vec![
+                "This anonymous operation must be the only defined operation.".to_string(),
+                "There can be only one operation named \"a\".".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_misplaced_directives() {
+        assert_eq!(
+            validation_errors("query @skip(if: true) { users @foo { id @include } }"),
+            vec![
+                "Directive \"@skip\" may not be used on QUERY.".to_string(),
+                "Unknown directive \"@foo\".".to_string(),
+                "Argument \"if\" of type \"Boolean!\" is required on directive \"@include\", \
+                 but it was not provided."
+                    .to_string(),
+            ]
+        );
+    }
+}
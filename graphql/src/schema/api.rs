@@ -1,15 +1,19 @@
 use graphql_parser::schema::*;
 use graphql_parser::Pos;
 use inflector::Inflector;
+use std::collections::HashSet;
 use std::error::Error;
 use std::fmt;
-use std::iter::IntoIterator;
+use std::iter::{self, IntoIterator};
+
+use graph::data::meta::META_TYPE_NAME;
 
 use schema::ast;
 
 #[derive(Debug)]
 pub enum APISchemaError {
     TypeExists(String),
+    FieldExists(String),
 }
 
 impl Error for APISchemaError {
@@ -28,6 +32,9 @@ impl fmt::Display for APISchemaError {
             APISchemaError::TypeExists(s) => {
                 write!(f, "Type \"{}\" already exists in the input schema", s)
             }
+            APISchemaError::FieldExists(s) => {
+                write!(f, "Query field \"{}\" is derived from more than one type", s)
+            }
         }
     }
 }
@@ -35,9 +42,9 @@ impl fmt::Display for APISchemaError {
 /// Derives a full-fledged GraphQL API schema from an input schema.
 ///
 /// The input schema should only have type/enum/interface/union definitions
-/// and must not include a root Query type. This Query type is derived,
-/// with all its fields and their input arguments, based on the existing
-/// types.
+/// and must not include a root Query or Subscription type. These types are
+/// derived, with all their fields and their input arguments, based on the
+/// existing types.
 pub fn api_schema(input_schema: &Document) -> Result<Document, APISchemaError> {
     let object_types = ast::get_object_type_definitions(input_schema);
     let interface_types = ast::get_interface_type_definitions(input_schema);
@@ -45,9 +52,12 @@ pub fn api_schema(input_schema: &Document) -> Result<Document, APISchemaError> {
     let mut schema = input_schema.clone();
     add_builtin_scalar_types(&mut schema)?;
     add_order_direction_enum(&mut schema);
+    add_entity_operation_enum(&mut schema);
     add_types_for_object_types(&mut schema, &object_types)?;
     add_types_for_interface_types(&mut schema, &interface_types)?;
+    add_meta_type(&mut schema)?;
     add_query_type(&mut schema, &object_types, &interface_types)?;
+    add_subscription_type(&mut schema, &object_types, &interface_types)?;
 
     Ok(schema)
 }
@@ -76,7 +86,7 @@ fn add_builtin_scalar_types(schema: &mut Document) -> Result<(), APISchemaError>
 fn add_order_direction_enum(schema: &mut Document) {
     let typedef = TypeDefinition::Enum(EnumType {
         position: Pos::default(),
-        description: None,
+        description: Some("Directions in which lists of entities can be ordered.".to_string()),
         name: "OrderDirection".to_string(),
         directives: vec![],
         values: ["asc", "desc"]
@@ -93,7 +103,30 @@ fn add_order_direction_enum(schema: &mut Document) {
     schema.definitions.push(def);
 }
 
-/// Adds `*_orderBy` and `*_filter` enum types for the given object types to the schema.
+/// Adds a global `EntityOperation` type to the schema, for the kinds of
+/// changes in the history of entities.
+fn add_entity_operation_enum(schema: &mut Document) {
+    let typedef = TypeDefinition::Enum(EnumType {
+        position: Pos::default(),
+        description: Some("Kinds of changes in the history of an entity.".to_string()),
+        name: "EntityOperation".to_string(),
+        directives: vec![],
+        values: ["insert", "update", "delete"]
+            .into_iter()
+            .map(|name| EnumValue {
+                position: Pos::default(),
+                description: None,
+                name: name.to_string(),
+                directives: vec![],
+            })
+            .collect(),
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+}
+
+/// Adds `*_orderBy` and `*_filter` enum types and `*_change` types for the
+/// given object types to the schema.
 fn add_types_for_object_types(
     schema: &mut Document,
     object_types: &Vec<&ObjectType>,
@@ -101,6 +134,7 @@ fn add_types_for_object_types(
     for object_type in object_types {
         add_order_by_type(schema, &object_type.name, &object_type.fields)?;
         add_filter_type(schema, &object_type.name, &object_type.fields)?;
+        add_change_type(schema, &object_type.name)?;
     }
     Ok(())
 }
@@ -123,14 +157,17 @@ fn add_order_by_type(
     type_name: &Name,
     fields: &Vec<Field>,
 ) -> Result<(), APISchemaError> {
-    let type_name = format!("{}_orderBy", type_name).to_string();
+    let order_by_type_name = format!("{}_orderBy", type_name).to_string();
 
-    match ast::get_named_type(schema, &type_name) {
+    match ast::get_named_type(schema, &order_by_type_name) {
         None => {
             let typedef = TypeDefinition::Enum(EnumType {
                 position: Pos::default(),
-                description: None,
-                name: type_name,
+                description: Some(format!(
+                    "Fields by which `{}` entities can be ordered.",
+                    type_name
+                )),
+                name: order_by_type_name,
                 directives: vec![],
                 values: fields
                     .iter()
@@ -146,7 +183,7 @@ fn add_order_by_type(
             let def = Definition::TypeDefinition(typedef);
             schema.definitions.push(def);
         }
-        Some(_) => return Err(APISchemaError::TypeExists(type_name)),
+        Some(_) => return Err(APISchemaError::TypeExists(order_by_type_name)),
     }
     Ok(())
 }
@@ -163,7 +200,10 @@ fn add_filter_type(
         None => {
             let typedef = TypeDefinition::InputObject(InputObjectType {
                 position: Pos::default(),
-                description: None,
+                description: Some(format!(
+                    "Conditions that `{}` entities must satisfy.",
+                    type_name
+                )),
                 name: filter_type_name,
                 directives: vec![],
                 fields: field_input_values(schema, fields),
@@ -275,6 +315,66 @@ fn input_value(name: &Name, suffix: &'static str, value_type: Type) -> InputValu
     }
 }
 
+/// Adds a `<type_name>_change` object type for the changes in the history of
+/// entities of the given type to the schema.
+fn add_change_type(schema: &mut Document, type_name: &Name) -> Result<(), APISchemaError> {
+    let change_type_name = format!("{}_change", type_name);
+    if ast::get_named_type(schema, &change_type_name).is_some() {
+        return Err(APISchemaError::TypeExists(change_type_name));
+    }
+
+    let field = |name: &str, field_type: Type| Field {
+        position: Pos::default(),
+        description: None,
+        name: name.to_string(),
+        arguments: vec![],
+        field_type,
+        directives: vec![],
+    };
+
+    let typedef = TypeDefinition::Object(ObjectType {
+        position: Pos::default(),
+        description: Some(format!("A change in the history of a `{}` entity.", type_name)),
+        name: change_type_name,
+        implements_interfaces: vec![],
+        directives: vec![],
+        fields: vec![
+            field(
+                "operation",
+                Type::NonNullType(Box::new(Type::NamedType("EntityOperation".to_string()))),
+            ),
+            field("blockHash", Type::NamedType("Bytes".to_string())),
+            field("blockNumber", Type::NamedType("Int".to_string())),
+            field("before", Type::NamedType(type_name.to_owned())),
+            field("after", Type::NamedType(type_name.to_owned())),
+        ],
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+    Ok(())
+}
+
+/// Adds the `_Meta_` type of the `_meta` field to the schema.
+fn add_meta_type(schema: &mut Document) -> Result<(), APISchemaError> {
+    if ast::get_named_type(schema, &META_TYPE_NAME.to_string()).is_some() {
+        return Err(APISchemaError::TypeExists(META_TYPE_NAME.to_owned()));
+    }
+
+    let meta_schema = parse_schema(&format!(
+        "\"The indexing status of a subgraph.\"
+        type {} {{
+            subgraph: ID!
+            blockNumber: Int
+            blockHash: Bytes
+            hasIndexingErrors: Boolean!
+            deployedAt: Int!
+        }}",
+        META_TYPE_NAME
+    )).expect("Failed to parse the _Meta_ type");
+    schema.definitions.extend(meta_schema.definitions);
+    Ok(())
+}
+
 /// Adds a root `Query` object type to the schema.
 fn add_query_type(
     schema: &mut Document,
@@ -285,17 +385,60 @@ fn add_query_type(
         return Err(APISchemaError::TypeExists("Query".to_owned()));
     }
 
+    let fields: Vec<Field> = object_types
+        .iter()
+        .map(|t| &t.name)
+        .chain(interface_types.iter().map(|t| &t.name))
+        .flat_map(|name| query_fields_for_type(schema, name))
+        .chain(object_types.iter().map(|t| history_field(t)))
+        .chain(iter::once(meta_field(schema)))
+        .collect();
+
+    // Field names derived from different types may collide, e.g. the history
+    // field of `User` and the singular field of `UserHistory`
+    let mut field_names = HashSet::new();
+    if let Some(field) = fields.iter().find(|field| !field_names.insert(&field.name)) {
+        return Err(APISchemaError::FieldExists(field.name.to_owned()));
+    }
+
     let typedef = TypeDefinition::Object(ObjectType {
         position: Pos::default(),
         description: None,
         name: "Query".to_string(),
         implements_interfaces: vec![],
         directives: vec![],
+        fields,
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+    Ok(())
+}
+
+/// Adds a root `Subscription` object type to the schema.
+///
+/// Subscriptions select the same fields as queries; their results are
+/// updated whenever the entities they select change.
+fn add_subscription_type(
+    schema: &mut Document,
+    object_types: &Vec<&ObjectType>,
+    interface_types: &Vec<&InterfaceType>,
+) -> Result<(), APISchemaError> {
+    if ast::get_named_type(schema, &"Subscription".to_string()).is_some() {
+        return Err(APISchemaError::TypeExists("Subscription".to_owned()));
+    }
+
+    let typedef = TypeDefinition::Object(ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: "Subscription".to_string(),
+        implements_interfaces: vec![],
+        directives: vec![],
         fields: object_types
             .iter()
             .map(|t| &t.name)
             .chain(interface_types.iter().map(|t| &t.name))
             .flat_map(|name| query_fields_for_type(schema, name))
+            .chain(iter::once(meta_field(schema)))
             .collect(),
     });
     let def = Definition::TypeDefinition(typedef);
@@ -308,7 +451,7 @@ fn query_fields_for_type(_schema: &Document, type_name: &Name) -> Vec<Field> {
     vec![
         Field {
             position: Pos::default(),
-            description: None,
+            description: Some(format!("Looks up a `{}` by its ID.", type_name)),
             name: type_name.as_str().to_camel_case(),
             arguments: vec![InputValue {
                 position: Pos::default(),
@@ -323,7 +466,10 @@ fn query_fields_for_type(_schema: &Document, type_name: &Name) -> Vec<Field> {
         },
         Field {
             position: Pos::default(),
-            description: None,
+            description: Some(format!(
+                "Lists `{}` entities, optionally filtered, ordered and paginated.",
+                type_name
+            )),
             name: type_name.to_plural().to_camel_case(),
             arguments: vec![
                 input_value(&"skip".to_string(), "", Type::NamedType("Int".to_string())),
@@ -355,11 +501,81 @@ fn query_fields_for_type(_schema: &Document, type_name: &Name) -> Vec<Field> {
     ]
 }
 
+/// Generates the `Query` field that returns the history of an entity of the
+/// given type (e.g. `userHistory`). The field is marked with `@entityHistory`,
+/// naming the type, and with the `@subgraphId` directive of the type, as the
+/// `*_change` type it returns has neither.
+fn history_field(object_type: &ObjectType) -> Field {
+    Field {
+        position: Pos::default(),
+        description: Some(format!(
+            "Lists the changes to a `{}`, oldest first.",
+            object_type.name
+        )),
+        name: format!("{}History", object_type.name.to_camel_case()),
+        arguments: vec![
+            input_value(
+                &"id".to_string(),
+                "",
+                Type::NonNullType(Box::new(Type::NamedType("ID".to_string()))),
+            ),
+            input_value(&"first".to_string(), "", Type::NamedType("Int".to_string())),
+            input_value(&"fromBlock".to_string(), "", Type::NamedType("Int".to_string())),
+        ],
+        field_type: Type::NonNullType(Box::new(Type::ListType(Box::new(Type::NonNullType(
+            Box::new(Type::NamedType(format!("{}_change", object_type.name))),
+        ))))),
+        directives: iter::once(history_directive(&object_type.name))
+            .chain(
+                object_type
+                    .directives
+                    .iter()
+                    .filter(|directive| directive.name == "subgraphId")
+                    .cloned(),
+            )
+            .collect(),
+    }
+}
+
+/// Generates the `@entityHistory` directive that marks the history field of
+/// the given type.
+fn history_directive(type_name: &Name) -> Directive {
+    Directive {
+        position: Pos::default(),
+        name: "entityHistory".to_string(),
+        arguments: vec![("entity".to_string(), Value::String(type_name.to_owned()))],
+    }
+}
+
+/// Generates the `_meta` field, which returns the indexing status of the
+/// subgraph. The field is marked with the `@subgraphId` directive of the types
+/// of the subgraph, so that it can be resolved after schemas are composed.
+fn meta_field(schema: &Document) -> Field {
+    Field {
+        position: Pos::default(),
+        description: Some("The indexing status of the subgraph.".to_string()),
+        name: "_meta".to_string(),
+        arguments: vec![],
+        field_type: Type::NamedType(META_TYPE_NAME.to_string()),
+        directives: get_subgraph_id_directive(schema).into_iter().collect(),
+    }
+}
+
+/// Returns the `@subgraphId` directive of the object types of a schema, if
+/// they have one.
+fn get_subgraph_id_directive(schema: &Document) -> Option<Directive> {
+    ast::get_object_type_definitions(schema)
+        .into_iter()
+        .flat_map(|t| t.directives.iter())
+        .find(|directive| directive.name == "subgraphId")
+        .cloned()
+}
+
 #[cfg(test)]
 mod tests {
     use graphql_parser::schema::*;
 
-    use super::api_schema;
+    use super::{api_schema, APISchemaError};
     use schema::ast;
 
     #[test]
@@ -405,6 +621,114 @@ mod tests {
             .expect("Root Query type is missing in API schema");
     }
 
+    #[test]
+    fn api_schema_contains_subscription_type() {
+        let input_schema =
+            parse_schema("type User { id: ID! }").expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+        let subscription_type = ast::get_root_subscription_type(&schema)
+            .expect("Root Subscription type is missing in API schema");
+        let field_names: Vec<&Name> = subscription_type
+            .fields
+            .iter()
+            .map(|field| &field.name)
+            .collect();
+        assert_eq!(
+            field_names,
+            [&"user".to_string(), &"users".to_string(), &"_meta".to_string()]
+        );
+    }
+
+    #[test]
+    fn api_schema_contains_history_fields_and_change_types() {
+        let input_schema = parse_schema("type User @subgraphId(id: \"a\") { id: ID! }")
+            .expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        let query_type =
+            ast::get_root_query_type(&schema).expect("Root Query type is missing in API schema");
+        let history_field = ast::get_field_type(query_type, &"userHistory".to_string())
+            .expect("userHistory field is missing on Query type");
+        assert_eq!(
+            history_field
+                .arguments
+                .iter()
+                .map(|input_value| input_value.name.as_str())
+                .collect::<Vec<_>>(),
+            ["id", "first", "fromBlock"]
+        );
+        assert_eq!(
+            ast::get_history_entity_type(history_field),
+            Some(&"User".to_string())
+        );
+        assert_eq!(history_field.directives[1].name, "subgraphId");
+
+        let change_type = match ast::get_named_type(&schema, &"User_change".to_string()) {
+            Some(TypeDefinition::Object(t)) => t,
+            _ => panic!("User_change type is missing in derived API schema"),
+        };
+        assert_eq!(
+            change_type
+                .fields
+                .iter()
+                .map(|field| field.name.as_str())
+                .collect::<Vec<_>>(),
+            ["operation", "blockHash", "blockNumber", "before", "after"]
+        );
+        assert!(change_type.directives.is_empty());
+    }
+
+    #[test]
+    fn api_schema_rejects_colliding_query_fields() {
+        let input_schema = parse_schema("type User { id: ID! } type UserHistory { id: ID! }")
+            .expect("Failed to parse input schema");
+        match api_schema(&input_schema) {
+            Err(APISchemaError::FieldExists(name)) => assert_eq!(name, "userHistory"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn api_schema_contains_descriptions() {
+        let input_schema = parse_schema(
+            "
+            \"A user of the app\"
+            type User {
+              id: ID!
+              \"The full name\"
+              name: String!
+            }
+            ",
+        ).expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        let user_type = match ast::get_named_type(&schema, &"User".to_string()) {
+            Some(TypeDefinition::Object(t)) => t,
+            _ => panic!("User type is missing in derived API schema"),
+        };
+        assert_eq!(user_type.description, Some("A user of the app".to_string()));
+        assert_eq!(
+            ast::get_field_type(user_type, &"name".to_string()).and_then(|f| f.description.clone()),
+            Some("The full name".to_string())
+        );
+
+        for name in ["User_orderBy", "User_filter", "User_change", "OrderDirection"].iter() {
+            let description = match ast::get_named_type(&schema, &name.to_string()) {
+                Some(TypeDefinition::Enum(t)) => t.description.clone(),
+                Some(TypeDefinition::InputObject(t)) => t.description.clone(),
+                Some(TypeDefinition::Object(t)) => t.description.clone(),
+                _ => panic!("{} type is missing in derived API schema", name),
+            };
+            assert!(description.is_some(), "{} has no description", name);
+        }
+
+        let query_type = match ast::get_named_type(&schema, &"Query".to_string()) {
+            Some(TypeDefinition::Object(t)) => t,
+            _ => panic!("Query type is missing in derived API schema"),
+        };
+        assert!(query_type.fields.iter().all(|f| f.description.is_some()));
+    }
+
     #[test]
     fn api_schema_contains_field_order_by_enum() {
         let input_schema = parse_schema("type User { id: ID!, name: String! }")
@@ -59,6 +59,25 @@ pub fn get_root_query_type(schema: &Document) -> Option<&ObjectType> {
         .next()
 }
 
+/// Returns the root subscription type (if there is one).
+pub fn get_root_subscription_type(schema: &Document) -> Option<&ObjectType> {
+    schema
+        .definitions
+        .iter()
+        .filter_map(|d| match d {
+            Definition::TypeDefinition(TypeDefinition::Object(t)) => {
+                if t.name == "Subscription".to_string() {
+                    Some(t)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        })
+        .peekable()
+        .next()
+}
+
 /// Returns all type definitions in the schema.
 pub fn get_type_definitions<'a>(schema: &'a Document) -> Vec<&'a TypeDefinition> {
     schema
@@ -95,6 +114,30 @@ pub fn get_interface_type_definitions<'a>(schema: &'a Document) -> Vec<&'a Inter
         .collect()
 }
 
+/// Returns the possible object types of an abstract type (interface or union).
+pub fn get_possible_types<'a>(
+    schema: &'a Document,
+    abstract_type: &TypeDefinition,
+) -> Vec<&'a ObjectType> {
+    get_object_type_definitions(schema)
+        .into_iter()
+        .filter(|object_type| is_possible_type(abstract_type, object_type))
+        .collect()
+}
+
+/// Returns true if the object type is a possible type of the abstract type
+/// (i.e. if it implements the interface or is a member of the union).
+pub fn is_possible_type(abstract_type: &TypeDefinition, object_type: &ObjectType) -> bool {
+    match abstract_type {
+        TypeDefinition::Interface(t) => object_type
+            .implements_interfaces
+            .iter()
+            .any(|name| name == &t.name),
+        TypeDefinition::Union(t) => t.types.iter().any(|name| name == &object_type.name),
+        _ => false,
+    }
+}
+
 /// Returns the type of a field of an object type.
 pub fn get_field_type<'a>(object_type: &'a ObjectType, name: &Name) -> Option<&'a Field> {
     object_type.fields.iter().find(|field| &field.name == name)
@@ -119,6 +162,41 @@ pub fn get_named_type<'a>(schema: &'a Document, name: &Name) -> Option<&'a TypeD
         })
 }
 
+/// Returns true if the given type is an input type.
+///
+/// Uses the algorithm outlined on
+/// https://facebook.github.io/graphql/draft/#IsInputType().
+pub fn is_input_type(schema: &Document, t: &Type) -> bool {
+    use graphql_parser::schema::TypeDefinition::*;
+
+    match t {
+        Type::NamedType(name) => match get_named_type(schema, name) {
+            Some(Scalar(_)) | Some(Enum(_)) | Some(InputObject(_)) => true,
+            _ => false,
+        },
+        Type::ListType(inner) => is_input_type(schema, inner),
+        Type::NonNullType(inner) => is_input_type(schema, inner),
+    }
+}
+
+/// Returns the name of the named type wrapped by a (list or non-null) type.
+pub fn get_base_type(t: &Type) -> &Name {
+    match t {
+        Type::NamedType(name) => name,
+        Type::ListType(inner) => get_base_type(inner),
+        Type::NonNullType(inner) => get_base_type(inner),
+    }
+}
+
+/// Returns true if the type is a list type, ignoring a non-null wrapper.
+pub fn is_list_type(t: &Type) -> bool {
+    match t {
+        Type::NamedType(_) => false,
+        Type::ListType(_) => true,
+        Type::NonNullType(inner) => is_list_type(inner),
+    }
+}
+
 /// Returns the name of a type.
 pub fn get_type_name(t: &TypeDefinition) -> &Name {
     match t {
@@ -143,6 +221,46 @@ pub fn get_type_description(t: &TypeDefinition) -> &Option<String> {
     }
 }
 
+/// Returns the name of the entity type whose changes a field returns, if it is
+/// a history field (e.g. `userHistory`) marked with `@entityHistory`.
+pub fn get_history_entity_type(field: &Field) -> Option<&Name> {
+    field
+        .directives
+        .iter()
+        .find(|directive| directive.name == "entityHistory")
+        .and_then(|directive| {
+            directive
+                .arguments
+                .iter()
+                .find(|(name, _)| name == "entity")
+                .and_then(|(_, value)| match value {
+                    Value::String(name) => Some(name),
+                    _ => None,
+                })
+        })
+}
+
+/// Returns the name that entities of an object type are stored under. Types
+/// that were renamed when composing schemas carry their original name in an
+/// `@originalName` directive.
+pub fn get_entity_name(object_type: &ObjectType) -> &Name {
+    object_type
+        .directives
+        .iter()
+        .find(|directive| directive.name == "originalName")
+        .and_then(|directive| {
+            directive
+                .arguments
+                .iter()
+                .find(|(name, _)| name == "name")
+                .and_then(|(_, value)| match value {
+                    Value::String(name) => Some(name),
+                    _ => None,
+                })
+        })
+        .unwrap_or(&object_type.name)
+}
+
 /// Returns the argument definitions for a field of an object type.
 pub fn get_argument_definitions<'a>(
     object_type: &'a ObjectType,
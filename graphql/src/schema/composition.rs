@@ -0,0 +1,413 @@
+use graphql_parser::schema::*;
+use graphql_parser::Pos;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use graph::prelude::Schema;
+
+use schema::ast;
+
+/// Names of the root types that are merged instead of being compared.
+const ROOT_TYPE_NAMES: [&'static str; 2] = ["Query", "Subscription"];
+
+#[derive(Debug, PartialEq)]
+pub enum CompositionError {
+    /// A subgraph defines a type differently than a subgraph composed before
+    /// it, even after its conflicting types were renamed:
+    /// `(subgraph, other subgraph, type name)`.
+    TypeConflict(String, String, String),
+}
+
+impl Error for CompositionError {
+    fn description(&self) -> &str {
+        "Schema composition error"
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        None
+    }
+}
+
+impl fmt::Display for CompositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompositionError::TypeConflict(subgraph, other_subgraph, type_name) => write!(
+                f,
+                "Type \"{}\" of subgraph \"{}\" conflicts with the type of the same name \
+                 in subgraph \"{}\"; subgraph \"{}\" is left out of the composed schema",
+                type_name, subgraph, other_subgraph, subgraph
+            ),
+        }
+    }
+}
+
+/// Composes the API schemas of several subgraphs into one schema.
+///
+/// Subgraphs are composed in the order in which they are passed. Types that
+/// several subgraphs define identically, like the built-in scalars, are
+/// included once. Types that a subgraph defines differently than a subgraph
+/// composed before it are prefixed with the ID of the subgraph, e.g.
+/// `QmHash_User`, along with all references to them; renamed object types
+/// keep their entity name in an `@originalName` directive. If the prefixed
+/// names are taken as well, the subgraph is left out; these conflicts are
+/// returned along with the composed schema.
+///
+/// The fields of the root `Query` and `Subscription` types are merged. Root
+/// fields whose names are already taken are prefixed in the same way, e.g.
+/// `QmHash_users`.
+pub fn compose_schemas(schemas: &[Schema]) -> (Option<Schema>, Vec<CompositionError>) {
+    let mut composed = Document {
+        definitions: vec![],
+    };
+    let mut root_types: Vec<ObjectType> = ROOT_TYPE_NAMES
+        .iter()
+        .map(|name| root_type(name))
+        .collect();
+    let mut type_owners: HashMap<String, &str> = HashMap::new();
+    let mut ids = vec![];
+    let mut errors = vec![];
+
+    for schema in schemas {
+        // Rename the types that conflict with types composed before; this
+        // changes the types that refer to them, which may then conflict too
+        let mut renamed_types: HashMap<Name, Name> = HashMap::new();
+        let mut document = schema.document.clone();
+        loop {
+            let new_conflicts: Vec<Name> = conflicting_types(&composed, &document)
+                .into_iter()
+                .filter(|name| !renamed_types.values().any(|new_name| new_name == *name))
+                .cloned()
+                .collect();
+            if new_conflicts.is_empty() {
+                break;
+            }
+
+            for name in new_conflicts {
+                let new_name = format!("{}_{}", name_prefix(&schema.id), name);
+                renamed_types.insert(name, new_name);
+            }
+            document = rename_types(&schema.document, &renamed_types);
+        }
+
+        // Leave out subgraphs whose types conflict even after renaming them,
+        // so that the composed schema never refers to types it doesn't contain
+        let conflicts = conflicting_types(&composed, &document);
+        if !conflicts.is_empty() {
+            errors.extend(conflicts.into_iter().map(|name| {
+                CompositionError::TypeConflict(
+                    schema.id.clone(),
+                    type_owners[name].to_owned(),
+                    name.clone(),
+                )
+            }));
+            continue;
+        }
+
+        for t in ast::get_type_definitions(&document) {
+            let name = ast::get_type_name(t);
+            if !ROOT_TYPE_NAMES.contains(&name.as_str())
+                && ast::get_named_type(&composed, name).is_none()
+            {
+                type_owners.insert(name.clone(), &schema.id);
+                composed
+                    .definitions
+                    .push(Definition::TypeDefinition(t.clone()));
+            }
+        }
+
+        for root_type in root_types.iter_mut() {
+            if let Some(TypeDefinition::Object(subgraph_root_type)) =
+                ast::get_named_type(&document, &root_type.name)
+            {
+                merge_root_fields(root_type, subgraph_root_type, &schema.id);
+            }
+        }
+
+        ids.push(schema.id.clone());
+    }
+
+    if ids.is_empty() {
+        return (None, errors);
+    }
+
+    composed.definitions.extend(
+        root_types
+            .into_iter()
+            .map(|t| Definition::TypeDefinition(TypeDefinition::Object(t))),
+    );
+
+    let schema = Schema {
+        id: ids.join(","),
+        document: composed,
+    };
+    (Some(schema), errors)
+}
+
+/// Returns the names of the types of a subgraph that a composed schema
+/// already defines differently.
+fn conflicting_types<'a>(composed: &Document, document: &'a Document) -> Vec<&'a Name> {
+    ast::get_type_definitions(document)
+        .into_iter()
+        .filter(|t| !ROOT_TYPE_NAMES.contains(&ast::get_type_name(t).as_str()))
+        .filter(|t| match ast::get_named_type(composed, ast::get_type_name(t)) {
+            Some(existing) => format!("{}", existing) != format!("{}", t),
+            None => false,
+        })
+        .map(ast::get_type_name)
+        .collect()
+}
+
+/// Renames types of a subgraph, along with all references to them.
+fn rename_types(document: &Document, renamed_types: &HashMap<Name, Name>) -> Document {
+    let rename = |name: &Name| renamed_types.get(name).unwrap_or(name).to_owned();
+
+    let rename_field_types = |fields: &mut Vec<Field>| {
+        for field in fields.iter_mut() {
+            field.field_type = rename_type(&field.field_type, &rename);
+            for argument in field.arguments.iter_mut() {
+                argument.value_type = rename_type(&argument.value_type, &rename);
+            }
+        }
+    };
+
+    let mut document = document.clone();
+    for definition in document.definitions.iter_mut() {
+        match definition {
+            Definition::TypeDefinition(TypeDefinition::Object(t)) => {
+                if renamed_types.contains_key(&t.name) {
+                    t.directives.push(original_name_directive(&t.name));
+                }
+                t.name = rename(&t.name);
+                t.implements_interfaces = t.implements_interfaces.iter().map(&rename).collect();
+                rename_field_types(&mut t.fields);
+            }
+            Definition::TypeDefinition(TypeDefinition::Interface(t)) => {
+                t.name = rename(&t.name);
+                rename_field_types(&mut t.fields);
+            }
+            Definition::TypeDefinition(TypeDefinition::Union(t)) => {
+                t.name = rename(&t.name);
+                t.types = t.types.iter().map(&rename).collect();
+            }
+            Definition::TypeDefinition(TypeDefinition::Enum(t)) => {
+                t.name = rename(&t.name);
+            }
+            Definition::TypeDefinition(TypeDefinition::Scalar(t)) => {
+                t.name = rename(&t.name);
+            }
+            Definition::TypeDefinition(TypeDefinition::InputObject(t)) => {
+                t.name = rename(&t.name);
+                for field in t.fields.iter_mut() {
+                    field.value_type = rename_type(&field.value_type, &rename);
+                }
+            }
+            _ => (),
+        }
+    }
+    document
+}
+
+/// Renames the named type that a type refers to.
+fn rename_type<F>(t: &Type, rename: &F) -> Type
+where
+    F: Fn(&Name) -> Name,
+{
+    match t {
+        Type::NamedType(name) => Type::NamedType(rename(name)),
+        Type::ListType(t) => Type::ListType(Box::new(rename_type(t, rename))),
+        Type::NonNullType(t) => Type::NonNullType(Box::new(rename_type(t, rename))),
+    }
+}
+
+/// Creates the directive that records the entity name of a renamed type.
+fn original_name_directive(name: &Name) -> Directive {
+    Directive {
+        position: Pos::default(),
+        name: "originalName".to_string(),
+        arguments: vec![("name".to_string(), Value::String(name.to_owned()))],
+    }
+}
+
+/// Creates an empty root type.
+fn root_type(name: &str) -> ObjectType {
+    ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: name.to_owned(),
+        implements_interfaces: vec![],
+        directives: vec![],
+        fields: vec![],
+    }
+}
+
+/// Adds the fields of a subgraph's root type to the composed root type,
+/// prefixing the names that are already taken.
+fn merge_root_fields(root_type: &mut ObjectType, subgraph_root_type: &ObjectType, id: &str) {
+    for field in subgraph_root_type.fields.iter() {
+        let mut field = field.clone();
+        if ast::get_field_type(root_type, &field.name).is_some() {
+            field.name = format!("{}_{}", name_prefix(id), field.name);
+        }
+        root_type.fields.push(field);
+    }
+}
+
+/// Turns a subgraph ID into a prefix that is valid in GraphQL names.
+fn name_prefix(id: &str) -> String {
+    let prefix: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    match prefix.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => prefix,
+        _ => format!("_{}", prefix),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graphql_parser;
+    use graphql_parser::schema::TypeDefinition;
+
+    use graph::prelude::Schema;
+
+    use super::{compose_schemas, CompositionError};
+    use schema::api::api_schema;
+    use schema::ast;
+
+    fn subgraph_schema(id: &str, sdl: &str) -> Schema {
+        Schema {
+            id: id.to_owned(),
+            document: api_schema(&graphql_parser::parse_schema(sdl).unwrap()).unwrap(),
+        }
+    }
+
+    fn root_field_names(schema: &Schema, root_type: &str) -> Vec<String> {
+        match ast::get_named_type(&schema.document, &root_type.to_owned()) {
+            Some(TypeDefinition::Object(t)) => t.fields.iter().map(|f| f.name.clone()).collect(),
+            _ => panic!("Root type missing in composed schema"),
+        }
+    }
+
+    #[test]
+    fn merges_the_types_and_root_fields_of_all_subgraphs() {
+        let (schema, errors) = compose_schemas(&[
+            subgraph_schema("a", "type User { id: ID!, name: String! }"),
+            subgraph_schema("b", "type Band { id: ID!, name: String! }"),
+        ]);
+        let schema = schema.expect("Should compose a schema");
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(schema.id, "a,b");
+        for name in ["User", "Band", "User_filter", "Band_orderBy", "String"].iter() {
+            ast::get_named_type(&schema.document, &name.to_string())
+                .expect("Type missing in composed schema");
+        }
+        assert_eq!(
+            root_field_names(&schema, "Query"),
+            vec!["user", "users", "userHistory", "_meta", "band", "bands", "bandHistory", "b__meta"]
+        );
+        assert_eq!(
+            root_field_names(&schema, "Subscription"),
+            vec!["user", "users", "_meta", "band", "bands", "b__meta"]
+        );
+    }
+
+    #[test]
+    fn prefixes_conflicting_types() {
+        let (schema, errors) = compose_schemas(&[
+            subgraph_schema("a", "type User { id: ID!, name: String! }"),
+            subgraph_schema(
+                "b",
+                "type User { id: ID!, email: String! } type Team { members: [User!]! }",
+            ),
+        ]);
+        let schema = schema.expect("Should compose a schema");
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(schema.id, "a,b");
+
+        // The renamed type is stored under its original name, and references
+        // to it are renamed as well
+        let user = match ast::get_named_type(&schema.document, &"b_User".to_string()) {
+            Some(TypeDefinition::Object(t)) => t,
+            _ => panic!("Renamed type missing in composed schema"),
+        };
+        assert_eq!(ast::get_entity_name(user), "User");
+        match ast::get_named_type(&schema.document, &"Team".to_string()) {
+            Some(TypeDefinition::Object(t)) => assert_eq!(
+                format!("{}", ast::get_field_type(t, &"members".to_string()).unwrap().field_type),
+                "[b_User!]!"
+            ),
+            _ => panic!("Type missing in composed schema"),
+        }
+        assert_eq!(
+            root_field_names(&schema, "Query"),
+            vec![
+                "user",
+                "users",
+                "userHistory",
+                "_meta",
+                "b_user",
+                "b_users",
+                "team",
+                "teams",
+                "b_userHistory",
+                "teamHistory",
+                "b__meta",
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_subgraphs_whose_renamed_types_conflict() {
+        let (schema, errors) = compose_schemas(&[
+            subgraph_schema("a", "type User { id: ID!, name: String! } type b_User { id: ID! }"),
+            subgraph_schema("b", "type User { id: ID!, email: String! }"),
+        ]);
+        let schema = schema.expect("Should compose a schema");
+
+        assert_eq!(
+            errors[0],
+            CompositionError::TypeConflict(
+                String::from("b"),
+                String::from("a"),
+                String::from("b_User"),
+            )
+        );
+        assert_eq!(schema.id, "a");
+    }
+
+    #[test]
+    fn prefixes_conflicting_root_fields() {
+        let (schema, errors) = compose_schemas(&[
+            subgraph_schema("a", "type User { id: ID! }"),
+            subgraph_schema("Qm-b", "type user { id: ID! }"),
+        ]);
+        let schema = schema.expect("Should compose a schema");
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(
+            root_field_names(&schema, "Query"),
+            vec![
+                "user",
+                "users",
+                "userHistory",
+                "_meta",
+                "Qm_b_user",
+                "Qm_b_users",
+                "Qm_b_userHistory",
+                "Qm_b__meta",
+            ]
+        );
+    }
+
+    #[test]
+    fn composes_nothing_from_no_subgraphs() {
+        let (schema, errors) = compose_schemas(&[]);
+        assert!(schema.is_none());
+        assert!(errors.is_empty());
+    }
+}
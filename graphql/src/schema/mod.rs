@@ -4,4 +4,12 @@ pub mod api;
 /// Utilities for working with GraphQL schema ASTs.
 pub mod ast;
 
+/// Composition of the API schemas of several subgraphs into one schema.
+pub mod composition;
+
+/// Rendering of schemas as SDL.
+pub mod printer;
+
 pub use self::api::{api_schema, APISchemaError};
+pub use self::composition::{compose_schemas, CompositionError};
+pub use self::printer::print_schema;
@@ -0,0 +1,92 @@
+use graphql_parser::schema::*;
+
+/// Built-in GraphQL scalars, which are implicit in SDL.
+const BUILTIN_SCALAR_TYPES: [&'static str; 5] = ["Boolean", "ID", "Int", "Float", "String"];
+
+/// Directives that are only meaningful to the node itself.
+const INTERNAL_DIRECTIVES: [&'static str; 4] =
+    ["subgraphId", "derivedFrom", "entityHistory", "originalName"];
+
+/// Renders a schema as SDL, e.g. to feed an API schema into client code
+/// generators. Built-in scalars and internal directives are left out.
+pub fn print_schema(schema: &Document) -> String {
+    let document = Document {
+        definitions: schema
+            .definitions
+            .iter()
+            .filter(|definition| match definition {
+                Definition::TypeDefinition(TypeDefinition::Scalar(t)) => {
+                    !BUILTIN_SCALAR_TYPES.contains(&t.name.as_str())
+                }
+                _ => true,
+            })
+            .cloned()
+            .map(|mut definition| {
+                if let Definition::TypeDefinition(ref mut t) = definition {
+                    strip_internal_directives(t);
+                }
+                definition
+            })
+            .collect(),
+    };
+
+    format!("{}", document)
+}
+
+/// Removes internal directives from a type and its fields.
+fn strip_internal_directives(t: &mut TypeDefinition) {
+    match t {
+        TypeDefinition::Object(t) => {
+            retain_public_directives(&mut t.directives);
+            for field in t.fields.iter_mut() {
+                retain_public_directives(&mut field.directives);
+            }
+        }
+        TypeDefinition::Interface(t) => {
+            retain_public_directives(&mut t.directives);
+            for field in t.fields.iter_mut() {
+                retain_public_directives(&mut field.directives);
+            }
+        }
+        TypeDefinition::Enum(t) => retain_public_directives(&mut t.directives),
+        TypeDefinition::Scalar(_) | TypeDefinition::Union(_) | TypeDefinition::InputObject(_) => (),
+    }
+}
+
+fn retain_public_directives(directives: &mut Vec<Directive>) {
+    directives.retain(|directive| !INTERNAL_DIRECTIVES.contains(&directive.name.as_str()));
+}
+
+#[cfg(test)]
+mod tests {
+    use graphql_parser;
+
+    use super::print_schema;
+    use schema::api::api_schema;
+    use schema::ast;
+
+    #[test]
+    fn printed_api_schemas_can_be_parsed_again() {
+        let input_schema = graphql_parser::parse_schema(
+            "
+            type User @subgraphId(id: \"a\") {
+                id: ID!
+                name: String!
+                friends: [User!]! @derivedFrom(field: \"friends\")
+            }
+            ",
+        ).unwrap();
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        let sdl = print_schema(&schema);
+        let printed = graphql_parser::parse_schema(&sdl).expect("Printed schema is invalid");
+
+        for name in ["User", "User_filter", "User_orderBy", "BigInt", "Query"].iter() {
+            ast::get_named_type(&printed, &name.to_string())
+                .expect("Type missing in printed schema");
+        }
+        assert!(ast::get_named_type(&printed, &"String".to_string()).is_none());
+        assert!(!sdl.contains("@subgraphId"));
+        assert!(!sdl.contains("@derivedFrom"));
+    }
+}
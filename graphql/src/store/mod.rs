@@ -1,5 +1,5 @@
 mod query;
 mod resolver;
 
-pub use self::query::build_query;
+pub use self::query::{build_query, DEFAULT_FIRST};
 pub use self::resolver::StoreResolver;
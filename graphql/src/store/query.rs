@@ -3,23 +3,86 @@ use graphql_parser::{query as q, schema};
 use schema::ast;
 use std::collections::{BTreeMap, HashMap};
 
+/// The number of entities fetched for list fields without a positive `first`
+/// argument.
+pub const DEFAULT_FIRST: usize = 100;
+
 /// Builds a StoreQuery from GraphQL arguments.
 pub fn build_query(
     entity: &schema::ObjectType,
     arguments: &HashMap<&q::Name, q::Value>,
-) -> StoreQuery {
-    StoreQuery {
-        subgraph: build_subgraph_id(entity)
-            .expect(format!("Failed to get subgraph ID from type: {}", entity.name).as_str()),
-        entity: entity.name.to_owned(),
+) -> Result<StoreQuery, QueryExecutionError> {
+    Ok(StoreQuery {
+        subgraph: build_subgraph_id(entity).ok_or_else(|| {
+            QueryExecutionError::ResolveEntitiesError(format!(
+                "Failed to get subgraph ID from type: {}",
+                entity.name
+            ))
+        })?,
+        entity: ast::get_entity_name(entity).to_owned(),
         range: build_range(arguments),
-        filter: build_filter(entity, arguments),
+        filter: build_filter(entity, arguments)?,
         order_by: build_order_by(arguments),
         order_direction: build_order_direction(arguments),
-    }
+    })
 }
 
-/// Parses GraphQL arguments into a StoreRange, if present.
+/// Builds a StoreHistoryQuery from the arguments of a history field (e.g.
+/// `userHistory`) that returns the given `*_change` type.
+pub fn build_history_query(
+    field_definition: &schema::Field,
+    change_type: &schema::ObjectType,
+    arguments: &HashMap<&q::Name, q::Value>,
+) -> Result<StoreHistoryQuery, QueryExecutionError> {
+    let entity = ast::get_history_entity_type(field_definition).ok_or_else(|| {
+        QueryExecutionError::ResolveEntitiesError(format!(
+            "Failed to get entity type from history field: {}",
+            field_definition.name
+        ))
+    })?;
+
+    let subgraph = build_subgraph_id_from_directives(&field_definition.directives)
+        .or_else(|| build_subgraph_id(change_type))
+        .ok_or_else(|| {
+            QueryExecutionError::ResolveEntitiesError(format!(
+                "Failed to get subgraph ID from field: {}",
+                field_definition.name
+            ))
+        })?;
+
+    let id = match arguments.get(&"id".to_string()) {
+        Some(q::Value::String(id)) => id.to_owned(),
+        _ => {
+            return Err(QueryExecutionError::ResolveEntitiesError(format!(
+                "Missing entity ID for field: {}",
+                field_definition.name
+            )))
+        }
+    };
+
+    let int_argument = |name: &str| {
+        arguments
+            .get(&name.to_string())
+            .and_then(|value| match value {
+                q::Value::Int(n) => n.as_i64(),
+                _ => None,
+            })
+            .and_then(|n| if n >= 0 { Some(n as u64) } else { None })
+    };
+
+    Ok(StoreHistoryQuery {
+        key: StoreKey {
+            subgraph,
+            entity: entity.to_owned(),
+            id,
+        },
+        first: int_argument("first").map(|first| first as usize),
+        from_block: int_argument("fromBlock"),
+    })
+}
+
+/// Parses GraphQL arguments into a StoreRange. A missing or non-positive
+/// `first` argument fetches `DEFAULT_FIRST` entities.
 fn build_range(arguments: &HashMap<&q::Name, q::Value>) -> Option<StoreRange> {
     let first = arguments
         .get(&"first".to_string())
@@ -27,7 +90,8 @@ fn build_range(arguments: &HashMap<&q::Name, q::Value>) -> Option<StoreRange> {
             q::Value::Int(n) => n.as_i64(),
             _ => None,
         })
-        .and_then(|n| if n > 0 { Some(n as usize) } else { None });
+        .and_then(|n| if n > 0 { Some(n as usize) } else { None })
+        .unwrap_or(DEFAULT_FIRST);
 
     let skip = arguments
         .get(&"skip".to_string())
@@ -35,36 +99,29 @@ fn build_range(arguments: &HashMap<&q::Name, q::Value>) -> Option<StoreRange> {
             q::Value::Int(n) => n.as_i64(),
             _ => None,
         })
-        .and_then(|n| if n >= 0 { Some(n as usize) } else { None });
+        .and_then(|n| if n >= 0 { Some(n as usize) } else { None })
+        .unwrap_or(0);
 
-    match (first, skip) {
-        (None, None) => None,
-        (Some(first), None) => Some(StoreRange { first, skip: 0 }),
-        (Some(first), Some(skip)) => Some(StoreRange { first, skip }),
-        (None, Some(skip)) => Some(StoreRange { first: 100, skip }),
-    }
+    Some(StoreRange { first, skip })
 }
 
 /// Parses GraphQL arguments into a StoreFilter, if present.
 fn build_filter(
     entity: &schema::ObjectType,
     arguments: &HashMap<&q::Name, q::Value>,
-) -> Option<StoreFilter> {
-    arguments
-        .get(&"where".to_string())
-        .and_then(|value| match value {
-            q::Value::Object(object) => Some(object),
-            _ => None,
-        })
-        .map(|object| build_filter_from_object(entity, object))
+) -> Result<Option<StoreFilter>, QueryExecutionError> {
+    match arguments.get(&"where".to_string()) {
+        Some(q::Value::Object(object)) => build_filter_from_object(entity, object).map(Some),
+        _ => Ok(None),
+    }
 }
 
 /// Parses a GraphQL input object into a StoreFilter, if present.
 fn build_filter_from_object(
     entity: &schema::ObjectType,
     object: &BTreeMap<q::Name, q::Value>,
-) -> StoreFilter {
-    StoreFilter::And(
+) -> Result<StoreFilter, QueryExecutionError> {
+    Ok(StoreFilter::And(
         object
             .iter()
             .map(|(key, value)| {
@@ -72,19 +129,23 @@ fn build_filter_from_object(
 
                 let (attribute, op) = ast::parse_field_as_filter(key);
 
-                let field = ast::get_field_type(entity, &attribute)
-                    .expect("attribute does not belong to entity");
+                let field = ast::get_field_type(entity, &attribute).ok_or_else(|| {
+                    QueryExecutionError::EntityAttributeError(
+                        entity.name.to_owned(),
+                        attribute.to_owned(),
+                    )
+                })?;
                 let ty = &field.field_type;
-                let store_value = Value::from_query_value(value, &ty);
+                let store_value = Value::from_query_value(value, &ty)?;
 
-                match op {
+                Ok(match op {
                     Not => StoreFilter::Not(attribute, store_value),
                     GreaterThan => StoreFilter::GreaterThan(attribute, store_value),
                     LessThan => StoreFilter::LessThan(attribute, store_value),
                     GreaterOrEqual => StoreFilter::GreaterOrEqual(attribute, store_value),
                     LessOrEqual => StoreFilter::LessOrEqual(attribute, store_value),
-                    In => StoreFilter::In(attribute, list_values(store_value)),
-                    NotIn => StoreFilter::NotIn(attribute, list_values(store_value)),
+                    In => StoreFilter::In(attribute, list_values(store_value, "_in")?),
+                    NotIn => StoreFilter::NotIn(attribute, list_values(store_value, "_not_in")?),
                     Contains => StoreFilter::Contains(attribute, store_value),
                     NotContains => StoreFilter::NotContains(attribute, store_value),
                     StartsWith => StoreFilter::StartsWith(attribute, store_value),
@@ -92,17 +153,17 @@ fn build_filter_from_object(
                     EndsWith => StoreFilter::EndsWith(attribute, store_value),
                     NotEndsWith => StoreFilter::NotEndsWith(attribute, store_value),
                     Equal => StoreFilter::Equal(attribute, store_value),
-                }
+                })
             })
-            .collect::<Vec<StoreFilter>>(),
-    )
+            .collect::<Result<Vec<StoreFilter>, QueryExecutionError>>()?,
+    ))
 }
 
 /// Parses a list of GraphQL values into a vector of entity attribute values.
-fn list_values(value: Value) -> Vec<Value> {
+fn list_values(value: Value, filter_type: &str) -> Result<Vec<Value>, QueryExecutionError> {
     match value {
-        Value::List(values) => values,
-        _ => panic!("value is not a list"),
+        Value::List(values) => Ok(values),
+        _ => Err(QueryExecutionError::ListFilterError(filter_type.to_string())),
     }
 }
 
@@ -129,19 +190,22 @@ fn build_order_direction(arguments: &HashMap<&q::Name, q::Value>) -> Option<Stor
 
 /// Parses the subgraph ID from the ObjectType directives.
 pub fn build_subgraph_id(entity: &schema::ObjectType) -> Option<String> {
-    entity
-        .clone()
-        .directives
-        .into_iter()
+    build_subgraph_id_from_directives(&entity.directives)
+}
+
+/// Parses the subgraph ID from a `@subgraphId` directive among `directives`.
+pub fn build_subgraph_id_from_directives(directives: &[schema::Directive]) -> Option<String> {
+    directives
+        .iter()
         .find(|directive| directive.name == "subgraphId".to_string())
         .and_then(|directive| {
             directive
                 .arguments
-                .into_iter()
+                .iter()
                 .find(|(name, _)| name == &"id".to_string())
         })
         .and_then(|(_, value)| match value {
-            schema::Value::String(id) => Some(id),
+            schema::Value::String(id) => Some(id.clone()),
             _ => None,
         })
 }
@@ -199,11 +263,11 @@ mod tests {
     #[test]
     fn build_query_uses_the_entity_name() {
         assert_eq!(
-            build_query(&object("Entity1"), &HashMap::new()).entity,
+            build_query(&object("Entity1"), &HashMap::new()).unwrap().entity,
             "Entity1".to_string()
         );
         assert_eq!(
-            build_query(&object("Entity2"), &HashMap::new()).entity,
+            build_query(&object("Entity2"), &HashMap::new()).unwrap().entity,
             "Entity2".to_string()
         );
     }
@@ -211,11 +275,11 @@ mod tests {
     #[test]
     fn build_query_yields_no_order_if_order_arguments_are_missing() {
         assert_eq!(
-            build_query(&default_object(), &HashMap::new()).order_by,
+            build_query(&default_object(), &HashMap::new()).unwrap().order_by,
             None,
         );
         assert_eq!(
-            build_query(&default_object(), &HashMap::new()).order_direction,
+            build_query(&default_object(), &HashMap::new()).unwrap().order_direction,
             None,
         );
     }
@@ -228,7 +292,7 @@ mod tests {
                 &HashMap::from_iter(
                     vec![(&"orderBy".to_string(), q::Value::Enum("name".to_string()))].into_iter(),
                 )
-            ).order_by,
+            ).unwrap().order_by,
             Some("name".to_string())
         );
         assert_eq!(
@@ -237,7 +301,7 @@ mod tests {
                 &HashMap::from_iter(
                     vec![(&"orderBy".to_string(), q::Value::Enum("email".to_string()))].into_iter()
                 )
-            ).order_by,
+            ).unwrap().order_by,
             Some("email".to_string())
         );
     }
@@ -251,7 +315,7 @@ mod tests {
                     vec![(&"orderBy".to_string(), q::Value::String("name".to_string()))]
                         .into_iter()
                 ),
-            ).order_by,
+            ).unwrap().order_by,
             None,
         );
         assert_eq!(
@@ -263,7 +327,7 @@ mod tests {
                         q::Value::String("email".to_string()),
                     )].into_iter(),
                 )
-            ).order_by,
+            ).unwrap().order_by,
             None,
         );
     }
@@ -279,7 +343,7 @@ mod tests {
                         q::Value::Enum("asc".to_string()),
                     )].into_iter(),
                 )
-            ).order_direction,
+            ).unwrap().order_direction,
             Some(StoreOrder::Ascending)
         );
         assert_eq!(
@@ -291,7 +355,7 @@ mod tests {
                         q::Value::Enum("desc".to_string()),
                     )].into_iter()
                 )
-            ).order_direction,
+            ).unwrap().order_direction,
             Some(StoreOrder::Descending)
         );
         assert_eq!(
@@ -303,7 +367,7 @@ mod tests {
                         q::Value::Enum("ascending...".to_string()),
                     )].into_iter()
                 )
-            ).order_direction,
+            ).unwrap().order_direction,
             None,
         );
     }
@@ -319,7 +383,7 @@ mod tests {
                         q::Value::String("asc".to_string()),
                     )].into_iter()
                 ),
-            ).order_direction,
+            ).unwrap().order_direction,
             None,
         );
         assert_eq!(
@@ -331,14 +395,36 @@ mod tests {
                         q::Value::String("desc".to_string()),
                     )].into_iter(),
                 )
-            ).order_direction,
+            ).unwrap().order_direction,
             None,
         );
     }
 
     #[test]
-    fn build_query_yields_no_range_if_none_is_present() {
-        assert_eq!(build_query(&default_object(), &HashMap::new()).range, None,);
+    fn build_query_yields_default_range_if_none_is_present() {
+        assert_eq!(
+            build_query(&default_object(), &HashMap::new()).unwrap().range,
+            Some(StoreRange {
+                first: 100,
+                skip: 0,
+            }),
+        );
+    }
+
+    #[test]
+    fn build_query_yields_default_first_if_first_is_not_positive() {
+        assert_eq!(
+            build_query(
+                &default_object(),
+                &HashMap::from_iter(
+                    vec![(&"first".to_string(), q::Value::Int(q::Number::from(0)))].into_iter()
+                )
+            ).unwrap().range,
+            Some(StoreRange {
+                first: 100,
+                skip: 0,
+            }),
+        );
     }
 
     #[test]
@@ -349,7 +435,7 @@ mod tests {
                 &HashMap::from_iter(
                     vec![(&"skip".to_string(), q::Value::Int(q::Number::from(50)))].into_iter()
                 )
-            ).range,
+            ).unwrap().range,
             Some(StoreRange {
                 first: 100,
                 skip: 50,
@@ -365,7 +451,7 @@ mod tests {
                 &HashMap::from_iter(
                     vec![(&"first".to_string(), q::Value::Int(q::Number::from(70)))].into_iter()
                 )
-            ).range,
+            ).unwrap().range,
             Some(StoreRange { first: 70, skip: 0 }),
         );
     }
@@ -387,7 +473,7 @@ mod tests {
                         )])),
                     )].into_iter(),
                 )
-            ).filter,
+            ).unwrap().filter,
             Some(StoreFilter::And(vec![StoreFilter::EndsWith(
                 "name".to_string(),
                 Value::String("ello".to_string()),
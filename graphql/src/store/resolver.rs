@@ -1,32 +1,47 @@
 use graphql_parser::{query as q, schema as s};
 use slog;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::ops::Deref;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use graph::components::store::*;
-use graph::prelude::{BasicStore, Value};
+use graph::data::meta::{meta_key, META_TYPE_NAME};
+use graph::data::store::scalar;
+use graph::prelude::{BasicStore, Entity, QueryExecutionError, QueryTracer, Value};
+use graph::prelude::{EntityChange, EntityOperation};
 
 use prelude::*;
 use query::ast as qast;
+use query::resolver::with_typename;
 use schema::ast as sast;
-use store::query::build_subgraph_id;
+use store::query::{
+    build_history_query, build_subgraph_id, build_subgraph_id_from_directives, DEFAULT_FIRST,
+};
 
 /// A resolver that fetches entities from a `Store`.
 #[derive(Clone)]
 pub struct StoreResolver {
     logger: slog::Logger,
-    store: Arc<Mutex<BasicStore>>,
+    store: Arc<Mutex<BasicStore + Send>>,
+    tracer: Option<QueryTracer>,
 }
 
 impl StoreResolver {
-    pub fn new(logger: &slog::Logger, store: Arc<Mutex<BasicStore>>) -> Self {
+    pub fn new(logger: &slog::Logger, store: Arc<Mutex<BasicStore + Send>>) -> Self {
         StoreResolver {
             logger: logger.new(o!("component" => "StoreResolver")),
             store,
+            tracer: None,
         }
     }
 
+    /// Records the store queries of the resolver with the given tracer.
+    pub fn with_tracer(mut self, tracer: QueryTracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
     /// If the field has a `@derivedFrom(field: "foo")` directive, obtain the
     /// name of the field (e.g. `"foo"`)
     fn get_derived_from_directive(field_definition: &s::Field) -> Option<&s::Directive> {
@@ -36,17 +51,13 @@ impl StoreResolver {
             .find(|directive| directive.name == s::Name::from("derivedFrom"))
     }
 
-    /// Adds a filter for matching entities that correspond to a derived field.
-    ///
-    /// Returns true if the field is a derived field (i.e., if it is defined with
-    /// a @derivedFrom directive).
-    fn add_filter_for_derived_field(
-        query: &mut StoreQuery,
-        parent: &Option<q::Value>,
+    /// Returns the field of the object type that a `@derivedFrom` field is
+    /// derived from, if the field is a derived field.
+    fn get_derived_from_field<'a>(
         field_definition: &s::Field,
-        object_type: &s::ObjectType,
-    ) -> bool {
-        let derived_from_field = Self::get_derived_from_directive(field_definition)
+        object_type: &'a s::ObjectType,
+    ) -> Option<&'a s::Field> {
+        Self::get_derived_from_directive(field_definition)
             .and_then(|directive| {
                 qast::get_argument_value(&directive.arguments, &q::Name::from("field"))
             })
@@ -56,9 +67,55 @@ impl StoreResolver {
             })
             .and_then(|derived_from_field_name| {
                 sast::get_field_type(object_type, derived_from_field_name)
-            });
+            })
+    }
+
+    /// Returns the ID of a parent object.
+    fn get_parent_id(parent: &Option<q::Value>) -> Result<Value, QueryExecutionError> {
+        parent
+            .as_ref()
+            .and_then(|value| match value {
+                q::Value::Object(o) => Some(o),
+                _ => None,
+            })
+            .and_then(|object| object.get(&q::Name::from("id")))
+            .and_then(|value| match value {
+                q::Value::String(s) => Some(Value::from(s)),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                QueryExecutionError::ResolveEntitiesError(String::from(
+                    "Parent object is missing an \"id\"",
+                ))
+            })
+    }
+
+    /// Adds a filter to the top-level `And` filter of a query, creating one if necessary.
+    fn add_filter(query: &mut StoreQuery, filter: StoreFilter) {
+        let top_level_filter = query.filter.get_or_insert(StoreFilter::And(vec![]));
+        *top_level_filter = match top_level_filter {
+            StoreFilter::And(ref mut filters) => {
+                let mut filters = filters.clone();
+                filters.push(filter);
+                StoreFilter::And(filters)
+            }
+            _ => top_level_filter.clone(),
+        };
+    }
 
-        if let Some(derived_from_field) = derived_from_field {
+    /// Adds a filter for matching entities that correspond to a derived field.
+    ///
+    /// Returns true if the field is a derived field (i.e., if it is defined with
+    /// a @derivedFrom directive).
+    fn add_filter_for_derived_field(
+        query: &mut StoreQuery,
+        parent: &Option<q::Value>,
+        field_definition: &s::Field,
+        object_type: &s::ObjectType,
+    ) -> Result<bool, QueryExecutionError> {
+        if let Some(derived_from_field) =
+            Self::get_derived_from_field(field_definition, object_type)
+        {
             // This field is derived from a field in the object type that we're trying
             // to resolve values for; e.g. a `bandMembers` field maybe be derived from
             // a `bands` or `band` field in a `Musician` type.
@@ -70,47 +127,24 @@ impl StoreResolver {
             let field_name = derived_from_field.name.clone();
 
             // To achieve this, we first identify the parent ID
-            let parent_id = parent
-                .as_ref()
-                .and_then(|value| match value {
-                    q::Value::Object(o) => Some(o),
-                    _ => None,
-                })
-                .and_then(|object| object.get(&q::Name::from("id")))
-                .and_then(|value| match value {
-                    q::Value::String(s) => Some(Value::from(s)),
-                    _ => None,
-                })
-                .expect("Parent object is missing an \"id\"")
-                .clone();
+            let parent_id = Self::get_parent_id(parent)?;
 
             // Depending on whether the field we're deriving from has a list or a
             // single value type, we either create a `Contains` or `Equal`
             // filter argument
-            let filter = match derived_from_field.field_type {
-                s::Type::ListType(_) => StoreFilter::Contains(field_name, parent_id),
-                s::Type::NonNullType(ref inner) => match inner.deref() {
-                    s::Type::ListType(_) => StoreFilter::Contains(field_name, parent_id),
-                    _ => StoreFilter::Equal(field_name, parent_id),
-                },
-                _ => StoreFilter::Equal(field_name, parent_id),
+            let filter = if sast::is_list_type(&derived_from_field.field_type) {
+                StoreFilter::Contains(field_name, parent_id)
+            } else {
+                StoreFilter::Equal(field_name, parent_id)
             };
 
             // Add the `Contains`/`Equal` filter to the top-level `And` filter, creating one
             // if necessary
-            let top_level_filter = query.filter.get_or_insert(StoreFilter::And(vec![]));
-            *top_level_filter = match top_level_filter {
-                StoreFilter::And(ref mut filters) => {
-                    let mut filters = filters.clone();
-                    filters.push(filter);
-                    StoreFilter::And(filters)
-                }
-                _ => top_level_filter.clone(),
-            };
+            Self::add_filter(query, filter);
 
-            true
+            Ok(true)
         } else {
-            false
+            Ok(false)
         }
     }
 
@@ -120,7 +154,7 @@ impl StoreResolver {
         parent: &Option<q::Value>,
         field_definition: &s::Field,
         _object_type: &s::ObjectType,
-    ) {
+    ) -> Result<(), QueryExecutionError> {
         if let Some(q::Value::Object(object)) = parent {
             // Create an `Or(Equals("id", ref_id1), ...)` filter that includes
             // all referenced IDs.
@@ -141,23 +175,34 @@ impl StoreResolver {
                     )),
                     _ => None,
                 })
-                .expect(
-                    format!(
+                .ok_or_else(|| {
+                    QueryExecutionError::ResolveEntitiesError(format!(
                         "Field \"{}\" missing in parent object",
                         field_definition.name
-                    ).as_str(),
-                );
+                    ))
+                })?;
 
             // Add the `Or` filter to the top-level `And` filter, creating one if necessary
-            let top_level_filter = query.filter.get_or_insert(StoreFilter::And(vec![]));
-            *top_level_filter = match top_level_filter {
-                StoreFilter::And(ref mut filters) => {
-                    let mut filters = filters.clone();
-                    filters.push(filter);
-                    StoreFilter::And(filters)
-                }
-                _ => top_level_filter.clone(),
-            };
+            Self::add_filter(query, filter);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the IDs that a parent object references in the given field.
+    fn get_referenced_ids<'a>(parent: &'a q::Value, field: &q::Name) -> Vec<&'a String> {
+        match parent {
+            q::Value::Object(object) => match object.get(field) {
+                Some(q::Value::String(id)) => vec![id],
+                Some(q::Value::List(ids)) => ids.iter()
+                    .filter_map(|id| match id {
+                        q::Value::String(id) => Some(id),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => vec![],
+            },
+            _ => vec![],
         }
     }
 
@@ -176,50 +221,332 @@ impl StoreResolver {
             })
             .unwrap_or(true)
     }
-}
 
-impl Resolver for StoreResolver {
-    fn resolve_objects(
-        &self,
+    /// Builds a store query for entities of the given object type that are
+    /// referenced by a parent object.
+    ///
+    /// Returns `None` if the parent holds an empty list of references, in which
+    /// case there is no point in querying the store.
+    fn build_query_for_objects(
         parent: &Option<q::Value>,
-        _field: &q::Name,
         field_definition: &s::Field,
         object_type: &s::ObjectType,
         arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value {
-        let mut query = build_query(&object_type, arguments);
+    ) -> Result<Option<StoreQuery>, QueryExecutionError> {
+        let mut query = build_query(&object_type, arguments)?;
 
         // Add matching filter for derived fields
         let is_derived =
-            Self::add_filter_for_derived_field(&mut query, parent, field_definition, object_type);
+            Self::add_filter_for_derived_field(&mut query, parent, field_definition, object_type)?;
 
-        // Return an empty list if we're dealing with a non-derived field that
-        // holds an empty list of references; there's no point in querying the store
-        // if the result will be empty anyway
+        // Return nothing if we're dealing with a non-derived field that
+        // holds an empty list of references
         if !is_derived
             && parent.is_some()
             && Self::references_field_is_empty(parent, &field_definition.name)
         {
-            return q::Value::List(vec![]);
+            return Ok(None);
         }
 
         // Add matching filter for reference fields
         if !is_derived {
-            Self::add_filter_for_reference_field(&mut query, parent, field_definition, object_type);
+            Self::add_filter_for_reference_field(&mut query, parent, field_definition, object_type)?;
         }
 
+        Ok(Some(query))
+    }
+
+    /// Builds the store key for an entity of the given object type.
+    fn build_key(object_type: &s::ObjectType, id: &String) -> Result<StoreKey, QueryExecutionError> {
+        Ok(StoreKey {
+            subgraph: build_subgraph_id(object_type).ok_or_else(|| {
+                QueryExecutionError::ResolveEntitiesError(format!(
+                    "Failed to get subgraph ID from type: {}",
+                    object_type.name
+                ))
+            })?,
+            entity: sast::get_entity_name(object_type).to_owned(),
+            id: id.to_owned(),
+        })
+    }
+
+    /// Finds entities in the store, failing if the store query fails.
+    fn find(&self, query: StoreQuery) -> Result<Vec<Entity>, QueryExecutionError> {
+        let entity = query.entity.clone();
         let store = self.store.lock().unwrap();
-        store
-            .find(query)
-            .map(|entities| {
+
+        let result = match self.tracer {
+            Some(ref tracer) => {
+                let sql = store.query_sql(&query);
+                let traced_query = query.clone();
+                let started = Instant::now();
+                let result = store.find(query);
+                let rows = result.as_ref().ok().map(|entities| entities.len());
+                tracer.trace_store_query(&traced_query, sql, rows, started);
+                result
+            }
+            None => store.find(query),
+        };
+
+        result.map_err(|_| {
+            QueryExecutionError::ResolveEntitiesError(format!(
+                "Failed to query `{}` entities",
+                entity
+            ))
+        })
+    }
+
+    /// Resolves the history of an entity into a list of `*_change` objects.
+    fn resolve_history(
+        &self,
+        field_definition: &s::Field,
+        change_type: &s::ObjectType,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let query = build_history_query(field_definition, change_type, arguments)?;
+        let entity = query.key.entity.clone();
+
+        self.store
+            .lock()
+            .unwrap()
+            .history(query)
+            .map(|changes| {
                 q::Value::List(
-                    entities
+                    changes
                         .into_iter()
-                        .map(|e| e.into())
+                        .map(Self::change_to_value)
                         .collect::<Vec<q::Value>>(),
                 )
             })
-            .unwrap_or(q::Value::Null)
+            .map_err(|_| {
+                QueryExecutionError::ResolveEntitiesError(format!(
+                    "Failed to query the history of `{}` entities",
+                    entity
+                ))
+            })
+    }
+
+    /// Converts a change made to an entity into a `*_change` object.
+    fn change_to_value(change: EntityChange) -> q::Value {
+        let operation = match change.operation {
+            EntityOperation::Insert => "insert",
+            EntityOperation::Update => "update",
+            EntityOperation::Delete => "delete",
+        };
+
+        object_value(vec![
+            ("operation", q::Value::Enum(operation.to_string())),
+            (
+                "blockHash",
+                change
+                    .block_hash
+                    .map(|hash| Value::Bytes(scalar::Bytes::from(&hash[..])).into())
+                    .unwrap_or(q::Value::Null),
+            ),
+            (
+                "blockNumber",
+                change
+                    .block_number
+                    .map(|number| Value::Int(number as i32).into())
+                    .unwrap_or(q::Value::Null),
+            ),
+            (
+                "before",
+                change.before.map(Into::into).unwrap_or(q::Value::Null),
+            ),
+            ("after", change.after.map(Into::into).unwrap_or(q::Value::Null)),
+        ])
+    }
+
+    /// Compares two entities by the value of the given attribute.
+    fn compare_entities(a: &Entity, b: &Entity, attribute: &String) -> Ordering {
+        match (a.get(attribute), b.get(attribute)) {
+            (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+            (Some(Value::Int(a)), Some(Value::Int(b))) => a.cmp(b),
+            (Some(Value::Float(a)), Some(Value::Float(b))) => {
+                a.partial_cmp(b).unwrap_or(Ordering::Equal)
+            }
+            (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+            (Some(Value::BigInt(a)), Some(Value::BigInt(b))) => a.cmp(b),
+            (Some(Value::Bytes(a)), Some(Value::Bytes(b))) => a.cmp(b),
+            (None, Some(_)) | (Some(Value::Null), Some(_)) => Ordering::Less,
+            (Some(_), None) | (Some(_), Some(Value::Null)) => Ordering::Greater,
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+impl Resolver for StoreResolver {
+    fn resolve_objects(
+        &self,
+        parent: &Option<q::Value>,
+        _field: &q::Name,
+        field_definition: &s::Field,
+        object_type: &s::ObjectType,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        // History fields (e.g. `userHistory`) return `*_change` objects
+        if sast::get_history_entity_type(field_definition).is_some() {
+            return self.resolve_history(field_definition, object_type, arguments);
+        }
+
+        let query = match Self::build_query_for_objects(
+            parent,
+            field_definition,
+            object_type,
+            arguments,
+        )? {
+            Some(query) => query,
+            None => return Ok(q::Value::List(vec![])),
+        };
+
+        self.find(query).map(|entities| {
+            q::Value::List(
+                entities
+                    .into_iter()
+                    .map(|e| e.into())
+                    .collect::<Vec<q::Value>>(),
+            )
+        })
+    }
+
+    fn resolve_objects_for_parents(
+        &self,
+        parents: &[q::Value],
+        field: &q::Name,
+        field_definition: &s::Field,
+        object_type: &s::ObjectType,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<Vec<q::Value>, QueryExecutionError> {
+        let mut query = build_query(&object_type, arguments)?;
+
+        // An explicit range applies to the entities of each parent
+        // individually and can't be applied to a single query for all parents
+        if ["first", "skip"]
+            .iter()
+            .any(|name| arguments.contains_key(&name.to_string()))
+        {
+            return parents
+                .iter()
+                .map(|parent| {
+                    self.resolve_objects(
+                        &Some(parent.clone()),
+                        field,
+                        field_definition,
+                        object_type,
+                        arguments,
+                    )
+                })
+                .collect();
+        }
+
+        // Build one filter that matches the entities of all parents, along
+        // with the keys that connect each parent to its entities and the
+        // attribute that holds these keys in the entities
+        let derived_from_field = Self::get_derived_from_field(field_definition, object_type);
+        let (parent_keys, key_attribute) = match derived_from_field {
+            Some(derived_from_field) => {
+                let field_name = derived_from_field.name.clone();
+                let parent_ids = parents
+                    .iter()
+                    .map(|parent| Self::get_parent_id(&Some(parent.clone())))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Self::add_filter(
+                    &mut query,
+                    if sast::is_list_type(&derived_from_field.field_type) {
+                        StoreFilter::Or(
+                            parent_ids
+                                .iter()
+                                .map(|id| StoreFilter::Contains(field_name.clone(), id.clone()))
+                                .collect(),
+                        )
+                    } else {
+                        StoreFilter::In(field_name.clone(), parent_ids.clone())
+                    },
+                );
+
+                let parent_keys = parent_ids
+                    .into_iter()
+                    .map(|id| match id {
+                        Value::String(id) => vec![id],
+                        _ => vec![],
+                    })
+                    .collect::<Vec<_>>();
+                (parent_keys, field_name)
+            }
+            None => {
+                let parent_keys = parents
+                    .iter()
+                    .map(|parent| {
+                        Self::get_referenced_ids(parent, &field_definition.name)
+                            .into_iter()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+                let ids = parent_keys
+                    .iter()
+                    .flat_map(|ids| ids.iter())
+                    .map(|id| Value::from(id))
+                    .collect::<Vec<_>>();
+
+                // There is no point in querying the store if nothing is referenced
+                if ids.is_empty() {
+                    return Ok(parents.iter().map(|_| q::Value::List(vec![])).collect());
+                }
+
+                Self::add_filter(&mut query, StoreFilter::In(String::from("id"), ids));
+                (parent_keys, String::from("id"))
+            }
+        };
+
+        // The default range is applied to each parent below
+        query.range = None;
+        let entities = self.find(query)?;
+
+        // Index the entities by their keys once, remembering their position
+        // so that each parent receives its entities in the store order
+        let mut entities_by_key: HashMap<&String, Vec<usize>> = HashMap::new();
+        for (position, entity) in entities.iter().enumerate() {
+            let keys = match entity.get(&key_attribute) {
+                Some(Value::String(key)) => vec![key],
+                Some(Value::List(values)) => values
+                    .iter()
+                    .filter_map(|value| match value {
+                        Value::String(key) => Some(key),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => vec![],
+            };
+            for key in keys {
+                entities_by_key
+                    .entry(key)
+                    .or_insert_with(Vec::new)
+                    .push(position);
+            }
+        }
+
+        Ok(parent_keys
+            .iter()
+            .map(|keys| {
+                let mut positions = keys.iter()
+                    .filter_map(|key| entities_by_key.get(key))
+                    .flat_map(|positions| positions.iter().cloned())
+                    .collect::<Vec<_>>();
+                positions.sort();
+                positions.dedup();
+                positions.truncate(DEFAULT_FIRST);
+
+                q::Value::List(
+                    positions
+                        .into_iter()
+                        .map(|position| entities[position].clone().into())
+                        .collect(),
+                )
+            })
+            .collect())
     }
 
     fn resolve_object(
@@ -229,42 +556,56 @@ impl Resolver for StoreResolver {
         field_definition: &s::Field,
         object_type: &s::ObjectType,
         arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value {
+    ) -> Result<q::Value, QueryExecutionError> {
+        // The indexing status of the subgraph that the `_meta` field belongs to;
+        // schemas marked after deriving the API schema only mark the `_Meta_` type
+        if object_type.name == META_TYPE_NAME {
+            let subgraph_id = build_subgraph_id_from_directives(&field_definition.directives)
+                .or_else(|| build_subgraph_id(object_type))
+                .ok_or_else(|| {
+                    QueryExecutionError::ResolveEntitiesError(format!(
+                        "Failed to get subgraph ID from field: {}",
+                        field_definition.name
+                    ))
+                })?;
+            let store = self.store.lock().unwrap();
+            return Ok(store
+                .get(meta_key(&subgraph_id))
+                .map(|entity| entity.into())
+                .unwrap_or(q::Value::Null));
+        }
+
         let id = arguments.get(&"id".to_string()).and_then(|id| match id {
             q::Value::String(s) => Some(s),
             _ => None,
         });
 
         if let Some(id) = id {
+            let key = Self::build_key(object_type, id)?;
             let store = self.store.lock().unwrap();
-            return store
-                .get(StoreKey {
-                    subgraph: build_subgraph_id(object_type)
-                        .expect(format!("Failed to get subgraph ID from type: {}", object_type.name).as_str()),
-                    entity: object_type.name.to_owned(),
-                    id: id.to_owned(),
-                })
+            return Ok(store
+                .get(key)
                 .map(|entity| entity.into())
-                .unwrap_or(q::Value::Null);
+                .unwrap_or(q::Value::Null));
         }
 
         match parent {
             Some(q::Value::Object(parent_object)) => match parent_object.get(field) {
-                Some(q::Value::String(id)) => self.store
-                    .lock()
-                    .unwrap()
-                    .get(StoreKey {
-                        subgraph: build_subgraph_id(object_type)
-                            .expect(format!("Failed to get subgraph ID from type: {}", object_type.name).as_str()),
-                        entity: object_type.name.to_owned(),
-                        id: id.to_owned(),
-                    })
-                    .map(|entity| entity.into())
-                    .unwrap_or(q::Value::Null),
-                _ => q::Value::Null,
+                Some(q::Value::String(id)) => {
+                    let key = Self::build_key(object_type, id)?;
+                    Ok(self.store
+                        .lock()
+                        .unwrap()
+                        .get(key)
+                        .map(|entity| entity.into())
+                        .unwrap_or(q::Value::Null))
+                }
+                // Entities that are part of the parent, like those of a change
+                Some(value @ q::Value::Object(_)) => Ok(value.clone()),
+                _ => Ok(q::Value::Null),
             },
             _ => {
-                let mut query = build_query(&object_type, arguments);
+                let mut query = build_query(&object_type, arguments)?;
 
                 // Add matching filter for derived fields
                 Self::add_filter_for_derived_field(
@@ -272,23 +613,197 @@ impl Resolver for StoreResolver {
                     parent,
                     field_definition,
                     object_type,
-                );
+                )?;
 
                 query.range = Some(StoreRange { first: 1, skip: 0 });
 
-                self.store
-                    .lock()
-                    .unwrap()
-                    .find(query)
-                    .map(|entities| {
-                        entities
-                            .into_iter()
-                            .next()
-                            .map(|entity| entity.into())
-                            .unwrap_or(q::Value::Null)
-                    })
+                self.find(query).map(|entities| {
+                    entities
+                        .into_iter()
+                        .next()
+                        .map(|entity| entity.into())
+                        .unwrap_or(q::Value::Null)
+                })
+            }
+        }
+    }
+
+    fn resolve_object_for_parents(
+        &self,
+        parents: &[q::Value],
+        field: &q::Name,
+        field_definition: &s::Field,
+        object_type: &s::ObjectType,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<Vec<q::Value>, QueryExecutionError> {
+        // Entities embedded in their parents, like those before and after a
+        // change, don't need to be fetched
+        let embedded = parents.iter().any(|parent| match parent {
+            q::Value::Object(object) => match object.get(field) {
+                Some(q::Value::Object(_)) => true,
+                _ => false,
+            },
+            _ => false,
+        });
+
+        // Entities selected by ID or other arguments are resolved for each
+        // parent individually, so that the arguments are honored
+        if embedded || !arguments.is_empty() {
+            return parents
+                .iter()
+                .map(|parent| {
+                    self.resolve_object(
+                        &Some(parent.clone()),
+                        field,
+                        field_definition,
+                        object_type,
+                        arguments,
+                    )
+                })
+                .collect();
+        }
+
+        let referenced_ids = parents
+            .iter()
+            .map(|parent| match parent {
+                q::Value::Object(object) => match object.get(field) {
+                    Some(q::Value::String(id)) => Some(id),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let ids = referenced_ids
+            .iter()
+            .filter_map(|id| id.map(|id| Value::from(id)))
+            .collect::<Vec<_>>();
+
+        // Fetch all referenced entities with a single query
+        let entities = if ids.is_empty() {
+            vec![]
+        } else {
+            let mut query = build_query(&object_type, &HashMap::new())?;
+            query.range = None;
+            Self::add_filter(&mut query, StoreFilter::In(String::from("id"), ids));
+            self.find(query)?
+        };
+
+        // Index the entities by their IDs once
+        let entities_by_id: HashMap<&String, &Entity> = entities
+            .iter()
+            .filter_map(|entity| match entity.get("id") {
+                Some(Value::String(id)) => Some((id, entity)),
+                _ => None,
+            })
+            .collect();
+
+        Ok(referenced_ids
+            .into_iter()
+            .map(|id| {
+                id.and_then(|id| entities_by_id.get(id))
+                    .map(|entity| (*entity).clone().into())
                     .unwrap_or(q::Value::Null)
+            })
+            .collect())
+    }
+
+    fn resolve_abstract_objects(
+        &self,
+        schema: &s::Document,
+        parent: &Option<q::Value>,
+        _field: &q::Name,
+        field_definition: &s::Field,
+        abstract_type: &s::TypeDefinition,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let mut entities = vec![];
+        let mut range = None;
+        let mut order_by = None;
+        let mut order_direction = None;
+
+        for object_type in sast::get_possible_types(schema, abstract_type) {
+            let mut query = match Self::build_query_for_objects(
+                parent,
+                field_definition,
+                object_type,
+                arguments,
+            )? {
+                Some(query) => query,
+                None => continue,
+            };
+
+            // The range applies to the combined entities of all possible types,
+            // so fetch enough entities of each type to fill the requested page
+            range = query.range.take();
+            query.range = range.as_ref().map(|range| StoreRange {
+                first: range.first.saturating_add(range.skip),
+                skip: 0,
+            });
+            order_by = query.order_by.clone();
+            order_direction = query.order_direction.clone();
+
+            entities.extend(
+                self.find(query)?
+                    .into_iter()
+                    .map(|entity| (object_type, entity)),
+            );
+        }
+
+        // Order the combined entities
+        if let Some(order_by) = order_by {
+            entities.sort_by(|(_, a), (_, b)| Self::compare_entities(a, b, &order_by));
+            if let Some(StoreOrder::Descending) = order_direction {
+                entities.reverse();
             }
         }
+
+        // Apply the range to the combined entities
+        let (skip, first) = range
+            .map(|range| (range.skip, range.first))
+            .unwrap_or((0, entities.len()));
+
+        Ok(q::Value::List(
+            entities
+                .into_iter()
+                .skip(skip)
+                .take(first)
+                .map(|(object_type, entity)| with_typename(entity.into(), object_type))
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use graph::data::store::scalar;
+    use graph::prelude::{Entity, Value};
+
+    use super::StoreResolver;
+
+    fn entity(value: Value) -> Entity {
+        Entity::from(vec![("value", value)])
+    }
+
+    #[test]
+    fn compares_entities_by_big_integers() {
+        let a = entity(Value::BigInt("-5".parse::<scalar::BigInt>().unwrap()));
+        let b = entity(Value::BigInt("100000000000000000000".parse().unwrap()));
+        let value = String::from("value");
+
+        assert_eq!(StoreResolver::compare_entities(&a, &b, &value), Ordering::Less);
+        assert_eq!(StoreResolver::compare_entities(&b, &a, &value), Ordering::Greater);
+    }
+
+    #[test]
+    fn compares_entities_by_bytes() {
+        let a = entity(Value::Bytes(scalar::Bytes::from(&[0x0a, 0xff][..])));
+        let b = entity(Value::Bytes(scalar::Bytes::from(&[0x0b][..])));
+        let value = String::from("value");
+
+        assert_eq!(StoreResolver::compare_entities(&a, &b, &value), Ordering::Less);
+        assert_eq!(StoreResolver::compare_entities(&a, &a, &value), Ordering::Equal);
     }
 }
@@ -29,6 +29,7 @@ impl MaybeCoercible<ScalarType> for Value {
         match (using_type.name.as_str(), self) {
             ("Boolean", v @ Value::Boolean(_)) => Some(v.clone()),
             ("Float", v @ Value::Float(_)) => Some(v.clone()),
+            ("Float", Value::Int(i)) => i.as_i64().map(|i| Value::Float(i as f64)),
             ("Int", v @ Value::Int(_)) => Some(v.clone()),
             ("String", v @ Value::String(_)) => Some(v.clone()),
             ("ID", v @ Value::String(_)) => Some(v.clone()),
@@ -54,8 +55,8 @@ impl MaybeCoercible<TypeDefinition> for Value {
                 _ => None,
             },
 
-            // Everything else remains unimplemented
-            _ => unimplemented!(),
+            // Values of all other types (objects, interfaces, unions) can't be coerced
+            _ => None,
         }
     }
 }
@@ -72,6 +73,9 @@ where
         // Non-null values may be coercible into non-null types
         (Type::NonNullType(t), _) => coerce_value(value, t, resolver),
 
+        // Null values are valid for all nullable types
+        (_, Value::Null) => Some(Value::Null),
+
         // Resolve named types, then try to coerce the value into the resolved type
         (Type::NamedType(name), _) => resolver(name).and_then(|def| value.coerce(def)),
 
@@ -95,14 +99,14 @@ where
             Some(Value::List(coerced_values))
         },
 
-        // Everything else is unsupported for now
-        _ => unimplemented!(),
+        // Non-list values are coerced into lists with a single item
+        (Type::ListType(t), _) => coerce_value(value, t, resolver).map(|v| Value::List(vec![v])),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use graphql_parser::query::Value;
+    use graphql_parser::query::{Number, Value};
     use graphql_parser::schema::{
         EnumType, EnumValue, InputObjectType, ScalarType, TypeDefinition,
     };
@@ -194,6 +198,12 @@ mod tests {
             Some(Value::Float(-5.879))
         );
 
+        // We can coerce from Value::Int -> TypeDefinition::Scalar(Float)
+        assert_eq!(
+            Value::Int(Number::from(5)).coerce(&float_type),
+            Some(Value::Float(5.0))
+        );
+
         // We don't support going from Value::String -> TypeDefinition::Scalar(Float)
         assert_eq!(Value::String("23.7".to_string()).coerce(&float_type), None,);
         assert_eq!(
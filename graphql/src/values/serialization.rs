@@ -30,7 +30,10 @@ impl<'a> Serialize for SerializableValue<'a> {
                 }
                 map.end()
             }
-            _ => unimplemented!(),
+            Value::Variable(name) => Err(S::Error::custom(format!(
+                "Cannot serialize unresolved variable: ${}",
+                name
+            ))),
         }
     }
 }
@@ -11,7 +11,7 @@ use futures::sync::oneshot;
 use graphql_parser::{query as q, schema as s};
 use std::collections::HashMap;
 
-use graph::prelude::{Query, QueryResult, Schema};
+use graph::prelude::{Query, QueryExecutionError, QueryResult, Schema};
 use graph_graphql::prelude::*;
 
 /// Mock resolver used in tests that don't need a resolver.
@@ -26,8 +26,8 @@ impl Resolver for MockResolver {
         _field_definition: &s::Field,
         _object_type: &s::ObjectType,
         _arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value {
-        q::Value::Null
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::Null)
     }
 
     fn resolve_object(
@@ -37,8 +37,8 @@ impl Resolver for MockResolver {
         _field_definition: &s::Field,
         _object_type: &s::ObjectType,
         _arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value {
-        q::Value::Null
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::Null)
     }
 }
 
@@ -49,8 +49,10 @@ fn mock_schema() -> Schema {
         id: "mock-schema".to_string(),
         document: graphql_parser::parse_schema(
             "
+             scalar Boolean
              scalar String
              scalar ID
+             scalar Int
 
              directive @language(
                language: String = \"English\"
@@ -93,6 +95,17 @@ fn mock_schema() -> Schema {
 /// Builds the expected result for GraphiQL's introspection query that we are
 /// using for testing.
 fn expected_mock_schema_introspection() -> q::Value {
+    let boolean_type = object_value(vec![
+        ("kind", q::Value::Enum("SCALAR".to_string())),
+        ("name", q::Value::String("Boolean".to_string())),
+        ("description", q::Value::Null),
+        ("fields", q::Value::Null),
+        ("inputFields", q::Value::Null),
+        ("enumValues", q::Value::Null),
+        ("interfaces", q::Value::Null),
+        ("possibleTypes", q::Value::Null),
+    ]);
+
     let string_type = object_value(vec![
         ("kind", q::Value::Enum("SCALAR".to_string())),
         ("name", q::Value::String("String".to_string())),
@@ -115,6 +128,17 @@ fn expected_mock_schema_introspection() -> q::Value {
         ("possibleTypes", q::Value::Null),
     ]);
 
+    let int_type = object_value(vec![
+        ("kind", q::Value::Enum("SCALAR".to_string())),
+        ("name", q::Value::String("Int".to_string())),
+        ("description", q::Value::Null),
+        ("fields", q::Value::Null),
+        ("inputFields", q::Value::Null),
+        ("enumValues", q::Value::Null),
+        ("interfaces", q::Value::Null),
+        ("possibleTypes", q::Value::Null),
+    ]);
+
     let role_type = object_value(vec![
         ("kind", q::Value::Enum("ENUM".to_string())),
         ("name", q::Value::String("Role".to_string())),
@@ -430,7 +454,9 @@ fn expected_mock_schema_introspection() -> q::Value {
     ]);
 
     let expected_types = q::Value::List(vec![
+        boolean_type,
         id_type,
+        int_type,
         node_type,
         query_type,
         role_type,
@@ -440,30 +466,188 @@ fn expected_mock_schema_introspection() -> q::Value {
         user_orderby_type,
     ]);
 
-    let expected_directives = q::Value::List(vec![object_value(vec![
-        ("name", q::Value::String("language".to_string())),
-        ("description", q::Value::Null),
-        (
-            "locations",
-            q::Value::List(vec![q::Value::Enum(String::from("FIELD_DEFINITION"))]),
+    // Directives that take a single `if: Boolean!` argument, like `@skip`
+    let conditional_directive = |name: &str, description: &str| {
+        object_value(vec![
+            ("name", q::Value::String(name.to_string())),
+            ("description", q::Value::String(description.to_string())),
+            (
+                "locations",
+                q::Value::List(vec![
+                    q::Value::Enum(String::from("FIELD")),
+                    q::Value::Enum(String::from("FRAGMENT_SPREAD")),
+                    q::Value::Enum(String::from("INLINE_FRAGMENT")),
+                ]),
+            ),
+            (
+                "args",
+                q::Value::List(vec![object_value(vec![
+                    ("name", q::Value::String("if".to_string())),
+                    ("description", q::Value::Null),
+                    ("defaultValue", q::Value::Null),
+                    (
+                        "type",
+                        object_value(vec![
+                            ("kind", q::Value::Enum("NON_NULL".to_string())),
+                            ("name", q::Value::Null),
+                            (
+                                "ofType",
+                                object_value(vec![
+                                    ("kind", q::Value::Enum("SCALAR".to_string())),
+                                    ("name", q::Value::String("Boolean".to_string())),
+                                    ("ofType", q::Value::Null),
+                                ]),
+                            ),
+                        ]),
+                    ),
+                ])]),
+            ),
+        ])
+    };
+
+    // Optional directive arguments of named types
+    let optional_argument = |name: &str, type_value: q::Value, default_value: q::Value| {
+        object_value(vec![
+            ("name", q::Value::String(name.to_string())),
+            ("description", q::Value::Null),
+            ("defaultValue", default_value),
+            ("type", type_value),
+        ])
+    };
+    let if_argument = optional_argument(
+        "if",
+        object_value(vec![
+            ("kind", q::Value::Enum("NON_NULL".to_string())),
+            ("name", q::Value::Null),
+            (
+                "ofType",
+                object_value(vec![
+                    ("kind", q::Value::Enum("SCALAR".to_string())),
+                    ("name", q::Value::String("Boolean".to_string())),
+                    ("ofType", q::Value::Null),
+                ]),
+            ),
+        ]),
+        q::Value::String("true".to_string()),
+    );
+    let label_argument = optional_argument(
+        "label",
+        object_value(vec![
+            ("kind", q::Value::Enum("SCALAR".to_string())),
+            ("name", q::Value::String("String".to_string())),
+            ("ofType", q::Value::Null),
+        ]),
+        q::Value::Null,
+    );
+
+    let expected_directives = q::Value::List(vec![
+        conditional_directive("skip", "Skips a field or fragment if the argument is true."),
+        conditional_directive(
+            "include",
+            "Includes a field or fragment only if the argument is true.",
         ),
-        (
-            "args",
-            q::Value::List(vec![object_value(vec![
-                ("name", q::Value::String("language".to_string())),
-                ("description", q::Value::Null),
-                ("defaultValue", q::Value::String("English".to_string())),
-                (
-                    "type",
+        object_value(vec![
+            ("name", q::Value::String("defer".to_string())),
+            (
+                "description",
+                q::Value::String(
+                    "Delivers a fragment in a later part of the response.".to_string(),
+                ),
+            ),
+            (
+                "locations",
+                q::Value::List(vec![
+                    q::Value::Enum(String::from("FRAGMENT_SPREAD")),
+                    q::Value::Enum(String::from("INLINE_FRAGMENT")),
+                ]),
+            ),
+            (
+                "args",
+                q::Value::List(vec![if_argument.clone(), label_argument.clone()]),
+            ),
+        ]),
+        object_value(vec![
+            ("name", q::Value::String("stream".to_string())),
+            (
+                "description",
+                q::Value::String(
+                    "Delivers the items of a list field incrementally, after the first \
+                     `initialCount`."
+                        .to_string(),
+                ),
+            ),
+            (
+                "locations",
+                q::Value::List(vec![q::Value::Enum(String::from("FIELD"))]),
+            ),
+            (
+                "args",
+                q::Value::List(vec![
+                    if_argument,
+                    label_argument,
+                    optional_argument(
+                        "initialCount",
+                        object_value(vec![
+                            ("kind", q::Value::Enum("SCALAR".to_string())),
+                            ("name", q::Value::String("Int".to_string())),
+                            ("ofType", q::Value::Null),
+                        ]),
+                        q::Value::String("0".to_string()),
+                    ),
+                ]),
+            ),
+        ]),
+        object_value(vec![
+            ("name", q::Value::String("deprecated".to_string())),
+            (
+                "description",
+                q::Value::String("Marks a field or enum value as no longer supported.".to_string()),
+            ),
+            (
+                "locations",
+                q::Value::List(vec![
+                    q::Value::Enum(String::from("FIELD_DEFINITION")),
+                    q::Value::Enum(String::from("ENUM_VALUE")),
+                ]),
+            ),
+            (
+                "args",
+                q::Value::List(vec![optional_argument(
+                    "reason",
                     object_value(vec![
                         ("kind", q::Value::Enum("SCALAR".to_string())),
                         ("name", q::Value::String("String".to_string())),
                         ("ofType", q::Value::Null),
                     ]),
-                ),
-            ])]),
-        ),
-    ])]);
+                    q::Value::String("\"No longer supported\"".to_string()),
+                )]),
+            ),
+        ]),
+        object_value(vec![
+            ("name", q::Value::String("language".to_string())),
+            ("description", q::Value::Null),
+            (
+                "locations",
+                q::Value::List(vec![q::Value::Enum(String::from("FIELD_DEFINITION"))]),
+            ),
+            (
+                "args",
+                q::Value::List(vec![object_value(vec![
+                    ("name", q::Value::String("language".to_string())),
+                    ("description", q::Value::Null),
+                    ("defaultValue", q::Value::String("\"English\"".to_string())),
+                    (
+                        "type",
+                        object_value(vec![
+                            ("kind", q::Value::Enum("SCALAR".to_string())),
+                            ("name", q::Value::String("String".to_string())),
+                            ("ofType", q::Value::Null),
+                        ]),
+                    ),
+                ])]),
+            ),
+        ]),
+    ]);
 
     let schema_type = object_value(vec![
         (
@@ -487,16 +671,17 @@ fn introspection_query(schema: Schema, query: &str) -> QueryResult {
         schema: schema,
         document: graphql_parser::parse_query(query).unwrap(),
         variables: None,
+        operation_name: None,
+        tracing: false,
         result_sender: sender,
+        pending: None,
+        deadline: None,
     };
 
     // Execute it
     execute(
         &query,
-        ExecutionOptions {
-            logger: slog::Logger::root(slog::Discard, o!()),
-            resolver: MockResolver,
-        },
+        ExecutionOptions::new(slog::Logger::root(slog::Discard, o!()), MockResolver),
     )
 }
 
@@ -1149,3 +1334,112 @@ type Parameter {
 
     assert!(result.errors.is_none(), format!("{:#?}", result.errors));
 }
+
+#[test]
+fn introspection_reports_descriptions_and_deprecations() {
+    let schema = Schema {
+        id: "deprecations".to_string(),
+        document: graphql_parser::parse_schema(
+            "
+            scalar Boolean
+            scalar String
+
+            \"The status of a user\"
+            enum Status {
+              active
+              banned @deprecated(reason: \"Use `active`\")
+            }
+
+            type Query {
+              \"The user's name\"
+              name: String
+              nick: String @deprecated
+              status: Status
+            }
+            ",
+        ).unwrap(),
+    };
+
+    let result = introspection_query(
+        schema,
+        "
+        {
+          query: __type(name: \"Query\") {
+            fields { name description isDeprecated deprecationReason }
+            allFields: fields(includeDeprecated: true) {
+              name isDeprecated deprecationReason
+            }
+          }
+          status: __type(name: \"Status\") {
+            description
+            enumValues(includeDeprecated: true) { name isDeprecated deprecationReason }
+          }
+        }
+        ",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+
+    let field = |name: &str, deprecation_reason: Option<&str>| {
+        object_value(vec![
+            ("name", q::Value::String(name.to_string())),
+            ("isDeprecated", q::Value::Boolean(deprecation_reason.is_some())),
+            (
+                "deprecationReason",
+                deprecation_reason.map_or(q::Value::Null, |s| q::Value::String(s.to_string())),
+            ),
+        ])
+    };
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "query",
+                object_value(vec![
+                    (
+                        "fields",
+                        q::Value::List(vec![
+                            object_value(vec![
+                                ("name", q::Value::String("name".to_string())),
+                                ("description", q::Value::String("The user's name".to_string())),
+                                ("isDeprecated", q::Value::Boolean(false)),
+                                ("deprecationReason", q::Value::Null),
+                            ]),
+                            object_value(vec![
+                                ("name", q::Value::String("status".to_string())),
+                                ("description", q::Value::Null),
+                                ("isDeprecated", q::Value::Boolean(false)),
+                                ("deprecationReason", q::Value::Null),
+                            ]),
+                        ]),
+                    ),
+                    (
+                        "allFields",
+                        q::Value::List(vec![
+                            field("name", None),
+                            field("nick", Some("No longer supported")),
+                            field("status", None),
+                        ]),
+                    ),
+                ]),
+            ),
+            (
+                "status",
+                object_value(vec![
+                    (
+                        "description",
+                        q::Value::String("The status of a user".to_string()),
+                    ),
+                    (
+                        "enumValues",
+                        q::Value::List(vec![
+                            field("active", None),
+                            field("banned", Some("Use `active`")),
+                        ]),
+                    ),
+                ]),
+            ),
+        ]))
+    );
+}
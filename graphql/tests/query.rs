@@ -9,13 +9,15 @@ extern crate graph_graphql;
 extern crate graph_node;
 
 use futures::sync::oneshot;
-use graphql_parser::query as q;
+use graphql_parser::{query as q, schema as s};
 use slog::Logger;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use graph::components::store::EventSource;
 use graph::prelude::*;
 use graph_graphql::prelude::*;
+use graph_graphql::schema::ast as sast;
 use graph_node::SubgraphProvider;
 
 fn test_schema() -> Schema {
@@ -23,15 +25,23 @@ fn test_schema() -> Schema {
         id: String::from("test-schema"),
         document: api_schema(&graphql_parser::parse_schema(
             "
-            type Musician {
+            interface Named {
+                id: ID!
+                name: String!
+            }
+
+            union Work = Song | Band
+
+            type Musician implements Named {
                 id: ID!
                 name: String!
                 mainBand: Band
                 bands: [Band!]!
                 writtenSongs: [Song]! @derivedFrom(field: \"writtenBy\")
+                favoriteWork: Work
             }
 
-            type Band {
+            type Band implements Named {
                 id: ID!
                 name: String!
                 members: [Musician!]! @derivedFrom(field: \"bands\")
@@ -42,6 +52,11 @@ fn test_schema() -> Schema {
                 title: String!
                 writtenBy: Musician!
             }
+
+            type Price_change {
+                id: ID!
+                amount: Int!
+            }
             ",
         ).expect("Test schema invalid"))
             .expect(
@@ -54,6 +69,8 @@ fn test_schema() -> Schema {
 #[derive(Clone)]
 struct TestStore {
     entities: Vec<Entity>,
+    /// The number of `find` calls made against the store.
+    find_calls: Arc<Mutex<usize>>,
 }
 
 impl TestStore {
@@ -65,6 +82,7 @@ impl TestStore {
                     ("id", Value::from("m1")),
                     ("name", Value::from("John")),
                     ("mainBand", Value::from("b1")),
+                    ("favoriteWork", Value::from("s3")),
                     (
                         "bands",
                         Value::List(vec![Value::from("b1"), Value::from("b2")]),
@@ -75,6 +93,7 @@ impl TestStore {
                     ("id", Value::from("m2")),
                     ("name", Value::from("Lisa")),
                     ("mainBand", Value::from("b1")),
+                    ("favoriteWork", Value::from("b2")),
                     ("bands", Value::List(vec![Value::from("b1")])),
                 ]),
                 Entity::from(vec![
@@ -128,7 +147,59 @@ impl TestStore {
                     ("title", Value::from("Folk Tune")),
                     ("writtenBy", Value::from("m3")),
                 ]),
+                // A song with missing (non-null) attributes, used to test
+                // field errors
+                Entity::from(vec![
+                    ("__typename", Value::from("Song")),
+                    ("id", Value::from("s5")),
+                ]),
+                Entity::from(vec![
+                    ("__typename", Value::from("Price_change")),
+                    ("id", Value::from("p1")),
+                    ("amount", Value::Int(5)),
+                ]),
+                Entity::from(vec![
+                    ("__typename", Value::from("_Meta_")),
+                    ("id", Value::from("test_subgraph")),
+                    ("subgraph", Value::from("test_subgraph")),
+                    ("blockNumber", Value::Int(42)),
+                    ("hasIndexingErrors", Value::Bool(false)),
+                    ("deployedAt", Value::Int(1_536_000_000)),
+                ]),
             ],
+            find_calls: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// Returns true if an entity matches a filter. We're only supporting the
+    /// filters generated for reference fields, @derivedFrom fields and
+    /// streamed lists here.
+    fn matches_filter(entity: &Entity, filter: &StoreFilter) -> bool {
+        match filter {
+            StoreFilter::Equal(k, v) => entity.get(k) == Some(&v),
+            StoreFilter::GreaterThan(k, Value::String(v)) => match entity.get(k) {
+                Some(Value::String(value)) => value > v,
+                _ => false,
+            },
+            StoreFilter::LessThan(k, Value::String(v)) => match entity.get(k) {
+                Some(Value::String(value)) => value < v,
+                _ => false,
+            },
+            StoreFilter::Contains(k, v) => match entity.get(k) {
+                Some(Value::List(values)) => values.contains(v),
+                _ => false,
+            },
+            StoreFilter::In(k, values) => entity
+                .get(k)
+                .map(|value| values.contains(value))
+                .unwrap_or(false),
+            StoreFilter::Or(filters) => filters
+                .iter()
+                .any(|filter| Self::matches_filter(entity, filter)),
+            StoreFilter::And(filters) => filters
+                .iter()
+                .all(|filter| Self::matches_filter(entity, filter)),
+            _ => unimplemented!(),
         }
     }
 }
@@ -153,68 +224,160 @@ impl BasicStore for TestStore {
     }
 
     fn find(&self, query: StoreQuery) -> Result<Vec<Entity>, ()> {
+        *self.find_calls.lock().unwrap() += 1;
+
         let entity_name = Value::String(query.entity.clone());
 
-        let entities = self.entities
+        let mut entities = self.entities
             .iter()
             .filter(|entity| entity.get("__typename") == Some(&entity_name))
-            // We're only supporting the following filters here to to test
-            // the filters generated for reference fields and @derivedFrom fields:
-            //
-            // - And(Contains(...))
-            // - And(Equal(...))
-            // - And(Or([Equal(...), ...]))
             .filter(|entity| {
                 query
                     .filter
                     .as_ref()
-                    .and_then(|filter| match filter {
-                        StoreFilter::And(filters) => filters.get(0),
-                        _ => None,
-                    })
-                    .map(|filter| match filter {
-                        StoreFilter::Equal(k, v) => entity.get(k) == Some(&v),
-                        StoreFilter::Contains(k, v) => match entity.get(k) {
-                            Some(Value::List(values)) => values.contains(v),
-                            _ => false,
-                        },
-                        StoreFilter::Or(filters) => filters.iter().any(|filter| match filter {
-                            StoreFilter::Equal(k,v) => entity.get(k) == Some(&v),
-                            _ => unimplemented!(),
-                        }),
-                        _ => unimplemented!(),
-                    })
+                    .map(|filter| Self::matches_filter(entity, filter))
                     .unwrap_or(true)
             })
             .map(|entity| entity.clone())
-            .collect();
+            .collect::<Vec<_>>();
+
+        // Only string attributes can be ordered by
+        if let Some(ref attribute) = query.order_by {
+            entities.sort_by_key(|entity| match entity.get(attribute) {
+                Some(Value::String(value)) => value.clone(),
+                _ => String::new(),
+            });
+            if query.order_direction == Some(StoreOrder::Descending) {
+                entities.reverse();
+            }
+        }
+
+        Ok(match query.range {
+            Some(range) => entities
+                .into_iter()
+                .skip(range.skip)
+                .take(range.first)
+                .collect(),
+            None => entities,
+        })
+    }
 
-        Ok(entities)
+    /// Band `b1` was renamed in block 2 and price change `p1` was created in
+    /// block 1; no other entity has changed.
+    fn history(&self, query: StoreHistoryQuery) -> Result<Vec<EntityChange>, ()> {
+        if query.key.entity == "Price_change" && query.key.id == "p1" {
+            return Ok(vec![EntityChange {
+                operation: EntityOperation::Insert,
+                block_hash: None,
+                block_number: Some(1),
+                before: None,
+                after: Some(self.get(query.key).unwrap()),
+            }]);
+        }
+        if query.key.entity != "Band" || query.key.id != "b1" {
+            return Ok(vec![]);
+        }
+
+        let band = |name: &str| {
+            Entity::from(vec![("id", Value::from("b1")), ("name", Value::from(name))])
+        };
+        let changes = vec![
+            EntityChange {
+                operation: EntityOperation::Insert,
+                block_hash: None,
+                block_number: Some(1),
+                before: None,
+                after: Some(band("The Musician")),
+            },
+            EntityChange {
+                operation: EntityOperation::Update,
+                block_hash: None,
+                block_number: Some(2),
+                before: Some(band("The Musician")),
+                after: Some(band("The Musicians")),
+            },
+        ];
+
+        Ok(changes
+            .into_iter()
+            .filter(|change| change.block_number >= query.from_block)
+            .take(query.first.unwrap_or(usize::max_value()))
+            .collect())
     }
 }
 
 fn execute_query(query: q::Document) -> QueryResult {
+    execute_query_with_variables(query, None)
+}
+
+fn execute_query_with_variables(
+    query: q::Document,
+    variables: Option<QueryVariables>,
+) -> QueryResult {
+    execute_query_against_store(query, variables, None, TestStore::new())
+}
+
+fn execute_operation(query: q::Document, operation_name: &str) -> QueryResult {
+    execute_query_against_store(
+        query,
+        None,
+        Some(String::from(operation_name)),
+        TestStore::new(),
+    )
+}
+
+fn execute_query_against_store(
+    query: q::Document,
+    variables: Option<QueryVariables>,
+    operation_name: Option<String>,
+    store: TestStore,
+) -> QueryResult {
     let (sender, _receiver) = oneshot::channel();
 
     let query = Query {
         schema: test_schema(),
         document: query,
-        variables: None,
+        variables,
+        operation_name,
+        tracing: false,
         result_sender: sender,
+        pending: None,
+        deadline: None,
     };
 
     let logger = Logger::root(slog::Discard, o!());
-    let store = Arc::new(Mutex::new(TestStore::new()));
+    let store = Arc::new(Mutex::new(store));
     let store_resolver = StoreResolver::new(&logger, store);
 
-    let options = ExecutionOptions {
-        logger: logger,
-        resolver: store_resolver,
-    };
+    let options = ExecutionOptions::new(logger, store_resolver);
 
     execute(&query, options)
 }
 
+fn execute_query_incrementally(
+    query: q::Document,
+    store: TestStore,
+) -> (QueryResult, Option<SubsequentResults<StoreResolver>>) {
+    let (sender, _receiver) = oneshot::channel();
+
+    let query = Query {
+        schema: test_schema(),
+        document: query,
+        variables: None,
+        operation_name: None,
+        tracing: false,
+        result_sender: sender,
+        pending: None,
+        deadline: None,
+    };
+
+    let logger = Logger::root(slog::Discard, o!());
+    let store = Arc::new(Mutex::new(store));
+    let store_resolver = StoreResolver::new(&logger, store);
+
+    execute_incrementally(&query, ExecutionOptions::new(logger, store_resolver))
+}
+
 #[test]
 fn can_query_one_to_one_relationship() {
     let result = execute_query(
@@ -454,3 +617,1086 @@ fn can_query_many_to_many_relationship() {
         )]))
     );
 }
+
+#[test]
+fn query_variables_are_used() {
+    let mut variables = QueryVariables::new();
+    variables.insert(String::from("id"), QueryVariableValue::from("m2"));
+
+    let result = execute_query_with_variables(
+        graphql_parser::parse_query(
+            "
+            query musician($id: ID!) {
+                musician(id: $id) {
+                    name
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+        Some(variables),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musician",
+            object_value(vec![("name", q::Value::String(String::from("Lisa")))]),
+        )]))
+    );
+}
+
+#[test]
+fn operations_are_selected_by_name() {
+    let document = graphql_parser::parse_query(
+        "
+        query john { musician(id: \"m1\") { name } }
+        query lisa { musician(id: \"m2\") { name } }
+        ",
+    ).expect("Invalid test query");
+
+    let result = execute_operation(document.clone(), "lisa");
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musician",
+            object_value(vec![("name", q::Value::String(String::from("Lisa")))]),
+        )]))
+    );
+
+    let errors = |result: QueryResult| {
+        result
+            .errors
+            .expect("Query without a valid operation name was not rejected")
+            .iter()
+            .map(|e| format!("{}", e))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        errors(execute_query(document.clone())),
+        vec!["Operation name required"]
+    );
+    assert_eq!(
+        errors(execute_operation(document, "tom")),
+        vec!["Operation name not found: tom"]
+    );
+}
+
+#[test]
+fn skip_and_include_directives_are_applied() {
+    let mut variables = QueryVariables::new();
+    variables.insert(String::from("withBands"), QueryVariableValue::from(q::Value::Boolean(false)));
+
+    let result = execute_query_with_variables(
+        graphql_parser::parse_query(
+            "
+            query musician($withBands: Boolean!) {
+                musician(id: \"m1\") {
+                    id @skip(if: true)
+                    name @include(if: true)
+                    bands @include(if: $withBands) { name }
+                    ... on Musician @skip(if: $withBands) {
+                        mainBand { name }
+                    }
+                    ...MusicianFields @include(if: false)
+                }
+            }
+
+            fragment MusicianFields on Musician {
+                writtenSongs { title }
+            }
+            ",
+        ).expect("Invalid test query"),
+        Some(variables),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musician",
+            object_value(vec![
+                ("name", q::Value::String(String::from("John"))),
+                (
+                    "mainBand",
+                    object_value(vec![("name", q::Value::String(String::from("The Musicians")))]),
+                ),
+            ]),
+        )]))
+    );
+}
+
+#[test]
+fn unknown_directives_are_rejected() {
+    let result = execute_query(
+        graphql_parser::parse_query("{ musicians { name @upperCase } }")
+            .expect("Invalid test query"),
+    );
+
+    assert_eq!(result.data, None);
+    assert_eq!(
+        result
+            .errors
+            .expect("Unknown directive was not rejected")
+            .iter()
+            .map(|e| format!("{}", e))
+            .collect::<Vec<_>>(),
+        vec!["Unknown directive \"@upperCase\"."]
+    );
+}
+
+#[test]
+fn relationships_are_resolved_in_batches() {
+    let store = TestStore::new();
+    let find_calls = store.find_calls.clone();
+
+    let result = execute_query_against_store(
+        graphql_parser::parse_query(
+            "
+            query {
+                musicians {
+                    name
+                    mainBand { name }
+                    bands { name }
+                    writtenSongs { title }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+        None,
+        None,
+        store,
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    // One query for the musicians and one for each relationship field,
+    // regardless of the number of musicians
+    assert_eq!(*find_calls.lock().unwrap(), 4);
+}
+
+#[test]
+fn ranges_of_relationships_apply_to_each_parent() {
+    let store = TestStore::new();
+    let find_calls = store.find_calls.clone();
+    let logger = Logger::root(slog::Discard, o!());
+    let resolver = StoreResolver::new(&logger, Arc::new(Mutex::new(store)));
+
+    let schema = test_schema();
+    let object_type = |name: &str| match sast::get_named_type(&schema.document, &name.to_string()) {
+        Some(s::TypeDefinition::Object(t)) => t,
+        _ => panic!("Missing object type: {}", name),
+    };
+    let field_definition = object_type("Musician")
+        .fields
+        .iter()
+        .find(|field| field.name == "writtenSongs")
+        .expect("Missing writtenSongs field");
+
+    let parents = ["m1", "m2", "m3", "m4"]
+        .iter()
+        .map(|id| object_value(vec![("id", q::Value::String(id.to_string()))]))
+        .collect::<Vec<_>>();
+    let first = "first".to_string();
+    let mut arguments = HashMap::new();
+    arguments.insert(&first, q::Value::Int(q::Number::from(1)));
+
+    let songs = resolver
+        .resolve_objects_for_parents(
+            &parents,
+            &field_definition.name,
+            field_definition,
+            object_type("Song"),
+            &arguments,
+        )
+        .expect("Failed to resolve songs");
+
+    let titles = songs
+        .iter()
+        .map(|songs| match songs {
+            q::Value::List(songs) => songs
+                .iter()
+                .map(|song| match song {
+                    q::Value::Object(song) => song["title"].clone(),
+                    _ => panic!("Song is not an object"),
+                })
+                .collect(),
+            _ => panic!("Songs are not a list"),
+        })
+        .collect::<Vec<Vec<_>>>();
+    assert_eq!(
+        titles,
+        vec![
+            vec![q::Value::String(String::from("Cheesy Tune"))],
+            vec![q::Value::String(String::from("Rock Tune"))],
+            vec![q::Value::String(String::from("Folk Tune"))],
+            vec![],
+        ]
+    );
+
+    // The range can't be applied to a single query for all musicians
+    assert_eq!(*find_calls.lock().unwrap(), 4);
+}
+
+#[test]
+fn queries_exceeding_the_limits_are_rejected_before_execution() {
+    let store = TestStore::new();
+    let find_calls = store.find_calls.clone();
+
+    let result = execute_query_against_store(
+        graphql_parser::parse_query("{ musicians(first: 1000000) { name } }")
+            .expect("Invalid test query"),
+        None,
+        None,
+        store,
+    );
+
+    assert_eq!(result.data, None);
+    assert_eq!(
+        result
+            .errors
+            .expect("Query exceeding the limits was not rejected")
+            .iter()
+            .map(|e| format!("{}", e))
+            .collect::<Vec<_>>(),
+        vec!["Value of argument \"first\" (1000000) exceeds the maximum of 1000"]
+    );
+    assert_eq!(*find_calls.lock().unwrap(), 0);
+}
+
+#[test]
+fn subscriptions_return_the_current_data() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            subscription {
+                musician(id: \"m2\") {
+                    name
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musician",
+            object_value(vec![("name", q::Value::String(String::from("Lisa")))]),
+        )]))
+    );
+}
+
+#[test]
+fn query_variables_fall_back_to_default_values() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            query musician($id: ID = \"m3\") {
+                musician(id: $id) {
+                    name
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musician",
+            object_value(vec![("name", q::Value::String(String::from("Tom")))]),
+        )]))
+    );
+}
+
+#[test]
+fn missing_required_query_variables_are_rejected() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            query musician($id: ID!) {
+                musician(id: $id) {
+                    name
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert_eq!(result.data, None);
+    match result.errors.as_ref().map(|errors| errors.as_slice()) {
+        Some([QueryError::ExecutionError(QueryExecutionError::MissingVariableError(_, name))]) => {
+            assert_eq!(name, "id")
+        }
+        _ => panic!("Unexpected errors returned for query: {:#?}", result.errors),
+    }
+}
+
+#[test]
+fn query_variables_are_coerced_into_their_declared_types() {
+    let mut variables = QueryVariables::new();
+    variables.insert(
+        String::from("id"),
+        QueryVariableValue::from(q::Value::Boolean(true)),
+    );
+
+    let result = execute_query_with_variables(
+        graphql_parser::parse_query(
+            "
+            query musician($id: ID!) {
+                musician(id: $id) {
+                    name
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+        Some(variables),
+    );
+
+    assert_eq!(result.data, None);
+    match result.errors.as_ref().map(|errors| errors.as_slice()) {
+        Some([QueryError::ExecutionError(QueryExecutionError::InvalidVariableError(
+            _,
+            name,
+            _,
+        ))]) => assert_eq!(name, "id"),
+        _ => panic!("Unexpected errors returned for query: {:#?}", result.errors),
+    }
+}
+
+#[test]
+fn can_query_interfaces_with_inline_fragments() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            query {
+                nameds(orderBy: name) {
+                    __typename
+                    name
+                    ... on Musician {
+                        mainBand {
+                            name
+                        }
+                    }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    let musician = |name: &str, band: Option<&str>| {
+        object_value(vec![
+            ("__typename", q::Value::String(String::from("Musician"))),
+            ("name", q::Value::String(String::from(name))),
+            (
+                "mainBand",
+                band.map(|band| object_value(vec![("name", q::Value::String(String::from(band)))]))
+                    .unwrap_or(q::Value::Null),
+            ),
+        ])
+    };
+    let band = |name: &str| {
+        object_value(vec![
+            ("__typename", q::Value::String(String::from("Band"))),
+            ("name", q::Value::String(String::from(name))),
+        ])
+    };
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "nameds",
+            q::Value::List(vec![
+                musician("John", Some("The Musicians")),
+                musician("Lisa", Some("The Musicians")),
+                band("The Amateurs"),
+                band("The Musicians"),
+                musician("Tom", Some("The Amateurs")),
+                musician("Valerie", None),
+            ]),
+        )]))
+    );
+}
+
+#[test]
+fn can_query_unions_with_inline_fragments() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            query {
+                musicians {
+                    name
+                    favoriteWork {
+                        __typename
+                        ... on Song {
+                            title
+                        }
+                        ... on Band {
+                            name
+                        }
+                    }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![
+                object_value(vec![
+                    ("name", q::Value::String(String::from("John"))),
+                    (
+                        "favoriteWork",
+                        object_value(vec![
+                            ("__typename", q::Value::String(String::from("Song"))),
+                            ("title", q::Value::String(String::from("Pop Tune"))),
+                        ]),
+                    ),
+                ]),
+                object_value(vec![
+                    ("name", q::Value::String(String::from("Lisa"))),
+                    (
+                        "favoriteWork",
+                        object_value(vec![
+                            ("__typename", q::Value::String(String::from("Band"))),
+                            ("name", q::Value::String(String::from("The Amateurs"))),
+                        ]),
+                    ),
+                ]),
+                object_value(vec![
+                    ("name", q::Value::String(String::from("Tom"))),
+                    ("favoriteWork", q::Value::Null),
+                ]),
+                object_value(vec![
+                    ("name", q::Value::String(String::from("Valerie"))),
+                    ("favoriteWork", q::Value::Null),
+                ]),
+            ]),
+        )]))
+    );
+}
+
+#[test]
+fn invalid_queries_are_rejected_before_execution() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            query {
+                musicians {
+                    name
+                    age
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert_eq!(result.data, None);
+    match result.errors.as_ref().map(|errors| errors.as_slice()) {
+        Some([QueryError::ExecutionError(QueryExecutionError::ValidationError(pos, message))]) => {
+            assert_eq!(pos, &graphql_parser::Pos { line: 5, column: 21 });
+            assert_eq!(message, "Cannot query field \"age\" on type \"Musician\".");
+        }
+        _ => panic!("Unexpected errors returned for query: {:#?}", result.errors),
+    }
+}
+
+#[test]
+fn field_errors_null_out_the_nearest_nullable_parent() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            query {
+                song(id: \"s5\") {
+                    id
+                    title
+                }
+                band(id: \"b1\") {
+                    name
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            ("band", object_value(vec![
+                ("name", q::Value::String(String::from("The Musicians"))),
+            ])),
+            ("song", q::Value::Null),
+        ]))
+    );
+
+    match result.errors.as_ref().map(|errors| errors.as_slice()) {
+        Some([QueryError::FieldError(QueryExecutionError::NonNullError(_, name), path, _)]) => {
+            assert_eq!(name, "title");
+            assert_eq!(
+                path,
+                &vec![
+                    PathSegment::Field(String::from("song")),
+                    PathSegment::Field(String::from("title")),
+                ]
+            );
+        }
+        _ => panic!("Unexpected errors returned for query: {:#?}", result.errors),
+    }
+}
+
+#[test]
+fn field_errors_propagate_through_non_null_lists() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            query {
+                songs {
+                    title
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    // `songs` is a non-null list of non-null songs, so the error nulls out
+    // the entire result
+    assert_eq!(result.data, None);
+
+    match result.errors.as_ref().map(|errors| errors.as_slice()) {
+        Some([QueryError::FieldError(QueryExecutionError::NonNullError(_, name), path, _)]) => {
+            assert_eq!(name, "title");
+            assert_eq!(
+                path,
+                &vec![
+                    PathSegment::Field(String::from("songs")),
+                    PathSegment::Index(4),
+                    PathSegment::Field(String::from("title")),
+                ]
+            );
+        }
+        _ => panic!("Unexpected errors returned for query: {:#?}", result.errors),
+    }
+}
+
+#[test]
+fn deferred_fragments_are_delivered_in_subsequent_results() {
+    let query = graphql_parser::parse_query(
+        "
+        query {
+            musician(id: \"m1\") {
+                id
+                ... @defer(label: \"details\") {
+                    name
+                }
+            }
+        }
+        ",
+    ).expect("Invalid test query");
+
+    let (result, subsequent_results) = execute_query_incrementally(query, TestStore::new());
+
+    assert!(result.errors.is_none());
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musician",
+            object_value(vec![("id", q::Value::String(String::from("m1")))]),
+        )]))
+    );
+
+    let subsequent_results: Vec<_> = subsequent_results
+        .expect("Deferred fragment was not deferred")
+        .collect();
+    assert_eq!(subsequent_results.len(), 1);
+    assert_eq!(
+        subsequent_results[0].data,
+        Some(object_value(vec![(
+            "name",
+            q::Value::String(String::from("John")),
+        )]))
+    );
+    assert_eq!(
+        subsequent_results[0].path,
+        vec![PathSegment::Field(String::from("musician"))]
+    );
+    assert_eq!(subsequent_results[0].label, Some(String::from("details")));
+    assert!(!subsequent_results[0].has_next);
+}
+
+#[test]
+fn streamed_lists_are_fetched_from_the_store_page_by_page() {
+    let query = graphql_parser::parse_query(
+        "
+        query {
+            musicians @stream(initialCount: 1) {
+                name
+            }
+        }
+        ",
+    ).expect("Invalid test query");
+    let store = TestStore::new();
+    let find_calls = store.find_calls.clone();
+
+    let (result, subsequent_results) = execute_query_incrementally(query, store);
+
+    assert!(result.errors.is_none());
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![object_value(vec![(
+                "name",
+                q::Value::String(String::from("John")),
+            )])]),
+        )]))
+    );
+
+    // The remaining musicians are only fetched once they are requested
+    assert_eq!(*find_calls.lock().unwrap(), 1);
+
+    let subsequent_results: Vec<_> = subsequent_results
+        .expect("Streamed list was not streamed")
+        .collect();
+    assert_eq!(*find_calls.lock().unwrap(), 2);
+    assert_eq!(subsequent_results.len(), 1);
+    assert_eq!(
+        subsequent_results[0].items,
+        Some(
+            ["Lisa", "Tom", "Valerie"]
+                .iter()
+                .map(|name| object_value(vec![("name", q::Value::String(name.to_string()))]))
+                .collect()
+        )
+    );
+    assert_eq!(
+        subsequent_results[0].path,
+        vec![
+            PathSegment::Field(String::from("musicians")),
+            PathSegment::Index(1),
+        ]
+    );
+    assert!(!subsequent_results[0].has_next);
+}
+
+#[test]
+fn streamed_lists_in_descending_order_are_paged_by_id() {
+    let query = graphql_parser::parse_query(
+        "
+        query {
+            musicians(orderDirection: desc) @stream(initialCount: 1) {
+                name
+            }
+        }
+        ",
+    ).expect("Invalid test query");
+    let store = TestStore::new();
+    let find_calls = store.find_calls.clone();
+
+    let (result, subsequent_results) = execute_query_incrementally(query, store);
+
+    assert!(result.errors.is_none());
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![object_value(vec![(
+                "name",
+                q::Value::String(String::from("Valerie")),
+            )])]),
+        )]))
+    );
+
+    // The next page continues below the ID of the last musician
+    let subsequent_results: Vec<_> = subsequent_results
+        .expect("Streamed list was not streamed")
+        .collect();
+    assert_eq!(*find_calls.lock().unwrap(), 2);
+    assert_eq!(subsequent_results.len(), 1);
+    assert_eq!(
+        subsequent_results[0].items,
+        Some(
+            ["Tom", "Lisa", "John"]
+                .iter()
+                .map(|name| object_value(vec![("name", q::Value::String(name.to_string()))]))
+                .collect()
+        )
+    );
+}
+
+#[test]
+fn streamed_lists_not_ordered_by_id_are_fetched_at_once() {
+    let query = graphql_parser::parse_query(
+        "
+        query {
+            musicians(orderBy: name, orderDirection: desc) @stream(initialCount: 1) {
+                name
+            }
+        }
+        ",
+    ).expect("Invalid test query");
+    let store = TestStore::new();
+    let find_calls = store.find_calls.clone();
+
+    let (result, subsequent_results) = execute_query_incrementally(query, store);
+
+    assert!(result.errors.is_none());
+    assert_eq!(*find_calls.lock().unwrap(), 1);
+
+    // The remaining musicians are streamed without fetching them again
+    let subsequent_results: Vec<_> = subsequent_results
+        .expect("Streamed list was not streamed")
+        .collect();
+    assert_eq!(*find_calls.lock().unwrap(), 1);
+    assert_eq!(subsequent_results.len(), 1);
+    assert_eq!(
+        subsequent_results[0].items,
+        Some(
+            ["Tom", "Lisa", "John"]
+                .iter()
+                .map(|name| object_value(vec![("name", q::Value::String(name.to_string()))]))
+                .collect()
+        )
+    );
+}
+
+#[test]
+fn streamed_lists_with_huge_initial_counts_are_returned_at_once() {
+    let query = graphql_parser::parse_query(
+        "
+        query {
+            musicians @stream(initialCount: 4294967297) {
+                name
+            }
+        }
+        ",
+    ).expect("Invalid test query");
+
+    let (result, subsequent_results) = execute_query_incrementally(query, TestStore::new());
+
+    assert!(result.errors.is_none());
+    assert!(subsequent_results.is_none());
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(
+                ["John", "Lisa", "Tom", "Valerie"]
+                    .iter()
+                    .map(|name| object_value(vec![("name", q::Value::String(name.to_string()))]))
+                    .collect(),
+            ),
+        )]))
+    );
+}
+
+#[test]
+fn defer_and_stream_are_ignored_without_incremental_delivery() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            query {
+                musicians(first: 2) @stream(initialCount: 0) {
+                    ... @defer {
+                        name
+                    }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(result.errors.is_none());
+    assert!(result.subsequent_results.is_none());
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(
+                ["John", "Lisa"]
+                    .iter()
+                    .map(|name| object_value(vec![("name", q::Value::String(name.to_string()))]))
+                    .collect()
+            ),
+        )]))
+    );
+}
+
+#[test]
+fn traced_queries_record_resolvers_and_store_queries() {
+    let (sender, _receiver) = oneshot::channel();
+    let query = Query {
+        schema: test_schema(),
+        document: graphql_parser::parse_query("query { musicians(first: 2) { name } }")
+            .expect("Invalid test query"),
+        variables: None,
+        operation_name: None,
+        tracing: true,
+        result_sender: sender,
+        pending: None,
+        deadline: None,
+    };
+
+    let logger = Logger::root(slog::Discard, o!());
+    let store = Arc::new(Mutex::new(TestStore::new()));
+    let tracer = QueryTracer::new();
+    let mut options = ExecutionOptions::new(
+        logger.clone(),
+        StoreResolver::new(&logger, store).with_tracer(tracer.clone()),
+    );
+    options.tracer = Some(tracer);
+
+    let result = execute(&query, options);
+    assert!(result.errors.is_none());
+
+    let trace = result.trace.expect("Traced query has no trace");
+    assert!(trace.end_time >= trace.start_time);
+
+    let resolvers = trace
+        .resolvers
+        .iter()
+        .map(|resolver| {
+            (
+                resolver.path.clone(),
+                resolver.parent_type.as_str(),
+                resolver.field_name.as_str(),
+                resolver.return_type.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        resolvers,
+        vec![
+            (
+                vec![
+                    PathSegment::Field(String::from("musicians")),
+                    PathSegment::Index(0),
+                    PathSegment::Field(String::from("name")),
+                ],
+                "Musician",
+                "name",
+                "String!",
+            ),
+            (
+                vec![
+                    PathSegment::Field(String::from("musicians")),
+                    PathSegment::Index(1),
+                    PathSegment::Field(String::from("name")),
+                ],
+                "Musician",
+                "name",
+                "String!",
+            ),
+            (
+                vec![PathSegment::Field(String::from("musicians"))],
+                "Query",
+                "musicians",
+                "[Musician!]!",
+            ),
+        ]
+    );
+    assert!(
+        trace
+            .resolvers
+            .iter()
+            .all(|resolver| resolver.start_offset + resolver.duration <= trace.duration)
+    );
+
+    assert_eq!(trace.store_queries.len(), 1);
+    assert_eq!(trace.store_queries[0].entity, "Musician");
+    assert_eq!(trace.store_queries[0].rows, Some(2));
+    assert_eq!(trace.store_queries[0].sql, None);
+}
+
+#[test]
+fn untraced_queries_have_no_trace() {
+    let result = execute_query(
+        graphql_parser::parse_query("query { musicians { name } }").expect("Invalid test query"),
+    );
+    assert!(result.trace.is_none());
+}
+
+#[test]
+fn meta_field_returns_the_indexing_status() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "query { _meta { subgraph blockNumber hasIndexingErrors deployedAt } }",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "_meta",
+            object_value(vec![
+                ("subgraph", q::Value::String(String::from("test_subgraph"))),
+                ("blockNumber", q::Value::Int(q::Number::from(42))),
+                ("hasIndexingErrors", q::Value::Boolean(false)),
+                ("deployedAt", q::Value::Int(q::Number::from(1_536_000_000))),
+            ]),
+        )]))
+    );
+}
+
+#[test]
+fn history_fields_return_the_changes_of_an_entity() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            query {
+                bandHistory(id: \"b1\") {
+                    operation
+                    blockNumber
+                    before { name }
+                    after { name }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "bandHistory",
+            q::Value::List(vec![
+                object_value(vec![
+                    ("operation", q::Value::Enum(String::from("insert"))),
+                    ("blockNumber", q::Value::Int(q::Number::from(1))),
+                    ("before", q::Value::Null),
+                    (
+                        "after",
+                        object_value(vec![(
+                            "name",
+                            q::Value::String(String::from("The Musician")),
+                        )]),
+                    ),
+                ]),
+                object_value(vec![
+                    ("operation", q::Value::Enum(String::from("update"))),
+                    ("blockNumber", q::Value::Int(q::Number::from(2))),
+                    (
+                        "before",
+                        object_value(vec![(
+                            "name",
+                            q::Value::String(String::from("The Musician")),
+                        )]),
+                    ),
+                    (
+                        "after",
+                        object_value(vec![(
+                            "name",
+                            q::Value::String(String::from("The Musicians")),
+                        )]),
+                    ),
+                ]),
+            ]),
+        )]))
+    );
+
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "query { bandHistory(id: \"b1\", fromBlock: 2) { operation blockNumber } }",
+        ).expect("Invalid test query"),
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "bandHistory",
+            q::Value::List(vec![object_value(vec![
+                ("operation", q::Value::Enum(String::from("update"))),
+                ("blockNumber", q::Value::Int(q::Number::from(2))),
+            ])]),
+        )]))
+    );
+}
+
+#[test]
+fn entity_types_named_like_change_types_are_not_mistaken_for_history() {
+    let result = execute_query(
+        graphql_parser::parse_query(
+            "
+            query {
+                priceChanges { id amount }
+                priceChangeHistory(id: \"p1\") {
+                    operation
+                    after { amount }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "priceChanges",
+                q::Value::List(vec![object_value(vec![
+                    ("id", q::Value::String(String::from("p1"))),
+                    ("amount", q::Value::Int(q::Number::from(5))),
+                ])]),
+            ),
+            (
+                "priceChangeHistory",
+                q::Value::List(vec![object_value(vec![
+                    ("operation", q::Value::Enum(String::from("insert"))),
+                    (
+                        "after",
+                        object_value(vec![("amount", q::Value::Int(q::Number::from(5)))]),
+                    ),
+                ])]),
+            ),
+        ]))
+    );
+}
@@ -12,6 +12,7 @@ use graph::prelude::*;
 pub struct MockQueryRunner<S> {
     logger: slog::Logger,
     query_sink: Sender<Query>,
+    store_event_sink: Sender<StoreEvent>,
     _store: Arc<S>,
     runtime: Handle,
 }
@@ -23,13 +24,16 @@ where
     /// Creates a new mock `QueryRunner`.
     pub fn new(logger: &slog::Logger, runtime: Handle, store: S) -> Self {
         let (sink, stream) = channel(100);
+        let (store_event_sink, store_event_stream) = channel(100);
         let runner = MockQueryRunner {
             logger: logger.new(o!("component" => "MockQueryRunner")),
             query_sink: sink,
+            store_event_sink,
             _store: Arc::new(store),
             runtime,
         };
         runner.run_queries(stream);
+        runner.handle_store_events(store_event_stream);
         runner
     }
 
@@ -61,10 +65,24 @@ where
             Ok(())
         }));
     }
+
+    /// Spawns a Tokio task to log any store events received through the given stream.
+    fn handle_store_events(&self, stream: Receiver<StoreEvent>) {
+        let logger = self.logger.clone();
+
+        self.runtime.spawn(stream.for_each(move |event| {
+            info!(logger, "Received store event"; "event" => format!("{:?}", event));
+            Ok(())
+        }));
+    }
 }
 
 impl<S> QueryRunner for MockQueryRunner<S> {
     fn query_sink(&mut self) -> Sender<Query> {
         self.query_sink.clone()
     }
+
+    fn store_event_sink(&mut self) -> Sender<StoreEvent> {
+        self.store_event_sink.clone()
+    }
 }
@@ -78,7 +78,7 @@ impl MockSchemaProvider {
             // Mock processing the event from the subgraph provider
             match event {
                 SchemaEvent::SchemaAdded(schema) | SchemaEvent::SchemaRemoved(schema) => {
-                    SchemaProviderEvent::SchemaChanged(Some(schema))
+                    SchemaProviderEvent::SchemaChanged(Some(schema.clone()), vec![schema])
                 }
             }
         })
@@ -73,7 +73,7 @@ impl MockGraphQLServer {
 
         tokio::spawn(stream.for_each(move |event| {
             info!(logger, "Received schema provider event"; "event" => format!("{:?}", event));
-            let SchemaProviderEvent::SchemaChanged(new_schema) = event;
+            let SchemaProviderEvent::SchemaChanged(new_schema, _) = event;
 
             let mut schema = schema.lock().unwrap();
             *schema = new_schema;
@@ -132,7 +132,11 @@ impl GraphQLServer for MockGraphQLServer {
                         schema: schema.clone().unwrap(),
                         document: graphql_parser::parse_query("{ allUsers { name }}").unwrap(),
                         variables: None,
+                        operation_name: None,
+                        tracing: false,
                         result_sender: sink,
+                        pending: None,
+                        deadline: None,
                     },
                 )
             })
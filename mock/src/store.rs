@@ -12,7 +12,6 @@ pub struct MockStore {
     logger: slog::Logger,
     event_sink: Option<Sender<StoreEvent>>,
     schema_provider_event_sink: Sender<SchemaProviderEvent>,
-    runtime: Handle,
     entities: Vec<Entity>,
 }
 
@@ -32,25 +31,28 @@ impl MockStore {
         }
 
         // Create a new mock store
-        let mut store = MockStore {
+        let store = MockStore {
             logger: logger.new(o!("component" => "MockStore")),
             event_sink: None,
             schema_provider_event_sink: sink,
-            runtime,
             entities,
         };
 
         // Spawn a task that handles incoming schema provider events
-        store.handle_schema_provider_events(stream);
+        store.handle_schema_provider_events(&runtime, stream);
 
         // Return the new store
         store
     }
 
     /// Handles incoming schema provider events.
-    fn handle_schema_provider_events(&mut self, stream: Receiver<SchemaProviderEvent>) {
+    fn handle_schema_provider_events(
+        &self,
+        runtime: &Handle,
+        stream: Receiver<SchemaProviderEvent>,
+    ) {
         let logger = self.logger.clone();
-        self.runtime.spawn(stream.for_each(move |event| {
+        runtime.spawn(stream.for_each(move |event| {
             info!(logger, "Received schema provider event: {:?}", event);
             Ok(())
         }));
@@ -100,6 +102,10 @@ impl BasicStore for MockStore {
     fn find(&self, _query: StoreQuery) -> Result<Vec<Entity>, ()> {
         Ok(self.entities.clone())
     }
+
+    fn history(&self, _query: StoreHistoryQuery) -> Result<Vec<EntityChange>, ()> {
+        Ok(vec![])
+    }
 }
 
 impl Store for MockStore {
@@ -141,6 +147,10 @@ impl BasicStore for FakeStore {
     fn find(&self, _: StoreQuery) -> Result<Vec<Entity>, ()> {
         panic!("called FakeStore")
     }
+
+    fn history(&self, _: StoreHistoryQuery) -> Result<Vec<EntityChange>, ()> {
+        panic!("called FakeStore")
+    }
 }
 
 impl Store for FakeStore {
@@ -9,6 +9,7 @@ extern crate slog;
 extern crate graph;
 extern crate graph_core;
 extern crate graph_datasource_ethereum;
+extern crate graph_graphql;
 extern crate graph_mock;
 extern crate graph_runtime_wasm;
 extern crate graph_server_http;
@@ -16,25 +17,32 @@ extern crate graph_store_postgres;
 extern crate ipfs_api;
 extern crate tokio;
 extern crate tokio_core;
+extern crate tokio_signal;
 
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use ipfs_api::IpfsClient;
 use sentry::integrations::panic::register_panic_handler;
 use std::env;
+use std::fs;
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::prelude::*;
 use tokio_core::reactor::Core;
 
 use graph::components::forward;
 use graph::components::EventProducer;
+use graph::data::subgraph::SchemaData;
 use graph::prelude::*;
 use graph::util::log::logger;
 use graph_datasource_ethereum::Transport;
+use graph_graphql::prelude::{api_schema, print_schema};
 use graph_node::SubgraphProvider as IpfsSubgraphProvider;
 use graph_runtime_wasm::RuntimeHostBuilder as WASMRuntimeHostBuilder;
-use graph_server_http::GraphQLServer as HyperGraphQLServer;
+use graph_server_http::{GraphQLServer as HyperGraphQLServer, GraphQLServerConfig, PersistedQueries};
 use graph_store_postgres::{Store as DieselStore, StoreConfig};
 
 fn main() {
@@ -47,6 +55,17 @@ fn main() {
         .version("0.1.0")
         .author("Graph Protocol, Inc.")
         .about("Scalable queries for a decentralized future")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(
+            SubCommand::with_name("schema")
+                .about("Prints the API schema of a subgraph as SDL, without connecting to anything")
+                .arg(
+                    Arg::with_name("manifest")
+                        .required(true)
+                        .value_name("MANIFEST")
+                        .help("Subgraph manifest, whose schema file is resolved relative to it"),
+                ),
+        )
         .arg(
             Arg::with_name("subgraph")
                 .takes_value(true)
@@ -98,8 +117,123 @@ fn main() {
                 .value_name("HOST:PORT")
                 .help("HTTP address of an IPFS node"),
         )
+        .arg(
+            Arg::with_name("persisted-queries")
+                .takes_value(true)
+                .long("persisted-queries")
+                .value_name("FILE")
+                .help("JSON file mapping SHA-256 hashes to the only queries that may be run"),
+        )
+        .arg(
+            Arg::with_name("http-addr")
+                .takes_value(true)
+                .long("http-addr")
+                .value_name("IP:PORT")
+                .default_value("0.0.0.0:8000")
+                .help("Address to serve GraphQL over HTTP on"),
+        )
+        .arg(
+            Arg::with_name("ws-addr")
+                .takes_value(true)
+                .long("ws-addr")
+                .value_name("IP:PORT")
+                .default_value("0.0.0.0:8001")
+                .help("Address to serve GraphQL subscriptions over WebSockets on"),
+        )
+        .arg(
+            Arg::with_name("max-request-body-size")
+                .takes_value(true)
+                .long("max-request-body-size")
+                .value_name("BYTES")
+                .default_value("1048576")
+                .help("Largest GraphQL request body that is accepted"),
+        )
+        .arg(
+            Arg::with_name("query-timeout")
+                .takes_value(true)
+                .long("query-timeout")
+                .value_name("SECONDS")
+                .default_value("60")
+                .help("Time after which queries fail with a timeout error"),
+        )
+        .arg(
+            Arg::with_name("max-queued-queries")
+                .takes_value(true)
+                .long("max-queued-queries")
+                .value_name("COUNT")
+                .default_value("100")
+                .help("Number of pending queries above which queries are rejected"),
+        )
+        .arg(
+            Arg::with_name("max-batch-size")
+                .takes_value(true)
+                .long("max-batch-size")
+                .value_name("COUNT")
+                .default_value("10")
+                .help("Largest number of operations in a batched GraphQL request"),
+        )
+        .arg(
+            Arg::with_name("query-cache-ttl")
+                .takes_value(true)
+                .long("query-cache-ttl")
+                .value_name("SECONDS")
+                .default_value("300")
+                .help("Time for which query results are cached at most, 0 disables the cache"),
+        )
+        .arg(
+            Arg::with_name("query-cache-size")
+                .takes_value(true)
+                .long("query-cache-size")
+                .value_name("BYTES")
+                .default_value("67108864")
+                .help("Estimated size of all cached query results, 0 disables the cache"),
+        )
+        .arg(
+            Arg::with_name("max-query-depth")
+                .takes_value(true)
+                .long("max-query-depth")
+                .value_name("DEPTH")
+                .default_value("10")
+                .help("Deepest nesting of fields in a query, 0 disables the limit"),
+        )
+        .arg(
+            Arg::with_name("max-query-complexity")
+                .takes_value(true)
+                .long("max-query-complexity")
+                .value_name("COUNT")
+                .default_value("10000000")
+                .help("Number of values a query may resolve, 0 disables the limit"),
+        )
+        .arg(
+            Arg::with_name("max-query-entities")
+                .takes_value(true)
+                .long("max-query-entities")
+                .value_name("COUNT")
+                .default_value("2000000")
+                .help("Number of entities a query may resolve, 0 disables the limit"),
+        )
+        .arg(
+            Arg::with_name("max-first")
+                .takes_value(true)
+                .long("max-first")
+                .value_name("COUNT")
+                .default_value("1000")
+                .help("Largest value of `first` arguments, 0 disables the limit"),
+        )
+        .arg(
+            Arg::with_name("max-skip")
+                .takes_value(true)
+                .long("max-skip")
+                .value_name("COUNT")
+                .default_value("5000")
+                .help("Largest value of `skip` arguments, 0 disables the limit"),
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("schema") {
+        return print_api_schema(matches);
+    }
+
     // Safe to unwrap because a value is required by CLI
     let postgres_url = matches.value_of("postgres-url").unwrap().to_string();
 
@@ -114,6 +248,36 @@ fn main() {
     let ipfs_socket_addr = SocketAddr::from_str(matches.value_of("ipfs").unwrap())
         .expect("could not parse IPFS address, expected format is host:port");
 
+    // Safe to unwrap because all server settings have defaults
+    let server_config = GraphQLServerConfig {
+        http_addr: SocketAddr::from_str(matches.value_of("http-addr").unwrap())
+            .expect("could not parse HTTP address, expected format is ip:port"),
+        ws_addr: SocketAddr::from_str(matches.value_of("ws-addr").unwrap())
+            .expect("could not parse WebSocket address, expected format is ip:port"),
+        max_body_size: matches
+            .value_of("max-request-body-size")
+            .unwrap()
+            .parse()
+            .expect("could not parse maximum request body size"),
+        query_timeout: Duration::from_secs(
+            matches
+                .value_of("query-timeout")
+                .unwrap()
+                .parse()
+                .expect("could not parse query timeout"),
+        ),
+        max_queued_queries: matches
+            .value_of("max-queued-queries")
+            .unwrap()
+            .parse()
+            .expect("could not parse maximum number of queued queries"),
+        max_batch_size: matches
+            .value_of("max-batch-size")
+            .unwrap()
+            .parse()
+            .expect("could not parse maximum batch size"),
+    };
+
     debug!(logger, "Setting up Sentry");
 
     // Set up Sentry, with release tracking and panic handling;
@@ -143,11 +307,16 @@ fn main() {
         runtime,
         &format!("/ipfs/{}", subgraph_hash.clone()),
         &resolver,
-    )).expect("Failed to initialize subgraph provider");
+    )).unwrap_or_else(|e| panic!("Failed to initialize subgraph provider: {}", e));
     let mut schema_provider = graph_core::SchemaProvider::new(&logger, core.handle());
     let store = DieselStore::new(StoreConfig { url: postgres_url }, &logger, core.handle());
     let protected_store = Arc::new(Mutex::new(store));
-    let mut graphql_server = HyperGraphQLServer::new(&logger, core.handle());
+    let mut graphql_server = HyperGraphQLServer::new(&logger, core.handle(), server_config);
+    if let Some(path) = matches.value_of("persisted-queries") {
+        let persisted_queries = PersistedQueries::from_file(Path::new(path))
+            .expect("Failed to load persisted queries");
+        graphql_server.set_persisted_queries(persisted_queries);
+    }
 
     // Create Ethereum adapter
     let (_transport_event_loop, transport) = ethereum_ipc
@@ -197,21 +366,51 @@ fn main() {
             .and_then(|_| Ok(()))
     });
 
-    // Forward store events to the GraphQL server
+    // Create a query runner that runs queries against the store
+    let query_runner_config = graph_core::QueryRunnerConfig {
+        cache_ttl: Duration::from_secs(
+            matches
+                .value_of("query-cache-ttl")
+                .unwrap()
+                .parse()
+                .expect("could not parse query cache TTL"),
+        ),
+        cache_max_size: matches
+            .value_of("query-cache-size")
+            .unwrap()
+            .parse()
+            .expect("could not parse query cache size"),
+        max_depth: query_limit(&matches, "max-query-depth"),
+        max_complexity: query_limit(&matches, "max-query-complexity"),
+        max_first: query_limit(&matches, "max-first"),
+        max_skip: query_limit(&matches, "max-skip"),
+        max_entities: query_limit(&matches, "max-query-entities"),
+    };
+    let mut query_runner = graph_core::QueryRunner::new(
+        &logger,
+        core.handle(),
+        protected_store.clone(),
+        query_runner_config,
+    );
+
+    // Forward store events to the GraphQL server and the query runner
     {
         let store_stream = protected_store.lock().unwrap().event_stream().unwrap();
         core.handle().spawn({
             store_stream
-                .forward(graphql_server.store_event_sink().sink_map_err(|e| {
-                    panic!("Failed to send store event to the GraphQL server: {:?}", e);
-                }))
+                .forward(
+                    graphql_server
+                        .store_event_sink()
+                        .fanout(query_runner.store_event_sink())
+                        .sink_map_err(|e| {
+                            panic!("Failed to send store event to server and runner: {:?}", e);
+                        }),
+                )
                 .and_then(|_| Ok(()))
         });
     }
 
     // Forward incoming queries from the GraphQL server to the query runner
-    let mut query_runner =
-        graph_core::QueryRunner::new(&logger, core.handle(), protected_store.clone());
     let query_stream = graphql_server.query_stream().unwrap();
     core.handle().spawn({
         query_stream
@@ -221,9 +420,63 @@ fn main() {
             .and_then(|_| Ok(()))
     });
 
-    // Serve GraphQL server over HTTP
+    // Serve GraphQL server over HTTP until interrupted, then let in-flight
+    // requests finish
+    let shutdown = tokio_signal::ctrl_c()
+        .flatten_stream()
+        .into_future()
+        .map(|_| ())
+        .map_err(|_| ());
     let http_server = graphql_server
-        .serve()
+        .serve_until(shutdown)
         .expect("Failed to start GraphQL server");
     core.run(http_server).unwrap();
+    info!(logger, "Shut down");
+}
+
+/// Prints the API schema of the subgraph manifest passed to the `schema`
+/// subcommand.
+fn print_api_schema(matches: &ArgMatches) {
+    // Safe to unwrap because a value is required by CLI
+    let manifest_path = PathBuf::from(matches.value_of("manifest").unwrap());
+    let manifest = fs::read_to_string(&manifest_path).expect("Failed to read subgraph manifest");
+
+    let schema = SchemaData::from_manifest(&manifest)
+        .expect("Failed to parse subgraph manifest")
+        .resolve(&FileLinkResolver::new(
+            manifest_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+        ))
+        .wait()
+        .expect("Failed to load subgraph schema");
+
+    if let Err(errors) = schema.validate() {
+        for error in errors {
+            eprintln!("{}", error);
+        }
+        process::exit(1);
+    }
+
+    let api_schema = api_schema(&schema.document).expect("Failed to derive API schema");
+    print!("{}", print_schema(&api_schema));
+}
+
+/// Parses the value of a query limit flag, where `0` disables the limit.
+fn query_limit<T>(matches: &ArgMatches, name: &str) -> Option<T>
+where
+    T: FromStr + Default + PartialEq,
+{
+    // Safe to unwrap because all query limits have defaults
+    let limit = matches
+        .value_of(name)
+        .unwrap()
+        .parse()
+        .unwrap_or_else(|_| panic!("could not parse --{}", name));
+    if limit == T::default() {
+        None
+    } else {
+        Some(limit)
+    }
 }
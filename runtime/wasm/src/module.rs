@@ -148,6 +148,7 @@ where
             heap: heap.clone(),
             ethereum_adapter: config.ethereum_adapter.clone(),
             link_resolver: config.link_resolver.clone(),
+            block: None,
         };
 
         let module = module
@@ -163,18 +164,30 @@ where
     }
 
     pub fn handle_ethereum_event(&mut self, handler_name: &str, event: EthereumEvent) {
-        self.module
-            .invoke_export(
-                handler_name,
-                &[RuntimeValue::from(self.heap.asc_new(&event))],
-                &mut self.externals,
-            )
-            .unwrap_or_else(|e| {
+        let block = EthereumBlockPointer {
+            hash: event.block_hash,
+            number: event.block_number,
+        };
+
+        self.externals.block = Some(block);
+        let result = self.module.invoke_export(
+            handler_name,
+            &[RuntimeValue::from(self.heap.asc_new(&event))],
+            &mut self.externals,
+        );
+
+        // Report the indexing progress after the store changes of the handler
+        let subgraph_id = self.externals.subgraph.id.clone();
+        let progress_event = match result {
+            Ok(_) => RuntimeHostEvent::EthereumEventHandled(subgraph_id, block),
+            Err(e) => {
                 warn!(self.logger, "Failed to handle Ethereum event";
                       "handler" => &handler_name,
                       "error" => format!("{}", e));
-                None
-            });
+                RuntimeHostEvent::EthereumEventFailed(subgraph_id, block)
+            }
+        };
+        self.externals.send_event(progress_event);
     }
 }
 
@@ -204,6 +217,8 @@ pub struct HostExternals<T, L> {
     heap: WasmiAscHeap,
     ethereum_adapter: Arc<Mutex<T>>,
     link_resolver: Arc<L>,
+    /// The block of the event that is being handled.
+    block: Option<EthereumBlockPointer>,
 }
 
 impl<T, L> HostExternals<T, L>
@@ -211,6 +226,31 @@ where
     T: EthereumAdapter,
     L: LinkResolver,
 {
+    /// Forwards an event to the runtime host.
+    fn send_event(&self, event: RuntimeHostEvent) {
+        let logger = self.logger.clone();
+        self.runtime.spawn(
+            self.event_sink
+                .clone()
+                .send(event)
+                .map_err(move |e| {
+                    error!(logger, "Failed to forward runtime host event";
+                           "error" => format!("{}", e));
+                })
+                .map(|_| ()),
+        );
+    }
+
+    /// Returns the block of the event that is being handled; store changes
+    /// are made for that block.
+    fn current_block(&self) -> Result<EthereumBlockPointer, Trap> {
+        self.block.ok_or_else(|| {
+            Trap::new(TrapKind::Host(Box::new(HostExternalsError(
+                "Store changes can only be made while handling an event",
+            ))))
+        })
+    }
+
     /// function store.set(blockHash: H256, entity: string, id: string, data: Entity): void
     fn store_set(
         &self,
@@ -230,19 +270,10 @@ where
         };
 
         let entity_data = Entity::from(data);
+        let block = self.current_block()?;
 
         // Send an entity set event
-        let logger = self.logger.clone();
-        self.runtime.spawn(
-            self.event_sink
-                .clone()
-                .send(RuntimeHostEvent::EntitySet(store_key, entity_data))
-                .map_err(move |e| {
-                    error!(logger, "Failed to forward runtime host event";
-                           "error" => format!("{}", e));
-                })
-                .map(|_| ()),
-        );
+        self.send_event(RuntimeHostEvent::EntitySet(store_key, entity_data, block));
 
         Ok(None)
     }
@@ -262,19 +293,10 @@ where
             entity,
             id,
         };
+        let block = self.current_block()?;
 
         // Send an entity removed event
-        let logger = self.logger.clone();
-        self.runtime.spawn(
-            self.event_sink
-                .clone()
-                .send(RuntimeHostEvent::EntityRemoved(store_key))
-                .map_err(move |e| {
-                    error!(logger, "Failed to forward runtime host event";
-                           "error" => format!("{}", e));
-                })
-                .and_then(|_| Ok(())),
-        );
+        self.send_event(RuntimeHostEvent::EntityRemoved(store_key, block));
 
         Ok(None)
     }
@@ -809,6 +831,7 @@ mod tests {
             address: Address::from("22843e74c59580b3eaf6c233fa67d8b7c561a835"),
             event_signature: util::ethereum::string_to_h256("ExampleEvent(string)"),
             block_hash: util::ethereum::string_to_h256("example block hash"),
+            block_number: 1,
             params: vec![LogParam {
                 name: String::from("exampleParam"),
                 value: Token::String(String::from("some data")),
@@ -855,6 +878,7 @@ mod tests {
             address: Address::from("22843e74c59580b3eaf6c233fa67d8b7c561a835"),
             event_signature: util::ethereum::string_to_h256("ExampleEvent(string)"),
             block_hash: util::ethereum::string_to_h256("example block hash"),
+            block_number: 1,
             params: vec![LogParam {
                 name: String::from("exampleParam"),
                 value: Token::String(String::from("some data")),
@@ -866,26 +890,38 @@ mod tests {
         module.handle_ethereum_event("handleExampleEvent", ethereum_event);
 
         // Expect a store set call to be made by the handler and a
-        // RuntimeHostEvent::EntitySet event to be written to the event stream
-        let work = receiver.take(1).into_future();
-        let store_event = core.run(work)
-            .expect("No store event received from runtime")
-            .0
-            .expect("Store event must not be None");
+        // RuntimeHostEvent::EntitySet event to be written to the event stream,
+        // followed by the indexing progress
+        let work = receiver.take(2).collect();
+        let events = core.run(work).expect("No store event received from runtime");
 
         // Verify that this event matches what the test module is sending
         assert_eq!(
-            store_event,
-            RuntimeHostEvent::EntitySet(
-                StoreKey {
-                    subgraph: String::from("example subgraph"),
-                    entity: String::from("ExampleEntity"),
-                    id: String::from("example id"),
-                },
-                Entity::from(HashMap::from_iter(
-                    vec![(String::from("exampleAttribute"), Value::from("some data"))].into_iter()
-                ))
-            )
+            events,
+            vec![
+                RuntimeHostEvent::EntitySet(
+                    StoreKey {
+                        subgraph: String::from("example subgraph"),
+                        entity: String::from("ExampleEntity"),
+                        id: String::from("example id"),
+                    },
+                    Entity::from(HashMap::from_iter(
+                        vec![(String::from("exampleAttribute"), Value::from("some data"))]
+                            .into_iter()
+                    )),
+                    EthereumBlockPointer {
+                        hash: util::ethereum::string_to_h256("example block hash"),
+                        number: 1,
+                    }
+                ),
+                RuntimeHostEvent::EthereumEventHandled(
+                    String::from("example subgraph"),
+                    EthereumBlockPointer {
+                        hash: util::ethereum::string_to_h256("example block hash"),
+                        number: 1,
+                    }
+                ),
+            ]
         );
     }
 }
@@ -0,0 +1,115 @@
+use futures::sync::mpsc::Sender;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use graph::components::server::GraphQLServerError;
+use graph::data::query::{PendingQuery, Query};
+
+/// Forwards queries to the query runner without blocking.
+///
+/// Queries count as pending from the moment they are dispatched until the
+/// query runner is done with them, even if their result is no longer awaited.
+/// Once `capacity` queries are pending, further queries are turned away
+/// instead of being queued.
+#[derive(Clone, Debug)]
+pub struct QueryDispatcher {
+    query_sink: Sender<Query>,
+    pending: Arc<AtomicUsize>,
+    capacity: usize,
+}
+
+impl QueryDispatcher {
+    pub fn new(query_sink: Sender<Query>, capacity: usize) -> Self {
+        QueryDispatcher {
+            query_sink,
+            pending: Arc::new(AtomicUsize::new(0)),
+            capacity,
+        }
+    }
+
+    /// The number of queries that are waiting for or being processed.
+    pub fn depth(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// The number of queries that may be pending at the same time.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sends a query to the query runner. The query counts as pending until
+    /// the query runner drops it.
+    ///
+    /// Since every sender is guaranteed a slot in the channel, it is the
+    /// number of pending queries, not the channel, that bounds the queue.
+    pub fn dispatch(&self, mut query: Query) -> Result<(), GraphQLServerError> {
+        let pending = PendingQuery::new(self.pending.clone());
+        if pending.position() > self.capacity {
+            return Err(GraphQLServerError::Overloaded);
+        }
+        query.pending = Some(pending);
+
+        self.query_sink.clone().try_send(query).map_err(|e| {
+            if e.is_full() {
+                GraphQLServerError::Overloaded
+            } else {
+                GraphQLServerError::from("Failed to forward incoming query")
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::prelude::*;
+    use futures::sync::mpsc::channel;
+    use futures::sync::oneshot;
+    use graphql_parser;
+
+    use graph::components::server::GraphQLServerError;
+    use graph::data::query::Query;
+    use graph::data::schema::Schema;
+
+    use super::QueryDispatcher;
+
+    fn query() -> Query {
+        let (result_sender, _) = oneshot::channel();
+        Query {
+            schema: Schema {
+                id: "test-schema".to_string(),
+                document: graphql_parser::parse_schema("type Query { name: String }").unwrap(),
+            },
+            document: graphql_parser::parse_query("{ name }").unwrap(),
+            variables: None,
+            operation_name: None,
+            tracing: false,
+            result_sender,
+            pending: None,
+            deadline: None,
+        }
+    }
+
+    #[test]
+    fn dispatching_more_queries_than_the_capacity_fails() {
+        let (query_sink, query_stream) = channel(10);
+        let dispatcher = QueryDispatcher::new(query_sink, 2);
+
+        dispatcher.dispatch(query()).expect("First query was rejected");
+        dispatcher.dispatch(query()).expect("Second query was rejected");
+        assert_eq!(dispatcher.depth(), 2);
+
+        match dispatcher.dispatch(query()) {
+            Err(GraphQLServerError::Overloaded) => (),
+            result => panic!("Unexpected dispatch result: {:?}", result),
+        }
+        assert_eq!(dispatcher.depth(), 2);
+
+        // Queries remain pending until the query runner is done with them
+        let (first, query_stream) = query_stream.into_future().wait().ok().unwrap();
+        assert_eq!(dispatcher.depth(), 2);
+        drop(first);
+        assert_eq!(dispatcher.depth(), 1);
+        dispatcher.dispatch(query()).expect("Query was rejected");
+        drop(query_stream);
+    }
+}
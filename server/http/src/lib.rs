@@ -3,22 +3,32 @@ extern crate graphql_parser;
 extern crate http;
 extern crate hyper;
 extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate serde_json;
+extern crate sha2;
 #[macro_use]
 extern crate slog;
 extern crate graph;
 extern crate graph_graphql;
 extern crate tokio;
 extern crate tokio_core;
+extern crate tokio_tungstenite;
+extern crate url;
 
+mod dispatch;
+mod persisted_queries;
 mod request;
 mod response;
 mod server;
 mod service;
+mod subscription;
 
+pub use self::dispatch::QueryDispatcher;
+pub use self::persisted_queries::{hash_query, PersistedQueries};
 pub use self::request::GraphQLRequest;
-pub use self::response::GraphQLResponse;
-pub use self::server::GraphQLServer;
+pub use self::response::{GraphQLBatchResponse, GraphQLResponse};
+pub use self::server::{GraphQLServer, GraphQLServerConfig};
 pub use self::service::{GraphQLService, GraphQLServiceResponse};
 
 pub mod test_utils;
@@ -0,0 +1,185 @@
+use serde_json;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use graph::components::server::GraphQLServerError;
+
+/// The number of query documents clients can register by default.
+pub const DEFAULT_MAX_PERSISTED_QUERIES: usize = 10_000;
+
+/// Returns the hex-encoded SHA-256 hash of a query document.
+pub fn hash_query(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
+
+/// A bounded store of query documents, keyed by the SHA-256 hashes that
+/// clients send instead of the documents (Apollo's automatic persisted queries).
+///
+/// Clients register documents by sending them along with their hash. Once the
+/// store is full, the oldest documents are evicted. A store loaded from a file
+/// acts as an allowlist: it never accepts new documents and only the queries
+/// it was loaded with can be executed.
+#[derive(Debug)]
+pub struct PersistedQueries {
+    queries: HashMap<String, String>,
+    /// Hashes of the stored documents, oldest first.
+    insertion_order: VecDeque<String>,
+    max_queries: usize,
+    allowlist: bool,
+}
+
+impl PersistedQueries {
+    /// Creates an empty store that holds at most `max_queries` documents.
+    pub fn new(max_queries: usize) -> Self {
+        PersistedQueries {
+            queries: HashMap::new(),
+            insertion_order: VecDeque::new(),
+            max_queries,
+            allowlist: false,
+        }
+    }
+
+    /// Loads an allowlist from a JSON file that maps hashes to query documents.
+    pub fn from_file(path: &Path) -> Result<Self, io::Error> {
+        let queries: HashMap<String, String> = serde_json::from_reader(File::open(path)?)?;
+
+        for (hash, query) in queries.iter() {
+            if hash_query(query) != hash.to_lowercase() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Hash does not match persisted query: {}", hash),
+                ));
+            }
+        }
+
+        // Allowlists never grow, so nothing is ever evicted from them
+        Ok(PersistedQueries {
+            queries: queries
+                .into_iter()
+                .map(|(hash, query)| (hash.to_lowercase(), query))
+                .collect(),
+            insertion_order: VecDeque::new(),
+            max_queries: usize::max_value(),
+            allowlist: true,
+        })
+    }
+
+    /// Returns true if the store only allows the queries it was loaded with.
+    pub fn is_allowlist(&self) -> bool {
+        self.allowlist
+    }
+
+    /// Returns the document registered for a hash, if there is one.
+    pub fn get(&self, hash: &str) -> Option<String> {
+        self.queries.get(&hash.to_lowercase()).cloned()
+    }
+
+    /// Registers a document under its hash, evicting the oldest documents if
+    /// the store is full. Fails if the hash does not match the document or if
+    /// the store is an allowlist that does not contain the document.
+    pub fn register(&mut self, hash: &str, query: &str) -> Result<(), GraphQLServerError> {
+        let hash = hash.to_lowercase();
+
+        if hash_query(query) != hash {
+            return Err(GraphQLServerError::ClientError(String::from(
+                "Provided sha256Hash does not match query",
+            )));
+        }
+
+        if self.queries.contains_key(&hash) {
+            return Ok(());
+        }
+
+        if self.allowlist {
+            return Err(GraphQLServerError::ClientError(String::from(
+                "Query is not in the allowlist of persisted queries",
+            )));
+        }
+
+        while self.queries.len() >= self.max_queries {
+            match self.insertion_order.pop_front() {
+                Some(oldest) => self.queries.remove(&oldest),
+                None => break,
+            };
+        }
+
+        self.queries.insert(hash.clone(), query.to_owned());
+        self.insertion_order.push_back(hash);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+
+    use super::{hash_query, PersistedQueries};
+
+    const QUERY: &'static str = "{ users { name } }";
+
+    #[test]
+    fn hashes_queries_with_sha256() {
+        assert_eq!(
+            hash_query(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn registers_queries_under_their_hash() {
+        let mut queries = PersistedQueries::new(10);
+        let hash = hash_query(QUERY);
+
+        assert_eq!(queries.get(&hash), None);
+        queries.register(&hash, QUERY).expect("Should register query");
+        assert_eq!(queries.get(&hash), Some(String::from(QUERY)));
+        assert_eq!(queries.get(&hash.to_uppercase()), Some(String::from(QUERY)));
+    }
+
+    #[test]
+    fn rejects_queries_with_mismatching_hashes() {
+        let mut queries = PersistedQueries::new(10);
+        queries
+            .register(&hash_query("{ other }"), QUERY)
+            .expect_err("Should reject mismatching hash");
+        assert_eq!(queries.get(&hash_query("{ other }")), None);
+    }
+
+    #[test]
+    fn evicts_the_oldest_queries_when_full() {
+        let mut queries = PersistedQueries::new(2);
+        for query in ["{ a }", "{ b }", "{ c }"].iter() {
+            queries.register(&hash_query(query), query).unwrap();
+        }
+
+        assert_eq!(queries.get(&hash_query("{ a }")), None);
+        assert!(queries.get(&hash_query("{ b }")).is_some());
+        assert!(queries.get(&hash_query("{ c }")).is_some());
+    }
+
+    #[test]
+    fn loads_allowlists_from_files() {
+        let path = env::temp_dir().join("graph-server-http-persisted-queries.json");
+        write!(
+            File::create(&path).unwrap(),
+            "{{ \"{}\": \"{}\" }}",
+            hash_query(QUERY),
+            QUERY
+        ).unwrap();
+
+        let mut queries = PersistedQueries::from_file(&path).expect("Should load allowlist");
+        assert!(queries.is_allowlist());
+        assert_eq!(queries.get(&hash_query(QUERY)), Some(String::from(QUERY)));
+        queries
+            .register(&hash_query(QUERY), QUERY)
+            .expect("Should accept allowlisted queries");
+        queries
+            .register(&hash_query("{ other }"), "{ other }")
+            .expect_err("Should reject queries that are not in the allowlist");
+    }
+}
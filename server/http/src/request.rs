@@ -3,21 +3,120 @@ use futures::sync::oneshot;
 use graphql_parser;
 use hyper::Chunk;
 use serde_json;
+use std::sync::{Arc, Mutex};
+use url::form_urlencoded;
 
 use graph::components::server::GraphQLServerError;
 use graph::prelude::*;
 
+use persisted_queries::{hash_query, PersistedQueries};
+
 /// Future for a query parsed from an HTTP request.
 pub struct GraphQLRequest {
-    body: Chunk,
+    /// The request data, or the error that occurred while decoding it.
+    data: Option<Result<serde_json::Value, GraphQLServerError>>,
     schema: Option<Schema>,
+    persisted_queries: Arc<Mutex<PersistedQueries>>,
 }
 
 impl GraphQLRequest {
     /// Creates a new GraphQLRequest future based on an HTTP request and a result sender.
-    pub fn new(body: Chunk, schema: Option<Schema>) -> Self {
-        GraphQLRequest { body, schema }
+    pub fn new(
+        body: Chunk,
+        schema: Option<Schema>,
+        persisted_queries: Arc<Mutex<PersistedQueries>>,
+    ) -> Self {
+        let data = serde_json::from_slice(&body)
+            .map_err(|e| GraphQLServerError::ClientError(format!("{}", e)));
+        GraphQLRequest::with_data(data, schema, persisted_queries)
+    }
+
+    /// Creates a new GraphQLRequest future based on a single operation of a
+    /// batched request.
+    pub fn from_json(
+        data: serde_json::Value,
+        schema: Option<Schema>,
+        persisted_queries: Arc<Mutex<PersistedQueries>>,
+    ) -> Self {
+        GraphQLRequest::with_data(Ok(data), schema, persisted_queries)
+    }
+
+    /// Creates a new GraphQLRequest future based on the query string of a GET
+    /// request, e.g. `query={...}&variables={...}&operationName=...`.
+    pub fn from_query_string(
+        query_string: &str,
+        schema: Option<Schema>,
+        persisted_queries: Arc<Mutex<PersistedQueries>>,
+    ) -> Self {
+        let data = form_urlencoded::parse(query_string.as_bytes())
+            .map(|(name, value)| {
+                // Variables and extensions are JSON encoded, all other
+                // parameters are plain strings
+                let value = match name.as_ref() {
+                    "variables" | "extensions" => {
+                        serde_json::from_str(&value).map_err(|e| {
+                            GraphQLServerError::ClientError(format!(
+                                "Invalid \"{}\" parameter: {}",
+                                name, e
+                            ))
+                        })?
+                    }
+                    _ => serde_json::Value::String(value.into_owned()),
+                };
+                Ok((name.into_owned(), value))
+            })
+            .collect::<Result<serde_json::Map<_, _>, _>>()
+            .map(serde_json::Value::Object);
+        GraphQLRequest::with_data(data, schema, persisted_queries)
+    }
+
+    fn with_data(
+        data: Result<serde_json::Value, GraphQLServerError>,
+        schema: Option<Schema>,
+        persisted_queries: Arc<Mutex<PersistedQueries>>,
+    ) -> Self {
+        GraphQLRequest {
+            data: Some(data),
+            schema,
+            persisted_queries,
+        }
+    }
+}
+
+/// Returns the hash from the `extensions.persistedQuery` field of the request
+/// data, if the client sent one.
+fn get_persisted_query_hash(
+    obj: &serde_json::Map<String, serde_json::Value>,
+) -> Result<Option<&str>, GraphQLServerError> {
+    let persisted_query = match obj.get("extensions")
+        .and_then(|extensions| extensions.get("persistedQuery"))
+    {
+        None | Some(serde_json::Value::Null) => return Ok(None),
+        Some(persisted_query) => persisted_query,
+    };
+
+    if persisted_query.get("version") != Some(&serde_json::Value::from(1)) {
+        return Err(GraphQLServerError::ClientError(String::from(
+            "Unsupported persisted query version",
+        )));
     }
+
+    persisted_query
+        .get("sha256Hash")
+        .and_then(|hash| hash.as_str())
+        .map(Some)
+        .ok_or(GraphQLServerError::ClientError(String::from(
+            "Invalid persisted query hash provided",
+        )))
+}
+
+/// Returns true if the client asked for the execution of the query to be
+/// traced by sending `"extensions": { "tracing": true }`.
+fn get_tracing_flag(obj: &serde_json::Map<String, serde_json::Value>) -> bool {
+    obj.get("extensions")
+        .and_then(|extensions| extensions.get("tracing"))
+        .and_then(|tracing| tracing.as_bool())
+        .unwrap_or(false)
 }
 
 impl Future for GraphQLRequest {
@@ -32,9 +131,10 @@ impl Future for GraphQLRequest {
                 "No schema available to query".to_string(),
             ))?;
 
-        // Parse request body as JSON
-        let json: serde_json::Value = serde_json::from_slice(&self.body)
-            .map_err(|e| GraphQLServerError::ClientError(format!("{}", e)))?;
+        // Fail if the request data could not be decoded
+        let json = self.data
+            .take()
+            .expect("GraphQLRequest polled after completion")?;
 
         // Ensure the JSON data is an object
         let obj = json.as_object()
@@ -42,19 +142,50 @@ impl Future for GraphQLRequest {
                 "Request data is not an object",
             )))?;
 
-        // Ensure the JSON data has a "query" field
-        let query_value = obj.get("query")
-            .ok_or(GraphQLServerError::ClientError(String::from(
-                "The \"query\" field missing in request data",
-            )))?;
+        // Ensure the "query" field is a string, if present
+        let query_string = match obj.get("query") {
+            None => None,
+            Some(query_value) => Some(query_value.as_str().ok_or(
+                GraphQLServerError::ClientError(String::from("The\"query\" field is not a string")),
+            )?),
+        };
+
+        // Look up or register the query document if the client sent a hash
+        // for it, otherwise require the "query" field
+        let query_string = match get_persisted_query_hash(obj)? {
+            Some(hash) => {
+                let mut persisted_queries = self.persisted_queries.lock().unwrap();
+                match query_string {
+                    Some(query_string) => {
+                        persisted_queries.register(hash, query_string)?;
+                        query_string.to_owned()
+                    }
+                    None => persisted_queries.get(hash).ok_or(
+                        GraphQLServerError::ClientError(String::from("PersistedQueryNotFound")),
+                    )?,
+                }
+            }
+            None => {
+                let query_string = query_string.ok_or(GraphQLServerError::ClientError(
+                    String::from("The \"query\" field missing in request data"),
+                ))?;
 
-        // Ensure the "query" field is a string
-        let query_string = query_value.as_str().ok_or(GraphQLServerError::ClientError(
-            String::from("The\"query\" field is not a string"),
-        ))?;
+                // Allowlists also restrict queries that are sent in full
+                let persisted_queries = self.persisted_queries.lock().unwrap();
+                if persisted_queries.is_allowlist()
+                    && persisted_queries.get(&hash_query(query_string)).is_none()
+                {
+                    return Err(GraphQLServerError::ClientError(String::from(
+                        "Query is not in the allowlist of persisted queries",
+                    )));
+                }
+
+                query_string.to_owned()
+            }
+        };
 
-        // Parse the "query" field of the JSON body
-        let document = graphql_parser::parse_query(query_string)
+        // Parse the query document
+        let document = graphql_parser::parse_query(&query_string)
             .map_err(|e| GraphQLServerError::from(QueryError::from(e)))?;
 
         // Parse the "variables" field of the JSON body, if present
@@ -70,6 +201,15 @@ impl Future for GraphQLRequest {
             ))),
         }?;
 
+        // Parse the "operationName" field of the JSON body, if present
+        let operation_name = match obj.get("operationName") {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(serde_json::Value::String(name)) => Ok(Some(name.to_owned())),
+            _ => Err(GraphQLServerError::ClientError(format!(
+                "Invalid operation name provided"
+            ))),
+        }?;
+
         // Create a one-shot channel to allow another part of the system
         // to notify the service when the query has completed
         let (sender, receiver) = oneshot::channel();
@@ -78,8 +218,12 @@ impl Future for GraphQLRequest {
             Query {
                 document,
                 variables,
+                operation_name,
+                tracing: get_tracing_flag(obj),
                 schema: schema,
                 result_sender: sender,
+                pending: None,
+                deadline: None,
             },
             receiver,
         )))
@@ -89,15 +233,33 @@ impl Future for GraphQLRequest {
 #[cfg(test)]
 mod tests {
     use graphql_parser;
+    use graphql_parser::query as q;
     use hyper;
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
     use tokio_core::reactor::Core;
 
     use graph::prelude::*;
 
     use super::GraphQLRequest;
+    use persisted_queries::{hash_query, PersistedQueries};
 
     const EXAMPLE_SCHEMA: &'static str = "type Query { users: [User!] }";
 
+    fn persisted_queries() -> Arc<Mutex<PersistedQueries>> {
+        Arc::new(Mutex::new(PersistedQueries::new(10)))
+    }
+
+    fn persisted_query_request(query: Option<&str>, hash: &str) -> hyper::Chunk {
+        let query = query
+            .map(|query| format!("\"query\": \"{}\", ", query))
+            .unwrap_or_default();
+        hyper::Chunk::from(format!(
+            "{{ {}\"extensions\": {{ \"persistedQuery\": {{ \"version\": 1, \"sha256Hash\": \"{}\" }} }} }}",
+            query, hash
+        ))
+    }
+
     #[test]
     fn rejects_invalid_json() {
         let mut core = Core::new().unwrap();
@@ -105,7 +267,7 @@ mod tests {
             id: "test".to_string(),
             document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
         };
-        let request = GraphQLRequest::new(hyper::Chunk::from("!@#)%"), Some(schema));
+        let request = GraphQLRequest::new(hyper::Chunk::from("!@#)%"), Some(schema), persisted_queries());
         let result = core.run(request);
         result.expect_err("Should reject invalid JSON");
     }
@@ -117,7 +279,7 @@ mod tests {
             id: "test".to_string(),
             document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
         };
-        let request = GraphQLRequest::new(hyper::Chunk::from("{}"), Some(schema));
+        let request = GraphQLRequest::new(hyper::Chunk::from("{}"), Some(schema), persisted_queries());
         let result = core.run(request);
         result.expect_err("Should reject JSON without query field");
     }
@@ -129,7 +291,7 @@ mod tests {
             id: "test".to_string(),
             document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
         };
-        let request = GraphQLRequest::new(hyper::Chunk::from("{\"query\": 5}"), Some(schema));
+        let request = GraphQLRequest::new(hyper::Chunk::from("{\"query\": 5}"), Some(schema), persisted_queries());
         let result = core.run(request);
         result.expect_err("Should reject JSON with a non-string query field");
     }
@@ -141,7 +303,7 @@ mod tests {
             id: "test".to_string(),
             document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
         };
-        let request = GraphQLRequest::new(hyper::Chunk::from("{\"query\": \"foo\"}"), Some(schema));
+        let request = GraphQLRequest::new(hyper::Chunk::from("{\"query\": \"foo\"}"), Some(schema), persisted_queries());
         let result = core.run(request);
         result.expect_err("Should reject broken queries");
     }
@@ -156,6 +318,7 @@ mod tests {
         let request = GraphQLRequest::new(
             hyper::Chunk::from("{\"query\": \"{ user { name } }\"}"),
             Some(schema),
+            persisted_queries(),
         );
         let result = core.run(request);
         let (query, _) = result.expect("Should accept valid queries");
@@ -165,6 +328,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn enables_tracing_through_extensions() {
+        let mut core = Core::new().unwrap();
+        let schema = Schema {
+            id: "test".to_string(),
+            document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
+        };
+
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from("{\"query\": \"{ user { name } }\"}"),
+            Some(schema.clone()),
+            persisted_queries(),
+        );
+        let (query, _) = core.run(request).expect("Should accept valid queries");
+        assert!(!query.tracing);
+
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(
+                "{\"query\": \"{ user { name } }\", \"extensions\": { \"tracing\": true }}",
+            ),
+            Some(schema),
+            persisted_queries(),
+        );
+        let (query, _) = core.run(request).expect("Should accept traced queries");
+        assert!(query.tracing);
+    }
+
     #[test]
     fn accepts_null_variables() {
         let mut core = Core::new().unwrap();
@@ -181,6 +371,7 @@ mod tests {
                  }",
             ),
             Some(schema),
+            persisted_queries(),
         );
         let result = core.run(request);
         let (query, _) = result.expect("Should accept null variables");
@@ -206,6 +397,7 @@ mod tests {
                  }",
             ),
             Some(schema),
+            persisted_queries(),
         );
         let result = core.run(request);
         result.expect_err("Should reject non-map variables");
@@ -227,6 +419,7 @@ mod tests {
                  }",
             ),
             Some(schema),
+            persisted_queries(),
         );
         let result = core.run(request);
         let (query, _) = result.expect("Should accept valid queries");
@@ -238,4 +431,212 @@ mod tests {
         assert_eq!(query.document, expected_query);
         assert_eq!(query.variables, Some(expected_variables));
     }
+
+    #[test]
+    fn parses_operation_name() {
+        let mut core = Core::new().unwrap();
+        let schema = Schema {
+            id: "test".to_string(),
+            document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
+        };
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(
+                "\
+                 {\
+                 \"query\": \"query users { users { name } } query other { users { id } }\", \
+                 \"operationName\": \"users\" \
+                 }",
+            ),
+            Some(schema),
+            persisted_queries(),
+        );
+        let result = core.run(request);
+        let (query, _) = result.expect("Should accept queries with an operation name");
+
+        assert_eq!(query.operation_name, Some(String::from("users")));
+    }
+
+    #[test]
+    fn rejects_non_string_operation_names() {
+        let mut core = Core::new().unwrap();
+        let schema = Schema {
+            id: "test".to_string(),
+            document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
+        };
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(
+                "\
+                 {\
+                 \"query\": \"{ users { name } }\", \
+                 \"operationName\": 5 \
+                 }",
+            ),
+            Some(schema),
+            persisted_queries(),
+        );
+        let result = core.run(request);
+        result.expect_err("Should reject non-string operation names");
+    }
+
+    #[test]
+    fn parses_typed_variables() {
+        let mut core = Core::new().unwrap();
+        let schema = Schema {
+            id: "test".to_string(),
+            document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
+        };
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(
+                "\
+                 {\
+                 \"query\": \"{ user { name } }\", \
+                 \"variables\": { \
+                 \"int\": 5, \
+                 \"float\": 1.5, \
+                 \"bool\": true, \
+                 \"null\": null, \
+                 \"list\": [\"a\", \"b\"], \
+                 \"object\": { \"foo\": \"bar\" } \
+                 } \
+                 }",
+            ),
+            Some(schema),
+            persisted_queries(),
+        );
+        let result = core.run(request);
+        let (query, _) = result.expect("Should accept valid queries");
+
+        let mut expected_object = BTreeMap::new();
+        expected_object.insert("foo".to_string(), q::Value::String("bar".to_string()));
+
+        let mut expected_variables = QueryVariables::new();
+        expected_variables.insert(
+            "int".to_string(),
+            QueryVariableValue::from(q::Value::Int(q::Number::from(5))),
+        );
+        expected_variables.insert(
+            "float".to_string(),
+            QueryVariableValue::from(q::Value::Float(1.5)),
+        );
+        expected_variables.insert(
+            "bool".to_string(),
+            QueryVariableValue::from(q::Value::Boolean(true)),
+        );
+        expected_variables.insert("null".to_string(), QueryVariableValue::from(q::Value::Null));
+        expected_variables.insert(
+            "list".to_string(),
+            QueryVariableValue::from(q::Value::List(vec![
+                q::Value::String("a".to_string()),
+                q::Value::String("b".to_string()),
+            ])),
+        );
+        expected_variables.insert(
+            "object".to_string(),
+            QueryVariableValue::from(q::Value::Object(expected_object)),
+        );
+
+        assert_eq!(query.variables, Some(expected_variables));
+    }
+
+    #[test]
+    fn registers_and_looks_up_persisted_queries() {
+        let mut core = Core::new().unwrap();
+        let schema = Schema {
+            id: "test".to_string(),
+            document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
+        };
+        let persisted_queries = persisted_queries();
+        let query = "{ users { name } }";
+        let hash = hash_query(query);
+
+        // Unknown hashes ask the client to send the full document
+        let request = GraphQLRequest::new(
+            persisted_query_request(None, &hash),
+            Some(schema.clone()),
+            persisted_queries.clone(),
+        );
+        let error = core.run(request)
+            .expect_err("Should reject unknown persisted queries");
+        assert_eq!(format!("{}", error), "PersistedQueryNotFound");
+
+        // Sending the document along with the hash registers it
+        let request = GraphQLRequest::new(
+            persisted_query_request(Some(query), &hash),
+            Some(schema.clone()),
+            persisted_queries.clone(),
+        );
+        let _ = core.run(request)
+            .expect("Should accept persisted queries with a document");
+
+        // After that, the hash is enough
+        let request = GraphQLRequest::new(
+            persisted_query_request(None, &hash),
+            Some(schema),
+            persisted_queries,
+        );
+        let (query, _) = core.run(request)
+            .expect("Should accept registered persisted queries");
+        assert_eq!(
+            query.document,
+            graphql_parser::parse_query("{ users { name } }").unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_persisted_queries_with_mismatching_hashes() {
+        let mut core = Core::new().unwrap();
+        let schema = Schema {
+            id: "test".to_string(),
+            document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
+        };
+        let request = GraphQLRequest::new(
+            persisted_query_request(Some("{ users { name } }"), &hash_query("{ users { id } }")),
+            Some(schema),
+            persisted_queries(),
+        );
+        let result = core.run(request);
+        result.expect_err("Should reject persisted queries with mismatching hashes");
+    }
+
+    #[test]
+    fn parses_query_strings() {
+        let mut core = Core::new().unwrap();
+        let schema = Schema {
+            id: "test".to_string(),
+            document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
+        };
+        let request = GraphQLRequest::from_query_string(
+            "query=query+users%28%24foo%3A+String%29+%7B+users+%7B+name+%7D+%7D\
+             &variables=%7B%22foo%22%3A%22bar%22%7D&operationName=users",
+            Some(schema),
+            persisted_queries(),
+        );
+        let (query, _) = core.run(request).expect("Should accept valid query strings");
+
+        let mut expected_variables = QueryVariables::new();
+        expected_variables.insert("foo".to_string(), QueryVariableValue::from("bar"));
+
+        assert_eq!(
+            query.document,
+            graphql_parser::parse_query("query users($foo: String) { users { name } }").unwrap()
+        );
+        assert_eq!(query.variables, Some(expected_variables));
+        assert_eq!(query.operation_name, Some(String::from("users")));
+    }
+
+    #[test]
+    fn rejects_query_strings_with_invalid_variables() {
+        let mut core = Core::new().unwrap();
+        let schema = Schema {
+            id: "test".to_string(),
+            document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
+        };
+        let request = GraphQLRequest::from_query_string(
+            "query=%7B+users+%7B+name+%7D+%7D&variables=%7Bfoo",
+            Some(schema),
+            persisted_queries(),
+        );
+        let result = core.run(request);
+        result.expect_err("Should reject query strings with invalid variables");
+    }
 }
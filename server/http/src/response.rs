@@ -1,23 +1,53 @@
 use futures::prelude::*;
+use futures::stream;
+use futures::sync::mpsc::Receiver;
 use http::status::StatusCode;
+use hyper::header::{HeaderValue, RETRY_AFTER};
 use hyper::{Body, Response};
 use serde::ser::*;
 use serde_json;
+use std::collections::BTreeMap;
 
 use graph::components::server::GraphQLServerError;
-use graph::data::query::QueryResult;
+use graph::data::query::{IncrementalResult, QueryResult};
 use graph_graphql::prelude::SerializableValue;
 
+/// The boundary between the parts of multipart responses.
+const MULTIPART_BOUNDARY: &'static str = "-";
+
+/// How many seconds clients should wait before retrying queries that were
+/// rejected because the server is overloaded.
+const RETRY_AFTER_SECONDS: &'static str = "1";
+
 /// Future for HTTP responses to GraphQL query requests.
+///
+/// Results with subsequent results for `@defer` and `@stream` are delivered
+/// as `multipart/mixed` responses, with one JSON part per result.
 pub struct GraphQLResponse {
     result: Result<QueryResult, GraphQLServerError>,
+    /// Whether subsequent results follow the result.
+    has_next: bool,
 }
 
 impl GraphQLResponse {
     /// Creates a new GraphQLResponse future based on the result generated by
     /// running a query.
     pub fn new(result: Result<QueryResult, GraphQLServerError>) -> Self {
-        GraphQLResponse { result }
+        GraphQLResponse {
+            result,
+            has_next: false,
+        }
+    }
+
+    /// Takes the subsequent results of `@defer` and `@stream` out of the
+    /// result; the response then announces that they follow.
+    pub fn take_subsequent_results(&mut self) -> Option<Receiver<IncrementalResult>> {
+        let subsequent_results = match self.result {
+            Ok(ref mut result) => result.subsequent_results.take(),
+            Err(_) => None,
+        };
+        self.has_next = subsequent_results.is_some();
+        subsequent_results
     }
 
     fn status_code_from_result(&self) -> StatusCode {
@@ -33,6 +63,9 @@ impl GraphQLResponse {
                 &GraphQLServerError::ClientError(_) | &GraphQLServerError::QueryError(_) => {
                     StatusCode::BAD_REQUEST
                 }
+                &GraphQLServerError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+                &GraphQLServerError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+                &GraphQLServerError::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
             },
         }
@@ -56,6 +89,16 @@ impl Serialize for GraphQLResponse {
                     map.serialize_entry("errors", errors)?;
                 }
 
+                if let Some(ref trace) = result.trace {
+                    let mut extensions = BTreeMap::new();
+                    extensions.insert("tracing", trace);
+                    map.serialize_entry("extensions", &extensions)?;
+                }
+
+                if self.has_next {
+                    map.serialize_entry("hasNext", &true)?;
+                }
+
                 map.end()
             }
             Err(ref e) => {
@@ -74,26 +117,176 @@ impl Future for GraphQLResponse {
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         let status_code = self.status_code_from_result();
-        let json =
-            serde_json::to_string(self).expect("Failed to serialize GraphQL response to JSON");
-        let response = Response::builder()
-            .status(status_code)
-            .header("Access-Control-Allow-Origin", "*")
-            .header("Access-Control-Allow-Headers", "Content-Type")
-            .body(Body::from(json))
-            .unwrap();
+
+        let mut response = match self.take_subsequent_results() {
+            Some(subsequent_results) => multipart_response(status_code, self, subsequent_results),
+            None => json_response(status_code, self),
+        };
+
+        // Ask clients to back off for a moment if the server is overloaded
+        if let Err(GraphQLServerError::Overloaded) = self.result {
+            response.headers_mut().insert(
+                RETRY_AFTER,
+                HeaderValue::from_static(RETRY_AFTER_SECONDS),
+            );
+        }
+
         Ok(Async::Ready(response))
     }
 }
 
+/// A subsequent result of a query, as delivered in multipart responses and
+/// subscription messages.
+pub struct SubsequentResponse(pub IncrementalResult);
+
+impl Serialize for SubsequentResponse {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let result = &self.0;
+        let mut map = serializer.serialize_map(None)?;
+
+        // The last result may only signal that there are no more results
+        if result.data.is_some() || result.items.is_some() || result.errors.is_some() {
+            map.serialize_entry("incremental", &[IncrementalPayload(result)])?;
+        }
+
+        map.serialize_entry("hasNext", &result.has_next)?;
+        map.end()
+    }
+}
+
+/// The data of a deferred fragment or the items of a streamed list in a
+/// subsequent result.
+struct IncrementalPayload<'a>(&'a IncrementalResult);
+
+impl<'a> Serialize for IncrementalPayload<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let result = self.0;
+        let mut map = serializer.serialize_map(None)?;
+
+        if let Some(ref data) = result.data {
+            map.serialize_entry("data", &SerializableValue(data))?;
+        }
+
+        if let Some(ref items) = result.items {
+            let items: Vec<_> = items.iter().map(SerializableValue).collect();
+            map.serialize_entry("items", &items)?;
+        }
+
+        map.serialize_entry("path", &result.path)?;
+
+        if let Some(ref label) = result.label {
+            map.serialize_entry("label", label)?;
+        }
+
+        if let Some(ref errors) = result.errors {
+            map.serialize_entry("errors", errors)?;
+        }
+
+        map.end()
+    }
+}
+
+/// Future for HTTP responses to batched GraphQL query requests.
+///
+/// The response contains the results of the operations in the order in which
+/// they were requested. Since the operations succeed or fail independently,
+/// the status code is always 200.
+pub struct GraphQLBatchResponse {
+    responses: Vec<GraphQLResponse>,
+}
+
+impl GraphQLBatchResponse {
+    /// Creates a new GraphQLBatchResponse future based on the results
+    /// generated by running the operations of a batch.
+    pub fn new(results: Vec<Result<QueryResult, GraphQLServerError>>) -> Self {
+        GraphQLBatchResponse {
+            responses: results
+                .into_iter()
+                .map(|result| match result {
+                    // Batches are delivered as a single JSON array
+                    Ok(ref result) if result.subsequent_results.is_some() => {
+                        Err(GraphQLServerError::ClientError(String::from(
+                            "@defer and @stream are not supported in batched requests",
+                        )))
+                    }
+                    result => result,
+                })
+                .map(GraphQLResponse::new)
+                .collect(),
+        }
+    }
+}
+
+impl Future for GraphQLBatchResponse {
+    type Item = Response<Body>;
+    type Error = GraphQLServerError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        Ok(Async::Ready(json_response(StatusCode::OK, &self.responses)))
+    }
+}
+
+/// Builds an HTTP response with the JSON serialization of a value as its body.
+fn json_response<T: Serialize>(status_code: StatusCode, value: &T) -> Response<Body> {
+    let json = serde_json::to_string(value).expect("Failed to serialize GraphQL response to JSON");
+    Response::builder()
+        .status(status_code)
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Access-Control-Allow-Headers", "Content-Type")
+        .body(Body::from(json))
+        .unwrap()
+}
+
+/// Builds a `multipart/mixed` HTTP response whose first part is the JSON
+/// serialization of a value, followed by one part per subsequent result as
+/// they arrive.
+fn multipart_response<T: Serialize>(
+    status_code: StatusCode,
+    value: &T,
+    subsequent_results: Receiver<IncrementalResult>,
+) -> Response<Body> {
+    let parts = stream::once(Ok(multipart_part(value)))
+        .chain(subsequent_results.map(|result| multipart_part(&SubsequentResponse(result))))
+        .chain(stream::once(Ok(format!("\r\n--{}--\r\n", MULTIPART_BOUNDARY))))
+        .map_err(|_| "Failed to receive subsequent query results");
+
+    Response::builder()
+        .status(status_code)
+        .header(
+            "Content-Type",
+            format!("multipart/mixed; boundary=\"{}\"", MULTIPART_BOUNDARY).as_str(),
+        )
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Access-Control-Allow-Headers", "Content-Type")
+        .body(Body::wrap_stream(parts))
+        .unwrap()
+}
+
+/// Serializes a value into a part of a multipart response.
+fn multipart_part<T: Serialize>(value: &T) -> String {
+    let json = serde_json::to_string(value).expect("Failed to serialize GraphQL response to JSON");
+    format!(
+        "\r\n--{}\r\nContent-Type: application/json; charset=utf-8\r\n\r\n{}",
+        MULTIPART_BOUNDARY, json
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use super::GraphQLResponse;
-    use futures::sync::oneshot;
+    use super::{GraphQLBatchResponse, GraphQLResponse};
+    use futures::prelude::*;
+    use futures::sync::{mpsc, oneshot};
     use graph::components::server::GraphQLServerError;
     use graph::prelude::*;
     use graphql_parser;
     use http::status::StatusCode;
+    use serde_json;
     use std::collections::BTreeMap;
     use tokio_core::reactor::Core;
 
@@ -192,6 +385,32 @@ mod tests {
         assert_eq!(message, "Something went wrong");
     }
 
+    #[test]
+    fn generates_valid_json_for_field_errors() {
+        let mut core = Core::new().unwrap();
+        let mut query_result = QueryResult::new(None);
+        query_result.add_error(QueryError::FieldError(
+            QueryExecutionError::ResolveEntitiesError(String::from("Store unavailable")),
+            vec![
+                PathSegment::Field(String::from("users")),
+                PathSegment::Index(0),
+            ],
+            Some(graphql_parser::Pos { line: 2, column: 3 }),
+        ));
+        let future = GraphQLResponse::new(Ok(query_result));
+        let response = core.run(future).expect("Should generate a response");
+        let errors =
+            test_utils::assert_error_response(&mut core, response, StatusCode::BAD_REQUEST);
+
+        let expected: serde_json::Value = serde_json::from_str(
+            "{ \"locations\": [{ \"line\": 2, \"column\": 3 }], \
+             \"path\": [\"users\", 0], \
+             \"message\": \"Failed to resolve entities: Store unavailable\", \
+             \"extensions\": { \"code\": \"STORE_ERROR\" } }",
+        ).unwrap();
+        assert_eq!(errors, vec![expected]);
+    }
+
     #[test]
     fn generates_valid_json_for_query_error() {
         let mut core = Core::new().unwrap();
@@ -272,4 +491,119 @@ mod tests {
 
         assert_eq!(message, "Something went wrong");
     }
+
+    #[test]
+    fn generates_arrays_for_batches() {
+        let mut core = Core::new().unwrap();
+        let data = graphql_parser::query::Value::Object(BTreeMap::new());
+        let future = GraphQLBatchResponse::new(vec![
+            Ok(QueryResult::new(Some(data))),
+            Err(GraphQLServerError::ClientError(String::from("foo"))),
+        ]);
+        let response = core.run(future).expect("Should generate a response");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let chunk = core.run(response.into_body().concat2())
+            .expect("Truncated response body");
+        let json: serde_json::Value =
+            serde_json::from_slice(&chunk).expect("GraphQL response is not valid JSON");
+        let expected: serde_json::Value =
+            serde_json::from_str(
+                "[{ \"data\": {} }, \
+                 { \"errors\": [{ \"message\": \"foo\", \
+                 \"extensions\": { \"code\": \"BAD_REQUEST\" } }] }]",
+            ).unwrap();
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn includes_traces_in_extensions() {
+        let mut core = Core::new().unwrap();
+        let mut query_result =
+            QueryResult::new(Some(graphql_parser::query::Value::Object(BTreeMap::new())));
+        query_result.trace = Some(QueryTracer::new().finish());
+        let future = GraphQLResponse::new(Ok(query_result));
+        let response = core.run(future).expect("Should generate a response");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let chunk = core.run(response.into_body().concat2())
+            .expect("Truncated response body");
+        let json: serde_json::Value =
+            serde_json::from_slice(&chunk).expect("GraphQL response is not valid JSON");
+        let tracing = json.get("extensions")
+            .and_then(|extensions| extensions.get("tracing"))
+            .expect("Response contains no trace");
+
+        assert_eq!(tracing.get("version"), Some(&serde_json::Value::from(1)));
+        assert!(tracing.get("startTime").unwrap().is_string());
+        assert!(tracing.get("endTime").unwrap().is_string());
+        assert!(tracing.get("duration").unwrap().is_u64());
+        assert_eq!(
+            tracing.get("execution"),
+            Some(&serde_json::from_str("{ \"resolvers\": [] }").unwrap())
+        );
+        assert_eq!(tracing.get("storeQueries"), Some(&serde_json::Value::Array(vec![])));
+    }
+
+    #[test]
+    fn generates_multipart_responses_for_subsequent_results() {
+        let mut core = Core::new().unwrap();
+        let (sender, receiver) = mpsc::channel(1);
+        sender
+            .send(IncrementalResult {
+                data: None,
+                items: Some(vec![graphql_parser::query::Value::String(String::from("b"))]),
+                path: vec![PathSegment::Field(String::from("list")), PathSegment::Index(1)],
+                label: Some(String::from("rest")),
+                errors: None,
+                has_next: false,
+            })
+            .wait()
+            .unwrap();
+
+        let mut query_result =
+            QueryResult::new(Some(graphql_parser::query::Value::Object(BTreeMap::new())));
+        query_result.subsequent_results = Some(receiver);
+        let future = GraphQLResponse::new(Ok(query_result));
+        let response = core.run(future).expect("Should generate a response");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            "multipart/mixed; boundary=\"-\""
+        );
+
+        let chunk = core.run(response.into_body().concat2())
+            .expect("Truncated response body");
+        assert_eq!(
+            String::from_utf8(chunk.to_vec()).unwrap(),
+            "\r\n---\r\nContent-Type: application/json; charset=utf-8\r\n\r\n\
+             {\"data\":{},\"hasNext\":true}\
+             \r\n---\r\nContent-Type: application/json; charset=utf-8\r\n\r\n\
+             {\"incremental\":[{\"items\":[\"b\"],\"path\":[\"list\",1],\"label\":\"rest\"}],\
+             \"hasNext\":false}\
+             \r\n-----\r\n"
+        );
+    }
+
+    #[test]
+    fn rejects_subsequent_results_in_batches() {
+        let mut core = Core::new().unwrap();
+        let (_sender, receiver) = mpsc::channel(1);
+        let mut query_result =
+            QueryResult::new(Some(graphql_parser::query::Value::Object(BTreeMap::new())));
+        query_result.subsequent_results = Some(receiver);
+        let future = GraphQLBatchResponse::new(vec![Ok(query_result)]);
+        let response = core.run(future).expect("Should generate a response");
+
+        let chunk = core.run(response.into_body().concat2())
+            .expect("Truncated response body");
+        let json: serde_json::Value =
+            serde_json::from_slice(&chunk).expect("GraphQL response is not valid JSON");
+        let expected: serde_json::Value = serde_json::from_str(
+            "[{ \"errors\": [{ \"message\": \
+             \"@defer and @stream are not supported in batched requests\", \
+             \"extensions\": { \"code\": \"BAD_REQUEST\" } }] }]",
+        ).unwrap();
+        assert_eq!(json, expected);
+    }
 }
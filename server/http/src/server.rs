@@ -1,12 +1,18 @@
 use futures::future;
 use futures::prelude::*;
-use futures::sync::mpsc::{channel, Receiver, Sender};
+use futures::sync::mpsc::{channel, unbounded, Receiver, Sender, UnboundedSender};
 use hyper;
 use hyper::Server;
 use slog;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::io;
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio;
+use tokio::net::TcpListener;
 use tokio_core::reactor::Handle;
 
 use graph::components::schema::SchemaProviderEvent;
@@ -16,12 +22,17 @@ use graph::data::schema::Schema;
 use graph::prelude::GraphQLServer as GraphQLServerTrait;
 use graph::util::stream::StreamError;
 
+use dispatch::QueryDispatcher;
+use persisted_queries::{PersistedQueries, DEFAULT_MAX_PERSISTED_QUERIES};
 use service::GraphQLService;
+use subscription::serve_subscriptions;
 
 /// Errors that may occur when starting the server.
 #[derive(Debug)]
 pub enum GraphQLServeError {
     OrphanError,
+    BindError(io::Error),
+    HttpBindError(hyper::Error),
 }
 
 impl Error for GraphQLServeError {
@@ -36,35 +47,83 @@ impl Error for GraphQLServeError {
 
 impl fmt::Display for GraphQLServeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "OrphanError: No component set up to handle the queries")
+        match self {
+            GraphQLServeError::OrphanError => {
+                write!(f, "OrphanError: No component set up to handle the queries")
+            }
+            GraphQLServeError::BindError(e) => write!(f, "BindError: {}", e),
+            GraphQLServeError::HttpBindError(e) => write!(f, "HttpBindError: {}", e),
+        }
+    }
+}
+
+/// Settings for the GraphQL server.
+#[derive(Clone, Debug)]
+pub struct GraphQLServerConfig {
+    /// Address to serve GraphQL over HTTP on.
+    pub http_addr: SocketAddr,
+    /// Address to serve GraphQL subscriptions over WebSockets on.
+    pub ws_addr: SocketAddr,
+    /// The largest request body, in bytes, that the server accepts.
+    pub max_body_size: usize,
+    /// How long to wait for the result of a query before failing it.
+    pub query_timeout: Duration,
+    /// How many queries may wait for or be processed by the query runner
+    /// before further queries are rejected.
+    pub max_queued_queries: usize,
+    /// The largest number of operations a batched request may contain.
+    pub max_batch_size: usize,
+}
+
+impl Default for GraphQLServerConfig {
+    fn default() -> Self {
+        GraphQLServerConfig {
+            http_addr: "0.0.0.0:8000".parse().unwrap(),
+            ws_addr: "0.0.0.0:8001".parse().unwrap(),
+            max_body_size: 1024 * 1024,
+            query_timeout: Duration::from_secs(60),
+            max_queued_queries: 100,
+            max_batch_size: 10,
+        }
     }
 }
 
 /// A GraphQL server based on Hyper.
 pub struct GraphQLServer {
+    config: GraphQLServerConfig,
     logger: slog::Logger,
     query_sink: Option<Sender<Query>>,
     schema_provider_event_sink: Sender<SchemaProviderEvent>,
     store_event_sink: Sender<StoreEvent>,
     runtime: Handle,
     schema: Arc<Mutex<Option<Schema>>>,
+    /// The API schemas of the individual subgraphs, by subgraph ID.
+    subgraph_schemas: Arc<Mutex<HashMap<String, Schema>>>,
+    subscribers: Arc<Mutex<Vec<UnboundedSender<Arc<StoreEvent>>>>>,
+    persisted_queries: Arc<Mutex<PersistedQueries>>,
 }
 
 impl GraphQLServer {
     /// Creates a new GraphQL server.
-    pub fn new(logger: &slog::Logger, runtime: Handle) -> Self {
+    pub fn new(logger: &slog::Logger, runtime: Handle, config: GraphQLServerConfig) -> Self {
         // Create channels for handling incoming events from the schema provider and the store
         let (store_sink, store_stream) = channel(100);
         let (schema_provider_sink, schema_provider_stream) = channel(100);
 
         // Create a new GraphQL server
         let mut server = GraphQLServer {
+            config,
             logger: logger.new(o!("component" => "GraphQLServer")),
             query_sink: None,
             schema_provider_event_sink: schema_provider_sink,
             store_event_sink: store_sink,
             runtime,
             schema: Arc::new(Mutex::new(None)),
+            subgraph_schemas: Arc::new(Mutex::new(HashMap::new())),
+            subscribers: Arc::new(Mutex::new(vec![])),
+            persisted_queries: Arc::new(Mutex::new(PersistedQueries::new(
+                DEFAULT_MAX_PERSISTED_QUERIES,
+            ))),
         };
 
         // Spawn tasks to handle incoming events from the schema provider and store
@@ -75,15 +134,117 @@ impl GraphQLServer {
         server
     }
 
+    /// Replaces the persisted queries the server looks up query hashes in,
+    /// e.g. with an allowlist loaded from a file.
+    pub fn set_persisted_queries(&mut self, persisted_queries: PersistedQueries) {
+        self.persisted_queries = Arc::new(Mutex::new(persisted_queries));
+    }
+
+    /// Like `serve`, but stops accepting connections once `signal` resolves.
+    /// The returned task finishes after responding to in-flight requests.
+    pub fn serve_until<F>(
+        &mut self,
+        signal: F,
+    ) -> Result<Box<Future<Item = (), Error = ()> + Send>, GraphQLServeError>
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        let logger = self.logger.clone();
+        let signal = signal.shared();
+
+        // Only launch the GraphQL server if there is a component that will handle incoming queries
+        let query_sink = self.query_sink
+            .as_ref()
+            .ok_or(GraphQLServeError::OrphanError)?;
+
+        // On every incoming request, launch a new GraphQL service that writes
+        // incoming queries to the query sink. The dispatcher is shared so that
+        // all services and subscriptions count against the same queue.
+        let dispatcher = QueryDispatcher::new(query_sink.clone(), self.config.max_queued_queries);
+        let subscription_dispatcher = dispatcher.clone();
+        let schema = self.schema.clone();
+        let subgraph_schemas = self.subgraph_schemas.clone();
+        let persisted_queries = self.persisted_queries.clone();
+        let config = self.config.clone();
+        let subscription_schema = schema.clone();
+        let new_service = move || {
+            let service = GraphQLService::new(
+                schema.clone(),
+                subgraph_schemas.clone(),
+                dispatcher.clone(),
+                persisted_queries.clone(),
+                config.clone(),
+            );
+            future::ok::<GraphQLService, hyper::Error>(service)
+        };
+
+        // Create a task to run the server and handle HTTP requests
+        let task = Server::try_bind(&self.config.http_addr)
+            .map_err(GraphQLServeError::HttpBindError)?
+            .serve(new_service)
+            .with_graceful_shutdown(signal.clone().then(|_| Ok::<_, ()>(())))
+            .map_err({
+                let logger = logger.clone();
+                move |e| error!(logger, "Server error"; "error" => format!("{}", e))
+            });
+
+        // Subscriptions are served over WebSocket connections
+        let listener =
+            TcpListener::bind(&self.config.ws_addr).map_err(GraphQLServeError::BindError)?;
+
+        // Serve each incoming connection in its own task, feeding it store
+        // events through a new subscriber channel
+        let subscribers = self.subscribers.clone();
+        let subscription_task = listener
+            .incoming()
+            .then({
+                let logger = logger.clone();
+                move |result| {
+                    if let Err(ref e) = result {
+                        warn!(logger, "Failed to accept connection"; "error" => format!("{}", e));
+                    }
+                    Ok::<_, ()>(result.ok())
+                }
+            })
+            .filter_map(|stream| stream)
+            .for_each(move |stream| {
+                let (subscriber, store_events) = unbounded();
+                subscribers.lock().unwrap().push(subscriber);
+
+                tokio::spawn(serve_subscriptions(
+                    logger.clone(),
+                    stream,
+                    subscription_schema.clone(),
+                    subscription_dispatcher.clone(),
+                    store_events,
+                ));
+                Ok(())
+            })
+            // Stop accepting subscriptions on shutdown as well
+            .select(signal.then(|_| Ok(())))
+            .then(|_| Ok(()));
+
+        Ok(Box::new(task.join(subscription_task).map(|_| ())))
+    }
+
     /// Handle incoming events from the schema provider
     fn handle_schema_provider_events(&mut self, stream: Receiver<SchemaProviderEvent>) {
         let logger = self.logger.clone();
         let schema = self.schema.clone();
+        let subgraph_schemas = self.subgraph_schemas.clone();
 
         self.runtime.spawn(stream.for_each(move |event| {
             info!(logger, "Received schema provider event");
 
-            let SchemaProviderEvent::SchemaChanged(new_schema) = event;
+            let SchemaProviderEvent::SchemaChanged(new_schema, new_subgraph_schemas) = event;
+
+            // Keep the schema of each subgraph around for the endpoints
+            // that serve individual subgraphs
+            *subgraph_schemas.lock().unwrap() = new_subgraph_schemas
+                .into_iter()
+                .map(|subgraph_schema| (subgraph_schema.id.clone(), subgraph_schema))
+                .collect();
+
             let mut schema = schema.lock().unwrap();
             *schema = new_schema;
 
@@ -94,9 +255,19 @@ impl GraphQLServer {
     // Handle incoming events from the store
     fn handle_store_events(&mut self, stream: Receiver<StoreEvent>) {
         let logger = self.logger.clone();
+        let subscribers = self.subscribers.clone();
 
         self.runtime.spawn(stream.for_each(move |event| {
             info!(logger, "Received store event"; "event" => format!("{:?}",  event));
+
+            // Forward the event to all subscription connections, dropping
+            // those that have been closed
+            let event = Arc::new(event);
+            subscribers
+                .lock()
+                .unwrap()
+                .retain(|subscriber| subscriber.unbounded_send(event.clone()).is_ok());
+
             Ok(())
         }));
     }
@@ -118,7 +289,7 @@ impl GraphQLServerTrait for GraphQLServer {
         match self.query_sink {
             Some(_) => Err(StreamError::AlreadyCreated),
             None => {
-                let (sink, stream) = channel(100);
+                let (sink, stream) = channel(self.config.max_queued_queries);
                 self.query_sink = Some(sink);
                 Ok(stream)
             }
@@ -126,30 +297,6 @@ impl GraphQLServerTrait for GraphQLServer {
     }
 
     fn serve(&mut self) -> Result<Box<Future<Item = (), Error = ()> + Send>, Self::ServeError> {
-        let logger = self.logger.clone();
-
-        // We will listen on port 8000
-        let addr = "0.0.0.0:8000".parse().unwrap();
-
-        // Only launch the GraphQL server if there is a component that will handle incoming queries
-        let query_sink = self.query_sink
-            .as_ref()
-            .ok_or(GraphQLServeError::OrphanError)?;
-
-        // On every incoming request, launch a new GraphQL service that writes
-        // incoming queries to the query sink.
-        let query_sink = query_sink.clone();
-        let schema = self.schema.clone();
-        let new_service = move || {
-            let service = GraphQLService::new(schema.clone(), query_sink.clone());
-            future::ok::<GraphQLService, hyper::Error>(service)
-        };
-
-        // Create a task to run the server and handle HTTP requests
-        let task = Server::bind(&addr)
-            .serve(new_service)
-            .map_err(move |e| error!(logger, "Server error"; "error" => format!("{}", e)));
-
-        Ok(Box::new(task))
+        self.serve_until(future::empty())
     }
 }
@@ -1,31 +1,70 @@
-use futures::future;
+use futures::future::{self, Either};
 use futures::prelude::*;
-use futures::sync::mpsc::Sender;
 use hyper::service::Service;
-use hyper::{Body, Method, Request, Response, StatusCode};
+use graphql_parser::query as q;
+use hyper::{Body, Chunk, Method, Request, Response, StatusCode};
+use serde_json;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::timer::Delay;
 
 use graph::components::server::GraphQLServerError;
 use graph::prelude::*;
+use graph_graphql::query::ast as qast;
+use graph_graphql::schema::print_schema;
 
+use dispatch::QueryDispatcher;
+use persisted_queries::PersistedQueries;
 use request::GraphQLRequest;
-use response::GraphQLResponse;
+use response::{GraphQLBatchResponse, GraphQLResponse};
+use server::GraphQLServerConfig;
 
 /// An asynchronous response to a GraphQL request.
 pub type GraphQLServiceResponse =
     Box<Future<Item = Response<Body>, Error = GraphQLServerError> + Send>;
 
-/// A Hyper Service that serves GraphQL over a POST / endpoint.
-#[derive(Debug)]
+/// The status reported on GET /status.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerStatus {
+    query_queue: QueueStatus,
+}
+
+/// How many queries are pending and how many may be.
+#[derive(Serialize)]
+struct QueueStatus {
+    depth: usize,
+    capacity: usize,
+}
+
+/// A Hyper Service that serves GraphQL over a /graphql endpoint.
+#[derive(Clone, Debug)]
 pub struct GraphQLService {
     schema: Arc<Mutex<Option<Schema>>>,
-    query_sink: Sender<Query>,
+    /// The API schemas of the individual subgraphs, by subgraph ID.
+    subgraph_schemas: Arc<Mutex<HashMap<String, Schema>>>,
+    dispatcher: QueryDispatcher,
+    persisted_queries: Arc<Mutex<PersistedQueries>>,
+    config: GraphQLServerConfig,
 }
 
 impl GraphQLService {
     /// Creates a new GraphQL service.
-    pub fn new(schema: Arc<Mutex<Option<Schema>>>, query_sink: Sender<Query>) -> Self {
-        GraphQLService { schema, query_sink }
+    pub fn new(
+        schema: Arc<Mutex<Option<Schema>>>,
+        subgraph_schemas: Arc<Mutex<HashMap<String, Schema>>>,
+        dispatcher: QueryDispatcher,
+        persisted_queries: Arc<Mutex<PersistedQueries>>,
+        config: GraphQLServerConfig,
+    ) -> Self {
+        GraphQLService {
+            schema,
+            subgraph_schemas,
+            dispatcher,
+            persisted_queries,
+            config,
+        }
     }
 
     /// Serves a GraphiQL index.html.
@@ -38,34 +77,233 @@ impl GraphQLService {
         ))
     }
 
-    /// Handles GraphQL queries received via POST /.
-    fn handle_graphql_query(&self, request: Request<Body>) -> GraphQLServiceResponse {
-        let query_sink = self.query_sink.clone();
-        let schema = self.schema.clone();
+    /// Serves an API schema as SDL, or a 404 if there is no schema yet.
+    fn serve_schema(&self, schema: Option<Schema>) -> GraphQLServiceResponse {
+        match schema {
+            Some(schema) => Box::new(future::ok(
+                Response::builder()
+                    .status(200)
+                    .header("Content-Type", "application/graphql")
+                    .header("Access-Control-Allow-Origin", "*")
+                    .body(Body::from(print_schema(&schema.document)))
+                    .unwrap(),
+            )),
+            None => Box::new(future::ok(
+                Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Body::from("No schema available"))
+                    .unwrap(),
+            )),
+        }
+    }
+
+    /// Handles GraphQL queries received via POST /graphql. The body contains
+    /// either a single operation or a JSON array of operations.
+    fn handle_graphql_query(
+        &self,
+        request: Request<Body>,
+        schema: Option<Schema>,
+    ) -> GraphQLServiceResponse {
+        let service = self.clone();
+        let max_body_size = self.config.max_body_size;
 
         Box::new(
             request
                 .into_body()
-                .concat2()
                 .map_err(|_| GraphQLServerError::from("Failed to read request body"))
-                .and_then(move |body| {
-                    let schema = schema.lock().unwrap();
-                    GraphQLRequest::new(body, schema.clone())
-                })
-                .and_then(move |(query, receiver)| {
-                    // Forward the query to the system
-                    query_sink
-                        .send(query)
-                        .wait()
-                        .expect("Failed to forward incoming query");
-
-                    // Continue with waiting to receive a result
-                    receiver.map_err(|e| GraphQLServerError::from(e))
+                // Stop reading bodies as soon as they exceed the limit
+                .fold(Chunk::default(), move |mut body, chunk| {
+                    if body.len() + chunk.len() > max_body_size {
+                        return Err(GraphQLServerError::PayloadTooLarge(max_body_size));
+                    }
+                    body.extend(chunk);
+                    Ok(body)
                 })
+                .then(move |result| -> GraphQLServiceResponse {
+                    let body = match result {
+                        Ok(body) => body,
+                        Err(e) => return Box::new(GraphQLResponse::new(Err(e))),
+                    };
+
+                    match serde_json::from_slice(&body) {
+                        Ok(serde_json::Value::Array(operations)) => {
+                            service.handle_graphql_batch(operations, schema)
+                        }
+                        _ => {
+                            let request = GraphQLRequest::new(
+                                body,
+                                schema,
+                                service.persisted_queries.clone(),
+                            );
+                            Box::new(
+                                service
+                                    .run_query(request, false)
+                                    .then(|result| GraphQLResponse::new(result)),
+                            )
+                        }
+                    }
+                }),
+        )
+    }
+
+    /// Handles batches of GraphQL operations. The operations are run
+    /// independently and their results are returned in the same order.
+    fn handle_graphql_batch(
+        &self,
+        operations: Vec<serde_json::Value>,
+        schema: Option<Schema>,
+    ) -> GraphQLServiceResponse {
+        if operations.is_empty() {
+            return Box::new(GraphQLResponse::new(Err(GraphQLServerError::ClientError(
+                String::from("Batched request contains no operations"),
+            ))));
+        }
+        if operations.len() > self.config.max_batch_size {
+            return Box::new(GraphQLResponse::new(Err(GraphQLServerError::ClientError(
+                format!(
+                    "Batched request contains more than {} operations",
+                    self.config.max_batch_size
+                ),
+            ))));
+        }
+
+        let results: Vec<_> = operations
+            .into_iter()
+            .map(|operation| {
+                let request = GraphQLRequest::from_json(
+                    operation,
+                    schema.clone(),
+                    self.persisted_queries.clone(),
+                );
+
+                // Errors only fail the operation they occur in
+                self.run_query(request, false)
+                    .then(|result| Ok::<_, GraphQLServerError>(result))
+            })
+            .collect();
+
+        Box::new(future::join_all(results).and_then(GraphQLBatchResponse::new))
+    }
+
+    /// Handles GraphQL queries received via GET /graphql?query=..., which
+    /// HTTP caches can store. Mutations are rejected.
+    fn handle_graphql_get(
+        &self,
+        request: Request<Body>,
+        schema: Option<Schema>,
+    ) -> GraphQLServiceResponse {
+        let request = GraphQLRequest::from_query_string(
+            request.uri().query().unwrap_or(""),
+            schema,
+            self.persisted_queries.clone(),
+        );
+
+        Box::new(
+            self.run_query(request, true)
                 .then(|result| GraphQLResponse::new(result)),
         )
     }
 
+    /// Forwards the query of a request to the system and waits for its result.
+    fn run_query(
+        &self,
+        request: GraphQLRequest,
+        read_only: bool,
+    ) -> Box<Future<Item = QueryResult, Error = GraphQLServerError> + Send> {
+        let dispatcher = self.dispatcher.clone();
+        let query_timeout = self.config.query_timeout;
+
+        Box::new(
+            request
+                .and_then(move |(mut query, receiver)| {
+                    if read_only && is_mutation(&query) {
+                        return Err(GraphQLServerError::ClientError(String::from(
+                            "Mutations cannot be sent via GET",
+                        )));
+                    }
+
+                    // The query, including any subsequent results, has to
+                    // finish within the timeout
+                    let deadline = Instant::now() + query_timeout;
+                    query.deadline = Some(deadline);
+
+                    // Forward the query to the system, unless too many queries
+                    // are pending already
+                    dispatcher.dispatch(query)?;
+
+                    Ok((receiver, deadline))
+                })
+                // Continue with waiting to receive a result, but not forever
+                .and_then(move |(receiver, deadline)| {
+                    let timeout = Delay::new(deadline);
+                    receiver
+                        .map_err(|e| GraphQLServerError::from(e))
+                        .select2(timeout)
+                        .then(move |result| match result {
+                            Ok(Either::A((result, _))) => Ok(result),
+                            Ok(Either::B(_)) => Err(GraphQLServerError::Timeout(query_timeout)),
+                            Err(Either::A((e, _))) => Err(e),
+                            Err(Either::B((e, _))) => Err(GraphQLServerError::InternalError(
+                                format!("Failed to time out query: {}", e),
+                            )),
+                        })
+                }),
+        )
+    }
+
+    /// Reports the number of pending queries, for monitoring.
+    fn serve_status(&self) -> GraphQLServiceResponse {
+        let status = ServerStatus {
+            query_queue: QueueStatus {
+                depth: self.dispatcher.depth(),
+                capacity: self.dispatcher.capacity(),
+            },
+        };
+        let json = serde_json::to_string(&status).expect("Failed to serialize server status");
+
+        Box::new(future::ok(
+            Response::builder()
+                .status(200)
+                .header("Content-Type", "application/json")
+                .body(Body::from(json))
+                .unwrap(),
+        ))
+    }
+
+    /// Handles requests to the endpoints of individual subgraphs, which are
+    /// served below /subgraphs/id/<id>.
+    fn handle_subgraph_request(&self, request: Request<Body>) -> GraphQLServiceResponse {
+        let (id, path) = {
+            let path = request.uri().path().trim_left_matches("/subgraphs/id/");
+            match path.find('/') {
+                Some(i) => (path[..i].to_owned(), path[i..].to_owned()),
+                None => (path.to_owned(), String::new()),
+            }
+        };
+
+        let schema = match self.subgraph_schemas.lock().unwrap().get(&id) {
+            Some(schema) => schema.clone(),
+            None => return self.handle_not_found(request),
+        };
+
+        match (request.method(), path.as_str()) {
+            // GraphiQL
+            (&Method::GET, "") | (&Method::GET, "/") => {
+                self.serve_file(include_str!("../assets/index.html"))
+            }
+
+            // GraphQL queries against the subgraph
+            (&Method::POST, "/graphql") => self.handle_graphql_query(request, Some(schema)),
+            (&Method::GET, "/graphql") => self.handle_graphql_get(request, Some(schema)),
+            (&Method::OPTIONS, "/graphql") => self.handle_graphql_options(request),
+
+            // The API schema of the subgraph as SDL
+            (&Method::GET, "/graphql/schema.graphql") => self.serve_schema(Some(schema)),
+
+            _ => self.handle_not_found(request),
+        }
+    }
+
     // Handles OPTIONS requests
     fn handle_graphql_options(&self, _request: Request<Body>) -> GraphQLServiceResponse {
         Box::new(future::ok(
@@ -89,6 +327,15 @@ impl GraphQLService {
     }
 }
 
+/// Returns true if the operation a query executes is a mutation.
+fn is_mutation(query: &Query) -> bool {
+    let operation_name = query.operation_name.as_ref().map(|name| name.as_str());
+    match qast::get_operation(&query.document, operation_name) {
+        Ok(q::OperationDefinition::Mutation(_)) => true,
+        _ => false,
+    }
+}
+
 impl Service for GraphQLService {
     type ReqBody = Body;
     type ResBody = Body;
@@ -106,12 +353,33 @@ impl Service for GraphQLService {
                 self.serve_file(include_str!("../assets/graphiql.min.js"))
             }
 
-            // POST / receives GraphQL queries
-            (&Method::POST, "/graphql") => self.handle_graphql_query(req),
+            // POST /graphql receives GraphQL queries, either one or a batch
+            (&Method::POST, "/graphql") => {
+                let schema = self.schema.lock().unwrap().clone();
+                self.handle_graphql_query(req, schema)
+            }
+
+            // GET /graphql receives GraphQL queries in the query string
+            (&Method::GET, "/graphql") => {
+                let schema = self.schema.lock().unwrap().clone();
+                self.handle_graphql_get(req, schema)
+            }
+
+            // GET /graphql/schema.graphql serves the API schema as SDL
+            (&Method::GET, "/graphql/schema.graphql") => {
+                let schema = self.schema.lock().unwrap().clone();
+                self.serve_schema(schema)
+            }
 
             // OPTIONS / allows to check for GraphQL HTTP features
             (&Method::OPTIONS, "/graphql") => self.handle_graphql_options(req),
 
+            // GET /status reports how many queries are pending
+            (&Method::GET, "/status") => self.serve_status(),
+
+            // Each subgraph can also be queried on its own
+            (_, path) if path.starts_with("/subgraphs/id/") => self.handle_subgraph_request(req),
+
             // Everything else results in a 404
             _ => self.handle_not_found(req),
         }
@@ -125,18 +393,61 @@ mod tests {
     use graphql_parser;
     use graphql_parser::query::Value;
     use http::status::StatusCode;
+    use hyper::header::RETRY_AFTER;
     use hyper::service::Service;
     use hyper::{Body, Method, Request};
-    use std::collections::BTreeMap;
+    use serde_json;
+    use std::collections::{BTreeMap, HashMap};
     use std::sync::{Arc, Mutex};
+    use std::time::Duration;
     use tokio_core::reactor::Core;
 
-    use graph::data::query::QueryResult;
+    use graph::data::query::{Query, QueryResult};
     use graph::data::schema::Schema;
 
     use super::GraphQLService;
+    use dispatch::QueryDispatcher;
+    use persisted_queries::PersistedQueries;
+    use server::GraphQLServerConfig;
     use test_utils;
 
+    /// Creates a service whose queries all resolve to `{ name: <operation name> }`.
+    fn echo_operation_name_service(core: &Core) -> GraphQLService {
+        let schema = Arc::new(Mutex::new(Some(Schema {
+            id: "test-schema".to_string(),
+            document: graphql_parser::parse_schema(
+                "\
+                 scalar String \
+                 type Query { name: String } \
+                 ",
+            ).unwrap(),
+        })));
+        let (query_sink, query_stream) = channel::<Query>(10);
+        let subgraph_schemas = Arc::new(Mutex::new(HashMap::new()));
+        let persisted_queries = Arc::new(Mutex::new(PersistedQueries::new(10)));
+
+        core.handle().spawn(
+            query_stream
+                .for_each(move |query| {
+                    let mut map = BTreeMap::new();
+                    let name = query.operation_name.clone().unwrap_or_default();
+                    map.insert("name".to_string(), Value::String(name));
+                    let result = QueryResult::new(Some(Value::Object(map)));
+                    query.result_sender.send(result).unwrap();
+                    Ok(())
+                })
+                .fuse(),
+        );
+
+        GraphQLService::new(
+            schema,
+            subgraph_schemas,
+            QueryDispatcher::new(query_sink, 10),
+            persisted_queries,
+            GraphQLServerConfig::default(),
+        )
+    }
+
     #[test]
     fn posting_invalid_query_yields_error_response() {
         let mut core = Core::new().unwrap();
@@ -151,7 +462,15 @@ mod tests {
             ).unwrap(),
         })));
         let (query_sink, _) = channel(1);
-        let mut service = GraphQLService::new(schema, query_sink);
+        let subgraph_schemas = Arc::new(Mutex::new(HashMap::new()));
+        let persisted_queries = Arc::new(Mutex::new(PersistedQueries::new(10)));
+        let mut service = GraphQLService::new(
+            schema,
+            subgraph_schemas,
+            QueryDispatcher::new(query_sink, 10),
+            persisted_queries,
+            GraphQLServerConfig::default(),
+        );
 
         let request = Request::builder()
             .method(Method::POST)
@@ -189,7 +508,15 @@ mod tests {
             ).unwrap(),
         })));
         let (query_sink, query_stream) = channel(1);
-        let mut service = GraphQLService::new(schema, query_sink);
+        let subgraph_schemas = Arc::new(Mutex::new(HashMap::new()));
+        let persisted_queries = Arc::new(Mutex::new(PersistedQueries::new(10)));
+        let mut service = GraphQLService::new(
+            schema,
+            subgraph_schemas,
+            QueryDispatcher::new(query_sink, 10),
+            persisted_queries,
+            GraphQLServerConfig::default(),
+        );
 
         core.handle().spawn(
             query_stream
@@ -222,4 +549,333 @@ mod tests {
             .expect("Query result field \"name\" is not a string");
         assert_eq!(name, "Jordi".to_string());
     }
+
+    #[test]
+    fn posting_batches_yields_results_in_order() {
+        let mut core = Core::new().unwrap();
+        let mut service = echo_operation_name_service(&core);
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://localhost:8000/graphql")
+            .body(Body::from(
+                "[\
+                 {\"query\": \"query a { name }\", \"operationName\": \"a\"}, \
+                 {\"query\": \"{\"}, \
+                 {\"query\": \"query b { name }\", \"operationName\": \"b\"}\
+                 ]",
+            ))
+            .unwrap();
+
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let chunk = core.run(response.into_body().concat2())
+            .expect("Truncated response body");
+        let json: serde_json::Value =
+            serde_json::from_slice(&chunk).expect("GraphQL response is not valid JSON");
+        let results = json.as_array().expect("Batch response is not an array");
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["data"]["name"], "a");
+        assert!(results[1]["errors"].is_array());
+        assert_eq!(results[2]["data"]["name"], "b");
+    }
+
+    #[test]
+    fn getting_queries_yields_result_response() {
+        let mut core = Core::new().unwrap();
+        let mut service = echo_operation_name_service(&core);
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("http://localhost:8000/graphql?query=query%20a%20%7B%20name%20%7D&operationName=a")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        let data = test_utils::assert_successful_response(&mut core, response);
+        assert_eq!(data["name"], "a");
+    }
+
+    #[test]
+    fn getting_mutations_yields_error_response() {
+        let mut core = Core::new().unwrap();
+        let mut service = echo_operation_name_service(&core);
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("http://localhost:8000/graphql?query=mutation%20%7B%20name%20%7D")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        let errors =
+            test_utils::assert_error_response(&mut core, response, StatusCode::BAD_REQUEST);
+        assert_eq!(errors[0]["message"], "Mutations cannot be sent via GET");
+    }
+
+    #[test]
+    fn queries_to_subgraph_endpoints_run_against_the_subgraph_schema() {
+        let mut core = Core::new().unwrap();
+
+        let schema = |id: &str| Schema {
+            id: id.to_string(),
+            document: graphql_parser::parse_schema(
+                "\
+                 scalar String \
+                 type Query { name: String } \
+                 ",
+            ).unwrap(),
+        };
+        let mut subgraph_schemas = HashMap::new();
+        subgraph_schemas.insert("QmSubgraph".to_string(), schema("QmSubgraph"));
+
+        // Resolve all queries to the ID of the schema they run against
+        let (query_sink, query_stream) = channel::<Query>(10);
+        core.handle().spawn(
+            query_stream
+                .for_each(move |query| {
+                    let mut map = BTreeMap::new();
+                    map.insert("name".to_string(), Value::String(query.schema.id.clone()));
+                    let result = QueryResult::new(Some(Value::Object(map)));
+                    query.result_sender.send(result).unwrap();
+                    Ok(())
+                })
+                .fuse(),
+        );
+
+        let mut service = GraphQLService::new(
+            Arc::new(Mutex::new(Some(schema("QmSubgraph,QmOther")))),
+            Arc::new(Mutex::new(subgraph_schemas)),
+            QueryDispatcher::new(query_sink, 10),
+            Arc::new(Mutex::new(PersistedQueries::new(10))),
+            GraphQLServerConfig::default(),
+        );
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://localhost:8000/subgraphs/id/QmSubgraph/graphql")
+            .body(Body::from("{\"query\": \"{ name }\"}"))
+            .unwrap();
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        let data = test_utils::assert_successful_response(&mut core, response);
+        assert_eq!(data["name"], "QmSubgraph");
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://localhost:8000/graphql")
+            .body(Body::from("{\"query\": \"{ name }\"}"))
+            .unwrap();
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        let data = test_utils::assert_successful_response(&mut core, response);
+        assert_eq!(data["name"], "QmSubgraph,QmOther");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("http://localhost:8000/subgraphs/id/QmSubgraph")
+            .body(Body::empty())
+            .unwrap();
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://localhost:8000/subgraphs/id/QmUnknown/graphql")
+            .body(Body::from("{\"query\": \"{ name }\"}"))
+            .unwrap();
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn getting_the_schema_yields_sdl() {
+        let mut core = Core::new().unwrap();
+        let mut service = echo_operation_name_service(&core);
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("http://localhost:8000/graphql/schema.graphql")
+            .body(Body::empty())
+            .unwrap();
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            "application/graphql"
+        );
+
+        let body = core.run(response.into_body().concat2()).unwrap();
+        let sdl = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(sdl, "type Query {\n  name: String\n}\n");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("http://localhost:8000/subgraphs/id/QmUnknown/graphql/schema.graphql")
+            .body(Body::empty())
+            .unwrap();
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn posting_oversized_bodies_yields_error_response() {
+        let mut core = Core::new().unwrap();
+        let (query_sink, _query_stream) = channel(1);
+        let mut service = GraphQLService::new(
+            Arc::new(Mutex::new(None)),
+            Arc::new(Mutex::new(HashMap::new())),
+            QueryDispatcher::new(query_sink, 10),
+            Arc::new(Mutex::new(PersistedQueries::new(10))),
+            GraphQLServerConfig {
+                max_body_size: 16,
+                ..GraphQLServerConfig::default()
+            },
+        );
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://localhost:8000/graphql")
+            .body(Body::from("{\"query\": \"{ name }\"}"))
+            .unwrap();
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        let errors =
+            test_utils::assert_error_response(&mut core, response, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(errors[0]["message"], "Request body is larger than 16 bytes");
+        assert_eq!(errors[0]["extensions"]["code"], "BAD_REQUEST");
+    }
+
+    #[test]
+    fn posting_oversized_batches_yields_error_response() {
+        let mut core = Core::new().unwrap();
+        let (query_sink, _query_stream) = channel(1);
+        let mut service = GraphQLService::new(
+            Arc::new(Mutex::new(None)),
+            Arc::new(Mutex::new(HashMap::new())),
+            QueryDispatcher::new(query_sink, 10),
+            Arc::new(Mutex::new(PersistedQueries::new(10))),
+            GraphQLServerConfig {
+                max_batch_size: 2,
+                ..GraphQLServerConfig::default()
+            },
+        );
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://localhost:8000/graphql")
+            .body(Body::from(
+                "[{\"query\": \"{ name }\"}, {\"query\": \"{ name }\"}, {\"query\": \"{ name }\"}]",
+            ))
+            .unwrap();
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        let errors =
+            test_utils::assert_error_response(&mut core, response, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            errors[0]["message"],
+            "Batched request contains more than 2 operations"
+        );
+        assert_eq!(errors[0]["extensions"]["code"], "BAD_REQUEST");
+    }
+
+    #[test]
+    fn queries_without_results_time_out() {
+        let mut core = Core::new().unwrap();
+        let schema = Schema {
+            id: "test-schema".to_string(),
+            document: graphql_parser::parse_schema("scalar String type Query { name: String }")
+                .unwrap(),
+        };
+
+        // Keep queries around without ever sending their results
+        let (query_sink, _query_stream) = channel(1);
+        let mut service = GraphQLService::new(
+            Arc::new(Mutex::new(Some(schema))),
+            Arc::new(Mutex::new(HashMap::new())),
+            QueryDispatcher::new(query_sink, 10),
+            Arc::new(Mutex::new(PersistedQueries::new(10))),
+            GraphQLServerConfig {
+                query_timeout: Duration::from_millis(10),
+                ..GraphQLServerConfig::default()
+            },
+        );
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://localhost:8000/graphql")
+            .body(Body::from("{\"query\": \"{ name }\"}"))
+            .unwrap();
+        let response = core.run(service.call(request))
+            .expect("Should return a response");
+        let errors =
+            test_utils::assert_error_response(&mut core, response, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(errors[0]["message"], "Query did not finish within 10 ms");
+        assert_eq!(errors[0]["extensions"]["code"], "TIMEOUT");
+    }
+
+    #[test]
+    fn saturated_query_runner_yields_overload_response() {
+        let mut core = Core::new().unwrap();
+        let schema = Schema {
+            id: "test-schema".to_string(),
+            document: graphql_parser::parse_schema("scalar String type Query { name: String }")
+                .unwrap(),
+        };
+
+        // Only allow a single query to be pending at a time
+        let (query_sink, query_stream) = channel(10);
+        let mut service = GraphQLService::new(
+            Arc::new(Mutex::new(Some(schema))),
+            Arc::new(Mutex::new(HashMap::new())),
+            QueryDispatcher::new(query_sink, 1),
+            Arc::new(Mutex::new(PersistedQueries::new(10))),
+            GraphQLServerConfig::default(),
+        );
+        let query_request = || {
+            Request::builder()
+                .method(Method::POST)
+                .uri("http://localhost:8000/graphql")
+                .body(Body::from("{\"query\": \"{ name }\"}"))
+                .unwrap()
+        };
+
+        // Keep the first query pending by never sending its result
+        core.handle()
+            .spawn(service.call(query_request()).then(|_| Ok(())));
+        let (_pending_query, _query_stream) = core.run(query_stream.into_future())
+            .unwrap_or_else(|_| panic!("Failed to receive the first query"));
+
+        let status_request = Request::builder()
+            .method(Method::GET)
+            .uri("http://localhost:8000/status")
+            .body(Body::empty())
+            .unwrap();
+        let response = core.run(service.call(status_request))
+            .expect("Should return a response");
+        let body = core.run(response.into_body().concat2()).unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status["queryQueue"]["depth"], 1);
+        assert_eq!(status["queryQueue"]["capacity"], 1);
+
+        let response = core.run(service.call(query_request()))
+            .expect("Should return a response");
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "1");
+        let errors =
+            test_utils::assert_error_response(&mut core, response, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            errors[0]["message"],
+            "Too many queries are pending, try again later"
+        );
+        assert_eq!(errors[0]["extensions"]["code"], "SERVICE_UNAVAILABLE");
+    }
 }
@@ -0,0 +1,712 @@
+use futures::future;
+use futures::prelude::*;
+use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::sync::oneshot;
+use graphql_parser;
+use graphql_parser::query as q;
+use serde::Serialize;
+use serde_json;
+use slog;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio;
+use tokio::net::TcpStream;
+use tokio_tungstenite::accept_hdr_async;
+use tokio_tungstenite::tungstenite::handshake::server::Request as WsRequest;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message as WsMessage};
+
+use graph::components::store::StoreEvent;
+use graph::prelude::*;
+use graph_graphql::query::ast as qast;
+use graph_graphql::query::get_selected_object_types;
+
+use dispatch::QueryDispatcher;
+use response::{GraphQLResponse, SubsequentResponse};
+
+/// Payload of a `start` message.
+#[derive(Debug, Deserialize)]
+struct StartPayload {
+    query: String,
+    variables: Option<QueryVariables>,
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+}
+
+/// Messages sent by clients according to the graphql-ws protocol.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum IncomingMessage {
+    ConnectionInit,
+    Start { id: String, payload: StartPayload },
+    Stop { id: String },
+    ConnectionTerminate,
+}
+
+/// Payload of `connection_error` and `error` messages.
+#[derive(Debug, Serialize)]
+struct ErrorPayload {
+    message: String,
+}
+
+/// Messages sent to clients according to the graphql-ws protocol.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OutgoingMessage {
+    ConnectionAck,
+    ConnectionError { payload: ErrorPayload },
+    Data { id: String, payload: serde_json::Value },
+    Error { id: String, payload: ErrorPayload },
+    Complete { id: String },
+}
+
+/// A subscription started by a client.
+struct Subscription {
+    document: q::Document,
+    variables: Option<QueryVariables>,
+    operation_name: Option<String>,
+    /// The object types whose entities the subscription selects.
+    object_types: HashSet<String>,
+    /// Tells apart subscriptions that reuse the ID of a stopped subscription,
+    /// so that results of the stopped subscription are not sent for them.
+    serial: usize,
+    /// Whether the subscription is being executed.
+    running: bool,
+    /// Whether a store event affected the subscription while it was being
+    /// executed, so that it needs to be executed once more.
+    stale: bool,
+}
+
+impl Subscription {
+    fn new(
+        document: q::Document,
+        variables: Option<QueryVariables>,
+        operation_name: Option<String>,
+        object_types: HashSet<String>,
+    ) -> Self {
+        Subscription {
+            document,
+            variables,
+            operation_name,
+            object_types,
+            serial: 0,
+            running: false,
+            stale: false,
+        }
+    }
+
+    /// Creates a query that executes the subscription once, along with the
+    /// receiver for its result.
+    fn query(&self, schema: Schema) -> (Query, oneshot::Receiver<QueryResult>) {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let query = Query {
+            schema,
+            document: self.document.clone(),
+            variables: self.variables.clone(),
+            operation_name: self.operation_name.clone(),
+            tracing: false,
+            result_sender,
+            pending: None,
+            deadline: None,
+        };
+        (query, result_receiver)
+    }
+
+    /// Returns true if the store event may change the result of the subscription.
+    fn is_affected_by(&self, event: &StoreEvent) -> bool {
+        let entity = match event {
+            StoreEvent::EntityAdded(entity)
+            | StoreEvent::EntityRemoved(entity)
+            | StoreEvent::EntityChanged(entity) => entity,
+            StoreEvent::EventsDropped => return true,
+        };
+
+        // Changes to entities of an unknown type may affect any subscription
+        match entity.get("__typename") {
+            Some(Value::String(entity_type)) => self.object_types.contains(entity_type),
+            _ => true,
+        }
+    }
+}
+
+/// A WebSocket connection over which a client runs GraphQL subscriptions.
+#[derive(Clone)]
+struct SubscriptionConnection {
+    logger: slog::Logger,
+    schema: Arc<Mutex<Option<Schema>>>,
+    dispatcher: QueryDispatcher,
+    /// Sender for messages to the client.
+    message_sink: UnboundedSender<WsMessage>,
+    /// Active subscriptions by the IDs that the client assigned to them.
+    subscriptions: Arc<Mutex<HashMap<String, Subscription>>>,
+    /// The serial number of the next subscription.
+    next_serial: Arc<AtomicUsize>,
+}
+
+impl SubscriptionConnection {
+    /// Handles a message from the client. Fails if the connection should be closed.
+    fn handle_message(&self, message: WsMessage) -> Result<(), ()> {
+        let text = match message {
+            WsMessage::Text(text) => text,
+            WsMessage::Close(_) => return Err(()),
+
+            // Pings are answered by the WebSocket implementation and binary
+            // messages are not part of the protocol
+            _ => return Ok(()),
+        };
+
+        let message = match serde_json::from_str(&text) {
+            Ok(message) => message,
+            Err(e) => {
+                self.send(OutgoingMessage::ConnectionError {
+                    payload: ErrorPayload {
+                        message: format!("Invalid message received: {}", e),
+                    },
+                });
+                return Ok(());
+            }
+        };
+
+        match message {
+            IncomingMessage::ConnectionInit => self.send(OutgoingMessage::ConnectionAck),
+            IncomingMessage::Start { id, payload } => self.start(id, payload),
+            IncomingMessage::Stop { id } => {
+                self.subscriptions.lock().unwrap().remove(&id);
+                self.send(OutgoingMessage::Complete { id });
+            }
+            IncomingMessage::ConnectionTerminate => return Err(()),
+        };
+
+        Ok(())
+    }
+
+    /// Starts an operation. Subscriptions remain active until the client stops
+    /// them, all other operations complete after their first result.
+    fn start(&self, id: String, payload: StartPayload) {
+        if self.subscriptions.lock().unwrap().contains_key(&id) {
+            let message = format!("Operation ID \"{}\" is already in use", id);
+            return self.send_error(id, message);
+        }
+
+        let document = match graphql_parser::parse_query(&payload.query) {
+            Ok(document) => document,
+            Err(e) => return self.send_error(id, format!("{}", QueryError::from(e))),
+        };
+
+        let schema = match self.schema.lock().unwrap().clone() {
+            Some(schema) => schema,
+            None => return self.send_error(id, String::from("No schema available to query")),
+        };
+
+        // Operations that are invalid are executed once, so that the client
+        // receives the errors produced by the execution
+        let operation_name = payload.operation_name.as_ref().map(|name| name.as_str());
+        let object_types = match qast::get_operation(&document, operation_name) {
+            Ok(operation @ q::OperationDefinition::Subscription(_)) => Some(
+                get_selected_object_types(&schema.document, &document, operation),
+            ),
+            _ => None,
+        };
+
+        match object_types {
+            Some(object_types) => {
+                let mut subscription = Subscription::new(
+                    document,
+                    payload.variables,
+                    payload.operation_name,
+                    object_types,
+                );
+                let serial = self.next_serial.fetch_add(1, Ordering::SeqCst);
+                subscription.serial = serial;
+                subscription.running = true;
+                let query = subscription.query(schema);
+                self.subscriptions
+                    .lock()
+                    .unwrap()
+                    .insert(id.clone(), subscription);
+                self.execute(id, query, Some(serial));
+            }
+            None => {
+                let operation = Subscription::new(
+                    document,
+                    payload.variables,
+                    payload.operation_name,
+                    HashSet::new(),
+                );
+                self.execute(id, operation.query(schema), None)
+            }
+        }
+    }
+
+    /// Re-runs all subscriptions whose results may be changed by a store event.
+    ///
+    /// Subscriptions that are being executed already are only marked as stale
+    /// and re-run once their current execution finishes, so that a burst of
+    /// events results in at most one further execution per subscription.
+    fn handle_store_event(&self, event: &StoreEvent) {
+        let schema = match self.schema.lock().unwrap().clone() {
+            Some(schema) => schema,
+            None => return,
+        };
+
+        let queries: Vec<_> = self.subscriptions
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .filter(|(_, subscription)| subscription.is_affected_by(event))
+            .filter_map(|(id, subscription)| {
+                if subscription.running {
+                    subscription.stale = true;
+                    None
+                } else {
+                    subscription.running = true;
+                    Some((
+                        id.clone(),
+                        subscription.query(schema.clone()),
+                        subscription.serial,
+                    ))
+                }
+            })
+            .collect();
+
+        for (id, query, serial) in queries {
+            self.execute(id, query, Some(serial));
+        }
+    }
+
+    /// Re-runs a subscription after it has been executed if it became stale
+    /// in the meantime.
+    fn finish_execution(&self, id: String, serial: usize) {
+        let schema = match self.schema.lock().unwrap().clone() {
+            Some(schema) => schema,
+            None => return,
+        };
+
+        let query = match self.subscriptions.lock().unwrap().get_mut(&id) {
+            // The subscription was stopped and its ID reused in the meantime
+            Some(ref subscription) if subscription.serial != serial => None,
+            Some(ref mut subscription) if subscription.stale => {
+                subscription.stale = false;
+                Some(subscription.query(schema))
+            }
+            Some(ref mut subscription) => {
+                subscription.running = false;
+                None
+            }
+            None => None,
+        };
+
+        if let Some(query) = query {
+            self.execute(id, query, Some(serial));
+        }
+    }
+
+    /// Returns true if results of an operation should still be sent to the
+    /// client, i.e. unless it is a subscription that has been stopped.
+    fn is_active(&self, id: &str, serial: Option<usize>) -> bool {
+        match serial {
+            Some(serial) => self.subscriptions
+                .lock()
+                .unwrap()
+                .get(id)
+                .map_or(false, |subscription| subscription.serial == serial),
+            None => true,
+        }
+    }
+
+    /// Executes an operation through the query dispatcher and sends its
+    /// result to the client.
+    ///
+    /// Subscriptions are identified by their serial number; operations
+    /// without one complete after their first result.
+    fn execute(
+        &self,
+        id: String,
+        (query, result_receiver): (Query, oneshot::Receiver<QueryResult>),
+        serial: Option<usize>,
+    ) {
+        let logger = self.logger.clone();
+        let connection = self.clone();
+
+        // Queries that are turned away because too many queries are pending
+        // are answered with an error; subscriptions remain active and are
+        // re-run on the next store event
+        if let Err(e) = self.dispatcher.dispatch(query) {
+            self.send_data(id.clone(), &GraphQLResponse::new(Err(e)));
+            match serial {
+                Some(serial) => self.finish_execution(id, serial),
+                None => self.send(OutgoingMessage::Complete { id }),
+            }
+            return;
+        }
+
+        let finished_id = id.clone();
+        let finished_connection = self.clone();
+
+        tokio::spawn(
+            result_receiver
+                .map_err(|e| format!("Failed to receive query result: {}", e))
+                .and_then(move |result| {
+                    let mut response = GraphQLResponse::new(Ok(result));
+                    let subsequent_results = response.take_subsequent_results();
+
+                    // Results of subscriptions that were stopped in the
+                    // meantime are dropped
+                    if connection.is_active(&id, serial) {
+                        connection.send_data(id.clone(), &response);
+                    }
+
+                    // Subsequent results of `@defer` and `@stream` follow as
+                    // further `data` messages
+                    let subsequent_data: Box<Future<Item = (), Error = String> + Send> =
+                        match subsequent_results {
+                            Some(subsequent_results) => {
+                                let connection = connection.clone();
+                                let id = id.clone();
+                                Box::new(
+                                    subsequent_results
+                                        .for_each(move |result| {
+                                            if connection.is_active(&id, serial) {
+                                                connection.send_data(
+                                                    id.clone(),
+                                                    &SubsequentResponse(result),
+                                                );
+                                            }
+                                            Ok(())
+                                        })
+                                        .map_err(|_| {
+                                            String::from(
+                                                "Failed to receive subsequent query results",
+                                            )
+                                        }),
+                                )
+                            }
+                            None => Box::new(future::ok(())),
+                        };
+
+                    subsequent_data.map(move |_| {
+                        if serial.is_none() {
+                            connection.send(OutgoingMessage::Complete { id });
+                        }
+                    })
+                })
+                .map_err(move |e| error!(logger, "Failed to run subscription"; "error" => e))
+                .then(move |result| {
+                    if let Some(serial) = serial {
+                        finished_connection.finish_execution(finished_id, serial);
+                    }
+                    result
+                }),
+        );
+    }
+
+    /// Sends a `data` message for an operation to the client.
+    fn send_data<T: Serialize>(&self, id: String, response: &T) {
+        let payload =
+            serde_json::to_value(response).expect("Failed to serialize GraphQL response to JSON");
+        self.send(OutgoingMessage::Data { id, payload });
+    }
+
+    /// Sends an `error` message for an operation to the client.
+    fn send_error(&self, id: String, message: String) {
+        self.send(OutgoingMessage::Error {
+            id,
+            payload: ErrorPayload { message },
+        })
+    }
+
+    /// Sends a message to the client.
+    fn send(&self, message: OutgoingMessage) {
+        let text =
+            serde_json::to_string(&message).expect("Failed to serialize subscription message");
+
+        // Messages to clients that have disconnected are dropped
+        let _ = self.message_sink.unbounded_send(WsMessage::Text(text));
+    }
+}
+
+/// Serves GraphQL subscriptions over a WebSocket connection, following the
+/// graphql-ws protocol. Subscriptions are re-run whenever a store event
+/// affects one of the entity types they select.
+pub fn serve_subscriptions(
+    logger: slog::Logger,
+    stream: TcpStream,
+    schema: Arc<Mutex<Option<Schema>>>,
+    dispatcher: QueryDispatcher,
+    store_events: UnboundedReceiver<Arc<StoreEvent>>,
+) -> Box<Future<Item = (), Error = ()> + Send> {
+    let handshake_logger = logger.clone();
+
+    Box::new(
+        accept_hdr_async(stream, |_request: &WsRequest| {
+            // Let the client know that we speak the graphql-ws protocol
+            Ok(Some(vec![(
+                String::from("Sec-WebSocket-Protocol"),
+                String::from("graphql-ws"),
+            )]))
+        }).map_err(move |e| {
+            debug!(handshake_logger, "WebSocket handshake failed"; "error" => format!("{}", e))
+        })
+            .and_then(move |ws_stream| {
+                let (ws_sink, ws_stream) = ws_stream.split();
+                let (message_sink, message_stream) = unbounded();
+
+                let connection = SubscriptionConnection {
+                    logger: logger.clone(),
+                    schema,
+                    dispatcher,
+                    message_sink,
+                    subscriptions: Arc::new(Mutex::new(HashMap::new())),
+                    next_serial: Arc::new(AtomicUsize::new(0)),
+                };
+
+                // Write outgoing messages to the client until the connection is dropped
+                tokio::spawn(
+                    ws_sink
+                        .sink_map_err(|_: WsError| ())
+                        .send_all(message_stream)
+                        .map(|_| ()),
+                );
+
+                // Handle incoming messages until the client closes the connection
+                let reader = {
+                    let connection = connection.clone();
+                    ws_stream
+                        .map_err(move |e| {
+                            debug!(logger, "WebSocket connection error"; "error" => format!("{}", e))
+                        })
+                        .for_each(move |message| connection.handle_message(message))
+                };
+
+                // Re-run subscriptions on store events while the connection is open
+                let updater =
+                    store_events.for_each(move |event| Ok(connection.handle_store_event(&event)));
+
+                reader.select(updater).then(|_| Ok(()))
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+    use futures::prelude::*;
+    use futures::sync::mpsc::{channel, unbounded, Receiver, UnboundedReceiver};
+    use graphql_parser;
+    use serde_json;
+    use slog;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio_core::reactor::Core;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    use graph::components::store::StoreEvent;
+    use graph::prelude::*;
+
+    use super::{
+        ErrorPayload, IncomingMessage, OutgoingMessage, StartPayload, Subscription,
+        SubscriptionConnection,
+    };
+    use dispatch::QueryDispatcher;
+
+    #[test]
+    fn parses_graphql_ws_messages() {
+        match serde_json::from_str(r#"{"type": "connection_init", "payload": {}}"#) {
+            Ok(IncomingMessage::ConnectionInit) => (),
+            other => panic!("Unexpected message: {:?}", other),
+        }
+
+        match serde_json::from_str(
+            r#"{"id": "1", "type": "start", "payload": {"query": "subscription { users { id } }"}}"#,
+        ) {
+            Ok(IncomingMessage::Start { id, payload }) => {
+                assert_eq!(id, "1");
+                assert_eq!(payload.query, "subscription { users { id } }");
+            }
+            other => panic!("Unexpected message: {:?}", other),
+        }
+
+        match serde_json::from_str(r#"{"id": "1", "type": "stop"}"#) {
+            Ok(IncomingMessage::Stop { id }) => assert_eq!(id, "1"),
+            other => panic!("Unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn serializes_graphql_ws_messages() {
+        assert_eq!(
+            serde_json::to_value(OutgoingMessage::ConnectionAck).unwrap(),
+            json_value(r#"{"type": "connection_ack"}"#)
+        );
+        assert_eq!(
+            serde_json::to_value(OutgoingMessage::Data {
+                id: String::from("1"),
+                payload: json_value(r#"{"data": {"users": []}}"#),
+            }).unwrap(),
+            json_value(r#"{"type": "data", "id": "1", "payload": {"data": {"users": []}}}"#)
+        );
+        assert_eq!(
+            serde_json::to_value(OutgoingMessage::Error {
+                id: String::from("1"),
+                payload: ErrorPayload {
+                    message: String::from("Failed"),
+                },
+            }).unwrap(),
+            json_value(r#"{"type": "error", "id": "1", "payload": {"message": "Failed"}}"#)
+        );
+    }
+
+    #[test]
+    fn subscriptions_are_only_affected_by_selected_entity_types() {
+        let subscription = Subscription::new(
+            ::graphql_parser::parse_query("subscription { users { id } }").unwrap(),
+            None,
+            None,
+            vec![String::from("User")].into_iter().collect::<HashSet<_>>(),
+        );
+
+        let user = Entity::from(vec![("__typename", Value::from("User"))]);
+        let post = Entity::from(vec![("__typename", Value::from("Post"))]);
+        let unknown = Entity::from(vec![("id", Value::from("1"))]);
+
+        assert!(subscription.is_affected_by(&StoreEvent::EntityChanged(user)));
+        assert!(!subscription.is_affected_by(&StoreEvent::EntityAdded(post)));
+        assert!(subscription.is_affected_by(&StoreEvent::EntityRemoved(unknown)));
+        assert!(subscription.is_affected_by(&StoreEvent::EventsDropped));
+    }
+
+    /// Creates a connection along with the streams of the queries it
+    /// dispatches and the messages it sends.
+    fn test_connection() -> (
+        SubscriptionConnection,
+        QueryDispatcher,
+        Receiver<Query>,
+        UnboundedReceiver<WsMessage>,
+    ) {
+        let schema = Schema {
+            id: "test-schema".to_string(),
+            document: graphql_parser::parse_schema(
+                "\
+                 scalar ID \
+                 type User { id: ID } \
+                 type Subscription { users: [User] } \
+                 ",
+            ).unwrap(),
+        };
+        let (query_sink, query_stream) = channel(10);
+        let (message_sink, message_stream) = unbounded();
+        let dispatcher = QueryDispatcher::new(query_sink, 10);
+        let connection = SubscriptionConnection {
+            logger: slog::Logger::root(slog::Discard, o!()),
+            schema: Arc::new(Mutex::new(Some(schema))),
+            dispatcher: dispatcher.clone(),
+            message_sink,
+            subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            next_serial: Arc::new(AtomicUsize::new(0)),
+        };
+        (connection, dispatcher, query_stream, message_stream)
+    }
+
+    fn start_payload() -> StartPayload {
+        StartPayload {
+            query: String::from("subscription { users { id } }"),
+            variables: None,
+            operation_name: None,
+        }
+    }
+
+    #[test]
+    fn store_events_are_coalesced_while_a_subscription_is_executed() {
+        let mut core = Core::new().unwrap();
+        let (connection, dispatcher, query_stream, message_stream) = test_connection();
+        let event = StoreEvent::EntityChanged(Entity::from(vec![(
+            "__typename",
+            Value::from("User"),
+        )]));
+
+        // Start the subscription and change users while it is being executed
+        core.run(future::lazy(|| {
+            connection.start(String::from("1"), start_payload());
+            for _ in 0..3 {
+                connection.handle_store_event(&event);
+            }
+            Ok::<_, ()>(())
+        })).unwrap();
+        assert_eq!(dispatcher.depth(), 1);
+
+        // All changes are covered by a single further execution
+        let (query, query_stream) = core.run(query_stream.into_future()).ok().unwrap();
+        query.unwrap().result_sender.send(QueryResult::new(None)).unwrap();
+        let (query, _query_stream) = core.run(query_stream.into_future()).ok().unwrap();
+        assert_eq!(dispatcher.depth(), 1);
+
+        query.unwrap().result_sender.send(QueryResult::new(None)).unwrap();
+        let messages = core.run(message_stream.take(2).collect()).unwrap();
+        assert_eq!(messages.len(), 2);
+
+        // Without further changes, the subscription is idle afterwards
+        let is_running = || connection.subscriptions.lock().unwrap()["1"].running;
+        for _ in 0..100 {
+            if !is_running() {
+                break;
+            }
+            core.turn(Some(Duration::from_millis(10)));
+        }
+        assert!(!is_running());
+        assert_eq!(dispatcher.depth(), 0);
+    }
+
+    #[test]
+    fn results_of_stopped_subscriptions_are_not_sent() {
+        let mut core = Core::new().unwrap();
+        let (connection, _dispatcher, query_stream, message_stream) = test_connection();
+
+        // Stop the subscription while it is being executed
+        core.run(future::lazy(|| {
+            connection.start(String::from("1"), start_payload());
+            connection
+                .handle_message(WsMessage::Text(String::from(r#"{"id": "1", "type": "stop"}"#)))
+        })).unwrap();
+        let (query, _query_stream) = core.run(query_stream.into_future()).ok().unwrap();
+        query.unwrap().result_sender.send(QueryResult::new(None)).unwrap();
+
+        // Only the `complete` message is sent once the connection is dropped
+        drop(connection);
+        let messages = core.run(message_stream.collect()).unwrap();
+        assert_eq!(
+            messages,
+            vec![WsMessage::Text(String::from(r#"{"type":"complete","id":"1"}"#))]
+        );
+    }
+
+    #[test]
+    fn operations_with_ids_in_use_are_rejected() {
+        let mut core = Core::new().unwrap();
+        let (connection, dispatcher, _query_stream, message_stream) = test_connection();
+
+        core.run(future::lazy(|| {
+            connection.start(String::from("1"), start_payload());
+            connection.start(String::from("1"), start_payload());
+            Ok::<_, ()>(())
+        })).unwrap();
+        assert_eq!(dispatcher.depth(), 1);
+
+        let (message, _) = core.run(message_stream.into_future()).ok().unwrap();
+        match message {
+            Some(WsMessage::Text(text)) => assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+                json_value(
+                    r#"{"type": "error", "id": "1",
+                        "payload": {"message": "Operation ID \"1\" is already in use"}}"#
+                )
+            ),
+            other => panic!("Unexpected message: {:?}", other),
+        }
+    }
+
+    fn json_value(s: &str) -> serde_json::Value {
+        serde_json::from_str(s).unwrap()
+    }
+}
@@ -22,7 +22,7 @@ use graph::components::schema::SchemaProviderEvent;
 use graph::prelude::*;
 
 use graph_server_http::test_utils;
-use graph_server_http::GraphQLServer as HyperGraphQLServer;
+use graph_server_http::{GraphQLServer as HyperGraphQLServer, GraphQLServerConfig};
 
 /// Helper function that simulates running a single incoming query and then
 /// closing the query stream.
@@ -46,7 +46,8 @@ fn rejects_empty_json() {
     let mut core = Core::new().unwrap();
     let logger = slog::Logger::root(slog::Discard, o!());
 
-    let mut server = HyperGraphQLServer::new(&logger, core.handle());
+    let config = GraphQLServerConfig::default();
+    let mut server = HyperGraphQLServer::new(&logger, core.handle(), config);
     let query_stream = server.query_stream().unwrap();
     let http_server = server.serve().expect("Failed to start GraphQL server");
 
@@ -62,7 +63,7 @@ fn rejects_empty_json() {
     };
     server
         .schema_provider_event_sink()
-        .send(SchemaProviderEvent::SchemaChanged(Some(schema)))
+        .send(SchemaProviderEvent::SchemaChanged(Some(schema), vec![]))
         .wait()
         .expect("Failed to send schema to server");
 
@@ -97,7 +98,8 @@ fn rejects_invalid_queries() {
     let mut core = Core::new().unwrap();
     let logger = slog::Logger::root(slog::Discard, o!());
 
-    let mut server = HyperGraphQLServer::new(&logger, core.handle());
+    let config = GraphQLServerConfig::default();
+    let mut server = HyperGraphQLServer::new(&logger, core.handle(), config);
     let query_stream = server.query_stream().unwrap();
     let http_server = server.serve().expect("Failed to start GraphQL server");
 
@@ -117,7 +119,7 @@ fn rejects_invalid_queries() {
     };
     server
         .schema_provider_event_sink()
-        .send(SchemaProviderEvent::SchemaChanged(Some(schema)))
+        .send(SchemaProviderEvent::SchemaChanged(Some(schema), vec![]))
         .wait()
         .expect("Failed to send schema to server");
 
@@ -181,7 +183,8 @@ fn accepts_valid_queries() {
     let mut core = Core::new().unwrap();
     let logger = slog::Logger::root(slog::Discard, o!());
 
-    let mut server = HyperGraphQLServer::new(&logger, core.handle());
+    let config = GraphQLServerConfig::default();
+    let mut server = HyperGraphQLServer::new(&logger, core.handle(), config);
     let query_stream = server.query_stream().unwrap();
     let http_server = server.serve().expect("Failed to start GraphQL server");
 
@@ -201,7 +204,7 @@ fn accepts_valid_queries() {
     };
     server
         .schema_provider_event_sink()
-        .send(SchemaProviderEvent::SchemaChanged(Some(schema)))
+        .send(SchemaProviderEvent::SchemaChanged(Some(schema), vec![]))
         .wait()
         .expect("Failed to send schema to server");
 
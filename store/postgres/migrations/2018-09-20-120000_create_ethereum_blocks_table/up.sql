@@ -0,0 +1,9 @@
+/**************************************************************
+* CREATE TABLE
+**************************************************************/
+-- Stores the numbers of the blocks that entity changes were made for, by the
+-- block hash recorded as the source of the changes in event_meta_data
+CREATE TABLE IF NOT EXISTS ethereum_blocks (
+    hash VARCHAR PRIMARY KEY,
+    number BIGINT NOT NULL
+);
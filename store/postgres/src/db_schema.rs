@@ -7,3 +7,10 @@ table! {
         event_source -> Varchar,
     }
 }
+
+table! {
+    ethereum_blocks (hash) {
+        hash -> Varchar,
+        number -> BigInt,
+    }
+}
@@ -4,6 +4,7 @@ extern crate diesel;
 extern crate diesel_dynamic_schema;
 #[macro_use]
 extern crate diesel_migrations;
+extern crate ethereum_types;
 extern crate futures;
 #[macro_use]
 extern crate slog;
@@ -1,5 +1,4 @@
-use diesel::sql_types::Jsonb;
-use diesel::sql_types::VarChar;
+use diesel::sql_types::{BigInt, Jsonb, Nullable, VarChar};
 use serde_json;
 
 pub type EntityJSON = serde_json::Value;
@@ -17,3 +16,28 @@ pub struct EntityTable {
     #[sql_type = "VarChar"]
     pub event_source: String,
 }
+
+/// A change to an entity from `entity_history`, along with the source of the
+/// change and the number of the block it was made for.
+#[derive(QueryableByName, Debug)]
+pub struct EntityHistoryRow {
+    #[sql_type = "Nullable<VarChar>"]
+    pub source: Option<String>,
+    #[sql_type = "Nullable<BigInt>"]
+    pub block_number: Option<i64>,
+    #[sql_type = "Nullable<Jsonb>"]
+    pub data_before: Option<EntityJSON>,
+    #[sql_type = "Nullable<Jsonb>"]
+    pub data_after: Option<EntityJSON>,
+}
+
+/// An entity that was changed for a block, as found in `entity_history`.
+#[derive(QueryableByName, Debug)]
+pub struct ChangedEntityRow {
+    #[sql_type = "VarChar"]
+    pub subgraph: String,
+    #[sql_type = "VarChar"]
+    pub entity: String,
+    #[sql_type = "VarChar"]
+    pub entity_id: String,
+}
@@ -2,16 +2,22 @@ use diesel::dsl::sql;
 use diesel::pg::Pg;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::sql_types::Text;
-use diesel::{delete, insert_into, result, select};
+use diesel::query_builder::BoxedSelectStatement;
+use diesel::sql_types::{BigInt, Jsonb, Nullable, Text};
+use diesel::{debug_query, delete, insert_into, result, select, sql_query};
+use ethereum_types::H256;
 use filter::store_filter;
 use futures::prelude::*;
 use futures::sync::mpsc::{channel, Receiver, Sender};
 use serde_json;
 use slog;
+use std::str::FromStr;
+use std::sync::Mutex;
 use tokio_core::reactor::Handle;
 
+use db_schema::{entities, ethereum_blocks};
 use functions::{revert_block, set_config};
+use models::{ChangedEntityRow, EntityHistoryRow};
 use graph::components::schema::SchemaProviderEvent;
 use graph::components::store::{Store as StoreTrait, *};
 use graph::data::store::*;
@@ -43,11 +49,17 @@ pub struct StoreConfig {
     pub url: String,
 }
 
+/// The sending end of the store event stream.
+struct EventSink {
+    sender: Sender<StoreEvent>,
+    /// Whether events were dropped since the last event that was sent.
+    dropped_events: bool,
+}
+
 /// A Store based on Diesel and Postgres.
 pub struct Store {
-    event_sink: Option<Sender<StoreEvent>>,
+    event_sink: Mutex<Option<EventSink>>,
     logger: slog::Logger,
-    runtime: Handle,
     schema_provider_event_sink: Sender<SchemaProviderEvent>,
     _config: StoreConfig,
     pub conn: PgConnection,
@@ -71,25 +83,28 @@ impl Store {
         initiate_schema(&logger, &conn);
 
         // Create the store
-        let mut store = Store {
+        let store = Store {
             logger,
-            event_sink: None,
+            event_sink: Mutex::new(None),
             schema_provider_event_sink: sink,
-            runtime,
             _config: config,
             conn: conn,
         };
 
         // Spawn a task that handles incoming schema provider events
-        store.handle_schema_provider_events(stream);
+        store.handle_schema_provider_events(&runtime, stream);
 
         // Return the store
         store
     }
 
     /// Handles incoming schema provider events.
-    fn handle_schema_provider_events(&mut self, stream: Receiver<SchemaProviderEvent>) {
-        self.runtime.spawn(stream.for_each(move |_| {
+    fn handle_schema_provider_events(
+        &self,
+        runtime: &Handle,
+        stream: Receiver<SchemaProviderEvent>,
+    ) {
+        runtime.spawn(stream.for_each(move |_| {
             // We are currently not doing anything in response to schema events
             Ok(())
         }));
@@ -98,9 +113,136 @@ impl Store {
     /// Handles block reorganizations.
     /// Revert all store events related to the given block
     pub fn revert_events(&self, block_hash: String) {
+        // Find out which entities the block changed, so that their reverted
+        // state can be announced afterwards
+        let changed_entities = sql_query(
+            "SELECT DISTINCT entity_history.subgraph, entity_history.entity, \
+                    entity_history.entity_id \
+             FROM entity_history \
+             JOIN event_meta_data ON event_meta_data.id = entity_history.event_id \
+             WHERE event_meta_data.source = $1",
+        ).bind::<Text, _>(&block_hash)
+            .load::<ChangedEntityRow>(&self.conn)
+            .unwrap();
+
         select(revert_block(block_hash))
             .execute(&self.conn)
             .unwrap();
+
+        for row in changed_entities {
+            let key = StoreKey {
+                subgraph: row.subgraph,
+                entity: row.entity,
+                id: row.entity_id,
+            };
+            let event = match self.get(key.clone()) {
+                Ok(entity) => StoreEvent::EntityChanged(typed_entity(&key, entity)),
+                Err(_) => StoreEvent::EntityRemoved(typed_entity(
+                    &key,
+                    Entity::from(vec![("id", Value::from(key.id.as_str()))]),
+                )),
+            };
+            self.emit_event(event);
+        }
+    }
+
+    /// Sends an event to the store event stream, if one has been created.
+    ///
+    /// Changes are made on the same event loop that receives the events, so
+    /// waiting for the receiver could block it forever. Instead, events are
+    /// dropped while the stream is full, and the receiver is told that any
+    /// entity may have changed with a single `EventsDropped` event.
+    fn emit_event(&self, event: StoreEvent) {
+        let mut event_sink = self.event_sink.lock().unwrap();
+        let sink = match *event_sink {
+            Some(ref mut sink) => sink,
+            None => return,
+        };
+
+        match sink.sender.try_send(event) {
+            Ok(()) => sink.dropped_events = false,
+            Err(ref e) if e.is_full() => {
+                // Every sender has room for one more message, so the
+                // `EventsDropped` event is queued behind the pending events
+                // right away; until the receiver catches up, it covers all
+                // further dropped events as well
+                if !sink.dropped_events {
+                    warn!(self.logger, "Store events are dropped because they are not received");
+                    sink.dropped_events = true;
+                    let _ = sink.sender.clone().try_send(StoreEvent::EventsDropped);
+                }
+            }
+            Err(e) => {
+                debug!(self.logger, "Failed to emit store event"; "error" => format!("{}", e));
+            }
+        }
+    }
+
+    /// Records the number of the block that changes are made for, so that the
+    /// history of entities can be filtered by block number.
+    fn record_block(&self, event_source: &EventSource) -> Result<(), result::Error> {
+        match event_source {
+            EventSource::EthereumBlock(block) => insert_into(ethereum_blocks::table)
+                .values((
+                    ethereum_blocks::hash.eq(event_source.to_string()),
+                    ethereum_blocks::number.eq(block.number as i64),
+                ))
+                .on_conflict_do_nothing()
+                .execute(&self.conn)
+                .map(|_| ()),
+            EventSource::None => Ok(()),
+        }
+    }
+
+    /// Builds the `SELECT data FROM entities` statement for a store query.
+    fn build_query(
+        &self,
+        query: StoreQuery,
+    ) -> Result<BoxedSelectStatement<'static, Jsonb, entities::table, Pg>, ()> {
+        use db_schema::entities::dsl::*;
+
+        // Create base boxed query; this will be added to based on the
+        // query parameters provided
+        let mut diesel_query = entities
+            .filter(entity.eq(query.entity))
+            .filter(subgraph.eq(query.subgraph))
+            .select(data)
+            .into_boxed::<Pg>();
+
+        // Add specified filter to query
+        if let Some(filter) = query.filter {
+            diesel_query = store_filter(diesel_query, filter).map_err(|e| {
+                error!(self.logger, "value does not support this filter";
+                                    "value" => format!("{:?}", e.value),
+                                    "filter" => e.filter)
+            })?;
+        }
+
+        // Add order by filters to query
+        if let Some(order_attribute) = query.order_by {
+            let direction = query
+                .order_direction
+                .map(|direction| match direction {
+                    StoreOrder::Ascending => String::from("ASC"),
+                    StoreOrder::Descending => String::from("DESC"),
+                })
+                .unwrap_or(String::from("ASC"));
+
+            diesel_query = diesel_query.order(
+                sql::<Text>("data ->> ")
+                    .bind::<Text, _>(order_attribute)
+                    .sql(&format!(" {} ", direction)),
+            )
+        }
+
+        // Add range filter to query
+        if let Some(range) = query.range {
+            diesel_query = diesel_query
+                .limit(range.first as i64)
+                .offset(range.skip as i64);
+        }
+
+        Ok(diesel_query)
     }
 }
 
@@ -132,18 +274,20 @@ impl BasicStore for Store {
         use db_schema::entities::dsl::*;
 
         // Update the existing entity, if necessary
-        let updated_entity = match self.get(key.clone()) {
+        let (updated_entity, existed) = match self.get(key.clone()) {
             Ok(mut existing_entity) => {
                 existing_entity.merge(input_entity);
-                existing_entity
+                (existing_entity, true)
             }
-            Err(_) => input_entity,
+            Err(_) => (input_entity, false),
         };
 
         // Convert Entity hashmap to serde_json::Value for insert
         let entity_json: serde_json::Value =
             serde_json::to_value(&updated_entity).expect("Failed to serialize entity");
 
+        self.record_block(&input_event_source).map_err(|_| ())?;
+
         // Insert entity, perform an update in case of a primary key conflict
         insert_into(entities)
             .values((
@@ -163,8 +307,15 @@ impl BasicStore for Store {
                 event_source.eq(&input_event_source.to_string()),
             ))
             .execute(&self.conn)
-            .map(|_| ())
-            .map_err(|_| ())
+            .map_err(|_| ())?;
+
+        let updated_entity = typed_entity(&key, updated_entity);
+        self.emit_event(if existed {
+            StoreEvent::EntityChanged(updated_entity)
+        } else {
+            StoreEvent::EntityAdded(updated_entity)
+        });
+        Ok(())
     }
 
     fn delete(&mut self, key: StoreKey, input_event_source: EventSource) -> Result<(), ()> {
@@ -172,6 +323,8 @@ impl BasicStore for Store {
 
         use db_schema::entities::dsl::*;
 
+        let existing_entity = self.get(key.clone()).ok();
+
         self.conn
             .transaction::<usize, result::Error, _>(|| {
                 // Set session variable to store the source of the event
@@ -182,6 +335,8 @@ impl BasicStore for Store {
                 )).execute(&self.conn)
                     .unwrap();
 
+                self.record_block(&input_event_source)?;
+
                 // Delete from DB where rows match the subgraph ID, entity name and ID
                 delete(
                     entities
@@ -190,56 +345,17 @@ impl BasicStore for Store {
                         .filter(id.eq(&key.id)),
                 ).execute(&self.conn)
             })
-            .map(|_| ())
-            .map_err(|_| ())
-    }
-
-    fn find(&self, query: StoreQuery) -> Result<Vec<Entity>, ()> {
-        use db_schema::entities::dsl::*;
-
-        // Create base boxed query; this will be added to based on the
-        // query parameters provided
-        let mut diesel_query = entities
-            .filter(entity.eq(query.entity))
-            .filter(subgraph.eq(query.subgraph))
-            .select(data)
-            .into_boxed::<Pg>();
-
-        // Add specified filter to query
-        if let Some(filter) = query.filter {
-            diesel_query = store_filter(diesel_query, filter).map_err(|e| {
-                error!(self.logger, "value does not support this filter";
-                                    "value" => format!("{:?}", e.value),
-                                    "filter" => e.filter)
-            })?;
-        }
-
-        // Add order by filters to query
-        if let Some(order_attribute) = query.order_by {
-            let direction = query
-                .order_direction
-                .map(|direction| match direction {
-                    StoreOrder::Ascending => String::from("ASC"),
-                    StoreOrder::Descending => String::from("DESC"),
-                })
-                .unwrap_or(String::from("ASC"));
+            .map_err(|_| ())?;
 
-            diesel_query = diesel_query.order(
-                sql::<Text>("data ->> ")
-                    .bind::<Text, _>(order_attribute)
-                    .sql(&format!(" {} ", direction)),
-            )
-        }
-
-        // Add range filter to query
-        if let Some(range) = query.range {
-            diesel_query = diesel_query
-                .limit(range.first as i64)
-                .offset(range.skip as i64);
+        if let Some(existing_entity) = existing_entity {
+            self.emit_event(StoreEvent::EntityRemoved(typed_entity(&key, existing_entity)));
         }
+        Ok(())
+    }
 
+    fn find(&self, query: StoreQuery) -> Result<Vec<Entity>, ()> {
         // Process results; deserialize JSON data
-        diesel_query
+        self.build_query(query)?
             .load::<serde_json::Value>(&self.conn)
             .map(|values| {
                 values
@@ -252,6 +368,64 @@ impl BasicStore for Store {
             })
             .map_err(|_| ())
     }
+
+    fn history(&self, query: StoreHistoryQuery) -> Result<Vec<EntityChange>, ()> {
+        debug!(self.logger, "history"; "key" => format!("{:?}", query.key));
+
+        // Changes made by reverting blocks are left out; they are not made
+        // by the subgraph
+        sql_query(
+            "SELECT event_meta_data.source, ethereum_blocks.number AS block_number, \
+                    entity_history.data_before, entity_history.data_after \
+             FROM entity_history \
+             JOIN event_meta_data ON event_meta_data.id = entity_history.event_id \
+             LEFT JOIN ethereum_blocks ON ethereum_blocks.hash = event_meta_data.source \
+             WHERE entity_history.subgraph = $1 \
+               AND entity_history.entity = $2 \
+               AND entity_history.entity_id = $3 \
+               AND NOT entity_history.reversion \
+               AND ($4 IS NULL OR ethereum_blocks.number >= $4) \
+             ORDER BY entity_history.id \
+             LIMIT $5",
+        ).bind::<Text, _>(query.key.subgraph)
+            .bind::<Text, _>(query.key.entity)
+            .bind::<Text, _>(query.key.id)
+            .bind::<Nullable<BigInt>, _>(query.from_block.map(|number| number as i64))
+            .bind::<Nullable<BigInt>, _>(query.first.map(|first| first as i64))
+            .load::<EntityHistoryRow>(&self.conn)
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|row| {
+                        let to_entity = |value| {
+                            serde_json::from_value::<Entity>(value)
+                                .expect("Failed to deserialize entity")
+                        };
+                        let before = row.data_before.map(&to_entity);
+                        let after = row.data_after.map(&to_entity);
+
+                        EntityChange {
+                            operation: match (&before, &after) {
+                                (None, _) => EntityOperation::Insert,
+                                (_, None) => EntityOperation::Delete,
+                                _ => EntityOperation::Update,
+                            },
+                            block_hash: row.source
+                                .and_then(|source| H256::from_str(&source).ok()),
+                            block_number: row.block_number.map(|number| number as u64),
+                            before,
+                            after,
+                        }
+                    })
+                    .collect()
+            })
+            .map_err(|_| ())
+    }
+
+    fn query_sql(&self, query: &StoreQuery) -> Option<String> {
+        self.build_query(query.clone())
+            .ok()
+            .map(|diesel_query| debug_query::<Pg, _>(&diesel_query).to_string())
+    }
 }
 
 impl StoreTrait for Store {
@@ -261,13 +435,24 @@ impl StoreTrait for Store {
 
     fn event_stream(&mut self) -> Result<Receiver<StoreEvent>, StreamError> {
         // If possible, create a new channel for streaming store events
-        match self.event_sink {
+        let mut event_sink = self.event_sink.lock().unwrap();
+        match *event_sink {
             Some(_) => Err(StreamError::AlreadyCreated),
             None => {
-                let (sink, stream) = channel(100);
-                self.event_sink = Some(sink);
+                let (sender, stream) = channel(100);
+                *event_sink = Some(EventSink {
+                    sender,
+                    dropped_events: false,
+                });
                 Ok(stream)
             }
         }
     }
 }
+
+/// Adds the entity type to an entity, so that receivers of store events can
+/// tell which queries the entity affects.
+fn typed_entity(key: &StoreKey, mut entity: Entity) -> Entity {
+    entity.insert(String::from("__typename"), Value::from(key.entity.as_str()));
+    entity
+}
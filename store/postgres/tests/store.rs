@@ -1,5 +1,6 @@
 extern crate diesel;
 extern crate ethereum_types;
+extern crate futures;
 extern crate graph;
 extern crate graph_store_postgres;
 extern crate serde_json;
@@ -10,12 +11,14 @@ extern crate slog;
 use diesel::pg::PgConnection;
 use diesel::*;
 use ethereum_types::H256;
+use futures::prelude::*;
 use slog::Logger;
 use std::panic;
 use tokio_core::reactor::Core;
 
+use graph::components::ethereum::EthereumBlockPointer;
 use graph::components::store::{
-    EventSource, StoreFilter, StoreKey, StoreOrder, StoreQuery, StoreRange,
+    EventSource, StoreEvent, StoreFilter, StoreKey, StoreOrder, StoreQuery, StoreRange,
 };
 use graph::prelude::*;
 use graph_store_postgres::{db_schema, Store as DieselStore, StoreConfig};
@@ -39,6 +42,11 @@ where
     result.expect("Failed to run test");
 }
 
+/// Creates the event source of a block.
+fn block_source(hash: H256, number: u64) -> EventSource {
+    EventSource::EthereumBlock(EthereumBlockPointer { hash, number })
+}
+
 /// Creates a test entity.
 fn create_test_entity(
     id: String,
@@ -64,7 +72,7 @@ fn create_test_entity(
     (
         test_key,
         test_entity,
-        EventSource::EthereumBlock(H256::from_slice(&block_hash.as_bytes())),
+        block_source(H256::from_slice(&block_hash.as_bytes()), 1),
     )
 }
 
@@ -157,7 +165,7 @@ fn delete_entity() {
             entity: String::from("user"),
             id: String::from("3"),
         };
-        let source = EventSource::EthereumBlock(H256::random());
+        let source = block_source(H256::random(), 1);
         store.delete(test_key, source).unwrap();
 
         //Get all ids in table
@@ -287,7 +295,7 @@ fn partially_update_existing() {
         ]);
 
         let original_entity = store.get(entity_key.clone()).unwrap();
-        let event_source = EventSource::EthereumBlock(H256::random());
+        let event_source = block_source(H256::random(), 1);
         // Verify that the entity before updating is different from what we expect afterwards
         assert_ne!(original_entity, partial_entity);
 
@@ -1100,6 +1108,119 @@ fn find_bool_not_equal() {
     })
 }
 
+#[test]
+fn changes_are_emitted_as_store_events() {
+    run_test(|| {
+        let core = Core::new().unwrap();
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let mut store = DieselStore::new(StoreConfig { url }, &logger, core.handle());
+        let events = store.event_stream().unwrap();
+
+        let (key, entity, source) = create_test_entity(
+            String::from("4"),
+            String::from("user"),
+            String::from("Tamara"),
+            String::from("tamara@email.com"),
+            35 as i32,
+            142.3 as f32,
+            true,
+            String::from("aF8vKmW3qPz7XrT2nL5c"),
+        );
+        store
+            .set(key.clone(), entity, source)
+            .expect("Failed to insert test entity into the store");
+
+        let mut update = Entity::new();
+        update.insert(String::from("age"), Value::Int(36));
+        store
+            .set(key.clone(), update, block_source(H256::random(), 2))
+            .expect("Failed to update test entity in the store");
+
+        store
+            .delete(key.clone(), block_source(H256::random(), 3))
+            .expect("Failed to delete test entity from the store");
+
+        let events = events.take(3).collect().wait().unwrap();
+        match (&events[0], &events[1], &events[2]) {
+            (
+                StoreEvent::EntityAdded(added),
+                StoreEvent::EntityChanged(changed),
+                StoreEvent::EntityRemoved(removed),
+            ) => {
+                // Events carry the entity type for invalidating queries
+                for entity in &[added, changed, removed] {
+                    assert_eq!(
+                        entity.get("__typename"),
+                        Some(&Value::String(String::from("user")))
+                    );
+                }
+                assert_eq!(added.get("age"), Some(&Value::Int(35)));
+                assert_eq!(changed.get("age"), Some(&Value::Int(36)));
+                assert_eq!(
+                    changed.get("name"),
+                    Some(&Value::String(String::from("Tamara")))
+                );
+            }
+            _ => panic!("Unexpected store events: {:?}", events),
+        }
+    })
+}
+
+#[test]
+fn dropped_store_events_are_announced() {
+    run_test(|| {
+        let core = Core::new().unwrap();
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let mut store = DieselStore::new(StoreConfig { url }, &logger, core.handle());
+        let events = store.event_stream().unwrap();
+
+        // Make more changes than the event stream has room for
+        let (key, entity, source) = create_test_entity(
+            String::from("4"),
+            String::from("user"),
+            String::from("Tamara"),
+            String::from("tamara@email.com"),
+            35 as i32,
+            142.3 as f32,
+            true,
+            String::from("aF8vKmW3qPz7XrT2nL5c"),
+        );
+        store
+            .set(key.clone(), entity, source)
+            .expect("Failed to insert test entity into the store");
+        for age in 0..150 {
+            let mut update = Entity::new();
+            update.insert(String::from("age"), Value::Int(age));
+            store
+                .set(key.clone(), update, block_source(H256::random(), 2))
+                .expect("Failed to update test entity in the store");
+        }
+
+        // Close the event stream by dropping the store
+        drop(store);
+        let events = events.collect().wait().unwrap();
+
+        // The dropped events are announced once, after the events that were sent
+        assert!(events.len() < 151);
+        match events.last() {
+            Some(StoreEvent::EventsDropped) => (),
+            _ => panic!("Unexpected last store event: {:?}", events.last()),
+        }
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| match event {
+                    StoreEvent::EventsDropped => true,
+                    _ => false,
+                })
+                .count(),
+            1
+        );
+    })
+}
+
 #[test]
 fn revert_block() {
     run_test(|| {
@@ -1120,8 +1241,7 @@ fn revert_block() {
         };
 
         let block_hash = "znuyjijnezBiGFuZAW9Q";
-        let event_source =
-            EventSource::EthereumBlock(H256::from_slice(&block_hash.as_bytes())).to_string();
+        let event_source = block_source(H256::from_slice(&block_hash.as_bytes()), 1).to_string();
 
         // Revert all events associated with event_source, "znuyjijnezBiGFuZAW9Q"
         store.revert_events(event_source);
@@ -1175,7 +1295,7 @@ fn revert_block_with_delete() {
         };
 
         let block_hash = "test_block_to_revert";
-        let event_source = EventSource::EthereumBlock(H256::from_slice(&block_hash.as_bytes()));
+        let event_source = block_source(H256::from_slice(&block_hash.as_bytes()), 1);
         let revert_event_source = event_source.to_string();
         store
             .delete(del_key.clone(), event_source)
@@ -1200,7 +1320,7 @@ fn revert_block_with_delete() {
         // Perform revert operation again to confirm idempotent nature of revert_events()
         // Delete an entity using a randomly created event source
         let block_hash = "test_block_to_revert";
-        let event_source = EventSource::EthereumBlock(H256::from_slice(&block_hash.as_bytes()));
+        let event_source = block_source(H256::from_slice(&block_hash.as_bytes()), 1);
         let revert_event_source = event_source.to_string();
         store
             .delete(del_key.clone(), event_source)
@@ -1237,7 +1357,7 @@ fn revert_block_with_partial_update() {
         ]);
 
         let original_entity = store.get(entity_key.clone()).unwrap();
-        let event_source = EventSource::EthereumBlock(H256::random());
+        let event_source = block_source(H256::random(), 1);
         let revert_event_source = event_source.to_string();
 
         // Verify that the entity before updating is different from what we expect afterwards
@@ -1264,3 +1384,71 @@ fn revert_block_with_partial_update() {
         assert_eq!(reverted_entity, original_entity);
     })
 }
+
+#[test]
+fn entity_history_records_changes_with_their_blocks() {
+    run_test(|| {
+        let core = Core::new().unwrap();
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let mut store = DieselStore::new(StoreConfig { url }, &logger, core.handle());
+
+        // Start from an empty history for the entity
+        sql_query("DELETE FROM entity_history WHERE subgraph = 'history_subgraph'")
+            .execute(&store.conn)
+            .expect("Failed to remove entity history");
+
+        let key = StoreKey {
+            subgraph: String::from("history_subgraph"),
+            entity: String::from("user"),
+            id: String::from("1"),
+        };
+        let first = Entity::from(vec![("name", Value::from("Johnton"))]);
+        let second = Entity::from(vec![("name", Value::from("Johnny"))]);
+
+        store
+            .set(key.clone(), first.clone(), block_source(H256::random(), 10))
+            .expect("Failed to insert entity");
+        store
+            .set(key.clone(), second.clone(), block_source(H256::random(), 11))
+            .expect("Failed to update entity");
+        store
+            .delete(key.clone(), block_source(H256::random(), 12))
+            .expect("Failed to delete entity");
+
+        let history = store
+            .history(StoreHistoryQuery {
+                key: key.clone(),
+                first: None,
+                from_block: None,
+            })
+            .expect("Failed to load entity history");
+        assert_eq!(
+            history
+                .iter()
+                .map(|change| (change.operation, change.block_number))
+                .collect::<Vec<_>>(),
+            vec![
+                (EntityOperation::Insert, Some(10)),
+                (EntityOperation::Update, Some(11)),
+                (EntityOperation::Delete, Some(12)),
+            ]
+        );
+        assert_eq!(history[0].before, None);
+        assert_eq!(history[0].after, Some(first.clone()));
+        assert_eq!(history[1].before, Some(first));
+        assert_eq!(history[1].after, Some(second.clone()));
+        assert_eq!(history[2].before, Some(second));
+        assert_eq!(history[2].after, None);
+
+        let history = store
+            .history(StoreHistoryQuery {
+                key,
+                first: Some(1),
+                from_block: Some(11),
+            })
+            .expect("Failed to load entity history");
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].operation, EntityOperation::Update);
+    })
+}